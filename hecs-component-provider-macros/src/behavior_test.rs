@@ -0,0 +1,115 @@
+use crate::error::error_with_help;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Error, Expr, FnArg, Ident, ItemFn, Result, Token, Type};
+
+pub(crate) fn generate(options: Options, input: ItemFn) -> Result<TokenStream2> {
+    let (query, run) = match options.run {
+        Some(run) => run,
+        None => {
+            return Err(error_with_help(
+                &input.sig.ident,
+                "missing run option",
+                "add the query and behavior to run, e.g. \
+                 `run(MovableQuery, |item| item.apply_velocity(0.1))`",
+            ))
+        }
+    };
+    let bundles = options.spawn;
+
+    let mut views = Vec::new();
+    for (i, arg) in input.sig.inputs.iter().enumerate() {
+        let arg = match arg {
+            FnArg::Typed(arg) => arg,
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new_spanned(
+                    receiver,
+                    "behavior tests can't take `self`",
+                ))
+            }
+        };
+        if i >= bundles.len() {
+            return Err(error_with_help(
+                arg,
+                "more parameters than spawned bundles",
+                "each parameter is a view of the entity spawned from the bundle at the same \
+                 position in the spawn option",
+            ));
+        }
+        let pat = &arg.pat;
+        let ty = &arg.ty;
+        let view = format_ident!("__view_{}", i);
+        let message = format!(
+            "entity {} doesn't match the query `{}`",
+            i,
+            quote! { #ty }.to_string().replace(' ', "")
+        );
+        views.push(quote! {
+            let mut #view = world.query_one::<#ty>(__entities[#i]);
+            let #pat = #view.get().expect(#message);
+        });
+    }
+
+    let attrs = &input.attrs;
+    let vis = &input.vis;
+    let ident = &input.sig.ident;
+    let output = &input.sig.output;
+    let block = &input.block;
+    Ok(quote! {
+        #[test]
+        #(#attrs)*
+        #vis fn #ident() #output {
+            let mut world = ::hecs_component_provider::hecs::World::new();
+            let __entities = [#(world.spawn(#bundles)),*];
+            ::hecs_component_provider::run_with::<#query>(&mut world, #run);
+            #(#views)*
+            #block
+        }
+    })
+}
+
+#[derive(Default)]
+pub(crate) struct Options {
+    spawn: Vec<Expr>,
+    run: Option<(Type, Expr)>,
+}
+
+impl Parse for Options {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut options = Options::default();
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            let content;
+            syn::parenthesized!(content in input);
+            if ident == "spawn" {
+                let bundles = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?;
+                options.spawn.extend(bundles);
+            } else if ident == "run" {
+                let query = content.parse()?;
+                content.parse::<Token![,]>()?;
+                let behavior = content.parse()?;
+                if !content.is_empty() {
+                    content.parse::<Token![,]>()?;
+                }
+                options.run = Some((query, behavior));
+            } else {
+                return Err(Error::new_spanned(
+                    ident,
+                    "unknown behavior_test option, expected `spawn` or `run`",
+                ));
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        if options.spawn.is_empty() {
+            return Err(Error::new(
+                Span::call_site(),
+                "behavior_test requires a spawn option with at least one bundle",
+            ));
+        }
+        Ok(options)
+    }
+}
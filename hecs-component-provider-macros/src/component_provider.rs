@@ -1,45 +1,114 @@
+use crate::error::{combine_errors, error_with_help};
 use itertools::izip;
-use proc_macro2::{Span, TokenStream as TokenStream2};
-use quote::quote;
-use syn::{DeriveInput, Error, Ident, Member, PathArguments, Result, Type, TypeReference};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, DeriveInput, Error, Ident, Member, PathArguments, Result, Token, Type,
+    TypeReference, Visibility,
+};
 use unzip_n::unzip_n;
 
 unzip_n!(3);
+unzip_n!(4);
 
 pub(crate) fn derive(input: DeriveInput) -> Result<TokenStream2> {
-    let stream_refs = derive_refs(input.clone())?;
-    let stream_muts = derive_muts(input.clone())?;
-    let stream_option_refs = derive_option_refs(input.clone())?;
-    let stream_option_muts = derive_option_muts(input)?;
+    // in dynamic mode the per-component provider impls are replaced by a single DynamicProvider impl
+    let providers = if parse_options(&input.attrs)?.dynamic {
+        derive_dynamic(input.clone())?
+    } else {
+        vec![
+            derive_refs(input.clone())?,
+            derive_muts(input.clone())?,
+            derive_option_refs(input.clone())?,
+            derive_option_muts(input.clone())?,
+            derive_unsized_refs(input.clone())?,
+            derive_unsized_muts(input.clone())?,
+            derive_or_refs(input.clone())?,
+            derive_or_muts(input.clone())?,
+            derive_disjoint(input.clone())?,
+            derive_split(input.clone())?,
+            derive_nested(input.clone())?,
+            derive_aliases(input.clone())?,
+            derive_cold(input.clone())?,
+        ]
+        .into_iter()
+        .collect()
+    };
 
-    Ok(stream_refs
+    let stream_query_access = derive_query_access(input.clone())?;
+    let stream_entity_builder = derive_entity_builder(input.clone())?;
+    let stream_diff = derive_diff(input.clone())?;
+    let stream_owned = derive_owned(input.clone())?;
+    let stream_readonly = derive_readonly(input.clone())?;
+    let stream_ensure = derive_ensure(input.clone())?;
+    let stream_remove = derive_remove(input.clone())?;
+    let stream_promote = derive_promote(input.clone())?;
+    let stream_replication = derive_replication(input.clone())?;
+    let stream_new_partial = derive_new_partial(input.clone())?;
+    let stream_validate = derive_validate(input.clone())?;
+    let stream_builder = derive_builder(input.clone())?;
+    let stream_thread_safety = derive_thread_safety(input.clone())?;
+    let stream_access_wrappers = derive_access_wrapper_check(input.clone())?;
+    let stream_arbitrary = derive_arbitrary(input)?;
+
+    Ok(providers
         .into_iter()
-        .chain(stream_muts)
-        .chain(stream_option_refs)
-        .chain(stream_option_muts)
+        .chain(stream_query_access)
+        .chain(stream_entity_builder)
+        .chain(stream_diff)
+        .chain(stream_owned)
+        .chain(stream_readonly)
+        .chain(stream_ensure)
+        .chain(stream_remove)
+        .chain(stream_promote)
+        .chain(stream_replication)
+        .chain(stream_new_partial)
+        .chain(stream_validate)
+        .chain(stream_builder)
+        .chain(stream_thread_safety)
+        .chain(stream_access_wrappers)
+        .chain(stream_arbitrary)
         .collect::<TokenStream2>())
 }
 
 fn derive_refs(input: DeriveInput) -> Result<TokenStream2> {
     let InputDecomposition {
+        inline,
         ident,
         fields,
         types,
         ref_types,
+        access_types,
         struct_type,
         ..
     } = decompose_derive_input(input)?;
 
     let tokens = match struct_type {
-        StructType::Bundle => quote! {
-            #(
-                impl ::hecs_component_provider::ComponentProvider<#types> for #ident {
-                    fn get(&self) -> &#types {
-                        &self.#fields
+        StructType::Bundle => {
+            let (types, getters): (Vec<_>, Vec<_>) = izip!(fields.iter(), types, access_types)
+                .map(|(f, t, a)| match a {
+                    Some((_, inner)) => (
+                        inner,
+                        quote! { <#t as ::hecs_component_provider::AccessWrapper>::inner(&self.#f) },
+                    ),
+                    None => (t, quote! { &self.#f }),
+                })
+                .unzip();
+            quote! {
+                #(
+                    impl ::hecs_component_provider::ComponentProvider<#types> for #ident {
+                        #inline
+                        fn get(&self) -> &#types {
+                            #getters
+                        }
                     }
-                }
-            )*
-        },
+                )*
+            }
+        }
         StructType::Query => {
             let (fields, types, ref_types): (Vec<_>, Vec<_>, Vec<_>) =
                 izip!(fields.into_iter(), types.into_iter(), ref_types.into_iter())
@@ -50,6 +119,7 @@ fn derive_refs(input: DeriveInput) -> Result<TokenStream2> {
             quote! {
                         #(
                             impl<'a> ::hecs_component_provider::ComponentProvider<#ref_types> for #ident<'a> {
+                                #inline
                                 fn get(&self) -> #types {
                                     self.#fields
                                 }
@@ -64,41 +134,76 @@ fn derive_refs(input: DeriveInput) -> Result<TokenStream2> {
 
 fn derive_muts(input: DeriveInput) -> Result<TokenStream2> {
     let InputDecomposition {
+        inline,
         ident,
         fields,
         types,
         ref_types,
+        access_types,
+        asserts,
         struct_type,
         ..
     } = decompose_derive_input(input)?;
 
     let tokens = match struct_type {
-        StructType::Bundle => quote! {
-            #(
-                impl ::hecs_component_provider::ComponentProviderMut<#types> for #ident {
-                    fn get_mut(&mut self) -> &mut #types {
-                        &mut self.#fields
-                    }
-                }
-            )*
-        },
-        StructType::Query => {
-            let (fields, types, ref_types): (Vec<_>, Vec<_>, Vec<_>) =
-                izip!(fields.into_iter(), types.into_iter(), ref_types.into_iter())
-                    .filter_map(|(f, t, p)| {
-                        if is_mutable_type_ref(&t) {
-                            Some((f, remove_type_lifetime(&t), p?))
-                        } else {
-                            None
+        StructType::Bundle => {
+            let (types, getters, checked): (Vec<_>, Vec<_>, Vec<_>) =
+                izip!(fields.iter(), types, access_types, asserts)
+                    .filter_map(|(f, t, a, assert)| match a {
+                        Some((Access::Read, _)) => None,
+                        Some((Access::Write, inner)) => {
+                            let getter = quote! { ::core::ops::DerefMut::deref_mut(&mut self.#f) };
+                            let checked = checked_getter(&inline, &inner, f, &getter, assert);
+                            Some((inner, getter, checked))
+                        }
+                        None => {
+                            let getter = quote! { &mut self.#f };
+                            let checked = checked_getter(&inline, &t, f, &getter, assert);
+                            Some((t, getter, checked))
                         }
                     })
                     .unzip_n();
+            quote! {
+                #(
+                    impl ::hecs_component_provider::ComponentProviderMut<#types> for #ident {
+                        #inline
+                        fn get_mut(&mut self) -> &mut #types {
+                            ::hecs_component_provider::record_mutation::<#types>();
+                            #getters
+                        }
+
+                        #checked
+                    }
+                )*
+            }
+        }
+        StructType::Query => {
+            let (fields, types, ref_types, checked): (Vec<_>, Vec<_>, Vec<_>, Vec<_>) = izip!(
+                fields.into_iter(),
+                types.into_iter(),
+                ref_types.into_iter(),
+                asserts
+            )
+            .filter_map(|(f, t, p, assert)| {
+                if is_mutable_type_ref(&t) {
+                    let p = p?;
+                    let checked = checked_getter(&inline, &p, &f, &quote! { self.#f }, assert);
+                    Some((f, remove_type_lifetime(&t), p, checked))
+                } else {
+                    None
+                }
+            })
+            .unzip_n();
             quote! {
                         #(
                             impl<'a> ::hecs_component_provider::ComponentProviderMut<#ref_types> for #ident<'a> {
+                                #inline
                                 fn get_mut(&mut self) -> #types {
+                                    ::hecs_component_provider::record_mutation::<#ref_types>();
                                     self.#fields
                                 }
+
+                                #checked
                             }
                         )*
             }
@@ -108,8 +213,58 @@ fn derive_muts(input: DeriveInput) -> Result<TokenStream2> {
     Ok(tokens)
 }
 
+/// Overrides `get_mut_checked` for a field with an `assert` option, checking the predicate with
+/// `self` replaced by the component
+fn checked_getter(
+    inline: &TokenStream2,
+    component: &Type,
+    field: &Member,
+    getter: &TokenStream2,
+    assert: Option<(syn::LitStr, syn::Expr)>,
+) -> TokenStream2 {
+    let (predicate, expr) = match assert {
+        Some(assert) => assert,
+        None => return quote! {},
+    };
+    let expr = replace_self(quote! { #expr });
+    let message = format!(
+        "assertion `{}` failed for field `{}`",
+        predicate.value(),
+        member_name(field)
+    );
+    quote! {
+        #inline
+        fn get_mut_checked(&mut self) -> ::hecs_component_provider::CheckedMut<'_, #component> {
+            fn check(__component: &#component) {
+                ::core::debug_assert!(#expr, #message);
+            }
+            ::hecs_component_provider::record_mutation::<#component>();
+            ::hecs_component_provider::CheckedMut::new(#getter, check)
+        }
+    }
+}
+
+fn replace_self(tokens: TokenStream2) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) if ident == "self" => {
+                proc_macro2::TokenTree::Ident(Ident::new("__component", ident.span()))
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                let mut replaced =
+                    proc_macro2::Group::new(group.delimiter(), replace_self(group.stream()));
+                replaced.set_span(group.span());
+                proc_macro2::TokenTree::Group(replaced)
+            }
+            token => token,
+        })
+        .collect()
+}
+
 fn derive_option_refs(input: DeriveInput) -> Result<TokenStream2> {
     let InputDecomposition {
+        inline,
         ident,
         fields,
         types,
@@ -133,6 +288,7 @@ fn derive_option_refs(input: DeriveInput) -> Result<TokenStream2> {
             quote! {
                     #(
                         impl<'a> ::hecs_component_provider::ComponentProviderOptional<#option_types> for #ident<'a> {
+                            #inline
                             fn get_optional(&self) -> #types {
                                 // convert Option<&mut T> to Option<&T>
                                 if let Some(v) = &self.#fields {
@@ -152,6 +308,7 @@ fn derive_option_refs(input: DeriveInput) -> Result<TokenStream2> {
 
 fn derive_option_muts(input: DeriveInput) -> Result<TokenStream2> {
     let InputDecomposition {
+        inline,
         ident,
         fields,
         types,
@@ -179,6 +336,7 @@ fn derive_option_muts(input: DeriveInput) -> Result<TokenStream2> {
             quote! {
                         #(
                             impl<'a> ::hecs_component_provider::ComponentProviderOptionalMut<#option_types> for #ident<'a> {
+                                #inline
                                 fn get_optional_mut(&mut self) -> #types {
                                     // fix Copy error when returning self.#fields directly
                                     if let Some(v) = &mut self.#fields {
@@ -196,98 +354,2269 @@ fn derive_option_muts(input: DeriveInput) -> Result<TokenStream2> {
     Ok(tokens)
 }
 
-struct InputDecomposition {
-    ident: Ident,
-    fields: Vec<Member>,
-    types: Vec<Type>,
-    ref_types: Vec<Option<Type>>,
-    option_types: Vec<Option<Type>>,
-    struct_type: StructType,
-}
+fn derive_unsized_refs(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        inline,
+        ident,
+        fields,
+        unsized_types,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
 
-enum StructType {
-    Bundle,
-    Query,
+    let (fields, unsized_types): (Vec<_>, Vec<_>) = fields
+        .into_iter()
+        .zip(unsized_types)
+        .filter_map(|(f, d)| Some((f, d?)))
+        .unzip();
+
+    let tokens = match struct_type {
+        StructType::Bundle => quote! {
+            #(
+                impl ::hecs_component_provider::ComponentProvider<#unsized_types> for #ident {
+                    #inline
+                    fn get(&self) -> &#unsized_types {
+                        &*self.#fields
+                    }
+                }
+            )*
+        },
+        StructType::Query => quote! {
+            #(
+                impl<'a> ::hecs_component_provider::ComponentProvider<#unsized_types> for #ident<'a> {
+                    #inline
+                    fn get(&self) -> &#unsized_types {
+                        &**self.#fields
+                    }
+                }
+            )*
+        },
+    };
+
+    Ok(tokens)
 }
 
-fn decompose_derive_input(input: DeriveInput) -> Result<InputDecomposition> {
-    let ident = input.ident;
-    let data = match input.data {
-        syn::Data::Struct(s) => s,
-        _ => {
-            return Err(Error::new_spanned(
-                ident,
-                "derive(ComponentProvider) may only be applied to structs",
-            ))
+fn derive_unsized_muts(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        inline,
+        ident,
+        fields,
+        types,
+        unsized_types,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let tokens = match struct_type {
+        StructType::Bundle => {
+            let (fields, unsized_types): (Vec<_>, Vec<_>) = fields
+                .into_iter()
+                .zip(unsized_types)
+                .filter_map(|(f, d)| Some((f, d?)))
+                .unzip();
+            quote! {
+                #(
+                    impl ::hecs_component_provider::ComponentProviderMut<#unsized_types> for #ident {
+                        #inline
+                        fn get_mut(&mut self) -> &mut #unsized_types {
+                            ::hecs_component_provider::record_mutation::<#unsized_types>();
+                            &mut *self.#fields
+                        }
+                    }
+                )*
+            }
+        }
+        StructType::Query => {
+            let (fields, unsized_types): (Vec<_>, Vec<_>) = izip!(
+                fields.into_iter(),
+                types.into_iter(),
+                unsized_types.into_iter()
+            )
+            .filter_map(|(f, t, d)| {
+                if is_mutable_type_ref(&t) {
+                    Some((f, d?))
+                } else {
+                    None
+                }
+            })
+            .unzip();
+            quote! {
+                #(
+                    impl<'a> ::hecs_component_provider::ComponentProviderMut<#unsized_types> for #ident<'a> {
+                        #inline
+                        fn get_mut(&mut self) -> &mut #unsized_types {
+                            ::hecs_component_provider::record_mutation::<#unsized_types>();
+                            &mut **self.#fields
+                        }
+                    }
+                )*
+            }
         }
     };
 
-    let lifetimes: Vec<_> = input.generics.lifetimes().cloned().collect();
-    if lifetimes.len() > 1 {
-        return Err(Error::new_spanned(
-            input.generics,
-            "must have <= 1 lifetime parameter",
-        ));
+    Ok(tokens)
+}
+
+fn derive_or_refs(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        inline,
+        ident,
+        fields,
+        or_types,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let tokens = match struct_type {
+        StructType::Bundle => quote! {},
+        StructType::Query => {
+            let (fields, sides, or_types): (Vec<_>, Vec<_>, Vec<_>) = fields
+                .into_iter()
+                .zip(or_types)
+                .filter_map(|(f, o)| Some((f, o?)))
+                .flat_map(|(f, (l, r))| {
+                    vec![(f.clone(), quote! { left }, l), (f, quote! { right }, r)]
+                })
+                .filter_map(|(f, side, t)| Some((f, side, extract_ref_type(&t)?)))
+                .unzip_n();
+            quote! {
+                #(
+                    impl<'a> ::hecs_component_provider::ComponentProviderOptional<#or_types> for #ident<'a> {
+                        #inline
+                        fn get_optional(&self) -> Option<&#or_types> {
+                            self.#fields.as_ref().#sides().map(|v| &**v)
+                        }
+                    }
+                )*
+            }
+        }
     };
 
-    if input.generics.params.len() != lifetimes.len() {
-        return Err(Error::new_spanned(ident, "must have no type parameters"));
-    }
+    Ok(tokens)
+}
 
-    let (fields, types) = match data.fields {
-        syn::Fields::Named(ref fields) => fields
-            .named
-            .iter()
-            .map(|f| (Member::Named(f.ident.clone().unwrap()), f.ty.clone()))
-            .unzip(),
-        syn::Fields::Unnamed(ref fields) => fields
-            .unnamed
-            .iter()
-            .enumerate()
-            .map(|(i, f)| {
-                (
-                    Member::Unnamed(syn::Index {
-                        index: i as u32,
-                        span: Span::call_site(),
-                    }),
-                    f.ty.clone(),
-                )
-            })
-            .unzip(),
-        syn::Fields::Unit => (Vec::new(), Vec::new()),
+fn derive_or_muts(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        inline,
+        ident,
+        fields,
+        or_types,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let tokens = match struct_type {
+        StructType::Bundle => quote! {},
+        StructType::Query => {
+            let (fields, sides, or_types): (Vec<_>, Vec<_>, Vec<_>) = fields
+                .into_iter()
+                .zip(or_types)
+                .filter_map(|(f, o)| Some((f, o?)))
+                .flat_map(|(f, (l, r))| {
+                    vec![(f.clone(), quote! { left }, l), (f, quote! { right }, r)]
+                })
+                .filter_map(|(f, side, t)| {
+                    if is_mutable_type_ref(&t) {
+                        Some((f, side, extract_ref_type(&t)?))
+                    } else {
+                        None
+                    }
+                })
+                .unzip_n();
+            quote! {
+                #(
+                    impl<'a> ::hecs_component_provider::ComponentProviderOptionalMut<#or_types> for #ident<'a> {
+                        #inline
+                        fn get_optional_mut(&mut self) -> Option<&mut #or_types> {
+                            self.#fields.as_mut().#sides().map(|v| &mut **v)
+                        }
+                    }
+                )*
+            }
+        }
     };
 
-    let ref_types: Vec<_> = types.iter().map(extract_ref_type).collect();
-    let option_types: Vec<_> = types.iter().map(extract_option_type).collect();
+    Ok(tokens)
+}
 
-    Ok(InputDecomposition {
+fn derive_disjoint(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        inline,
         ident,
         fields,
         types,
         ref_types,
-        option_types,
-        struct_type: if lifetimes.len() == 0 {
-            StructType::Bundle
-        } else {
-            StructType::Query
-        },
+        access_types,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let tokens = match struct_type {
+        StructType::Bundle => {
+            let (types, ptrs): (Vec<_>, Vec<_>) = izip!(fields, types, access_types)
+                .map(|(f, t, a)| {
+                    let ptr = quote! { ::core::ptr::addr_of_mut!((*this).#f) };
+                    match a {
+                        Some((_, inner)) => (
+                            inner,
+                            quote! { <#t as ::hecs_component_provider::AccessWrapper>::as_inner_ptr(#ptr) },
+                        ),
+                        None => (t, ptr),
+                    }
+                })
+                .unzip();
+            quote! {
+                #(
+                    unsafe impl ::hecs_component_provider::ComponentProviderDisjoint<#types> for #ident {
+                        #inline
+                        unsafe fn get_ptr(this: *mut Self) -> *mut #types {
+                            unsafe { #ptrs }
+                        }
+                    }
+                )*
+            }
+        }
+        StructType::Query => {
+            let (fields, types, ref_types): (Vec<_>, Vec<_>, Vec<_>) =
+                izip!(fields.into_iter(), types.into_iter(), ref_types.into_iter())
+                    .filter_map(|(f, t, p)| Some((f, t, p?)))
+                    .unzip_n();
+            let ptrs = types.iter().zip(fields.iter()).map(|(t, f)| {
+                if is_mutable_type_ref(t) {
+                    quote! { ::core::ptr::addr_of_mut!(*(*this).#f) }
+                } else {
+                    quote! { ::core::ptr::addr_of!(*(*this).#f) as *mut _ }
+                }
+            });
+            quote! {
+                #(
+                    unsafe impl<'a> ::hecs_component_provider::ComponentProviderDisjoint<#ref_types> for #ident<'a> {
+                        #inline
+                        unsafe fn get_ptr(this: *mut Self) -> *mut #ref_types {
+                            unsafe { #ptrs }
+                        }
+                    }
+                )*
+            }
+        }
+    };
+
+    Ok(tokens)
+}
+
+fn derive_split(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        vis,
+        fields,
+        types,
+        struct_type,
+        options,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let groups = match options.split {
+        Some(groups) => groups,
+        None => return Ok(quote! {}),
+    };
+
+    let mut used_fields = Vec::new();
+    let mut part_structs = Vec::new();
+    let mut part_types = Vec::new();
+    let mut part_values = Vec::new();
+    for SplitGroup {
+        ident: part_ident,
+        fields: part_fields,
+    } in groups
+    {
+        let mut field_types = Vec::new();
+        let mut field_values = Vec::new();
+        for part_field in &part_fields {
+            if used_fields.contains(part_field) {
+                return Err(Error::new_spanned(
+                    part_field,
+                    "field may only appear in one split group",
+                ));
+            }
+            used_fields.push(part_field.clone());
+
+            let index = fields
+                .iter()
+                .position(|f| matches!(f, Member::Named(f) if f == part_field))
+                .ok_or_else(|| Error::new_spanned(part_field, "unknown field"))?;
+            let (field_type, field_value) = match struct_type {
+                StructType::Bundle => {
+                    let t = &types[index];
+                    (quote! { &'a mut #t }, quote! { &mut self.#part_field })
+                }
+                StructType::Query => split_query_field(&types[index], part_field)?,
+            };
+            field_types.push(field_type);
+            field_values.push(field_value);
+        }
+
+        part_structs.push(quote! {
+            #[derive(::hecs_component_provider::ComponentProvider)]
+            #vis struct #part_ident<'a> {
+                #(#vis #part_fields: #field_types,)*
+            }
+        });
+        part_types.push(quote! { #part_ident<'_> });
+        part_values.push(quote! { #part_ident { #(#part_fields: #field_values,)* } });
+    }
+
+    let impl_header = match struct_type {
+        StructType::Bundle => quote! { impl #ident },
+        StructType::Query => quote! { impl<'a> #ident<'a> },
+    };
+
+    Ok(quote! {
+        #(#part_structs)*
+
+        #impl_header {
+            /// Splits the borrowed fields into disjoint component providers
+            #vis fn split(&mut self) -> (#(#part_types,)*) {
+                (#(#part_values,)*)
+            }
+        }
     })
 }
 
-fn extract_ref_type(t: &Type) -> Option<Type> {
-    match t {
-        Type::Reference(type_reference) => Some(type_reference.elem.as_ref().clone()),
-        _ => None,
+fn derive_query_access(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        types,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    if let StructType::Bundle = struct_type {
+        return Ok(quote! {});
     }
+
+    // fields borrowing `InteriorMutable` components are found through the probe traits, so that
+    // they're recognized however the component type is named
+    let accesses = types.iter().map(|t| {
+        quote! {
+            (&&::hecs_component_provider::AccessProbe::<#t>(::core::marker::PhantomData)).access()
+        }
+    });
+    // bounded on the field types so that fields with custom query types don't prevent the derive
+    Ok(quote! {
+        impl<'a> ::hecs_component_provider::QueryAccess for #ident<'a>
+        where
+            #(#types: ::hecs_component_provider::QueryAccess,)*
+        {
+            fn access() -> ::std::vec::Vec<::hecs_component_provider::ComponentAccess> {
+                #[allow(unused_imports)]
+                use ::hecs_component_provider::{InteriorAccessProbe as _, PlainAccessProbe as _};
+                #[allow(unused_mut)]
+                let mut access = ::std::vec::Vec::new();
+                #(
+                    access.extend(#accesses);
+                )*
+                access
+            }
+        }
+    })
 }
 
-fn extract_option_type(t: &Type) -> Option<Type> {
-    if let Type::Path(type_path) = t {
-        let segment = type_path.path.segments.first()?;
-        if segment.ident == "Option" {
-            if let PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments {
-                args, ..
-            }) = &segment.arguments
+fn derive_nested(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        nested,
+        inline,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let (fields, types, components): (Vec<_>, Vec<_>, Vec<_>) = nested
+        .into_iter()
+        .flat_map(|(f, t, cs)| cs.into_iter().map(move |c| (f.clone(), t.clone(), c)))
+        .unzip_n();
+
+    Ok(quote! {
+        #(
+            impl ::hecs_component_provider::ComponentProvider<#components> for #ident {
+                #inline
+                fn get(&self) -> &#components {
+                    <#types as ::hecs_component_provider::ComponentProvider<#components>>::get(
+                        &self.#fields,
+                    )
+                }
+            }
+
+            impl ::hecs_component_provider::ComponentProviderMut<#components> for #ident {
+                #inline
+                fn get_mut(&mut self) -> &mut #components {
+                    <#types as ::hecs_component_provider::ComponentProviderMut<#components>>::get_mut(
+                        &mut self.#fields,
+                    )
+                }
+            }
+        )*
+    })
+}
+
+fn derive_aliases(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        inline,
+        ident,
+        fields,
+        types,
+        ref_types,
+        access_types,
+        aliases,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let mut tokens = TokenStream2::new();
+    for (field, ty, ref_type, access_type, aliases) in
+        izip!(fields, types, ref_types, access_types, aliases)
+    {
+        if aliases.is_empty() {
+            continue;
+        }
+
+        let (component, mutable) = match struct_type {
+            StructType::Bundle if access_type.is_none() => (ty, true),
+            StructType::Query if ref_type.is_some() => {
+                let mutable = is_mutable_type_ref(&ty);
+                (ref_type.unwrap(), mutable)
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    ty,
+                    "aliases are only supported on component fields",
+                ))
+            }
+        };
+
+        let (generics, field_ref, field_mut) = match struct_type {
+            StructType::Bundle => (
+                quote! {},
+                quote! { &self.#field },
+                quote! { &mut self.#field },
+            ),
+            StructType::Query => (
+                quote! { <'a> },
+                quote! { &*self.#field },
+                quote! { &mut *self.#field },
+            ),
+        };
+
+        for alias in aliases {
+            // the bounds are higher-ranked for the same reason as in `derive_entity_builder`
+            tokens.extend(quote! {
+                impl #generics ::hecs_component_provider::ComponentProvider<#alias> for #ident #generics
+                where
+                    for<'__a> #component: ::core::convert::AsRef<#alias>,
+                {
+                    #inline
+                    fn get(&self) -> &#alias {
+                        ::core::convert::AsRef::as_ref(#field_ref)
+                    }
+                }
+            });
+            if mutable {
+                tokens.extend(quote! {
+                    impl #generics ::hecs_component_provider::ComponentProviderMut<#alias> for #ident #generics
+                    where
+                        for<'__a> #component: ::core::convert::AsMut<#alias>,
+                    {
+                        #inline
+                        fn get_mut(&mut self) -> &mut #alias {
+                            ::core::convert::AsMut::as_mut(#field_mut)
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn derive_dynamic(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        fields,
+        types,
+        ref_types,
+        option_types,
+        access_types,
+        nested,
+        aliases,
+        inline,
+        struct_type,
+        options,
+        ..
+    } = decompose_derive_input(input)?;
+
+    if let Some((_, ty, _)) = nested.first() {
+        return Err(error_with_help(
+            ty,
+            "nested providers aren't supported in dynamic mode",
+            "remove the dynamic option, or add the nested bundle's components as fields",
+        ));
+    }
+    if let Some(alias) = aliases.iter().flatten().next() {
+        return Err(error_with_help(
+            alias,
+            "aliases aren't supported in dynamic mode",
+            "remove the dynamic option, or remove the alias",
+        ));
+    }
+    if let Some(group) = options.split.as_ref().and_then(|groups| groups.first()) {
+        return Err(error_with_help(
+            &group.ident,
+            "split isn't supported in dynamic mode",
+            "remove the dynamic option, or remove the split option",
+        ));
+    }
+
+    let mut refs = Vec::new();
+    let mut muts = Vec::new();
+    for (field, ty, ref_type, option_type, access_type) in
+        izip!(fields, types, ref_types, option_types, access_types)
+    {
+        match struct_type {
+            StructType::Bundle => {
+                let component = match access_type {
+                    Some(_) => continue,
+                    None => ty,
+                };
+                refs.push(quote! {
+                    if type_id == ::core::any::TypeId::of::<#component>() {
+                        return ::core::option::Option::Some(&self.#field);
+                    }
+                });
+                muts.push(quote! {
+                    if type_id == ::core::any::TypeId::of::<#component>() {
+                        return ::core::option::Option::Some(&mut self.#field);
+                    }
+                });
+            }
+            StructType::Query => {
+                let mutable = is_mutable_type_ref(&ty);
+                let (component, get, get_mut) = match (ref_type, option_type) {
+                    (Some(component), _) => (
+                        component,
+                        quote! { ::core::option::Option::Some(&*self.#field) },
+                        quote! { ::core::option::Option::Some(&mut *self.#field) },
+                    ),
+                    (_, Some(component)) => (
+                        component,
+                        quote! { self.#field.as_deref().map(|c| c as &dyn ::core::any::Any) },
+                        quote! {
+                            self.#field.as_deref_mut().map(|c| c as &mut dyn ::core::any::Any)
+                        },
+                    ),
+                    _ => continue,
+                };
+                refs.push(quote! {
+                    if type_id == ::core::any::TypeId::of::<#component>() {
+                        return #get;
+                    }
+                });
+                if mutable {
+                    muts.push(quote! {
+                        if type_id == ::core::any::TypeId::of::<#component>() {
+                            return #get_mut;
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    let generics = match struct_type {
+        StructType::Bundle => quote! {},
+        StructType::Query => quote! { <'a> },
+    };
+
+    Ok(quote! {
+        impl #generics ::hecs_component_provider::DynamicProvider for #ident #generics {
+            #inline
+            fn get_dyn(
+                &self,
+                type_id: ::core::any::TypeId,
+            ) -> ::core::option::Option<&dyn ::core::any::Any> {
+                #(#refs)*
+                ::core::option::Option::None
+            }
+
+            #inline
+            fn get_dyn_mut(
+                &mut self,
+                type_id: ::core::any::TypeId,
+            ) -> ::core::option::Option<&mut dyn ::core::any::Any> {
+                #(#muts)*
+                ::core::option::Option::None
+            }
+        }
+    })
+}
+
+fn derive_entity_builder(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        fields,
+        types,
+        access_types,
+        component_types,
+        cold,
+        nested,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    if let StructType::Query = struct_type {
+        return Ok(quote! {});
+    }
+
+    let values = izip!(&fields, &types, &access_types, cold)
+        .map(|(f, t, a, cold)| component_value(t, a, cold, quote! { self.#f }));
+    let (nested_fields, nested_types): (Vec<_>, Vec<_>) =
+        nested.into_iter().map(|(f, t, _)| (f, t)).unzip();
+
+    // the bounds are higher-ranked so that they aren't checked eagerly, which leaves the impl
+    // out instead of failing to compile for bundles that can't be spawned
+    Ok(quote! {
+        impl ::hecs_component_provider::IntoEntityBuilder for #ident
+        where
+            #(for<'__a> #component_types: ::hecs_component_provider::hecs::Component,)*
+            #(for<'__a> #nested_types: ::hecs_component_provider::IntoEntityBuilder,)*
+        {
+            fn add_to_builder(self, builder: &mut ::hecs_component_provider::hecs::EntityBuilder) {
+                #(builder.add(#values);)*
+                #(
+                    ::hecs_component_provider::IntoEntityBuilder::add_to_builder(
+                        self.#nested_fields,
+                        builder,
+                    );
+                )*
+            }
+        }
+
+        impl ::core::convert::From<#ident> for ::hecs_component_provider::hecs::EntityBuilder
+        where
+            for<'__a> #ident: ::hecs_component_provider::IntoEntityBuilder,
+        {
+            fn from(bundle: #ident) -> Self {
+                ::hecs_component_provider::IntoEntityBuilder::into_entity_builder(bundle)
+            }
+        }
+    })
+}
+
+fn derive_diff(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        fields,
+        types,
+        nested,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    if let StructType::Query = struct_type {
+        return Ok(quote! {});
+    }
+
+    let names = fields.iter().map(member_name);
+    let (nested_fields, nested_types): (Vec<_>, Vec<_>) =
+        nested.into_iter().map(|(f, t, _)| (f, t)).unzip();
+    let nested_names = nested_fields.iter().map(member_name);
+
+    Ok(quote! {
+        impl ::hecs_component_provider::DiffBundle for #ident
+        where
+            #(for<'__a> #types: ::core::cmp::PartialEq,)*
+            #(for<'__a> #nested_types: ::hecs_component_provider::DiffBundle,)*
+        {
+            fn diff(&self, other: &Self) -> ::hecs_component_provider::BundleDiff {
+                let mut diff = ::hecs_component_provider::BundleDiff::new();
+                #(diff.push(#names, ::core::cmp::PartialEq::ne(&self.#fields, &other.#fields));)*
+                #(
+                    diff.push_nested(
+                        #nested_names,
+                        ::hecs_component_provider::DiffBundle::diff(
+                            &self.#nested_fields,
+                            &other.#nested_fields,
+                        ),
+                    );
+                )*
+                diff
+            }
+        }
+    })
+}
+
+fn derive_ensure(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        component_types,
+        nested,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    if let StructType::Query = struct_type {
+        return Ok(quote! {});
+    }
+
+    let nested_types: Vec<_> = nested.into_iter().map(|(_, t, _)| t).collect();
+
+    Ok(quote! {
+        impl ::hecs_component_provider::EnsureComponents for #ident
+        where
+            #(for<'__a> #component_types: ::hecs_component_provider::hecs::Component + ::core::default::Default,)*
+            #(for<'__a> #nested_types: ::hecs_component_provider::EnsureComponents,)*
+        {
+            fn ensure_components(
+                world: &mut ::hecs_component_provider::hecs::World,
+                entity: ::hecs_component_provider::hecs::Entity,
+                added: &mut ::std::vec::Vec<::hecs_component_provider::ComponentAccess>,
+            ) -> ::core::result::Result<(), ::hecs_component_provider::hecs::NoSuchEntity> {
+                #(::hecs_component_provider::ensure_one::<#component_types>(world, entity, added)?;)*
+                #(
+                    <#nested_types as ::hecs_component_provider::EnsureComponents>::ensure_components(
+                        world,
+                        entity,
+                        added,
+                    )?;
+                )*
+                ::core::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+fn derive_remove(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        fields,
+        types,
+        access_types,
+        component_types,
+        cold,
+        nested,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    if let StructType::Query = struct_type {
+        return Ok(quote! {});
+    }
+
+    let values = izip!(&types, &access_types, &component_types, cold).map(|(t, a, c, cold)| {
+        let component = quote! { ::hecs_component_provider::take_one::<#c>(world, entity) };
+        field_value(t, a, cold, component)
+    });
+    let (nested_fields, nested_types): (Vec<_>, Vec<_>) =
+        nested.into_iter().map(|(f, t, _)| (f, t)).unzip();
+
+    // tuple structs can also be constructed with their indices as field names
+    Ok(quote! {
+        impl ::hecs_component_provider::RemoveBundle for #ident
+        where
+            #(for<'__a> #component_types: ::hecs_component_provider::hecs::Component,)*
+            #(for<'__a> #nested_types: ::hecs_component_provider::RemoveBundle,)*
+        {
+            fn check_components(
+                entity: ::hecs_component_provider::hecs::EntityRef<'_>,
+            ) -> ::core::result::Result<(), ::hecs_component_provider::hecs::MissingComponent> {
+                #(::hecs_component_provider::check_one::<#component_types>(entity)?;)*
+                #(<#nested_types as ::hecs_component_provider::RemoveBundle>::check_components(entity)?;)*
+                ::core::result::Result::Ok(())
+            }
+
+            fn take_components(
+                world: &mut ::hecs_component_provider::hecs::World,
+                entity: ::hecs_component_provider::hecs::Entity,
+            ) -> Self {
+                #ident {
+                    #(#fields: #values,)*
+                    #(
+                        #nested_fields:
+                            <#nested_types as ::hecs_component_provider::RemoveBundle>::take_components(
+                                world,
+                                entity,
+                            ),
+                    )*
+                }
+            }
+        }
+    })
+}
+
+/// The expression adding the value of a bundle field to an entity, which unwraps access wrappers
+/// and wraps cold fields
+fn component_value(
+    ty: &Type,
+    access_type: &Option<(Access, Type)>,
+    cold: bool,
+    value: TokenStream2,
+) -> TokenStream2 {
+    let value = match access_type {
+        Some(_) => quote! { <#ty as ::hecs_component_provider::AccessWrapper>::into_inner(#value) },
+        None => value,
+    };
+    if cold {
+        quote! { ::hecs_component_provider::Cold::new(#value) }
+    } else {
+        value
+    }
+}
+
+/// The inverse of `component_value`, converting a component removed from an entity to the value of
+/// the bundle field
+fn field_value(
+    ty: &Type,
+    access_type: &Option<(Access, Type)>,
+    cold: bool,
+    component: TokenStream2,
+) -> TokenStream2 {
+    let value = if cold {
+        quote! { #component.into_inner() }
+    } else {
+        component
+    };
+    match access_type {
+        Some(_) => quote! { <#ty as ::hecs_component_provider::AccessWrapper>::from_inner(#value) },
+        None => value,
+    }
+}
+
+fn derive_promote(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        fields,
+        types,
+        access_types,
+        component_types,
+        cold,
+        nested,
+        struct_type,
+        options,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let first = match options.promote.first() {
+        Some(promote) => &promote.ident,
+        None => return Ok(quote! {}),
+    };
+    if let StructType::Query = struct_type {
+        return Err(error_with_help(
+            first,
+            "the promote option is only supported in bundle structs",
+            "add the option to the bundle struct that is spawned instead",
+        ));
+    }
+    if fields.iter().any(|f| matches!(f, Member::Unnamed(_))) {
+        return Err(error_with_help(
+            first,
+            "the promote option is only supported in structs with named fields",
+            "name the fields, since they are matched with the fields of the lower bundle by name",
+        ));
+    }
+
+    let mut tokens = TokenStream2::new();
+    for Promotion { lower, extra, .. } in &options.promote {
+        let mut extra_indices = Vec::new();
+        for field in extra {
+            let is_field = |f: &Member| matches!(f, Member::Named(f) if f == field);
+            match fields.iter().position(is_field) {
+                Some(index) if extra_indices.contains(&index) => {
+                    return Err(Error::new_spanned(field, "duplicate extra field"));
+                }
+                Some(index) => extra_indices.push(index),
+                None if nested.iter().any(|(f, _, _)| is_field(f)) => {
+                    return Err(error_with_help(
+                        field,
+                        "nested fields can't be extra",
+                        "add the nested bundle to the lower bundle, or add its components as \
+                         separate fields",
+                    ));
+                }
+                None => return Err(Error::new_spanned(field, "unknown field")),
+            }
+        }
+
+        let shared = fields
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !extra_indices.contains(index))
+            .map(|(_, f)| f)
+            .chain(nested.iter().map(|(f, _, _)| f));
+        let shared: Vec<_> = shared.collect();
+        let extra_types = extra_indices.iter().map(|&i| &types[i]);
+        let extra_components = extra_indices.iter().map(|&i| &component_types[i]);
+        // the extra components are accessed by their index in the tuple, so that they can't
+        // shadow the parameters
+        let indices: Vec<_> = (0..extra.len()).map(syn::Index::from).collect();
+        let extra_values = extra_indices.iter().zip(&indices).map(|(&i, index)| {
+            component_value(
+                &types[i],
+                &access_types[i],
+                cold[i],
+                quote! { extra.#index },
+            )
+        });
+        let extra_fields = extra_indices.iter().zip(&indices).map(|(&i, index)| {
+            field_value(
+                &types[i],
+                &access_types[i],
+                cold[i],
+                quote! { components.#index },
+            )
+        });
+
+        tokens.extend(quote! {
+            impl ::hecs_component_provider::Promote<#lower> for #ident {
+                type Extra = (#(#extra_types,)*);
+
+                fn promote(lower: #lower, extra: Self::Extra) -> Self {
+                    #ident {
+                        #(#shared: lower.#shared,)*
+                        #(#extra: extra.#indices,)*
+                    }
+                }
+
+                fn demote(self) -> (#lower, Self::Extra) {
+                    (#lower { #(#shared: self.#shared,)* }, (#(self.#extra,)*))
+                }
+            }
+
+            impl ::hecs_component_provider::PromoteEntity<#lower> for #ident {
+                fn promote_entity(
+                    world: &mut ::hecs_component_provider::hecs::World,
+                    entity: ::hecs_component_provider::hecs::Entity,
+                    extra: Self::Extra,
+                ) -> ::core::result::Result<(), ::hecs_component_provider::hecs::ComponentError> {
+                    let mut builder = ::hecs_component_provider::hecs::EntityBuilder::new();
+                    #(builder.add(#extra_values);)*
+                    world.insert(entity, builder.build())?;
+                    ::core::result::Result::Ok(())
+                }
+
+                fn demote_entity(
+                    world: &mut ::hecs_component_provider::hecs::World,
+                    entity: ::hecs_component_provider::hecs::Entity,
+                ) -> ::core::result::Result<Self::Extra, ::hecs_component_provider::hecs::ComponentError> {
+                    let components = world.remove::<(#(#extra_components,)*)>(entity)?;
+                    ::core::result::Result::Ok((#(#extra_fields,)*))
+                }
+            }
+        });
+    }
+    Ok(tokens)
+}
+
+fn derive_cold(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        inline,
+        ident,
+        fields,
+        types,
+        cold_types,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    if let StructType::Bundle = struct_type {
+        return Ok(quote! {});
+    }
+
+    let (fields, mutable, cold_types): (Vec<_>, Vec<_>, Vec<_>) = izip!(
+        fields.into_iter(),
+        types.into_iter(),
+        cold_types.into_iter()
+    )
+    .filter_map(|(f, t, c)| Some((f, is_mutable_type_ref(&t), c?)))
+    .unzip_n();
+    // the fields are dereferenced through the crate's `Cold`, so that a field referring to
+    // another type named `Cold` fails to compile
+    let muts = izip!(&fields, mutable, &cold_types).filter_map(|(f, mutable, c)| {
+        mutable.then(|| {
+            let deref = quote_spanned! {c.span()=>
+                <::hecs_component_provider::Cold<#c> as ::core::ops::DerefMut>::deref_mut(
+                    &mut *self.#f,
+                )
+            };
+            quote! {
+                impl<'a> ::hecs_component_provider::ComponentProviderMut<#c> for #ident<'a> {
+                    #inline
+                    fn get_mut(&mut self) -> &mut #c {
+                        ::hecs_component_provider::record_mutation::<#c>();
+                        #deref
+                    }
+                }
+            }
+        })
+    });
+    let refs = izip!(&fields, &cold_types).map(|(f, c)| {
+        let deref = quote_spanned! {c.span()=>
+            <::hecs_component_provider::Cold<#c> as ::core::ops::Deref>::deref(&*self.#f)
+        };
+        quote! {
+            impl<'a> ::hecs_component_provider::ComponentProvider<#c> for #ident<'a> {
+                #inline
+                fn get(&self) -> &#c {
+                    #deref
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        #(#refs)*
+        #(#muts)*
+    })
+}
+
+fn derive_replication(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        fields,
+        component_types,
+        nested,
+        replicated,
+        quantize,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    if let StructType::Query = struct_type {
+        return Ok(quote! {});
+    }
+
+    let names = fields.iter().map(member_name);
+    let modes = replicated.into_iter().map(|replicated| {
+        if replicated {
+            quote! { ::hecs_component_provider::Replication::Replicate }
+        } else {
+            quote! { ::hecs_component_provider::Replication::ServerOnly }
+        }
+    });
+    let quantizations = quantize.into_iter().map(|quantize| match quantize {
+        Some(Quantize { bits, min, max }) => quote! {
+            ::core::option::Option::Some(::hecs_component_provider::Quantization::new(
+                #bits, #min, #max,
+            ))
+        },
+        None => quote! { ::core::option::Option::None },
+    });
+    let (nested_fields, nested_types): (Vec<_>, Vec<_>) =
+        nested.into_iter().map(|(f, t, _)| (f, t)).unzip();
+    let nested_names = nested_fields.iter().map(member_name);
+
+    Ok(quote! {
+        impl ::hecs_component_provider::ReplicatedBundle for #ident
+        where
+            #(for<'__a> #nested_types: ::hecs_component_provider::ReplicatedBundle,)*
+        {
+            fn replication() -> ::hecs_component_provider::ReplicationDescriptor {
+                let mut descriptor = ::hecs_component_provider::ReplicationDescriptor::new();
+                #(descriptor.push::<#component_types>(#names, #modes, #quantizations);)*
+                #(
+                    descriptor.push_nested(
+                        #nested_names,
+                        <#nested_types as ::hecs_component_provider::ReplicatedBundle>::replication(),
+                    );
+                )*
+                descriptor
+            }
+        }
+    })
+}
+
+fn derive_owned(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        vis,
+        fields,
+        types,
+        ref_types,
+        struct_type,
+        options,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let owned = match options.owned {
+        Some(owned) => owned,
+        None => return Ok(quote! {}),
+    };
+    if let StructType::Bundle = struct_type {
+        return Err(Error::new_spanned(
+            owned,
+            "the owned option is only supported in query structs",
+        ));
+    }
+
+    let mut owned_fields = Vec::new();
+    let mut owned_types = Vec::new();
+    for (field, ty, ref_type) in izip!(fields, types, ref_types) {
+        match ref_type {
+            Some(ref_type) => {
+                owned_fields.push(field);
+                owned_types.push(ref_type);
+            }
+            // entity ids aren't components
+            None if is_entity_type(&ty) => {}
+            None => {
+                return Err(error_with_help(
+                    ty,
+                    "only `&T`, `&mut T` and `Entity` fields can be converted to an owned bundle",
+                    "move this field into a separate query struct, or remove the owned option",
+                ))
+            }
+        }
+    }
+
+    let (definition, construction) = match owned_fields.first() {
+        Some(Member::Unnamed(_)) => (
+            quote! { #vis struct #owned(#(#vis #owned_types),*); },
+            quote! { #owned(#(::core::clone::Clone::clone(&*self.#owned_fields)),*) },
+        ),
+        _ => (
+            quote! { #vis struct #owned { #(#vis #owned_fields: #owned_types,)* } },
+            quote! {
+                #owned {
+                    #(#owned_fields: ::core::clone::Clone::clone(&*self.#owned_fields),)*
+                }
+            },
+        ),
+    };
+
+    Ok(quote! {
+        #[derive(::hecs_component_provider::hecs::Bundle, ::hecs_component_provider::ComponentProvider)]
+        #definition
+
+        impl<'a> #ident<'a> {
+            /// Clones the borrowed components into an owned bundle
+            #vis fn to_bundle(&self) -> #owned {
+                #construction
+            }
+        }
+
+        impl<'a> ::hecs_component_provider::OwnedQuery for #ident<'a> {
+            type Owned = #owned;
+
+            fn to_owned_item(item: &#ident<'_>) -> #owned {
+                item.to_bundle()
+            }
+        }
+    })
+}
+
+fn derive_readonly(input: DeriveInput) -> Result<TokenStream2> {
+    let generics = input.generics.clone();
+    let data = input.data.clone();
+    let InputDecomposition {
+        ident,
+        vis,
+        fields,
+        types,
+        option_types,
+        or_types,
+        struct_type,
+        options,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let readonly = match options.readonly_as {
+        Some(readonly) => readonly,
+        None => return Ok(quote! {}),
+    };
+    if let StructType::Bundle = struct_type {
+        return Err(Error::new_spanned(
+            readonly,
+            "the readonly_as option is only supported in query structs",
+        ));
+    }
+    let data_fields = match data {
+        syn::Data::Struct(s) => s.fields,
+        _ => unreachable!("decomposition only succeeds for structs"),
+    };
+
+    let mut conversions = Vec::new();
+    for (field, ty, option_type, or_type) in izip!(&fields, &types, option_types, or_types) {
+        let conversion = if option_type.is_some() {
+            quote! { item.#field.map(|c| &*c) }
+        } else if or_type.is_some() {
+            quote! { item.#field.map(|c| &*c, |c| &*c) }
+        } else if matches!(ty, Type::Reference(_)) || !is_mutable_type_ref(ty) {
+            // `&'a mut T` coerces to `&'a T`
+            quote! { item.#field }
+        } else {
+            return Err(error_with_help(
+                ty,
+                "this field can't be converted to a read-only field",
+                "use `&'a mut T`, `Option<&'a mut T>` or `Or<&'a mut A, &'a mut B>`",
+            ));
+        };
+        conversions.push(conversion);
+    }
+
+    let definition_fields = data_fields.iter().map(|f| {
+        let syn::Field {
+            attrs, vis, ident, ..
+        } = f;
+        let ty = remove_type_mutability(&f.ty);
+        match ident {
+            Some(ident) => quote! { #(#attrs)* #vis #ident: #ty },
+            None => quote! { #(#attrs)* #vis #ty },
+        }
+    });
+    let definition = match data_fields {
+        syn::Fields::Named(_) => {
+            quote! { #vis struct #readonly #generics { #(#definition_fields,)* } }
+        }
+        _ => quote! { #vis struct #readonly #generics (#(#definition_fields),*); },
+    };
+    let mut struct_options = Vec::new();
+    if options.no_inline {
+        struct_options.push(quote! { no_inline });
+    }
+    if options.dynamic {
+        struct_options.push(quote! { dynamic });
+    }
+    let struct_options = if struct_options.is_empty() {
+        quote! {}
+    } else {
+        quote! { #[provider(#(#struct_options),*)] }
+    };
+
+    // tuple structs can also be constructed with their indices as field names
+    Ok(quote! {
+        #[derive(::hecs_component_provider::hecs::Query, ::hecs_component_provider::ComponentProvider)]
+        #struct_options
+        #definition
+
+        impl<'a> ::core::convert::From<#ident<'a>> for #readonly<'a> {
+            fn from(item: #ident<'a>) -> Self {
+                #readonly {
+                    #(#fields: #conversions,)*
+                }
+            }
+        }
+    })
+}
+
+fn derive_new_partial(input: DeriveInput) -> Result<TokenStream2> {
+    let data = input.data.clone();
+    let InputDecomposition { ident, vis, .. } = decompose_derive_input(input)?;
+
+    let data_fields = match data {
+        syn::Data::Struct(s) => s.fields,
+        _ => unreachable!("decomposition only succeeds for structs"),
+    };
+    // fields without an init expression become parameters, in declaration order
+    let mut params = Vec::new();
+    let mut values = Vec::new();
+    let mut has_init = false;
+    for (i, field) in data_fields.iter().enumerate() {
+        match parse_field_options(&field.attrs)?.init {
+            Some((_, init)) => {
+                has_init = true;
+                values.push(quote! { #init });
+            }
+            None => {
+                let ty = &field.ty;
+                let param = match &field.ident {
+                    Some(ident) => ident.clone(),
+                    None => format_ident!("field_{}", i),
+                };
+                params.push(quote! { #param: #ty });
+                values.push(quote! { #param });
+            }
+        }
+    }
+    if !has_init {
+        return Ok(quote! {});
+    }
+
+    let construction = match &data_fields {
+        syn::Fields::Named(_) => {
+            let names = data_fields.iter().map(|f| &f.ident);
+            quote! { #ident { #(#names: #values,)* } }
+        }
+        _ => quote! { #ident(#(#values),*) },
+    };
+
+    Ok(quote! {
+        impl #ident {
+            /// Constructs the bundle from the fields without an `init` expression, evaluating the
+            /// expressions for the others
+            #[allow(clippy::too_many_arguments)]
+            #vis fn new_partial(#(#params),*) -> Self {
+                #construction
+            }
+        }
+    })
+}
+
+fn derive_validate(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        struct_type,
+        options,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let validate = match options.validate {
+        Some(validate) => validate,
+        None => return Ok(quote! {}),
+    };
+    let impl_header = match struct_type {
+        StructType::Bundle => quote! { impl ::hecs_component_provider::Validate for #ident },
+        StructType::Query => {
+            quote! { impl<'a> ::hecs_component_provider::Validate for #ident<'a> }
+        }
+    };
+
+    Ok(quote! {
+        #impl_header {
+            fn validate(
+                &self,
+            ) -> ::core::result::Result<(), ::hecs_component_provider::ValidationError> {
+                #validate(self)
+            }
+        }
+    })
+}
+
+fn derive_builder(input: DeriveInput) -> Result<TokenStream2> {
+    let data = input.data.clone();
+    let InputDecomposition {
+        ident,
+        vis,
+        struct_type,
+        options,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let builder = match options.builder {
+        Some(builder) => builder,
+        None => return Ok(quote! {}),
+    };
+    if let StructType::Query = struct_type {
+        return Err(error_with_help(
+            builder,
+            "the builder option is only supported in bundle structs",
+            "query fields are borrowed from the world, modify them through the providers instead",
+        ));
+    }
+    let named = match data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(named),
+            ..
+        }) => named,
+        _ => {
+            return Err(error_with_help(
+                builder,
+                "the builder option is only supported in structs with named fields",
+                "name the fields, since the methods are named after them",
+            ))
+        }
+    };
+
+    let methods = named.named.iter().map(|field| {
+        let field_ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let with = format_ident!("with_{}", field_ident.unraw());
+        let map = format_ident!("map_{}", field_ident.unraw());
+        let with_doc = format!("Returns the bundle with `{}` replaced", field_ident.unraw());
+        let map_doc = format!(
+            "Returns the bundle with `{}` mapped by `f`",
+            field_ident.unraw()
+        );
+        quote! {
+            #[doc = #with_doc]
+            #[must_use]
+            #vis fn #with(mut self, #field_ident: #ty) -> Self {
+                self.#field_ident = #field_ident;
+                self
+            }
+
+            #[doc = #map_doc]
+            #[must_use]
+            #vis fn #map(mut self, f: impl ::core::ops::FnOnce(#ty) -> #ty) -> Self {
+                self.#field_ident = f(self.#field_ident);
+                self
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #ident {
+            #(#methods)*
+        }
+    })
+}
+
+fn derive_thread_safety(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ref_types,
+        option_types,
+        or_types,
+        component_types,
+        struct_type,
+        options,
+        ..
+    } = decompose_derive_input(input)?;
+
+    if options.non_send {
+        return Ok(quote! {});
+    }
+    // the components added to or borrowed from entities, which hecs requires to be
+    // `Send + Sync + 'static`, so that a violation is reported at the field rather than where the
+    // struct is spawned or queried. Query fields are written with the struct's lifetime in place of
+    // `'static`, e.g. `&'a &'a str`, so only bundle components are checked for `'static`.
+    let (generics, bounds, components): (_, _, Vec<Type>) = match struct_type {
+        StructType::Bundle => (quote! {}, quote! { + 'static }, component_types),
+        StructType::Query => (
+            quote! { <'a> },
+            quote! {},
+            izip!(ref_types, option_types, or_types)
+                .flat_map(|(ref_type, option_type, or_types)| {
+                    let or_types = or_types
+                        .into_iter()
+                        .flat_map(|(l, r)| vec![extract_ref_type(&l), extract_ref_type(&r)]);
+                    ref_type
+                        .into_iter()
+                        .chain(option_type)
+                        .chain(or_types.flatten())
+                })
+                .collect(),
+        ),
+    };
+    let assertions = components.iter().map(|ty| {
+        quote_spanned! {ty.span()=>
+            component_must_be_send_and_sync::<#ty>();
+        }
+    });
+
+    Ok(quote! {
+        const _: () = {
+            fn component_must_be_send_and_sync<
+                T: ?::core::marker::Sized + ::core::marker::Send + ::core::marker::Sync #bounds,
+            >() {
+            }
+
+            #[allow(dead_code)]
+            fn assert_components #generics () {
+                #(#assertions)*
+            }
+        };
+    })
+}
+
+fn derive_access_wrapper_check(input: DeriveInput) -> Result<TokenStream2> {
+    let InputDecomposition {
+        ident,
+        types,
+        access_types,
+        struct_type,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let wrapped = izip!(&types, &access_types).find(|(_, access_type)| access_type.is_some());
+    let wrapped = match (struct_type, wrapped) {
+        (StructType::Bundle, Some((wrapped, _))) => wrapped,
+        _ => return Ok(quote! {}),
+    };
+    // `derive(hecs::Bundle)` would add the wrappers themselves as components rather than their
+    // contents, so a bundle with access wrappers must only be spawned through `IntoEntityBuilder`.
+    // Derive macros can't see each other, so the bundle impl is detected by making the trait
+    // selection below ambiguous if it exists.
+    let span = wrapped.span();
+    Ok(quote_spanned! {span=>
+        const _: () = {
+            trait AccessWrapperFieldsConflictWithHecsBundle<A> {
+                fn check() {}
+            }
+
+            impl<T: ?::core::marker::Sized> AccessWrapperFieldsConflictWithHecsBundle<()> for T {}
+
+            impl<T: ?::core::marker::Sized + ::hecs_component_provider::hecs::DynamicBundle>
+                AccessWrapperFieldsConflictWithHecsBundle<u8> for T
+            {
+            }
+
+            #[allow(dead_code)]
+            fn assert_not_hecs_bundle() {
+                <#ident as AccessWrapperFieldsConflictWithHecsBundle<_>>::check();
+            }
+        };
+    })
+}
+
+fn derive_arbitrary(input: DeriveInput) -> Result<TokenStream2> {
+    let data = input.data.clone();
+    let InputDecomposition {
+        ident,
+        struct_type,
+        options,
+        ..
+    } = decompose_derive_input(input)?;
+
+    let arbitrary = match options.arbitrary {
+        Some(arbitrary) => arbitrary,
+        None => return Ok(quote! {}),
+    };
+    if let StructType::Query = struct_type {
+        return Err(error_with_help(
+            arbitrary,
+            "the arbitrary option is only supported in bundle structs",
+            "derive Arbitrary for the bundle struct that is spawned instead",
+        ));
+    }
+    let fields = match data {
+        syn::Data::Struct(s) => s.fields,
+        _ => unreachable!("checked by decompose_derive_input"),
+    };
+    let value = |field: &syn::Field| {
+        let ty = &field.ty;
+        quote! {
+            <#ty as ::hecs_component_provider::arbitrary::Arbitrary<'__arbitrary>>::arbitrary(u)?
+        }
+    };
+    let construct = match &fields {
+        syn::Fields::Named(named) => {
+            let fields = named.named.iter().map(|field| {
+                let field_ident = &field.ident;
+                let value = value(field);
+                quote! { #field_ident: #value }
+            });
+            quote! { #ident { #(#fields),* } }
+        }
+        syn::Fields::Unnamed(unnamed) => {
+            let values = unnamed.unnamed.iter().map(value);
+            quote! { #ident(#(#values),*) }
+        }
+        syn::Fields::Unit => quote! { #ident },
+    };
+
+    Ok(quote! {
+        impl<'__arbitrary> ::hecs_component_provider::arbitrary::Arbitrary<'__arbitrary> for #ident {
+            fn arbitrary(
+                u: &mut ::hecs_component_provider::arbitrary::Unstructured<'__arbitrary>,
+            ) -> ::hecs_component_provider::arbitrary::Result<Self> {
+                ::core::result::Result::Ok(#construct)
+            }
+        }
+    })
+}
+
+fn not_a_struct_error(token: impl quote::ToTokens) -> Error {
+    error_with_help(
+        token,
+        "derive(ComponentProvider) may only be applied to structs",
+        "use a query struct with one lifetime, e.g. `struct MyQuery<'a>`, or a bundle struct \
+         without lifetimes",
+    )
+}
+
+fn is_entity_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Entity"),
+        _ => false,
+    }
+}
+
+fn member_name(member: &Member) -> String {
+    match member {
+        Member::Named(ident) => ident.unraw().to_string(),
+        Member::Unnamed(index) => index.index.to_string(),
+    }
+}
+
+/// Returns the type of a query field reborrowed for a split part, and the expression reborrowing it
+fn split_query_field(ty: &Type, field: &Ident) -> Result<(TokenStream2, TokenStream2)> {
+    let ty = remove_type_lifetime(ty);
+    if let Some(elem) = extract_ref_type(&ty) {
+        return Ok(if is_mutable_type_ref(&ty) {
+            (quote! { &'a mut #elem }, quote! { &mut *self.#field })
+        } else {
+            (quote! { &'a #elem }, quote! { self.#field })
+        });
+    }
+    if let Some(elem) = extract_option_type(&ty) {
+        return Ok(if is_mutable_type_ref(&ty) {
+            (
+                quote! { Option<&'a mut #elem> },
+                quote! { self.#field.as_deref_mut() },
+            )
+        } else {
+            (quote! { Option<&'a #elem> }, quote! { self.#field })
+        });
+    }
+    Err(error_with_help(
+        ty,
+        "only `&T`, `&mut T`, `Option<&T>` and `Option<&mut T>` fields can be split",
+        "remove this field from the split groups",
+    ))
+}
+
+#[derive(Default)]
+struct ProviderOptions {
+    split: Option<Vec<SplitGroup>>,
+    owned: Option<Ident>,
+    readonly_as: Option<Ident>,
+    validate: Option<syn::Path>,
+    builder: Option<Ident>,
+    arbitrary: Option<Ident>,
+    promote: Vec<Promotion>,
+    no_inline: bool,
+    non_send: bool,
+    dynamic: bool,
+}
+
+struct SplitGroup {
+    ident: Ident,
+    fields: Vec<Ident>,
+}
+
+/// `promote(Lower, extra = [a, b])`
+struct Promotion {
+    ident: Ident,
+    lower: Type,
+    extra: Vec<Ident>,
+}
+
+impl Parse for SplitGroup {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let content;
+        syn::bracketed!(content in input);
+        let fields = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+        Ok(SplitGroup {
+            ident,
+            fields: fields.into_iter().collect(),
+        })
+    }
+}
+
+fn parse_options(attrs: &[Attribute]) -> Result<ProviderOptions> {
+    let mut options = ProviderOptions::default();
+
+    for attr in attrs.iter().filter(|a| a.path.is_ident("provider")) {
+        attr.parse_args_with(|input: ParseStream| {
+            let items = Punctuated::<ProviderOption, Token![,]>::parse_terminated(input)?;
+            for item in items {
+                match item {
+                    ProviderOption::Split(ident, groups) => {
+                        if options.split.is_some() {
+                            return Err(Error::new_spanned(ident, "duplicate split option"));
+                        }
+                        options.split = Some(groups);
+                    }
+                    ProviderOption::NoInline => options.no_inline = true,
+                    ProviderOption::NonSend => options.non_send = true,
+                    ProviderOption::Builder(ident) => options.builder = Some(ident),
+                    ProviderOption::Arbitrary(ident) => options.arbitrary = Some(ident),
+                    ProviderOption::Dynamic => options.dynamic = true,
+                    ProviderOption::Promote(promotion) => options.promote.push(*promotion),
+                    ProviderOption::Owned(ident, owned) => {
+                        if options.owned.is_some() {
+                            return Err(Error::new_spanned(ident, "duplicate owned option"));
+                        }
+                        options.owned = Some(owned);
+                    }
+                    ProviderOption::ReadonlyAs(ident, readonly) => {
+                        if options.readonly_as.is_some() {
+                            return Err(Error::new_spanned(ident, "duplicate readonly_as option"));
+                        }
+                        options.readonly_as = Some(readonly);
+                    }
+                    ProviderOption::Validate(ident, validate) => {
+                        if options.validate.is_some() {
+                            return Err(Error::new_spanned(ident, "duplicate validate option"));
+                        }
+                        options.validate = Some(validate);
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(options)
+}
+
+enum ProviderOption {
+    Split(Ident, Vec<SplitGroup>),
+    Owned(Ident, Ident),
+    ReadonlyAs(Ident, Ident),
+    Validate(Ident, syn::Path),
+    Builder(Ident),
+    Arbitrary(Ident),
+    Promote(Box<Promotion>),
+    NoInline,
+    NonSend,
+    Dynamic,
+}
+
+impl Parse for ProviderOption {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident: Ident = input.parse()?;
+        if ident == "split" {
+            let content;
+            syn::parenthesized!(content in input);
+            let groups = Punctuated::<SplitGroup, Token![,]>::parse_terminated(&content)?;
+            Ok(ProviderOption::Split(ident, groups.into_iter().collect()))
+        } else if ident == "no_inline" {
+            Ok(ProviderOption::NoInline)
+        } else if ident == "non_send" {
+            Ok(ProviderOption::NonSend)
+        } else if ident == "builder" {
+            Ok(ProviderOption::Builder(ident))
+        } else if ident == "arbitrary" {
+            Ok(ProviderOption::Arbitrary(ident))
+        } else if ident == "dynamic" {
+            Ok(ProviderOption::Dynamic)
+        } else if ident == "promote" {
+            let content;
+            syn::parenthesized!(content in input);
+            let lower = content.parse()?;
+            content.parse::<Token![,]>()?;
+            let extra_ident: Ident = content.parse()?;
+            if extra_ident != "extra" {
+                return Err(error_with_help(
+                    extra_ident,
+                    "expected `extra`",
+                    "list the fields that the lower bundle doesn't have, like \
+                     `promote(Corpse, extra = [health, brain])`",
+                ));
+            }
+            content.parse::<Token![=]>()?;
+            let fields;
+            syn::bracketed!(fields in content);
+            let extra = Punctuated::<Ident, Token![,]>::parse_terminated(&fields)?;
+            Ok(ProviderOption::Promote(Box::new(Promotion {
+                ident,
+                lower,
+                extra: extra.into_iter().collect(),
+            })))
+        } else if ident == "owned" {
+            let content;
+            syn::parenthesized!(content in input);
+            let owned = content.parse()?;
+            Ok(ProviderOption::Owned(ident, owned))
+        } else if ident == "readonly_as" {
+            input.parse::<Token![=]>()?;
+            let name: syn::LitStr = input.parse()?;
+            let readonly = name.parse().map_err(|_| {
+                error_with_help(
+                    &name,
+                    "expected the name of the read-only struct",
+                    "use a name like `readonly_as = \"MyQueryRead\"`",
+                )
+            })?;
+            Ok(ProviderOption::ReadonlyAs(ident, readonly))
+        } else if ident == "validate" {
+            input.parse::<Token![=]>()?;
+            let path: syn::LitStr = input.parse()?;
+            let validate = path.parse().map_err(|_| {
+                error_with_help(
+                    &path,
+                    "expected the path of a validation function",
+                    "use a path like `validate = \"check_health\"`",
+                )
+            })?;
+            Ok(ProviderOption::Validate(ident, validate))
+        } else {
+            Err(Error::new_spanned(
+                ident,
+                "unknown provider option, expected `split`, `owned`, `readonly_as`, `validate`, \
+                 `builder`, `arbitrary`, `promote`, `no_inline`, `non_send` or `dynamic`",
+            ))
+        }
+    }
+}
+
+/// Parses the `#[provider(...)]` options on a field, e.g. `#[provider(nested(A, B))]`
+fn parse_field_options(attrs: &[Attribute]) -> Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+
+    for attr in attrs.iter().filter(|a| a.path.is_ident("provider")) {
+        attr.parse_args_with(|input: ParseStream| loop {
+            parse_field_option(input, &mut options)?;
+            if input.is_empty() {
+                return Ok(());
+            }
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                return Ok(());
+            }
+        })?;
+    }
+
+    if let (Some(_), Some(alias)) = (&options.nested, options.aliases.first()) {
+        return Err(Error::new_spanned(
+            alias,
+            "nested fields can't have aliases, alias the nested bundle's fields instead",
+        ));
+    }
+    if let (Some(_), Some(cold)) = (&options.nested, &options.cold) {
+        return Err(error_with_help(
+            cold,
+            "nested fields can't be cold",
+            "mark the nested bundle's fields as cold instead",
+        ));
+    }
+    if let (Some(_), Some((assert, _, _))) = (&options.nested, &options.assert) {
+        return Err(error_with_help(
+            assert,
+            "nested fields can't have assertions",
+            "add the assertion to the nested bundle's fields instead",
+        ));
+    }
+    let replication = options
+        .replication
+        .as_ref()
+        .or(options.quantize.as_ref().map(|(ident, _)| ident));
+    if let (Some(_), Some(replication)) = (&options.nested, replication) {
+        return Err(error_with_help(
+            replication,
+            "nested fields can't have replication options",
+            "mark the nested bundle's fields instead",
+        ));
+    }
+
+    Ok(options)
+}
+
+fn parse_field_option(input: ParseStream, options: &mut FieldOptions) -> Result<()> {
+    let ident: Ident = input.parse()?;
+    if ident == "cold" {
+        if options.cold.is_some() {
+            return Err(Error::new_spanned(ident, "duplicate cold option"));
+        }
+        options.cold = Some(ident);
+        return Ok(());
+    }
+    if ident == "replicate" || ident == "server_only" {
+        if options.replication.is_some() {
+            return Err(Error::new_spanned(ident, "duplicate replication option"));
+        }
+        options.replication = Some(ident);
+        return Ok(());
+    }
+    if ident == "assert" {
+        if options.assert.is_some() {
+            return Err(Error::new_spanned(ident, "duplicate assert option"));
+        }
+        input.parse::<Token![=]>()?;
+        let predicate: syn::LitStr = input.parse()?;
+        let expr = predicate.parse().map_err(|_| {
+            error_with_help(
+                &predicate,
+                "expected a predicate",
+                "use an expression referring to the component as `self`, like \
+                 `assert = \"self.0 >= 0.0\"`",
+            )
+        })?;
+        options.assert = Some((ident, predicate, expr));
+        return Ok(());
+    }
+    if ident == "init" {
+        if options.init.is_some() {
+            return Err(Error::new_spanned(ident, "duplicate init option"));
+        }
+        input.parse::<Token![=]>()?;
+        let expr: syn::LitStr = input.parse()?;
+        let expr = expr.parse().map_err(|_| {
+            error_with_help(
+                &expr,
+                "expected an expression",
+                "use an expression like `init = \"Vec2::ZERO\"`",
+            )
+        })?;
+        options.init = Some((ident, expr));
+        return Ok(());
+    }
+    let content;
+    syn::parenthesized!(content in input);
+    if ident == "quantize" {
+        if options.quantize.is_some() {
+            return Err(Error::new_spanned(ident, "duplicate quantize option"));
+        }
+        options.quantize = Some((ident.clone(), parse_quantize(&ident, &content)?));
+        return Ok(());
+    }
+    let types = Punctuated::<Type, Token![,]>::parse_terminated(&content)?;
+    if ident == "nested" {
+        if options.nested.is_some() {
+            return Err(Error::new_spanned(ident, "duplicate nested option"));
+        }
+        options.nested = Some(types.into_iter().collect());
+    } else if ident == "alias" {
+        options.aliases.extend(types);
+    } else {
+        return Err(Error::new_spanned(
+            ident,
+            "unknown provider field option, expected `nested`, `alias`, `cold`, `replicate`, \
+             `server_only`, `quantize`, `init` or `assert`",
+        ));
+    }
+    Ok(())
+}
+
+/// Parses the contents of `quantize(bits = 12, range = "-512.0..512.0")`
+fn parse_quantize(ident: &Ident, content: ParseStream) -> Result<Quantize> {
+    let mut bits = None;
+    let mut range = None;
+    for meta in Punctuated::<syn::MetaNameValue, Token![,]>::parse_terminated(content)? {
+        match &meta.lit {
+            syn::Lit::Int(lit) if meta.path.is_ident("bits") => {
+                let value: u32 = lit.base10_parse()?;
+                if !(1..=32).contains(&value) {
+                    return Err(Error::new_spanned(lit, "bits must be between 1 and 32"));
+                }
+                bits = Some(value);
+            }
+            syn::Lit::Str(lit) if meta.path.is_ident("range") => {
+                let error = || {
+                    error_with_help(
+                        lit,
+                        "invalid quantization range",
+                        "expected a range of floats like \"-512.0..512.0\"",
+                    )
+                };
+                let value = lit.value();
+                let (min, max) = value.split_once("..").ok_or_else(error)?;
+                let min: f32 = min.trim().parse().map_err(|_| error())?;
+                let max: f32 = max.trim().parse().map_err(|_| error())?;
+                if !(min.is_finite() && max.is_finite() && min < max) {
+                    return Err(error());
+                }
+                range = Some((min, max));
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    meta,
+                    "unknown quantize option, expected `bits = <integer>` or `range = \"<min>..<max>\"`",
+                ))
+            }
+        }
+    }
+    match (bits, range) {
+        (Some(bits), Some((min, max))) => Ok(Quantize { bits, min, max }),
+        _ => Err(Error::new_spanned(
+            ident,
+            "quantize requires both `bits` and `range`",
+        )),
+    }
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    nested: Option<Vec<Type>>,
+    aliases: Vec<Type>,
+    // `replicate` or `server_only`
+    replication: Option<Ident>,
+    quantize: Option<(Ident, Quantize)>,
+    cold: Option<Ident>,
+    init: Option<(Ident, syn::Expr)>,
+    assert: Option<(Ident, syn::LitStr, syn::Expr)>,
+}
+
+#[derive(Clone, Copy)]
+struct Quantize {
+    bits: u32,
+    min: f32,
+    max: f32,
+}
+
+struct InputDecomposition {
+    ident: Ident,
+    vis: Visibility,
+    fields: Vec<Member>,
+    types: Vec<Type>,
+    ref_types: Vec<Option<Type>>,
+    option_types: Vec<Option<Type>>,
+    unsized_types: Vec<Option<Type>>,
+    or_types: Vec<Option<(Type, Type)>>,
+    access_types: Vec<Option<(Access, Type)>>,
+    // fields marked `#[provider(nested(...))]`, which are excluded from `fields` and `types`
+    nested: Vec<(Member, Type, Vec<Type>)>,
+    // components declared with `#[provider(alias(...))]` for each field in `fields`
+    aliases: Vec<Vec<Type>>,
+    // whether each field in `fields` is marked `#[provider(replicate)]`
+    replicated: Vec<bool>,
+    // the `#[provider(quantize(...))]` option of each field in `fields`
+    quantize: Vec<Option<Quantize>>,
+    // the type each field in `fields` is added to entities as, which is `Cold<T>` for bundle
+    // fields marked `#[provider(cold)]`
+    component_types: Vec<Type>,
+    // whether each field in `fields` is a bundle field marked `#[provider(cold)]`
+    cold: Vec<bool>,
+    // `T` for query fields of type `&Cold<T>` or `&mut Cold<T>`
+    cold_types: Vec<Option<Type>>,
+    // the `#[provider(assert = "...")]` predicate of each field in `fields`, with its source
+    asserts: Vec<Option<(syn::LitStr, syn::Expr)>>,
+    // attribute applied to generated getters, unless disabled with `#[provider(no_inline)]`
+    inline: TokenStream2,
+    struct_type: StructType,
+    options: ProviderOptions,
+}
+
+enum Access {
+    Read,
+    Write,
+}
+
+enum StructType {
+    Bundle,
+    Query,
+}
+
+fn decompose_derive_input(input: DeriveInput) -> Result<InputDecomposition> {
+    let options = parse_options(&input.attrs)?;
+    let inline = if options.no_inline {
+        quote! {}
+    } else {
+        quote! { #[inline] }
+    };
+    let ident = input.ident;
+    let vis = input.vis;
+    let data = match input.data {
+        syn::Data::Struct(s) => s,
+        syn::Data::Enum(e) => return Err(not_a_struct_error(e.enum_token)),
+        syn::Data::Union(u) => return Err(not_a_struct_error(u.union_token)),
+    };
+
+    let lifetimes: Vec<_> = input.generics.lifetimes().cloned().collect();
+    let lifetime_errors = lifetimes.iter().skip(1).map(|lifetime| {
+        error_with_help(
+            lifetime,
+            "must have <= 1 lifetime parameter",
+            &format!(
+                "query structs borrow every component for the same lifetime, replace `{}` with `{}`",
+                lifetime.lifetime, lifetimes[0].lifetime
+            ),
+        )
+    });
+    let param_errors = input
+        .generics
+        .params
+        .iter()
+        .filter(|param| !matches!(param, syn::GenericParam::Lifetime(_)))
+        .map(|param| {
+            error_with_help(
+                param,
+                "must have no type parameters",
+                "providers are implemented for concrete component types, replace the parameter \
+                 with the component type",
+            )
+        });
+    if let Some(error) = combine_errors(lifetime_errors.chain(param_errors)) {
+        return Err(error);
+    }
+
+    let all_fields: Vec<_> = match data.fields {
+        syn::Fields::Named(ref fields) => fields
+            .named
+            .iter()
+            .map(|f| (Member::Named(f.ident.clone().unwrap()), f))
+            .collect(),
+        syn::Fields::Unnamed(ref fields) => fields
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                (
+                    Member::Unnamed(syn::Index {
+                        index: i as u32,
+                        span: f.ty.span(),
+                    }),
+                    f,
+                )
+            })
+            .collect(),
+        syn::Fields::Unit => Vec::new(),
+    };
+
+    let struct_type = if lifetimes.is_empty() {
+        StructType::Bundle
+    } else {
+        StructType::Query
+    };
+
+    let mut fields = Vec::new();
+    let mut types = Vec::new();
+    let mut nested = Vec::new();
+    let mut aliases = Vec::new();
+    let mut replicated = Vec::new();
+    let mut quantize = Vec::new();
+    let mut cold = Vec::new();
+    let mut asserts = Vec::new();
+    for (member, field) in all_fields {
+        let field_options = parse_field_options(&field.attrs)?;
+        if let (StructType::Query, Some((assert, _, _))) = (&struct_type, &field_options.assert) {
+            let ty = &field.ty;
+            if !matches!(ty, Type::Reference(r) if r.mutability.is_some()) {
+                return Err(error_with_help(
+                    assert,
+                    "assertions are only supported on `&'a mut T` query fields",
+                    "assertions are checked after the component is borrowed mutably",
+                ));
+            }
+        }
+        if let (StructType::Query, Some((init, _))) = (&struct_type, &field_options.init) {
+            return Err(error_with_help(
+                init,
+                "init expressions are only supported in bundle structs",
+                "remove the init option, query fields are borrowed from the world",
+            ));
+        }
+        if let (StructType::Query, Some(cold)) = (&struct_type, &field_options.cold) {
+            return Err(error_with_help(
+                cold,
+                "cold fields are only supported in bundle structs",
+                "query the field as `&'a Cold<T>` instead",
+            ));
+        }
+        let replication = field_options
+            .replication
+            .as_ref()
+            .or(field_options.quantize.as_ref().map(|(ident, _)| ident));
+        if let (StructType::Query, Some(replication)) = (&struct_type, replication) {
+            return Err(error_with_help(
+                replication,
+                "replication options are only supported in bundle structs",
+                "mark the fields of the bundle struct that is spawned instead",
+            ));
+        }
+        match field_options.nested {
+            Some(components) => {
+                if let StructType::Query = struct_type {
+                    return Err(error_with_help(
+                        &field.ty,
+                        "nested providers are only supported in bundle structs",
+                        "query the nested bundle's components as separate fields instead",
+                    ));
+                }
+                nested.push((member, field.ty.clone(), components));
+            }
+            None => {
+                fields.push(member);
+                types.push(field.ty.clone());
+                aliases.push(field_options.aliases);
+                replicated.push(
+                    field_options
+                        .replication
+                        .is_some_and(|replication| replication == "replicate"),
+                );
+                quantize.push(field_options.quantize.map(|(_, quantize)| quantize));
+                cold.push(field_options.cold.is_some());
+                asserts.push(
+                    field_options
+                        .assert
+                        .map(|(_, predicate, expr)| (predicate, expr)),
+                );
+            }
+        }
+    }
+
+    let access_types: Vec<_> = match struct_type {
+        StructType::Bundle => types.iter().map(extract_access_type).collect(),
+        StructType::Query => types.iter().map(|_| None).collect(),
+    };
+
+    // access wrappers only exist on the bundle struct, entities store their contents
+    let component_types: Vec<Type> = izip!(&types, &access_types, &cold)
+        .map(|(t, access_type, &cold)| {
+            let t = access_type.as_ref().map_or(t, |(_, inner)| inner);
+            if cold {
+                syn::parse_quote!(::hecs_component_provider::Cold<#t>)
+            } else {
+                t.clone()
+            }
+        })
+        .collect();
+
+    let ref_types: Vec<_> = types.iter().map(extract_ref_type).collect();
+    let cold_types: Vec<_> = ref_types
+        .iter()
+        .map(|t| t.as_ref().and_then(extract_cold_type))
+        .collect();
+    let option_types: Vec<_> = types.iter().map(extract_option_type).collect();
+    let or_types: Vec<_> = types.iter().map(extract_or_types).collect();
+
+    if let StructType::Query = struct_type {
+        if let Some(t) = ref_types
+            .iter()
+            .flatten()
+            .find(|t| matches!(t, Type::TraitObject(_)))
+        {
+            return Err(error_with_help(
+                t,
+                "trait object components must be boxed",
+                &format!(
+                    "store the component as `Box<{}>` and query it as `&'a Box<{}>`",
+                    quote! { #t },
+                    quote! { #t }
+                ),
+            ));
+        }
+    }
+
+    let unsized_types: Vec<_> = match struct_type {
+        StructType::Bundle => types.iter().map(extract_boxed_unsized_type).collect(),
+        StructType::Query => ref_types
+            .iter()
+            .map(|t| t.as_ref().and_then(extract_boxed_unsized_type))
+            .collect(),
+    };
+
+    Ok(InputDecomposition {
+        ident,
+        vis,
+        fields,
+        types,
+        ref_types,
+        option_types,
+        unsized_types,
+        or_types,
+        access_types,
+        nested,
+        aliases,
+        replicated,
+        quantize,
+        component_types,
+        cold,
+        cold_types,
+        asserts,
+        inline,
+        struct_type,
+        options,
+    })
+}
+
+fn extract_ref_type(t: &Type) -> Option<Type> {
+    match t {
+        Type::Reference(type_reference) => Some(type_reference.elem.as_ref().clone()),
+        _ => None,
+    }
+}
+
+fn extract_option_type(t: &Type) -> Option<Type> {
+    if let Type::Path(type_path) = t {
+        let segment = type_path.path.segments.first()?;
+        if segment.ident == "Option" {
+            if let PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments {
+                args, ..
+            }) = &segment.arguments
             {
                 if let Some(syn::GenericArgument::Type(t)) = args.first() {
                     return extract_ref_type(t);
@@ -298,6 +2627,122 @@ fn extract_option_type(t: &Type) -> Option<Type> {
     None
 }
 
+/// The last segment of a type's path if it may name one of this crate's wrapper types, i.e. if
+/// the path is unqualified or goes through `hecs_component_provider`
+///
+/// Other types with the same name as a wrapper can then be used by a qualified path like
+/// `self::Cold<T>`. The code generated for wrappers only compiles for the crate's own types, so
+/// any other type that is matched here fails to compile rather than being reinterpreted.
+fn crate_type_segment(t: &Type) -> Option<&syn::PathSegment> {
+    if let Type::Path(type_path) = t {
+        if type_path.qself.is_some() {
+            return None;
+        }
+        let segments = &type_path.path.segments;
+        let qualified_by_crate =
+            segments.len() == 2 && segments[0].ident == "hecs_component_provider";
+        if segments.len() == 1 || qualified_by_crate {
+            return segments.last();
+        }
+    }
+    None
+}
+
+/// Extracts the contents of a `Read<T>` or `Write<T>` bundle field
+///
+/// The generated code requires the wrapper to implement `AccessWrapper`.
+fn extract_access_type(t: &Type) -> Option<(Access, Type)> {
+    let segment = crate_type_segment(t)?;
+    let access = if segment.ident == "Read" {
+        Access::Read
+    } else if segment.ident == "Write" {
+        Access::Write
+    } else {
+        return None;
+    };
+    if let PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. }) =
+        &segment.arguments
+    {
+        if let Some(syn::GenericArgument::Type(t)) = args.first() {
+            return Some((access, t.clone()));
+        }
+    }
+    None
+}
+
+/// Extracts the reference types `&L` and `&R` of an `Or<&L, &R>` query field
+fn extract_or_types(t: &Type) -> Option<(Type, Type)> {
+    if let Type::Path(type_path) = t {
+        let segment = type_path.path.segments.last()?;
+        if segment.ident == "Or" {
+            if let PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments {
+                args, ..
+            }) = &segment.arguments
+            {
+                if let [syn::GenericArgument::Type(l @ Type::Reference(_)), syn::GenericArgument::Type(r @ Type::Reference(_))] =
+                    args.iter().collect::<Vec<_>>().as_slice()
+                {
+                    return Some((remove_type_lifetime(l), remove_type_lifetime(r)));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Extracts `T` from the `Cold<T>` referent of a query field
+///
+/// The generated code dereferences the field as `hecs_component_provider::Cold<T>`.
+fn extract_cold_type(t: &Type) -> Option<Type> {
+    let segment = crate_type_segment(t)?;
+    if segment.ident == "Cold" {
+        if let PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. }) =
+            &segment.arguments
+        {
+            if let Some(syn::GenericArgument::Type(t)) = args.first() {
+                return Some(t.clone());
+            }
+        }
+    }
+    None
+}
+
+fn extract_boxed_unsized_type(t: &Type) -> Option<Type> {
+    if let Type::Path(type_path) = t {
+        let segment = type_path.path.segments.last()?;
+        if segment.ident == "Box" {
+            if let PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments {
+                args, ..
+            }) = &segment.arguments
+            {
+                match args.first() {
+                    Some(syn::GenericArgument::Type(Type::TraitObject(trait_object))) => {
+                        // match the implicit `'static` bound of the boxed trait object
+                        let mut trait_object = trait_object.clone();
+                        let has_lifetime = trait_object
+                            .bounds
+                            .iter()
+                            .any(|b| matches!(b, syn::TypeParamBound::Lifetime(_)));
+                        if !has_lifetime {
+                            trait_object.bounds.push(syn::parse_quote!('static));
+                        }
+                        // parenthesize so that `&(dyn Trait + 'static)` parses in the generated code
+                        return Some(syn::parse_quote!((#trait_object)));
+                    }
+                    Some(syn::GenericArgument::Type(t @ Type::Slice(_))) => return Some(t.clone()),
+                    Some(syn::GenericArgument::Type(t @ Type::Path(p)))
+                        if p.path.is_ident("str") =>
+                    {
+                        return Some(t.clone())
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    None
+}
+
 fn is_mutable_type_ref(ty: &Type) -> bool {
     struct Visitor {
         is_mut: bool,
@@ -311,7 +2756,7 @@ fn is_mutable_type_ref(ty: &Type) -> bool {
     }
 
     let mut visitor = Visitor { is_mut: false };
-    syn::visit::visit_type(&mut visitor, &ty);
+    syn::visit::visit_type(&mut visitor, ty);
     visitor.is_mut
 }
 
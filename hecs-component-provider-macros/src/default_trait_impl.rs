@@ -1,9 +1,195 @@
-use proc_macro2::TokenStream as TokenStream2;
-use quote::quote;
-use syn::{ItemTrait, Result};
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::{
+    Error, GenericArgument, Ident, ItemTrait, PathArguments, Result, Token, TraitItem, Type,
+    TypeParamBound,
+};
 
-pub(crate) fn generate(input: ItemTrait) -> Result<TokenStream2> {
+pub(crate) fn generate(options: Options, input: ItemTrait) -> Result<TokenStream2> {
     let ident = &input.ident;
     let supertraits = &input.supertraits;
-    Ok(quote! { #input impl<T> #ident for T where T: #supertraits {} })
+    let info = match options.info {
+        Some(info) => generate_info(&info, &input)?,
+        None => quote! {},
+    };
+    let warnings = if options.warn_unused {
+        generate_unused_warnings(&input)
+    } else {
+        quote! {}
+    };
+    Ok(quote! { #input impl<T: ?Sized> #ident for T where T: #supertraits {} #info #warnings })
+}
+
+/// Warns about provider supertraits whose component is never named in the trait's items
+///
+/// This is a heuristic, since a component may be used through type inference alone, e.g. by
+/// passing `self.get()` to a function. Proc macros can't emit warnings on stable Rust, so each
+/// warning is raised by using a deprecated item with the warning as its note.
+fn generate_unused_warnings(input: &ItemTrait) -> TokenStream2 {
+    let mut idents = Vec::new();
+    for item in &input.items {
+        collect_idents(item.to_token_stream(), &mut idents);
+    }
+
+    let mut warnings = TokenStream2::new();
+    for (i, bound) in input.supertraits.iter().enumerate() {
+        let segment = match bound {
+            TypeParamBound::Trait(bound) => match bound.path.segments.last() {
+                Some(segment) => segment,
+                None => continue,
+            },
+            TypeParamBound::Lifetime(_) => continue,
+        };
+        if !is_provider_trait(&segment.ident) {
+            continue;
+        }
+        let component = match &segment.arguments {
+            PathArguments::AngleBracketed(args) if args.args.len() == 1 => &args.args[0],
+            _ => continue,
+        };
+        let name = match component {
+            GenericArgument::Type(Type::Path(path)) => match path.path.segments.last() {
+                Some(segment) => segment.ident.clone(),
+                None => continue,
+            },
+            _ => continue,
+        };
+        if idents.contains(&name) {
+            continue;
+        }
+
+        let note = format!(
+            "`{}` requires `{}<{}>` but never uses `{}`, which needlessly excludes entities \
+             without it; remove the supertrait or annotate where it is used",
+            input.ident, segment.ident, name, name
+        );
+        let warning = format_ident!(
+            "__unused_supertrait_{}_{}",
+            input.ident,
+            i,
+            span = name.span()
+        );
+        warnings.extend(quote_spanned! {name.span()=>
+            const _: () = {
+                #[deprecated(note = #note)]
+                #[allow(non_camel_case_types)]
+                struct #warning;
+                let _ = #warning;
+            };
+        });
+    }
+    warnings
+}
+
+fn is_provider_trait(ident: &Ident) -> bool {
+    ident == "ComponentProvider"
+        || ident == "ComponentProviderMut"
+        || ident == "ComponentProviderOptional"
+        || ident == "ComponentProviderOptionalMut"
+}
+
+fn collect_idents(tokens: TokenStream2, idents: &mut Vec<Ident>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => idents.push(ident),
+            TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+fn generate_info(info: &Ident, input: &ItemTrait) -> Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "behavior info can't be generated for generic traits",
+        ));
+    }
+
+    let vis = &input.vis;
+    let name = input.ident.unraw().to_string();
+    let methods = input.items.iter().filter_map(|item| match item {
+        TraitItem::Method(method) => Some(method.sig.ident.unraw().to_string()),
+        _ => None,
+    });
+
+    let mut access = Vec::new();
+    let mut dependencies = Vec::new();
+    for bound in &input.supertraits {
+        let path = match bound {
+            TypeParamBound::Trait(bound) => &bound.path,
+            TypeParamBound::Lifetime(_) => continue,
+        };
+        let segment = match path.segments.last() {
+            Some(segment) => segment,
+            None => continue,
+        };
+        let (mutable, required) = match segment.ident.unraw().to_string().as_str() {
+            "ComponentProvider" => (false, true),
+            "ComponentProviderMut" => (true, true),
+            "ComponentProviderOptional" => (false, false),
+            "ComponentProviderOptionalMut" => (true, false),
+            _ => {
+                dependencies.push(segment.ident.unraw().to_string());
+                continue;
+            }
+        };
+        let component = match &segment.arguments {
+            PathArguments::AngleBracketed(args) if args.args.len() == 1 => &args.args[0],
+            _ => {
+                return Err(Error::new_spanned(
+                    segment,
+                    "expected a single component type",
+                ))
+            }
+        };
+        access.push(quote! {
+            ::hecs_component_provider::ComponentAccess::new::<#component>(#mutable, #required)
+        });
+    }
+
+    Ok(quote! {
+        /// Describes the behavior, for registration in a
+        /// [`BehaviorRegistry`](::hecs_component_provider::BehaviorRegistry)
+        #vis fn #info() -> ::hecs_component_provider::BehaviorInfo {
+            ::hecs_component_provider::BehaviorInfo::new(
+                #name,
+                ::std::vec![#(#methods),*],
+                ::std::vec![#(#access),*],
+                ::std::vec![#(#dependencies),*],
+            )
+        }
+    })
+}
+
+#[derive(Default)]
+pub(crate) struct Options {
+    info: Option<Ident>,
+    warn_unused: bool,
+}
+
+impl Parse for Options {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut options = Options::default();
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            if ident == "info" {
+                input.parse::<Token![=]>()?;
+                options.info = Some(input.parse()?);
+            } else if ident == "warn_unused" {
+                options.warn_unused = true;
+            } else {
+                return Err(Error::new_spanned(
+                    ident,
+                    "unknown default_trait_impl option, expected `info` or `warn_unused`",
+                ));
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(options)
+    }
 }
@@ -0,0 +1,15 @@
+use quote::ToTokens;
+use syn::Error;
+
+/// Creates an error spanning `tokens`, followed by a suggestion for fixing it
+pub(crate) fn error_with_help(tokens: impl ToTokens, message: &str, help: &str) -> Error {
+    Error::new_spanned(tokens, format!("{}\nhelp: {}", message, help))
+}
+
+/// Combines errors so that they are all reported, returning `None` if there are none
+pub(crate) fn combine_errors(errors: impl IntoIterator<Item = Error>) -> Option<Error> {
+    errors.into_iter().reduce(|mut combined, error| {
+        combined.combine(error);
+        combined
+    })
+}
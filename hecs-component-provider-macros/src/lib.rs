@@ -1,11 +1,15 @@
 use proc_macro::TokenStream;
-use syn::{parse_macro_input, DeriveInput, ItemTrait};
+use syn::{parse_macro_input, DeriveInput, ItemFn, ItemTrait};
 
+mod behavior_test;
 mod component_provider;
 mod default_trait_impl;
+mod error;
+mod reflect;
 mod self_component_provider;
+mod tag;
 
-#[proc_macro_derive(ComponentProvider)]
+#[proc_macro_derive(ComponentProvider, attributes(provider))]
 pub fn component_provider_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -16,7 +20,7 @@ pub fn component_provider_derive(input: TokenStream) -> TokenStream {
     .into()
 }
 
-#[proc_macro_derive(SelfComponentProvider)]
+#[proc_macro_derive(SelfComponentProvider, attributes(self_provider))]
 pub fn self_component_provider_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -27,11 +31,46 @@ pub fn self_component_provider_derive(input: TokenStream) -> TokenStream {
     .into()
 }
 
+#[proc_macro_derive(Tag)]
+pub fn tag_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match tag::derive(input) {
+        Ok(ts) => ts,
+        Err(e) => e.to_compile_error(),
+    }
+    .into()
+}
+
+#[proc_macro_derive(Reflect)]
+pub fn reflect_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match reflect::derive(input) {
+        Ok(ts) => ts,
+        Err(e) => e.to_compile_error(),
+    }
+    .into()
+}
+
 #[proc_macro_attribute]
-pub fn default_trait_impl(_attr: TokenStream, input: TokenStream) -> TokenStream {
+pub fn default_trait_impl(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let options = parse_macro_input!(attr as default_trait_impl::Options);
     let input = parse_macro_input!(input as ItemTrait);
 
-    match default_trait_impl::generate(input) {
+    match default_trait_impl::generate(options, input) {
+        Ok(ts) => ts,
+        Err(e) => e.to_compile_error(),
+    }
+    .into()
+}
+
+#[proc_macro_attribute]
+pub fn behavior_test(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let options = parse_macro_input!(attr as behavior_test::Options);
+    let input = parse_macro_input!(input as ItemFn);
+
+    match behavior_test::generate(options, input) {
         Ok(ts) => ts,
         Err(e) => e.to_compile_error(),
     }
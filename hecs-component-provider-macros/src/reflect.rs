@@ -0,0 +1,88 @@
+use crate::error::error_with_help;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Fields, Index, Result};
+
+pub(crate) fn derive(input: DeriveInput) -> Result<TokenStream2> {
+    let ident = input.ident;
+    let message = "derive(Reflect) may only be applied to structs";
+    let fields = match input.data {
+        Data::Struct(data) => data.fields,
+        Data::Enum(e) => {
+            return Err(error_with_help(
+                e.enum_token,
+                message,
+                "wrap the enum in a struct field to expose it",
+            ))
+        }
+        Data::Union(u) => {
+            return Err(error_with_help(
+                u.union_token,
+                message,
+                "wrap the union in a struct field to expose it",
+            ))
+        }
+    };
+
+    if let Some(param) = input.generics.params.first() {
+        return Err(error_with_help(
+            param,
+            "must have no generic parameters",
+            "reflected components are looked up by name, so declare a separate struct for each \
+             instantiation",
+        ));
+    }
+
+    // fields are named by their identifiers, or by their index for tuple structs
+    let (names, members): (Vec<String>, Vec<TokenStream2>) = match &fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|f| {
+                let ident = f.ident.as_ref().unwrap();
+                (ident.unraw().to_string(), quote!(#ident))
+            })
+            .unzip(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|i| {
+                let index = Index::from(i);
+                (i.to_string(), quote!(#index))
+            })
+            .unzip(),
+        Fields::Unit => (Vec::new(), Vec::new()),
+    };
+    let types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+
+    Ok(quote! {
+        impl ::hecs_component_provider::Reflect for #ident {
+            fn field_names(&self) -> &'static [&'static str] {
+                &[#(#names),*]
+            }
+
+            fn field_type_name(&self, name: &str) -> ::core::option::Option<&'static str> {
+                match name {
+                    #(#names => ::core::option::Option::Some(::core::any::type_name::<#types>()),)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            fn field(&self, name: &str) -> ::core::option::Option<&dyn ::core::any::Any> {
+                match name {
+                    #(#names => ::core::option::Option::Some(&self.#members),)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            fn field_mut(
+                &mut self,
+                name: &str,
+            ) -> ::core::option::Option<&mut dyn ::core::any::Any> {
+                match name {
+                    #(#names => ::core::option::Option::Some(&mut self.#members),)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        }
+    })
+}
@@ -1,46 +1,122 @@
+use crate::error::{combine_errors, error_with_help};
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{DeriveInput, Error, Result};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, DeriveInput, Error, Ident, Result, Token};
 
 pub(crate) fn derive(input: DeriveInput) -> Result<TokenStream2> {
     let ident = input.ident;
+    let help =
+        "components are stored in the world, so they must be concrete types that own their data";
     match input.data {
         syn::Data::Struct(_) => (),
-        _ => {
-            return Err(Error::new_spanned(
-                ident,
+        syn::Data::Enum(e) => {
+            return Err(error_with_help(
+                e.enum_token,
                 "derive(SelfComponentProvider) may only be applied to structs",
+                "wrap the enum in a struct, e.g. `struct MyComponent(MyEnum)`",
             ))
         }
+        syn::Data::Union(u) => {
+            return Err(error_with_help(
+                u.union_token,
+                "derive(SelfComponentProvider) may only be applied to structs",
+                "wrap the union in a struct",
+            ))
+        }
+    };
+
+    let errors = input.generics.params.iter().map(|param| match param {
+        syn::GenericParam::Lifetime(_) => {
+            error_with_help(param, "must have no lifetime parameters", help)
+        }
+        _ => error_with_help(param, "must have no type parameters", help),
+    });
+    if let Some(error) = combine_errors(errors) {
+        return Err(error);
+    }
+
+    let options = parse_options(&input.attrs)?;
+    let inline = if options.no_inline {
+        quote! {}
+    } else {
+        quote! { #[inline] }
     };
 
-    let lifetimes: Vec<_> = input
-        .generics
-        .lifetimes()
-        .map(|x| x.lifetime.clone())
-        .collect();
-    if lifetimes.len() > 0 {
-        return Err(Error::new_spanned(
-            input.generics,
-            "must have no lifetime parameters",
-        ));
+    let deref_tokens = if options.deref {
+        quote! {
+            impl ::hecs_component_provider::ComponentProvider<<#ident as ::core::ops::Deref>::Target> for #ident {
+                #inline
+                fn get(&self) -> &<#ident as ::core::ops::Deref>::Target {
+                    ::core::ops::Deref::deref(self)
+                }
+            }
+        }
+    } else {
+        quote! {}
     };
 
-    if input.generics.params.len() > 0 {
-        return Err(Error::new_spanned(ident, "must have no type parameters"));
-    }
+    let deref_mut_tokens = if options.deref_mut {
+        quote! {
+            impl ::hecs_component_provider::ComponentProviderMut<<#ident as ::core::ops::Deref>::Target> for #ident {
+                #inline
+                fn get_mut(&mut self) -> &mut <#ident as ::core::ops::Deref>::Target {
+                    ::core::ops::DerefMut::deref_mut(self)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
 
     Ok(quote! {
         impl ::hecs_component_provider::ComponentProvider<#ident> for #ident {
+            #inline
             fn get(&self) -> &#ident {
                 self
             }
         }
 
         impl ::hecs_component_provider::ComponentProviderMut<#ident> for #ident {
+            #inline
             fn get_mut(&mut self) -> &mut #ident {
                 self
             }
         }
+
+        #deref_tokens
+        #deref_mut_tokens
     })
 }
+
+#[derive(Default)]
+struct Options {
+    deref: bool,
+    deref_mut: bool,
+    no_inline: bool,
+}
+
+fn parse_options(attrs: &[Attribute]) -> Result<Options> {
+    let mut options = Options::default();
+
+    for attr in attrs.iter().filter(|a| a.path.is_ident("self_provider")) {
+        let idents = attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)?;
+        for ident in idents {
+            if ident == "deref" {
+                options.deref = true;
+            } else if ident == "deref_mut" {
+                options.deref = true;
+                options.deref_mut = true;
+            } else if ident == "no_inline" {
+                options.no_inline = true;
+            } else {
+                return Err(Error::new_spanned(
+                    ident,
+                    "unknown self_provider option, expected `deref`, `deref_mut` or `no_inline`",
+                ));
+            }
+        }
+    }
+
+    Ok(options)
+}
@@ -0,0 +1,41 @@
+use crate::error::error_with_help;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Result};
+
+pub(crate) fn derive(input: DeriveInput) -> Result<TokenStream2> {
+    let ident = input.ident;
+    let message = "derive(Tag) may only be applied to unit structs";
+    match input.data {
+        Data::Struct(data) => match data.fields {
+            Fields::Unit => (),
+            fields => {
+                return Err(error_with_help(
+                    fields,
+                    message,
+                    &format!(
+                        "tags carry no data, use `struct {};` or derive ComponentProvider on a \
+                         regular component instead",
+                        ident
+                    ),
+                ))
+            }
+        },
+        Data::Enum(e) => return Err(error_with_help(e.enum_token, message, "use a unit struct")),
+        Data::Union(u) => return Err(error_with_help(u.union_token, message, "use a unit struct")),
+    };
+
+    if let Some(param) = input.generics.params.first() {
+        return Err(error_with_help(
+            param,
+            "must have no generic parameters",
+            "declare a separate tag struct for each variant",
+        ));
+    }
+
+    Ok(quote! {
+        impl ::hecs_component_provider::Tag for #ident {
+            const TAG: Self = #ident;
+        }
+    })
+}
@@ -0,0 +1,165 @@
+use core::ops::{Deref, DerefMut};
+
+/// Bundle field wrapper declaring that behaviors may only read the component
+///
+/// `derive(ComponentProvider)` implements [`ComponentProvider<T>`](crate::ComponentProvider) for
+/// `Read<T>` fields, but not [`ComponentProviderMut<T>`](crate::ComponentProviderMut), so behaviors
+/// that mutate the component can't be called on the bundle.
+///
+/// Fields are treated as access wrappers when their type is written as `Read<T>`, `Write<T>` or
+/// through `hecs_component_provider::`, so other types with these names can be used as plain
+/// components by a qualified path like `self::Read<T>`.
+///
+/// Wrapped fields are added to and removed from entities as their contents, so spawning a bundle
+/// through [`IntoEntityBuilder`](crate::IntoEntityBuilder) adds a `T` component that queries for
+/// `&T` find. [`hecs::Bundle`] would store the fields by their declared type instead, adding
+/// `Read<T>` components, so deriving it on a struct with access wrappers fails to compile.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, Read, Write};
+///
+/// #[derive(ComponentProvider)]
+/// struct Snapshot {
+///     position: Write<i32>,
+///     name: Read<&'static str>,
+/// }
+///
+/// let mut snapshot = Snapshot { position: Write::new(1), name: Read::new("abc") };
+/// let name: &&str = snapshot.get();
+/// assert_eq!(*name, "abc");
+/// ```
+///
+/// ```compile_fail
+/// # use hecs_component_provider::{ComponentProvider, ComponentProviderMut, Read};
+/// #[derive(ComponentProvider)]
+/// struct Snapshot {
+///     name: Read<&'static str>,
+/// }
+///
+/// let mut snapshot = Snapshot { name: Read::new("abc") };
+/// let name: &mut &str = snapshot.get_mut();
+/// ```
+///
+/// ```compile_fail
+/// # use hecs_component_provider::{ComponentProvider, Read};
+/// #[derive(hecs::Bundle, ComponentProvider)]
+/// struct Snapshot {
+///     name: Read<&'static str>,
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct Read<T>(T);
+
+impl<T> Read<T> {
+    pub fn new(value: T) -> Self {
+        Read(value)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Read<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Bundle field wrapper declaring that behaviors may read and write the component
+///
+/// `derive(ComponentProvider)` implements both [`ComponentProvider<T>`](crate::ComponentProvider)
+/// and [`ComponentProviderMut<T>`](crate::ComponentProviderMut) for `Write<T>` fields.
+/// See [`Read`] for details.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct Write<T>(T);
+
+impl<T> Write<T> {
+    pub fn new(value: T) -> Self {
+        Write(value)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Write<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Write<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl<T> Sealed for super::Read<T> {}
+    impl<T> Sealed for super::Write<T> {}
+}
+
+/// Implemented only by [`Read`] and [`Write`], so that the derive macro can tell them apart from
+/// other types with the same name and reach their contents without knowing their layout
+///
+/// # Safety
+///
+/// `as_inner_ptr` must return a pointer to the wrapped value that is valid for the same accesses as
+/// the wrapper pointer it was given.
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` isn't one of the `Read` or `Write` access wrappers",
+    label = "treated as an access wrapper because of its name",
+    note = "bundle fields named `Read<T>` or `Write<T>` are expected to be `hecs_component_provider::Read` or `hecs_component_provider::Write`",
+    note = "refer to other types by a qualified path, like `self::Read<T>`, to add them as plain components"
+)]
+pub unsafe trait AccessWrapper: sealed::Sealed {
+    type Inner;
+
+    fn as_inner_ptr(this: *mut Self) -> *mut Self::Inner;
+
+    fn inner(&self) -> &Self::Inner;
+
+    fn from_inner(inner: Self::Inner) -> Self;
+
+    fn into_inner(self) -> Self::Inner;
+}
+
+macro_rules! impl_access_wrapper {
+    ($($wrapper:ident),*) => {
+        $(
+            // the wrappers are `repr(transparent)`, so a pointer to one is a pointer to its contents
+            unsafe impl<T> AccessWrapper for $wrapper<T> {
+                type Inner = T;
+
+                fn as_inner_ptr(this: *mut Self) -> *mut T {
+                    this.cast()
+                }
+
+                fn inner(&self) -> &T {
+                    &self.0
+                }
+
+                fn from_inner(inner: T) -> Self {
+                    $wrapper(inner)
+                }
+
+                fn into_inner(self) -> T {
+                    self.0
+                }
+            }
+        )*
+    };
+}
+
+impl_access_wrapper!(Read, Write);
@@ -0,0 +1,244 @@
+use crate::metadata::merge_access;
+use crate::ComponentAccess;
+
+/// Describes a behavior trait, generated by `#[default_trait_impl(info = fn_name)]`
+///
+/// The components are those required directly by the trait's provider supertraits. Other
+/// supertraits are listed as dependencies by name, and their components can be included with
+/// [`BehaviorRegistry::access`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BehaviorInfo {
+    name: &'static str,
+    methods: Vec<&'static str>,
+    access: Vec<ComponentAccess>,
+    dependencies: Vec<&'static str>,
+}
+
+impl BehaviorInfo {
+    #[doc(hidden)]
+    pub fn new(
+        name: &'static str,
+        methods: Vec<&'static str>,
+        access: Vec<ComponentAccess>,
+        dependencies: Vec<&'static str>,
+    ) -> Self {
+        BehaviorInfo {
+            name,
+            methods,
+            access: merge_access(access),
+            dependencies,
+        }
+    }
+
+    /// The name of the trait
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The names of the trait's methods, in declaration order
+    pub fn methods(&self) -> &[&'static str] {
+        &self.methods
+    }
+
+    /// The components provided by the trait's provider supertraits
+    ///
+    /// Components from `ComponentProviderOptional` supertraits aren't required.
+    pub fn access(&self) -> &[ComponentAccess] {
+        &self.access
+    }
+
+    /// The names of the trait's other supertraits, such as behaviors it builds on
+    pub fn dependencies(&self) -> &[&'static str] {
+        &self.dependencies
+    }
+}
+
+/// A runtime list of behaviors, for tooling that inspects which behaviors exist and which
+/// components they need
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, BehaviorRegistry, ComponentProvider, ComponentProviderMut
+/// };
+///
+/// struct Position(i32);
+/// struct Velocity(i32);
+///
+/// #[default_trait_impl(info = apply_velocity_info)]
+/// trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+///     fn apply_velocity(&mut self) {
+///         let &Velocity(v) = self.get();
+///         let position: &mut Position = self.get_mut();
+///         position.0 += v;
+///     }
+/// }
+///
+/// #[default_trait_impl(info = patrol_info)]
+/// trait Patrol: ApplyVelocity {
+///     fn patrol(&mut self) {
+///         self.apply_velocity();
+///     }
+/// }
+///
+/// let mut registry = BehaviorRegistry::new();
+/// registry.register(apply_velocity_info());
+/// registry.register(patrol_info());
+///
+/// let patrol = registry.get("Patrol").unwrap();
+/// assert_eq!(patrol.methods(), ["patrol"]);
+/// assert_eq!(patrol.dependencies(), ["ApplyVelocity"]);
+/// assert!(patrol.access().is_empty());
+///
+/// let access = registry.access("Patrol");
+/// assert_eq!(access.len(), 2);
+/// assert!(access[0].type_name.ends_with("Position") && access[0].mutable);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BehaviorRegistry {
+    behaviors: Vec<BehaviorInfo>,
+}
+
+impl BehaviorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a behavior, replacing any behavior with the same name
+    pub fn register(&mut self, info: BehaviorInfo) {
+        match self.behaviors.iter_mut().find(|b| b.name == info.name) {
+            Some(existing) => *existing = info,
+            None => self.behaviors.push(info),
+        }
+    }
+
+    /// The registered behaviors, in registration order
+    pub fn behaviors(&self) -> &[BehaviorInfo] {
+        &self.behaviors
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BehaviorInfo> {
+        self.behaviors.iter().find(|b| b.name == name)
+    }
+
+    /// The components required by the behavior named `name` and the registered behaviors it
+    /// depends on, directly or indirectly
+    ///
+    /// Dependencies that haven't been registered are skipped.
+    pub fn access(&self, name: &str) -> Vec<ComponentAccess> {
+        let mut visited = Vec::new();
+        let mut pending = vec![name];
+        let mut access = Vec::new();
+        while let Some(name) = pending.pop() {
+            if visited.contains(&name) {
+                continue;
+            }
+            visited.push(name);
+            if let Some(info) = self.get(name) {
+                access.extend(info.access.iter().copied());
+                pending.extend(info.dependencies.iter().rev());
+            }
+        }
+        merge_access(access)
+    }
+
+    /// Returns a Graphviz DOT graph of the registered behaviors, the components they read and
+    /// write, and the behaviors they depend on
+    ///
+    /// Reads are drawn as edges from components to behaviors, writes as edges from behaviors to
+    /// components, and optional components with dashed edges. Dependencies are drawn as bold
+    /// edges from a behavior to the behavior it builds on, including unregistered ones.
+    ///
+    /// ```
+    /// use hecs_component_provider::{default_trait_impl, BehaviorRegistry, ComponentProviderMut};
+    ///
+    /// struct Position(i32);
+    ///
+    /// #[default_trait_impl(info = move_right_info)]
+    /// trait MoveRight: ComponentProviderMut<Position> {
+    ///     fn move_right(&mut self) {
+    ///         let position: &mut Position = self.get_mut();
+    ///         position.0 += 1;
+    ///     }
+    /// }
+    ///
+    /// let mut registry = BehaviorRegistry::new();
+    /// registry.register(move_right_info());
+    ///
+    /// let dot = registry.export_dot();
+    /// assert!(dot.starts_with("digraph behaviors {"));
+    /// assert!(dot.contains("\"MoveRight\" -> \"component:"));
+    /// ```
+    pub fn export_dot(&self) -> String {
+        let mut dot = String::from("digraph behaviors {\n    rankdir=LR;\n");
+        let mut components: Vec<&'static str> = Vec::new();
+        for info in &self.behaviors {
+            dot.push_str(&format!("    {} [shape=box];\n", quote(info.name)));
+            for access in &info.access {
+                if !components.contains(&access.type_name) {
+                    components.push(access.type_name);
+                }
+            }
+        }
+        for name in components {
+            dot.push_str(&format!(
+                "    {} [shape=ellipse, label={}];\n",
+                quote(&format!("component:{}", name)),
+                quote(&short_type_name(name)),
+            ));
+        }
+
+        for info in &self.behaviors {
+            let behavior = quote(info.name);
+            for access in &info.access {
+                let component = quote(&format!("component:{}", access.type_name));
+                let style = if access.required { "solid" } else { "dashed" };
+                if access.mutable || access.interior_mutable {
+                    let label = if access.mutable {
+                        "write"
+                    } else {
+                        "shared write"
+                    };
+                    dot.push_str(&format!(
+                        "    {} -> {} [label=\"{}\", style={}];\n",
+                        behavior, component, label, style
+                    ));
+                } else {
+                    dot.push_str(&format!(
+                        "    {} -> {} [label=\"read\", style={}];\n",
+                        component, behavior, style
+                    ));
+                }
+            }
+            for dependency in &info.dependencies {
+                dot.push_str(&format!(
+                    "    {} -> {} [style=bold];\n",
+                    behavior,
+                    quote(dependency)
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Strips module paths from a type name, e.g. `alloc::boxed::Box<game::Ai>` becomes `Box<Ai>`
+fn short_type_name(name: &str) -> String {
+    let mut short = String::new();
+    let mut segment = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            segment.push(c);
+        } else {
+            short.push_str(segment.rsplit("::").next().unwrap_or(""));
+            segment.clear();
+            short.push(c);
+        }
+    }
+    short.push_str(segment.rsplit("::").next().unwrap_or(""));
+    short
+}
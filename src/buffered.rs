@@ -0,0 +1,112 @@
+use crate::ComponentProviderMut;
+use hecs::{Component, World};
+
+/// A component holding the value from the previous frame and the value being built for the next
+///
+/// Behaviors read the previous value with [`read`](Self::read) and write the next one with
+/// [`write`](Self::write), so every entity sees the same state regardless of the order entities
+/// are processed in, as cellular automata and other simultaneous-update simulations require. The
+/// next values become current when [`flip`] is called at the end of the frame.
+///
+/// Behavior traits access it through [`BufferedProvider`].
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DoubleBuffered<T> {
+    current: T,
+    next: T,
+}
+
+impl<T: Clone> DoubleBuffered<T> {
+    pub fn new(value: T) -> Self {
+        DoubleBuffered {
+            next: value.clone(),
+            current: value,
+        }
+    }
+
+    /// Makes the written value current, and starts the next value as a copy of it
+    pub fn flip(&mut self) {
+        core::mem::swap(&mut self.current, &mut self.next);
+        self.next.clone_from(&self.current);
+    }
+}
+
+impl<T> DoubleBuffered<T> {
+    /// The value as of the last [`flip`](Self::flip)
+    pub fn read(&self) -> &T {
+        &self.current
+    }
+
+    /// The value that becomes current at the next [`flip`](Self::flip)
+    pub fn write(&mut self) -> &mut T {
+        &mut self.next
+    }
+}
+
+/// Provides read-old/write-new access to a [`DoubleBuffered`] component
+///
+/// This is implemented for every type that provides `DoubleBuffered<T>` mutably, such as query
+/// structs with a `&mut DoubleBuffered<T>` field.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, flip, BufferedProvider, ComponentProvider, DoubleBuffered
+/// };
+///
+/// #[derive(Clone)]
+/// struct Heat(f32);
+///
+/// #[default_trait_impl]
+/// trait Cool: BufferedProvider<Heat> {
+///     fn cool(&mut self) {
+///         let old: &Heat = self.read();
+///         let cooled = old.0 * 0.5;
+///         let new: &mut Heat = self.write();
+///         new.0 = cooled;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct HeatQuery<'a> {
+///     heat: &'a mut DoubleBuffered<Heat>,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((DoubleBuffered::new(Heat(8.0)),));
+///
+/// for mut item in world.query_mut::<HeatQuery>() {
+///     item.cool();
+///     // reads still see the previous frame until the buffers are flipped
+///     item.cool();
+/// }
+/// assert_eq!(world.get::<&DoubleBuffered<Heat>>(entity).unwrap().read().0, 8.0);
+///
+/// flip::<Heat>(&mut world);
+/// assert_eq!(world.get::<&DoubleBuffered<Heat>>(entity).unwrap().read().0, 4.0);
+/// ```
+pub trait BufferedProvider<T> {
+    fn read(&self) -> &T;
+    fn write(&mut self) -> &mut T;
+}
+
+impl<T, P> BufferedProvider<T> for P
+where
+    P: ComponentProviderMut<DoubleBuffered<T>>,
+{
+    #[inline]
+    fn read(&self) -> &T {
+        self.get().read()
+    }
+
+    #[inline]
+    fn write(&mut self) -> &mut T {
+        self.get_mut().write()
+    }
+}
+
+/// Flips every `DoubleBuffered<T>` component in the world, making the values written this frame
+/// current
+pub fn flip<T: Clone + Component>(world: &mut World) {
+    for buffered in world.query_mut::<&mut DoubleBuffered<T>>() {
+        buffered.flip();
+    }
+}
@@ -0,0 +1,535 @@
+use crate::{Cold, ComponentAccess, ComponentProvider, ComponentProviderOptional, WorldEntity};
+use core::marker::PhantomData;
+use hecs::{
+    Bundle, Component, ComponentError, Entity, EntityBuilder, EntityRef, MissingComponent,
+    NoSuchEntity, World,
+};
+
+/// Adds the components of a bundle struct to an [`EntityBuilder`], flattening nested bundles
+///
+/// This is implemented by `derive(ComponentProvider)` for bundle structs whose fields are all
+/// components. Fields marked `#[provider(nested(...))]` add their own components instead of being
+/// added as a single component, which [`hecs::Bundle`] can't do.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, IntoEntityBuilder};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Position(i32);
+/// #[derive(Debug, PartialEq)]
+/// struct Health(i32);
+///
+/// #[derive(ComponentProvider)]
+/// struct PhysicsBundle {
+///     position: Position,
+/// }
+///
+/// #[derive(ComponentProvider)]
+/// struct Player {
+///     health: Health,
+///     #[provider(nested(Position))]
+///     physics: PhysicsBundle,
+/// }
+///
+/// let player = Player { health: Health(10), physics: PhysicsBundle { position: Position(1) } };
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn(player.into_entity_builder().build());
+/// assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(1));
+/// assert_eq!(*world.get::<&Health>(entity).unwrap(), Health(10));
+/// ```
+///
+/// The derive also implements `From<Bundle> for EntityBuilder`, so a typed bundle can be converted
+/// into a builder and extended with components that are only added conditionally.
+///
+/// ```
+/// use hecs_component_provider::ComponentProvider;
+///
+/// struct Position(i32);
+/// struct Flying;
+///
+/// #[derive(ComponentProvider)]
+/// struct Bird {
+///     position: Position,
+/// }
+///
+/// let can_fly = true;
+/// let mut builder = hecs::EntityBuilder::from(Bird { position: Position(0) });
+/// if can_fly {
+///     builder.add(Flying);
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let bird = world.spawn(builder.build());
+/// assert!(world.satisfies::<(&Position, &Flying)>(bird));
+/// ```
+pub trait IntoEntityBuilder: Sized {
+    fn add_to_builder(self, builder: &mut EntityBuilder);
+
+    fn into_entity_builder(self) -> EntityBuilder {
+        let mut builder = EntityBuilder::new();
+        self.add_to_builder(&mut builder);
+        builder
+    }
+}
+
+/// Construct and spawn bundle structs from their [`Default`] values
+///
+/// This is implemented for all types implementing [`Default`] and [`IntoEntityBuilder`], such as
+/// bundle structs that derive both `Default` and `ComponentProvider`. The closures passed to the
+/// `_with` methods receive the bundle before it is spawned, so that initialization behaviors can
+/// be called on it through its component providers.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, ComponentProvider, ComponentProviderMut, SpawnDefault
+/// };
+///
+/// #[derive(Default)]
+/// struct Health { current: i32, max: i32 }
+/// #[derive(Default)]
+/// struct Name(String);
+///
+/// #[default_trait_impl]
+/// trait InitHealth: ComponentProviderMut<Health> {
+///     fn init_health(&mut self, max: i32) {
+///         let health: &mut Health = self.get_mut();
+///         *health = Health { current: max, max };
+///     }
+/// }
+///
+/// #[derive(Default, ComponentProvider)]
+/// struct Monster {
+///     health: Health,
+///     name: Name,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let rat = Monster::spawn_default_with(&mut world, |monster| monster.init_health(5));
+/// assert_eq!(world.get::<&Health>(rat).unwrap().current, 5);
+///
+/// let blank = Monster::spawn_default(&mut world);
+/// assert_eq!(world.get::<&Name>(blank).unwrap().0, "");
+/// ```
+pub trait SpawnDefault: IntoEntityBuilder + Default {
+    /// Returns the default value after passing it to `f`
+    fn default_with(f: impl FnOnce(&mut Self)) -> Self {
+        let mut bundle = Self::default();
+        f(&mut bundle);
+        bundle
+    }
+
+    fn spawn_default(world: &mut World) -> Entity {
+        world.spawn(Self::default().into_entity_builder().build())
+    }
+
+    /// Spawns the default value after passing it to `f`
+    fn spawn_default_with(world: &mut World, f: impl FnOnce(&mut Self)) -> Entity {
+        world.spawn(Self::default_with(f).into_entity_builder().build())
+    }
+}
+
+impl<T: IntoEntityBuilder + Default> SpawnDefault for T {}
+
+/// Spawns a batch of bundle structs after passing each to `f`, returning the spawned entities
+///
+/// This runs an initialization behavior on every bundle through its component providers, while
+/// still spawning the batch with [`World::spawn_batch`], which reserves space for all entities at
+/// once. The bundles must implement [`hecs::Bundle`], so nested bundles need to be spawned
+/// individually through [`IntoEntityBuilder`] instead.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, spawn_batch_with, ComponentProvider, ComponentProviderMut
+/// };
+///
+/// struct Health { current: i32, max: i32 }
+///
+/// #[default_trait_impl]
+/// trait InitHealth: ComponentProviderMut<Health> {
+///     fn init_health(&mut self) {
+///         let health: &mut Health = self.get_mut();
+///         health.current = health.max;
+///     }
+/// }
+///
+/// #[derive(hecs::Bundle, ComponentProvider)]
+/// struct Monster {
+///     health: Health,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let monsters = (1..=1000).map(|max| Monster { health: Health { current: 0, max } });
+/// let entities = spawn_batch_with(&mut world, monsters, |monster| monster.init_health());
+///
+/// assert_eq!(entities.len(), 1000);
+/// assert_eq!(world.get::<&Health>(entities[9]).unwrap().current, 10);
+/// ```
+pub fn spawn_batch_with<B, I, F>(world: &mut World, bundles: I, mut f: F) -> Vec<Entity>
+where
+    B: Bundle + 'static,
+    I: IntoIterator<Item = B>,
+    F: FnMut(&mut B),
+{
+    let bundles = bundles.into_iter().map(|mut bundle| {
+        f(&mut bundle);
+        bundle
+    });
+    world.spawn_batch(bundles).collect()
+}
+
+/// Compares two values of a bundle struct field by field
+///
+/// This is implemented by `derive(ComponentProvider)` for bundle structs whose fields all
+/// implement [`PartialEq`]. Fields marked `#[provider(nested(...))]` are compared field by field
+/// as well, and reported with their path, e.g. `physics.position`.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, DiffBundle};
+///
+/// #[derive(Clone, PartialEq)]
+/// struct Position(i32);
+/// #[derive(Clone, PartialEq)]
+/// struct Health(i32);
+///
+/// #[derive(Clone, ComponentProvider)]
+/// struct Player {
+///     position: Position,
+///     health: Health,
+/// }
+///
+/// let before = Player { position: Position(0), health: Health(10) };
+/// let mut after = before.clone();
+/// after.health.0 -= 1;
+///
+/// let diff = before.diff(&after);
+/// assert!(diff.is_changed("health"));
+/// assert!(!diff.is_changed("position"));
+/// assert_eq!(diff.changed().collect::<Vec<_>>(), ["health"]);
+/// ```
+pub trait DiffBundle {
+    fn diff(&self, other: &Self) -> BundleDiff;
+}
+
+/// The per-field result of [`DiffBundle::diff`]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BundleDiff {
+    fields: Vec<(String, bool)>,
+}
+
+impl BundleDiff {
+    #[doc(hidden)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[doc(hidden)]
+    pub fn push(&mut self, name: impl Into<String>, changed: bool) {
+        self.fields.push((name.into(), changed));
+    }
+
+    #[doc(hidden)]
+    pub fn push_nested(&mut self, name: &str, diff: BundleDiff) {
+        for (field, changed) in diff.fields {
+            self.push(format!("{}.{}", name, field), changed);
+        }
+    }
+
+    /// Every compared field and whether it changed, in declaration order
+    pub fn fields(&self) -> impl Iterator<Item = (&str, bool)> + '_ {
+        self.fields
+            .iter()
+            .map(|(name, changed)| (name.as_str(), *changed))
+    }
+
+    /// The fields that changed
+    pub fn changed(&self) -> impl Iterator<Item = &str> + '_ {
+        self.fields()
+            .filter(|(_, changed)| *changed)
+            .map(|(name, _)| name)
+    }
+
+    /// Whether the field at `path` changed, or false if there is no such field
+    pub fn is_changed(&self, path: &str) -> bool {
+        self.fields().any(|(name, changed)| changed && name == path)
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.fields.iter().any(|(_, changed)| *changed)
+    }
+}
+
+/// Inserts the components of a bundle struct that an entity is missing
+///
+/// This is implemented by `derive(ComponentProvider)` for bundle structs whose fields all implement
+/// [`Default`], and is used through [`ensure`].
+pub trait EnsureComponents {
+    #[doc(hidden)]
+    fn ensure_components(
+        world: &mut World,
+        entity: Entity,
+        added: &mut Vec<ComponentAccess>,
+    ) -> Result<(), NoSuchEntity>;
+}
+
+/// Inserts the [`Default`] value of each component in the bundle struct `B` that `entity` is
+/// missing, returning the components that were added
+///
+/// Components the entity already has are left unchanged. This lets behaviors that require
+/// components be run on entities that were spawned without them, instead of those entities being
+/// silently skipped. Components of nested bundles are ensured individually.
+///
+/// ```
+/// use hecs_component_provider::{ensure, ComponentProvider};
+///
+/// #[derive(Default)]
+/// struct Position(i32);
+/// #[derive(Default)]
+/// struct Velocity(i32);
+///
+/// #[derive(ComponentProvider)]
+/// struct MovableBundle {
+///     position: Position,
+///     velocity: Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((Position(3),));
+///
+/// let added = ensure::<MovableBundle>(&mut world, entity).unwrap();
+/// assert_eq!(added.len(), 1);
+/// assert_eq!(added[0].type_id, std::any::TypeId::of::<Velocity>());
+/// assert_eq!(world.get::<&Position>(entity).unwrap().0, 3);
+/// assert!(ensure::<MovableBundle>(&mut world, entity).unwrap().is_empty());
+/// ```
+pub fn ensure<B: EnsureComponents>(
+    world: &mut World,
+    entity: Entity,
+) -> Result<Vec<ComponentAccess>, NoSuchEntity> {
+    let mut added = Vec::new();
+    B::ensure_components(world, entity, &mut added)?;
+    Ok(added)
+}
+
+#[doc(hidden)]
+pub fn ensure_one<T: Component + Default>(
+    world: &mut World,
+    entity: Entity,
+    added: &mut Vec<ComponentAccess>,
+) -> Result<(), NoSuchEntity> {
+    if !world.entity(entity)?.has::<T>() {
+        world.insert_one(entity, T::default())?;
+        added.push(ComponentAccess::write::<T>());
+    }
+    Ok(())
+}
+
+/// Removes the components of a bundle struct from an entity
+///
+/// This is implemented by `derive(ComponentProvider)` for bundle structs whose fields are all
+/// components, and is used through [`remove`].
+pub trait RemoveBundle: Sized {
+    #[doc(hidden)]
+    fn check_components(entity: EntityRef<'_>) -> Result<(), MissingComponent>;
+
+    #[doc(hidden)]
+    fn take_components(world: &mut World, entity: Entity) -> Self;
+}
+
+/// Removes every component of the bundle struct `B` from `entity`, returning them as a `B`
+///
+/// If the entity is missing any of the components, none are removed. Components of nested
+/// bundles are removed individually, so the entity doesn't need to have the nested bundle as a
+/// component. This is useful for moving entities between worlds, or salvaging components before
+/// despawning an entity.
+///
+/// ```
+/// use hecs_component_provider::{remove, ComponentProvider};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Position(i32);
+/// #[derive(Debug, PartialEq)]
+/// struct Velocity(i32);
+///
+/// #[derive(ComponentProvider)]
+/// struct MovableBundle {
+///     position: Position,
+///     velocity: Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((Position(1), Velocity(2)));
+///
+/// let mut other_world = hecs::World::new();
+/// let bundle = remove::<MovableBundle>(&mut world, entity).unwrap();
+/// other_world.spawn((bundle.position, bundle.velocity));
+///
+/// assert!(world.get::<&Position>(entity).is_err());
+/// assert!(remove::<MovableBundle>(&mut world, entity).is_err());
+/// ```
+pub fn remove<B: RemoveBundle>(world: &mut World, entity: Entity) -> Result<B, ComponentError> {
+    B::check_components(world.entity(entity)?)?;
+    Ok(B::take_components(world, entity))
+}
+
+#[doc(hidden)]
+pub fn check_one<T: Component>(entity: EntityRef<'_>) -> Result<(), MissingComponent> {
+    if entity.has::<T>() {
+        Ok(())
+    } else {
+        Err(MissingComponent::new::<T>())
+    }
+}
+
+#[doc(hidden)]
+pub fn take_one<T: Component>(world: &mut World, entity: Entity) -> T {
+    world
+        .remove_one::<T>(entity)
+        .expect("component was checked to exist")
+}
+
+/// Converts between a bundle struct and a richer bundle struct that contains its components, for
+/// entity lifecycle transitions such as a corpse being revived as a creature
+///
+/// The conversion moves the shared components, so `Extra` only needs to hold the components the
+/// richer bundle adds.
+///
+/// `#[provider(promote(Lower, extra = [a, b]))]` on a bundle struct with named fields derives the
+/// conversion from `Lower`, whose fields must be the struct's other fields with the same names.
+/// `Extra` is then the tuple of the listed fields. Derived conversions can also be applied to
+/// entities with [`promote`] and [`demote`], which only add or remove these components, moving the
+/// entity to its new archetype once.
+pub trait Promote<Lower>: Sized {
+    /// The components added by promotion and discarded by demotion
+    type Extra;
+
+    fn promote(lower: Lower, extra: Self::Extra) -> Self;
+
+    fn demote(self) -> (Lower, Self::Extra);
+}
+
+/// Applies a derived [`Promote`] conversion to an entity
+///
+/// Implemented by `#[provider(promote(...))]` only, since it relies on the lower bundle's fields
+/// being the same components as the matching fields of `Self`.
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't derive the promotion from `{Lower}`",
+    label = "no derived promotion from `{Lower}`",
+    note = "add `#[provider(promote({Lower}, extra = [...]))]` to `{Self}`"
+)]
+pub trait PromoteEntity<Lower>: Promote<Lower> {
+    /// Adds `extra` to an entity that was checked to have the components of `Lower`
+    fn promote_entity(
+        world: &mut World,
+        entity: Entity,
+        extra: Self::Extra,
+    ) -> Result<(), ComponentError>;
+
+    /// Removes the components of `Self` that `Lower` doesn't have from an entity that was checked
+    /// to have the components of `Self`
+    fn demote_entity(world: &mut World, entity: Entity) -> Result<Self::Extra, ComponentError>;
+}
+
+/// The components of the bundle struct `L` of an entity being promoted, see [`promote`]
+///
+/// It provides each component `L` provides, with cold fields looked up through their [`Cold`]
+/// box.
+pub struct BundleRef<'w, L> {
+    entity: WorldEntity<'w>,
+    _bundle: PhantomData<fn() -> L>,
+}
+
+impl<'w, L> BundleRef<'w, L> {
+    pub fn entity(&self) -> Entity {
+        self.entity.entity()
+    }
+}
+
+impl<'w, L, C: Component> ComponentProvider<C> for BundleRef<'w, L>
+where
+    L: ComponentProvider<C>,
+{
+    fn get(&self) -> &C {
+        match ComponentProviderOptional::<C>::get_optional(&self.entity) {
+            Some(component) => component,
+            None => {
+                let cold: &Cold<C> = self.entity.get();
+                cold
+            }
+        }
+    }
+}
+
+/// Converts `entity` from the bundle struct `L` to the richer bundle struct `U`, constructing the
+/// components `U` adds with `fill`
+///
+/// `fill` is given the components of `L` before the conversion. The components of `L` are moved
+/// into `U` rather than recreated, and components that are in neither bundle are left unchanged.
+/// If the entity is missing any of the components of `L`, it isn't changed and `fill` isn't
+/// called.
+///
+/// ```
+/// use hecs_component_provider::{demote, promote, ComponentProvider};
+///
+/// struct Position(i32);
+/// struct Health(i32);
+/// struct Brain { aggression: i32 }
+///
+/// #[derive(ComponentProvider)]
+/// struct Corpse {
+///     position: Position,
+/// }
+///
+/// #[derive(ComponentProvider)]
+/// #[provider(promote(Corpse, extra = [health, brain]))]
+/// struct Creature {
+///     position: Position,
+///     health: Health,
+///     brain: Brain,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((Position(4),));
+///
+/// promote::<Corpse, Creature>(&mut world, entity, |corpse| {
+///     let position: &Position = corpse.get();
+///     (Health(10), Brain { aggression: position.0 })
+/// })
+/// .unwrap();
+/// assert_eq!(world.get::<&Brain>(entity).unwrap().aggression, 4);
+///
+/// let (health, _) = demote::<Creature, Corpse>(&mut world, entity).unwrap();
+/// assert_eq!(health.0, 10);
+/// assert!(world.satisfies::<&Position>(entity));
+/// assert!(!world.satisfies::<&Health>(entity));
+/// ```
+pub fn promote<L, U>(
+    world: &mut World,
+    entity: Entity,
+    fill: impl FnOnce(&BundleRef<'_, L>) -> U::Extra,
+) -> Result<(), ComponentError>
+where
+    L: RemoveBundle,
+    U: PromoteEntity<L>,
+{
+    L::check_components(world.entity(entity)?)?;
+    let extra = fill(&BundleRef {
+        entity: WorldEntity::new(world, entity),
+        _bundle: PhantomData,
+    });
+    U::promote_entity(world, entity, extra)
+}
+
+/// Converts `entity` from the bundle struct `U` back to the bundle struct `L`, returning the
+/// components that only `U` has
+///
+/// Like [`promote`], the shared components are moved and components in neither bundle are left
+/// unchanged. If the entity is missing any of the components of `U`, it isn't changed.
+pub fn demote<U, L>(world: &mut World, entity: Entity) -> Result<U::Extra, ComponentError>
+where
+    U: PromoteEntity<L> + RemoveBundle,
+{
+    U::check_components(world.entity(entity)?)?;
+    U::demote_entity(world, entity)
+}
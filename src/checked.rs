@@ -0,0 +1,91 @@
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+/// A mutable borrow of a component that checks the component's invariants when it is dropped
+///
+/// Returned by [`ComponentProviderMut::get_mut_checked`](crate::ComponentProviderMut::get_mut_checked).
+/// Structs deriving `ComponentProvider` check fields marked `#[provider(assert = "...")]`, where
+/// `self` refers to the component, with a `debug_assert!`. This reports an invariant violation
+/// where the component was modified rather than wherever it is next read. Other providers return
+/// a guard that doesn't check anything.
+///
+/// **The assertion is only checked through this guard.** The plain `&mut` reference returned by
+/// [`get_mut`](crate::ComponentProviderMut::get_mut) can't check anything when the borrow ends, so
+/// behaviors calling `get_mut` modify the component unchecked.
+///
+/// ```
+/// use hecs_component_provider::{default_trait_impl, ComponentProvider, ComponentProviderMut};
+///
+/// struct Fuel(f32);
+///
+/// #[default_trait_impl]
+/// trait Burn: ComponentProviderMut<Fuel> {
+///     fn burn(&mut self, amount: f32) {
+///         let mut fuel = self.get_mut_checked();
+///         fuel.0 -= amount;
+///     }
+/// }
+///
+/// #[derive(ComponentProvider)]
+/// struct Rocket {
+///     #[provider(assert = "self.0 >= 0.0")]
+///     fuel: Fuel,
+/// }
+///
+/// let mut rocket = Rocket { fuel: Fuel(1.0) };
+/// rocket.burn(0.5);
+///
+/// // not checked, since it isn't borrowed through a guard
+/// let fuel: &mut Fuel = rocket.get_mut();
+/// fuel.0 = -1.0;
+///
+/// # #[cfg(debug_assertions)]
+/// assert!(std::panic::catch_unwind(move || rocket.burn(1.0)).is_err());
+/// ```
+pub struct CheckedMut<'a, C: ?Sized> {
+    value: &'a mut C,
+    check: Option<fn(&C)>,
+}
+
+impl<'a, C: ?Sized> CheckedMut<'a, C> {
+    /// Wraps `value`, calling `check` with it when the guard is dropped
+    pub fn new(value: &'a mut C, check: fn(&C)) -> Self {
+        CheckedMut {
+            value,
+            check: Some(check),
+        }
+    }
+
+    /// Wraps `value` without checking it
+    pub fn unchecked(value: &'a mut C) -> Self {
+        CheckedMut { value, check: None }
+    }
+}
+
+impl<C: ?Sized> Deref for CheckedMut<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.value
+    }
+}
+
+impl<C: ?Sized> DerefMut for CheckedMut<'_, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.value
+    }
+}
+
+impl<C: ?Sized> Drop for CheckedMut<'_, C> {
+    fn drop(&mut self) {
+        if let Some(check) = self.check {
+            check(self.value);
+        }
+    }
+}
+
+impl<C: ?Sized + fmt::Debug> fmt::Debug for CheckedMut<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.value, f)
+    }
+}
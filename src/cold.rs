@@ -0,0 +1,84 @@
+use core::ops::{Deref, DerefMut};
+
+/// A boxed component, for large fields that are rarely accessed
+///
+/// Bundle struct fields marked `#[provider(cold)]` are added to entities as `Cold<T>` instead of
+/// `T`, so that only a pointer is stored alongside the entity's other components. Query structs
+/// access them with `&'a Cold<T>` or `&'a mut Cold<T>` fields, which provide `T` as well as
+/// `Cold<T>`, so behaviors don't need to know which fields are cold.
+///
+/// Query fields are treated as cold when their type is written as `Cold<T>` or through
+/// `hecs_component_provider::`, so other types with this name can be queried as plain components
+/// by a qualified path like `self::Cold<T>`.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, Cold, ComponentProvider, ComponentProviderMut, IntoEntityBuilder
+/// };
+///
+/// struct Position(f32);
+/// struct History(Vec<f32>);
+///
+/// #[default_trait_impl]
+/// trait Record: ComponentProvider<Position> + ComponentProviderMut<History> {
+///     fn record(&mut self) {
+///         let &Position(position) = self.get();
+///         let history: &mut History = self.get_mut();
+///         history.0.push(position);
+///     }
+/// }
+///
+/// #[derive(ComponentProvider)]
+/// struct Unit {
+///     position: Position,
+///     #[provider(cold)]
+///     history: History,
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct RecordQuery<'a> {
+///     position: &'a Position,
+///     history: &'a mut Cold<History>,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let unit = Unit { position: Position(1.0), history: History(Vec::new()) };
+/// let entity = world.spawn(unit.into_entity_builder().build());
+///
+/// for mut item in world.query_mut::<RecordQuery>() {
+///     item.record();
+/// }
+/// assert_eq!(world.get::<&Cold<History>>(entity).unwrap().0, [1.0]);
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Cold<T>(Box<T>);
+
+impl<T> Cold<T> {
+    pub fn new(value: T) -> Self {
+        Cold(Box::new(value))
+    }
+
+    pub fn into_inner(self) -> T {
+        *self.0
+    }
+}
+
+impl<T> From<T> for Cold<T> {
+    fn from(value: T) -> Self {
+        Cold::new(value)
+    }
+}
+
+impl<T> Deref for Cold<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Cold<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
@@ -0,0 +1,353 @@
+use crate::{ComponentProviderOptional, ComponentProviderOptionalMut};
+
+/// Compose several providers into a single tuple struct that provides the components of all of them
+///
+/// Rust's coherence rules prevent a blanket implementation for tuples from choosing, per component,
+/// the element that provides it. Instead, this macro generates a tuple struct and takes a mapping
+/// from each element index to the components that element supplies, resolving every access at
+/// compile time. Prefix a component with `mut` to also forward [`ComponentProviderMut`](crate::ComponentProviderMut).
+///
+/// This is useful for combining a query item with other data, such as a resources handle, so that
+/// the combined value satisfies all of a behavior's supertraits.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, gen_composed_component_providers, ComponentProvider,
+///     ComponentProviderMut, SelfComponentProvider
+/// };
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+/// #[derive(SelfComponentProvider)]
+/// struct Time { dt: f32 }
+///
+/// #[default_trait_impl]
+/// trait ApplyVelocity:
+///     ComponentProviderMut<Position> + ComponentProvider<Velocity> + ComponentProvider<Time>
+/// {
+///     fn apply_velocity(&mut self) {
+///         let &Time { dt } = self.get();
+///         let &Velocity(v) = self.get();
+///         let position: &mut Position = self.get_mut();
+///         position.0 += v * dt;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// gen_composed_component_providers!(
+///     MovableWithTime<'a>(MovableQuery<'a>, &'a Time) {
+///         0 => [mut Position, Velocity],
+///         1 => [Time],
+///     }
+/// );
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(1.0), Velocity(2.0)));
+///
+/// let time = Time { dt: 0.5 };
+/// for entity in world.query_mut::<MovableQuery>() {
+///     let mut combined = MovableWithTime(entity, &time);
+///     combined.apply_velocity();
+///     assert_eq!(combined.0.position.0, 2.0);
+/// }
+/// ```
+#[macro_export]
+macro_rules! gen_composed_component_providers {
+    (
+        $vis:vis $name:ident $(<$($lt:lifetime),+>)? ($($element:ty),+ $(,)?) {
+            $($index:tt => [$($components:tt)*]),+ $(,)?
+        }
+    ) => {
+        $vis struct $name$(<$($lt),+>)?($(pub $element),+);
+
+        $crate::gen_composed_component_providers!(
+            @elements $name [$($($lt),+)?] $($index [$($components)*])+
+        );
+    };
+
+    (@elements $name:ident $lifetimes:tt $($index:tt [$($components:tt)*])+) => {
+        $(
+            $crate::gen_composed_component_providers!(@element $name $lifetimes $index $($components)*);
+        )+
+    };
+
+    (@element $name:ident [$($lt:lifetime),*] $index:tt) => {};
+
+    (@element $name:ident [$($lt:lifetime),*] $index:tt mut $component:ty $(, $($rest:tt)*)?) => {
+        $crate::gen_composed_component_providers!(@ref $name [$($lt),*] $index $component);
+
+        impl<$($lt),*> $crate::ComponentProviderMut<$component> for $name<$($lt),*> {
+            #[inline]
+            fn get_mut(&mut self) -> &mut $component {
+                $crate::ComponentProviderMut::<$component>::get_mut(&mut self.$index)
+            }
+
+            #[inline]
+            fn get_mut_checked(&mut self) -> $crate::CheckedMut<'_, $component> {
+                $crate::ComponentProviderMut::<$component>::get_mut_checked(&mut self.$index)
+            }
+        }
+
+        $crate::gen_composed_component_providers!(@element $name [$($lt),*] $index $($($rest)*)?);
+    };
+
+    (@element $name:ident [$($lt:lifetime),*] $index:tt $component:ty $(, $($rest:tt)*)?) => {
+        $crate::gen_composed_component_providers!(@ref $name [$($lt),*] $index $component);
+        $crate::gen_composed_component_providers!(@element $name [$($lt),*] $index $($($rest)*)?);
+    };
+
+    (@ref $name:ident [$($lt:lifetime),*] $index:tt $component:ty) => {
+        impl<$($lt),*> $crate::ComponentProvider<$component> for $name<$($lt),*> {
+            #[inline]
+            fn get(&self) -> &$component {
+                $crate::ComponentProvider::<$component>::get(&self.$index)
+            }
+        }
+    };
+}
+
+/// Adapter that provides optional components from `A`, falling back to `B` when `A` lacks them
+///
+/// Both `A` and `B` must implement the optional provider traits for a component for the chain to
+/// provide it. A typical use is an entity with optional overrides falling back to a shared template.
+///
+/// ```
+/// use hecs_component_provider::{
+///     gen_tuple_query_component_providers, Chain, ComponentProviderOptional
+/// };
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Color(u8, u8, u8);
+///
+/// let mut world = hecs::World::new();
+/// let template = world.spawn((Color(255, 0, 0),));
+/// let plain = world.spawn(("plain",));
+/// let overridden = world.spawn(("overridden", Color(0, 0, 255)));
+///
+/// gen_tuple_query_component_providers!(ColorQuery, (Option<&Color>,));
+///
+/// let mut template_query = world.query_one::<ColorQuery>(template);
+/// let template = template_query.get().unwrap();
+///
+/// let mut plain_query = world.query_one::<ColorQuery>(plain);
+/// let chain = Chain(plain_query.get().unwrap(), &template);
+/// assert_eq!(chain.get_optional(), Some(&Color(255, 0, 0)));
+///
+/// let mut overridden_query = world.query_one::<ColorQuery>(overridden);
+/// let chain = Chain(overridden_query.get().unwrap(), &template);
+/// assert_eq!(chain.get_optional(), Some(&Color(0, 0, 255)));
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Chain<A, B>(pub A, pub B);
+
+impl<Component: ?Sized, A, B> ComponentProviderOptional<Component> for Chain<A, B>
+where
+    A: ComponentProviderOptional<Component>,
+    B: ComponentProviderOptional<Component>,
+{
+    #[inline]
+    fn get_optional(&self) -> Option<&Component> {
+        self.0.get_optional().or_else(|| self.1.get_optional())
+    }
+}
+
+impl<Component: ?Sized, A, B> ComponentProviderOptionalMut<Component> for Chain<A, B>
+where
+    A: ComponentProviderOptionalMut<Component>,
+    B: ComponentProviderOptionalMut<Component>,
+{
+    #[inline]
+    fn get_optional_mut(&mut self) -> Option<&mut Component> {
+        match self.0.get_optional_mut() {
+            Some(component) => Some(component),
+            None => self.1.get_optional_mut(),
+        }
+    }
+}
+
+/// Adapter that provides components converted from the components of the wrapped provider
+///
+/// Conversions are declared per component pair with [`gen_mapped_component_providers`](crate::gen_mapped_component_providers),
+/// which uses [`AsRef`] (and [`AsMut`] for mutable access) on the source component.
+/// This allows behaviors written against interface-like components to run on providers that only
+/// expose richer concrete types.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MapProvider<P>(pub P);
+
+/// Declare conversions between components for use with [`MapProvider`]
+///
+/// Each `Source => Target` pair implements [`ComponentProvider<Target>`](crate::ComponentProvider)
+/// for `MapProvider<P>` whenever `P` provides `Source`. Prefix the pair with `mut` to also
+/// implement [`ComponentProviderMut<Target>`](crate::ComponentProviderMut).
+///
+/// Due to Rust's orphan rules, `Target` must be defined in the crate invoking the macro.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, gen_mapped_component_providers, ComponentProvider,
+///     ComponentProviderMut, MapProvider
+/// };
+///
+/// struct Transform { x: f32 }
+///
+/// struct GlobalTransform { local: Transform, parent_x: f32 }
+///
+/// impl AsRef<Transform> for GlobalTransform {
+///     fn as_ref(&self) -> &Transform { &self.local }
+/// }
+///
+/// impl AsMut<Transform> for GlobalTransform {
+///     fn as_mut(&mut self) -> &mut Transform { &mut self.local }
+/// }
+///
+/// gen_mapped_component_providers!(mut GlobalTransform => Transform);
+///
+/// #[default_trait_impl]
+/// trait MoveRight: ComponentProviderMut<Transform> {
+///     fn move_right(&mut self) {
+///         let transform: &mut Transform = self.get_mut();
+///         transform.x += 1.0;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct GlobalTransformQuery<'a> {
+///     transform: &'a mut GlobalTransform,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((GlobalTransform { local: Transform { x: 1.0 }, parent_x: 5.0 },));
+///
+/// for entity in world.query_mut::<GlobalTransformQuery>() {
+///     let mut mapped = MapProvider(entity);
+///     mapped.move_right();
+///     assert_eq!(mapped.0.transform.local.x, 2.0);
+/// }
+/// ```
+#[macro_export]
+macro_rules! gen_mapped_component_providers {
+    ($(,)?) => {};
+
+    (mut $source:ty => $target:ty $(, $($rest:tt)*)?) => {
+        $crate::gen_mapped_component_providers!($source => $target);
+
+        impl<P> $crate::ComponentProviderMut<$target> for $crate::MapProvider<P>
+        where
+            P: $crate::ComponentProviderMut<$source>,
+        {
+            #[inline]
+            fn get_mut(&mut self) -> &mut $target {
+                ::core::convert::AsMut::<$target>::as_mut(
+                    $crate::ComponentProviderMut::<$source>::get_mut(&mut self.0),
+                )
+            }
+        }
+
+        $crate::gen_mapped_component_providers!($($($rest)*)?);
+    };
+
+    ($source:ty => $target:ty $(, $($rest:tt)*)?) => {
+        impl<P> $crate::ComponentProvider<$target> for $crate::MapProvider<P>
+        where
+            P: $crate::ComponentProvider<$source>,
+        {
+            #[inline]
+            fn get(&self) -> &$target {
+                ::core::convert::AsRef::<$target>::as_ref(
+                    $crate::ComponentProvider::<$source>::get(&self.0),
+                )
+            }
+        }
+
+        $crate::gen_mapped_component_providers!($($($rest)*)?);
+    };
+}
+
+/// Implement the provider traits for a type by naming the field that holds each component
+///
+/// This is for types that can't use the derive, such as hand-written caches or wrappers whose
+/// components are stored in nested fields. Each entry maps a component type to a field path
+/// starting with `self`, and `mut` also implements [`ComponentProviderMut`](crate::ComponentProviderMut).
+/// The type may have lifetime parameters, e.g. `View<'a> { ... }`.
+///
+/// Due to Rust's orphan rules, either the type or the component must be defined in the crate
+/// invoking the macro.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, impl_component_provider, ComponentProvider, ComponentProviderMut
+/// };
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// struct PhysicsCache {
+///     state: (Position, Velocity),
+///     dirty: bool,
+/// }
+///
+/// impl_component_provider!(PhysicsCache {
+///     mut Position => self.state.0,
+///     Velocity => self.state.1,
+/// });
+///
+/// #[default_trait_impl]
+/// trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+///     fn apply_velocity(&mut self) {
+///         let &Velocity(v) = self.get();
+///         let position: &mut Position = self.get_mut();
+///         position.0 += v;
+///     }
+/// }
+///
+/// let mut cache = PhysicsCache { state: (Position(1.0), Velocity(2.0)), dirty: false };
+/// cache.apply_velocity();
+/// assert_eq!(cache.state.0 .0, 3.0);
+/// ```
+#[macro_export]
+macro_rules! impl_component_provider {
+    ($name:ident <$($lt:lifetime),+> { $($fields:tt)* }) => {
+        $crate::impl_component_provider!(@fields [$($lt),+] $name<$($lt),+>; $($fields)*);
+    };
+
+    ($target:ty { $($fields:tt)* }) => {
+        $crate::impl_component_provider!(@fields [] $target; $($fields)*);
+    };
+
+    (@fields [$($lt:lifetime),*] $target:ty; $(,)?) => {};
+
+    (
+        @fields [$($lt:lifetime),*] $target:ty;
+        mut $component:ty => self $(. $field:tt)+ $(, $($rest:tt)*)?
+    ) => {
+        $crate::impl_component_provider!(@fields [$($lt),*] $target; $component => self $(. $field)+);
+
+        impl<$($lt),*> $crate::ComponentProviderMut<$component> for $target {
+            #[inline]
+            fn get_mut(&mut self) -> &mut $component {
+                &mut self $(. $field)+
+            }
+        }
+
+        $crate::impl_component_provider!(@fields [$($lt),*] $target; $($($rest)*)?);
+    };
+
+    (
+        @fields [$($lt:lifetime),*] $target:ty;
+        $component:ty => self $(. $field:tt)+ $(, $($rest:tt)*)?
+    ) => {
+        impl<$($lt),*> $crate::ComponentProvider<$component> for $target {
+            #[inline]
+            fn get(&self) -> &$component {
+                &self $(. $field)+
+            }
+        }
+
+        $crate::impl_component_provider!(@fields [$($lt),*] $target; $($($rest)*)?);
+    };
+}
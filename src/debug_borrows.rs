@@ -0,0 +1,105 @@
+use core::any::TypeId;
+#[cfg(feature = "debug-borrows")]
+use core::panic::Location;
+#[cfg(feature = "debug-borrows")]
+use core::sync::atomic::{AtomicU64, Ordering};
+use hecs::{Entity, World};
+#[cfg(feature = "debug-borrows")]
+use std::sync::Mutex;
+
+/// Records a borrow of an entity's component made through a runtime provider, which is released
+/// when dropped
+///
+/// With the `debug-borrows` feature, borrows of the same component of the same entity in the same
+/// world are checked against each other, and a conflicting borrow panics with the locations of
+/// both borrows. Without it, this does nothing.
+pub(crate) struct TrackedBorrow {
+    #[cfg(feature = "debug-borrows")]
+    id: u64,
+}
+
+#[cfg(not(feature = "debug-borrows"))]
+#[inline]
+pub(crate) fn track(
+    _world: &World,
+    _entity: Entity,
+    _type_id: TypeId,
+    _type_name: &'static str,
+    _mutable: bool,
+) -> TrackedBorrow {
+    TrackedBorrow {}
+}
+
+#[cfg(feature = "debug-borrows")]
+struct Outstanding {
+    id: u64,
+    world: usize,
+    entity: Entity,
+    type_id: TypeId,
+    mutable: bool,
+    location: &'static Location<'static>,
+}
+
+#[cfg(feature = "debug-borrows")]
+static OUTSTANDING: Mutex<Vec<Outstanding>> = Mutex::new(Vec::new());
+#[cfg(feature = "debug-borrows")]
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "debug-borrows")]
+fn describe(mutable: bool) -> &'static str {
+    if mutable {
+        "mutably"
+    } else {
+        "immutably"
+    }
+}
+
+#[cfg(feature = "debug-borrows")]
+#[track_caller]
+pub(crate) fn track(
+    world: &World,
+    entity: Entity,
+    type_id: TypeId,
+    type_name: &'static str,
+    mutable: bool,
+) -> TrackedBorrow {
+    let location = Location::caller();
+    let world = world as *const World as usize;
+    let mut outstanding = OUTSTANDING.lock().unwrap_or_else(|e| e.into_inner());
+    let conflict = outstanding.iter().find(|b| {
+        b.world == world && b.entity == entity && b.type_id == type_id && (mutable || b.mutable)
+    });
+    if let Some(conflict) = conflict {
+        let message = format!(
+            "component `{}` of entity {:?} can't be borrowed {} at {}, it is already borrowed {} \
+             at {}",
+            type_name,
+            entity,
+            describe(mutable),
+            location,
+            describe(conflict.mutable),
+            conflict.location
+        );
+        // release the lock before panicking so that unwinding guards can still release theirs
+        drop(outstanding);
+        panic!("{}", message);
+    }
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    outstanding.push(Outstanding {
+        id,
+        world,
+        entity,
+        type_id,
+        mutable,
+        location,
+    });
+    TrackedBorrow { id }
+}
+
+#[cfg(feature = "debug-borrows")]
+impl Drop for TrackedBorrow {
+    fn drop(&mut self) {
+        let mut outstanding = OUTSTANDING.lock().unwrap_or_else(|e| e.into_inner());
+        outstanding.retain(|b| b.id != self.id);
+    }
+}
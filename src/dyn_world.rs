@@ -0,0 +1,248 @@
+use crate::debug_borrows::{self, TrackedBorrow};
+use core::any::{type_name, Any, TypeId};
+use core::cell::RefCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use hecs::{Component, Entity, World};
+use std::collections::HashMap;
+
+/// Provides the components of any entity in a world by [`TypeId`], checking borrows at runtime
+///
+/// This is intended for scripting and editor layers, which can't name query types statically.
+/// Component types must be [registered](Self::register) before they can be accessed, since hecs
+/// can only look components up by their static type.
+///
+/// Like a `RefCell`, each component type can be borrowed immutably any number of times or mutably
+/// once at a time, and the borrow ends when the returned guard is dropped. As hecs tracks borrows
+/// per component type rather than per entity, this applies across all entities: borrowing the
+/// `Position` of one entity mutably prevents borrowing the `Position` of any other entity.
+///
+/// # Panics
+///
+/// Accessing a component panics if its type wasn't registered, or if it would conflict with an
+/// existing borrow of the same type through this provider. The message names the component.
+/// Borrows made through the world by other code, e.g. a query that is being iterated, are checked
+/// by hecs, which also panics. With the `debug-borrows` feature, a conflicting borrow of the same
+/// component of the same entity, including one made through a [`WorldFallback`](crate::WorldFallback),
+/// panics with the locations of both borrows.
+///
+/// ```
+/// use hecs_component_provider::DynWorldProvider;
+/// use std::any::TypeId;
+///
+/// struct Health(i32);
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((Health(10),));
+///
+/// let mut provider = DynWorldProvider::new(&world);
+/// provider.register::<Health>();
+///
+/// // e.g. a command typed into an in-game console
+/// let type_id = TypeId::of::<Health>();
+/// let mut health = provider.get_mut(entity, type_id).unwrap();
+/// health.downcast_mut::<Health>().unwrap().0 += 5;
+/// drop(health);
+///
+/// let health = provider.get(entity, type_id).unwrap();
+/// assert_eq!(health.downcast_ref::<Health>().unwrap().0, 15);
+/// ```
+pub struct DynWorldProvider<'w> {
+    world: &'w World,
+    accessors: HashMap<TypeId, Accessor>,
+    // the number of shared borrows of each component type, or -1 if it is borrowed mutably
+    borrows: RefCell<HashMap<TypeId, isize>>,
+}
+
+struct Accessor {
+    type_name: &'static str,
+    get: for<'a> fn(&'a World, Entity) -> Borrowed<'a, *const dyn Any>,
+    get_mut: for<'a> fn(&'a World, Entity) -> Borrowed<'a, *mut dyn Any>,
+}
+
+// a pointer to a component, with the guard keeping it borrowed
+type Borrowed<'a, P> = Option<(P, Box<dyn Guard + 'a>)>;
+
+trait Guard {}
+impl<T> Guard for T {}
+
+fn get<T: Component>(world: &World, entity: Entity) -> Borrowed<'_, *const dyn Any> {
+    let guard = world.get::<&T>(entity).ok()?;
+    let ptr = &*guard as &dyn Any as *const dyn Any;
+    Some((ptr, Box::new(guard)))
+}
+
+fn get_mut<T: Component>(world: &World, entity: Entity) -> Borrowed<'_, *mut dyn Any> {
+    let mut guard = world.get::<&mut T>(entity).ok()?;
+    let ptr = &mut *guard as &mut dyn Any as *mut dyn Any;
+    Some((ptr, Box::new(guard)))
+}
+
+impl<'w> DynWorldProvider<'w> {
+    pub fn new(world: &'w World) -> Self {
+        DynWorldProvider {
+            world,
+            accessors: HashMap::new(),
+            borrows: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Allows components of type `T` to be accessed by their [`TypeId`]
+    pub fn register<T: Component>(&mut self) -> &mut Self {
+        self.accessors.insert(
+            TypeId::of::<T>(),
+            Accessor {
+                type_name: type_name::<T>(),
+                get: get::<T>,
+                get_mut: get_mut::<T>,
+            },
+        );
+        self
+    }
+
+    pub fn is_registered(&self, type_id: TypeId) -> bool {
+        self.accessors.contains_key(&type_id)
+    }
+
+    /// The name of a registered component type
+    pub fn type_name(&self, type_id: TypeId) -> Option<&'static str> {
+        self.accessors.get(&type_id).map(|a| a.type_name)
+    }
+
+    pub fn world(&self) -> &'w World {
+        self.world
+    }
+
+    /// Borrows the component of `entity` with type `type_id` immutably, returning `None` if the
+    /// entity doesn't exist or doesn't have it
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type isn't registered or the component type is borrowed mutably.
+    #[track_caller]
+    pub fn get(&self, entity: Entity, type_id: TypeId) -> Option<DynRef<'_>> {
+        let accessor = self.accessor(type_id);
+        let tracked = debug_borrows::track(self.world, entity, type_id, accessor.type_name, false);
+        let release = self.borrow(type_id, accessor.type_name, false);
+        let (ptr, guard) = (accessor.get)(self.world, entity)?;
+        // SAFETY: the component stays borrowed by its guard for as long as the reference
+        Some(DynRef {
+            value: unsafe { &*ptr },
+            _guard: guard,
+            _release: release,
+            _tracked: tracked,
+        })
+    }
+
+    /// Borrows the component of `entity` with type `type_id` mutably, returning `None` if the
+    /// entity doesn't exist or doesn't have it
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type isn't registered or the component type is already borrowed.
+    #[track_caller]
+    pub fn get_mut(&self, entity: Entity, type_id: TypeId) -> Option<DynRefMut<'_>> {
+        let accessor = self.accessor(type_id);
+        let tracked = debug_borrows::track(self.world, entity, type_id, accessor.type_name, true);
+        let release = self.borrow(type_id, accessor.type_name, true);
+        let (ptr, guard) = (accessor.get_mut)(self.world, entity)?;
+        // SAFETY: the component stays borrowed uniquely by its guard for as long as the reference
+        Some(DynRefMut {
+            value: unsafe { &mut *ptr },
+            _guard: guard,
+            _release: release,
+            _tracked: tracked,
+        })
+    }
+
+    fn accessor(&self, type_id: TypeId) -> &Accessor {
+        self.accessors.get(&type_id).unwrap_or_else(|| {
+            panic!(
+                "component type {:?} isn't registered with the provider",
+                type_id
+            )
+        })
+    }
+
+    fn borrow(&self, type_id: TypeId, type_name: &'static str, mutable: bool) -> Release<'_> {
+        let mut borrows = self.borrows.borrow_mut();
+        let count = borrows.entry(type_id).or_insert(0);
+        match (*count, mutable) {
+            (-1, _) => panic!("component `{}` is already borrowed mutably", type_name),
+            (n, true) if n > 0 => panic!(
+                "component `{}` can't be borrowed mutably while it is borrowed immutably",
+                type_name
+            ),
+            (_, true) => *count = -1,
+            (_, false) => *count += 1,
+        }
+        Release {
+            borrows: &self.borrows,
+            type_id,
+        }
+    }
+}
+
+// ends a borrow tracked by the provider when dropped
+struct Release<'a> {
+    borrows: &'a RefCell<HashMap<TypeId, isize>>,
+    type_id: TypeId,
+}
+
+impl Drop for Release<'_> {
+    fn drop(&mut self) {
+        let mut borrows = self.borrows.borrow_mut();
+        let count = borrows.get_mut(&self.type_id).unwrap();
+        *count = if *count == -1 { 0 } else { *count - 1 };
+    }
+}
+
+/// An immutable borrow of a component through a [`DynWorldProvider`]
+pub struct DynRef<'a> {
+    value: &'a dyn Any,
+    _guard: Box<dyn Guard + 'a>,
+    _release: Release<'a>,
+    _tracked: TrackedBorrow,
+}
+
+impl Deref for DynRef<'_> {
+    type Target = dyn Any;
+
+    fn deref(&self) -> &(dyn Any + 'static) {
+        self.value
+    }
+}
+
+impl fmt::Debug for DynRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynRef").finish_non_exhaustive()
+    }
+}
+
+/// A mutable borrow of a component through a [`DynWorldProvider`]
+pub struct DynRefMut<'a> {
+    value: &'a mut dyn Any,
+    _guard: Box<dyn Guard + 'a>,
+    _release: Release<'a>,
+    _tracked: TrackedBorrow,
+}
+
+impl Deref for DynRefMut<'_> {
+    type Target = dyn Any;
+
+    fn deref(&self) -> &(dyn Any + 'static) {
+        self.value
+    }
+}
+
+impl DerefMut for DynRefMut<'_> {
+    fn deref_mut(&mut self) -> &mut (dyn Any + 'static) {
+        self.value
+    }
+}
+
+impl fmt::Debug for DynRefMut<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynRefMut").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    ComponentProvider, ComponentProviderMut, ComponentProviderOptional,
+    ComponentProviderOptionalMut,
+};
+use core::any::{type_name, Any, TypeId};
+
+/// Provides components looked up by [`TypeId`] at runtime, implemented by
+/// `derive(ComponentProvider)` with `#[provider(dynamic)]`
+///
+/// A struct in dynamic mode has a single implementation of this trait instead of one provider
+/// implementation per component, which reduces code size for large numbers of structs, e.g. on
+/// wasm. Behaviors are called on it by wrapping it in [`Dyn`].
+///
+/// Whether a component is provided is only checked at runtime, so a missing component causes a
+/// panic instead of a compile error, and each access costs a comparison per field.
+pub trait DynamicProvider {
+    fn get_dyn(&self, type_id: TypeId) -> Option<&dyn Any>;
+
+    /// Returns `None` for components that are only borrowed immutably
+    fn get_dyn_mut(&mut self, type_id: TypeId) -> Option<&mut dyn Any>;
+}
+
+/// Implements every provider trait for a [`DynamicProvider`] by looking components up at runtime
+///
+/// [`ComponentProvider::get`] and [`ComponentProviderMut::get_mut`] panic if the component isn't
+/// provided, while the optional providers return `None`. In dynamic mode, `Option<&T>` query
+/// fields are provided through all of the provider traits.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, ComponentProvider, ComponentProviderMut, Dyn
+/// };
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// #[default_trait_impl]
+/// trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+///     fn apply_velocity(&mut self) {
+///         let &Velocity(v) = self.get();
+///         let position: &mut Position = self.get_mut();
+///         position.0 += v;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(dynamic)]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(0.0), Velocity(1.0)));
+/// for item in world.query_mut::<MovableQuery>() {
+///     Dyn(item).apply_velocity();
+/// }
+/// assert_eq!(world.query_mut::<&Position>().into_iter().next().unwrap().0, 1.0);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Dyn<P>(pub P);
+
+impl<C: Any, P: DynamicProvider> ComponentProvider<C> for Dyn<P> {
+    #[inline]
+    fn get(&self) -> &C {
+        self.get_optional()
+            .unwrap_or_else(|| panic!("component `{}` is not provided", type_name::<C>()))
+    }
+}
+
+impl<C: Any, P: DynamicProvider> ComponentProviderMut<C> for Dyn<P> {
+    #[inline]
+    fn get_mut(&mut self) -> &mut C {
+        self.get_optional_mut()
+            .unwrap_or_else(|| panic!("component `{}` is not provided mutably", type_name::<C>()))
+    }
+}
+
+impl<C: Any, P: DynamicProvider> ComponentProviderOptional<C> for Dyn<P> {
+    #[inline]
+    fn get_optional(&self) -> Option<&C> {
+        self.0.get_dyn(TypeId::of::<C>())?.downcast_ref()
+    }
+}
+
+impl<C: Any, P: DynamicProvider> ComponentProviderOptionalMut<C> for Dyn<P> {
+    #[inline]
+    fn get_optional_mut(&mut self) -> Option<&mut C> {
+        self.0.get_dyn_mut(TypeId::of::<C>())?.downcast_mut()
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{Commands, ComponentProvider};
+use hecs::{Component, DynamicBundle, Entity};
+
+/// Deferred changes to a single entity, recorded through the [`Commands`] of a
+/// [`Schedule`](crate::Schedule) and applied at the end of the stage
+///
+/// `EntityCommands` provides itself, so a behavior can require [`EntityCommandsProvider`] and
+/// change its own entity without being passed its id. It is created for each entity with
+/// [`Commands::entity`] and composed with the query item using
+/// [`gen_composed_component_providers`](crate::gen_composed_component_providers).
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, gen_composed_component_providers, ComponentProvider, EntityCommands,
+///     EntityCommandsProvider, Schedule, Stage,
+/// };
+///
+/// struct Temperature(i32);
+/// struct Shield;
+/// struct Burning;
+///
+/// #[default_trait_impl]
+/// trait Ignite: ComponentProvider<Temperature> + EntityCommandsProvider {
+///     fn ignite(&self) {
+///         let temperature: &Temperature = self.get();
+///         if temperature.0 > 100 {
+///             self.commands().insert(Burning);
+///             self.commands().remove::<Shield>();
+///         }
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct TemperatureQuery<'a> {
+///     entity: hecs::Entity,
+///     temperature: &'a Temperature,
+/// }
+///
+/// gen_composed_component_providers!(
+///     TemperatureWithCommands<'a>(TemperatureQuery<'a>, EntityCommands) {
+///         0 => [Temperature],
+///         1 => [EntityCommands],
+///     }
+/// );
+///
+/// let mut world = hecs::World::new();
+/// let torch = world.spawn((Temperature(300), Shield));
+/// let stone = world.spawn((Temperature(20), Shield));
+///
+/// let mut schedule = Schedule::new();
+/// let commands = schedule.commands();
+/// schedule.add_behavior::<TemperatureQuery, _>("ignite", move |item| {
+///     let entity = commands.entity(item.entity);
+///     TemperatureWithCommands(item, entity).ignite();
+/// });
+/// schedule.run(&mut world);
+///
+/// assert!(world.get::<&Burning>(torch).is_ok());
+/// assert!(world.get::<&Shield>(torch).is_err());
+/// assert!(world.get::<&Burning>(stone).is_err());
+/// assert!(world.get::<&Shield>(stone).is_ok());
+/// ```
+#[derive(Clone)]
+pub struct EntityCommands {
+    entity: Entity,
+    commands: Commands,
+}
+
+impl EntityCommands {
+    pub(crate) fn new(entity: Entity, commands: Commands) -> Self {
+        EntityCommands { entity, commands }
+    }
+
+    /// The entity the commands apply to
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Adds `component` to the entity, replacing any existing component of the same type
+    pub fn insert(&self, component: impl Component) {
+        self.commands.insert_one(self.entity, component);
+    }
+
+    /// Adds all components of `components` to the entity
+    pub fn insert_bundle(&self, components: impl DynamicBundle) {
+        self.commands.insert(self.entity, components);
+    }
+
+    /// Removes the entity's component of type `T`, which has no effect if it doesn't have one
+    pub fn remove<T: Component>(&self) {
+        self.commands.remove_one::<T>(self.entity);
+    }
+
+    pub fn despawn(&self) {
+        self.commands.despawn(self.entity);
+    }
+}
+
+impl ComponentProvider<EntityCommands> for EntityCommands {
+    fn get(&self) -> &EntityCommands {
+        self
+    }
+}
+
+/// Gives behaviors access to deferred changes to their own entity through [`EntityCommands`]
+///
+/// This is implemented for every type providing `EntityCommands`.
+pub trait EntityCommandsProvider: ComponentProvider<EntityCommands> {
+    fn commands(&self) -> &EntityCommands {
+        self.get()
+    }
+}
+
+impl<P: ComponentProvider<EntityCommands> + ?Sized> EntityCommandsProvider for P {}
@@ -0,0 +1,112 @@
+use hecs::Entity;
+
+/// A set of entities stored as a bitset indexed by entity id
+///
+/// Inserting, removing and checking membership take constant time, and iteration visits members
+/// in order of their ids while skipping empty regions a word at a time. This makes it suitable
+/// for restricting behaviors to a subset of entities, such as those in the active chunk, with
+/// [`run_behavior_in_set`](crate::run_behavior_in_set).
+///
+/// Memory use is proportional to the highest entity id inserted.
+#[derive(Clone, Debug, Default)]
+pub struct EntitySet {
+    words: Vec<u64>,
+    // the member with each id, to distinguish it from other generations of the same id
+    slots: Vec<Entity>,
+    len: usize,
+}
+
+impl EntitySet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `entity`, returning whether it wasn't already a member
+    ///
+    /// An entity with the same id but a different generation is replaced.
+    pub fn insert(&mut self, entity: Entity) -> bool {
+        let id = entity.id() as usize;
+        if id >= self.slots.len() {
+            self.slots.resize(id + 1, Entity::DANGLING);
+            self.words.resize(id / 64 + 1, 0);
+        }
+        let (word, bit) = (id / 64, 1 << (id % 64));
+        let was_set = self.words[word] & bit != 0;
+        let replaced = was_set && self.slots[id] == entity;
+        if !was_set {
+            self.len += 1;
+        }
+        self.words[word] |= bit;
+        self.slots[id] = entity;
+        !replaced
+    }
+
+    /// Removes `entity`, returning whether it was a member
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        if !self.contains(entity) {
+            return false;
+        }
+        let id = entity.id() as usize;
+        self.words[id / 64] &= !(1 << (id % 64));
+        self.slots[id] = Entity::DANGLING;
+        self.len -= 1;
+        true
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        let id = entity.id() as usize;
+        self.words
+            .get(id / 64)
+            .is_some_and(|word| word & (1 << (id % 64)) != 0)
+            && self.slots[id] == entity
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.slots.clear();
+        self.len = 0;
+    }
+
+    /// The members in order of their ids
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| **word != 0)
+            .flat_map(move |(index, &word)| {
+                let mut word = word;
+                core::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    Some(self.slots[index * 64 + bit])
+                })
+            })
+    }
+}
+
+impl Extend<Entity> for EntitySet {
+    fn extend<I: IntoIterator<Item = Entity>>(&mut self, iter: I) {
+        for entity in iter {
+            self.insert(entity);
+        }
+    }
+}
+
+impl core::iter::FromIterator<Entity> for EntitySet {
+    fn from_iter<I: IntoIterator<Item = Entity>>(iter: I) -> Self {
+        let mut set = EntitySet::new();
+        set.extend(iter);
+        set
+    }
+}
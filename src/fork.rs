@@ -0,0 +1,117 @@
+use crate::{EntityMap, OwnedQuery, Snapshot};
+use core::marker::PhantomData;
+use hecs::{DynamicBundle, Entity, World};
+
+/// A scratch world holding copies of the components of the entities matching a query, for
+/// simulating ahead without changing the original world
+///
+/// Created by [`fork`]. Behaviors can be run on [`world_mut`](Self::world_mut) as usual, then the
+/// results are either dropped with [`discard`](Self::discard) or written back with
+/// [`merge`](Self::merge).
+pub struct Fork<Q: OwnedQuery> {
+    world: World,
+    // original entities to their copies
+    map: EntityMap,
+    _query: PhantomData<fn() -> Q>,
+}
+
+/// Copies the components of every entity matching `Q` into a new scratch world
+///
+/// Only the components in `Q`'s owned bundle, set with `#[provider(owned(Name))]`, are copied.
+///
+/// ```
+/// use hecs_component_provider::{fork, ComponentProvider};
+///
+/// #[derive(Clone)]
+/// struct Position(f32);
+/// #[derive(Clone)]
+/// struct Velocity(f32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(owned(MovableBundle))]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((Position(0.0), Velocity(1.0)));
+///
+/// // plan a few ticks ahead without moving the real entity
+/// let mut plan = fork::<MovableQuery>(&world);
+/// for _ in 0..3 {
+///     for item in plan.world_mut().query_mut::<MovableQuery>() {
+///         item.position.0 += item.velocity.0;
+///     }
+/// }
+/// let copy = plan.get(entity).unwrap();
+/// assert_eq!(plan.world().get::<&Position>(copy).unwrap().0, 3.0);
+/// assert_eq!(world.get::<&Position>(entity).unwrap().0, 0.0);
+///
+/// plan.merge(&mut world);
+/// assert_eq!(world.get::<&Position>(entity).unwrap().0, 3.0);
+/// ```
+///
+/// # Panics
+///
+/// Panics if a component accessed by `Q` is already borrowed mutably.
+pub fn fork<Q>(world: &World) -> Fork<Q>
+where
+    Q: OwnedQuery,
+    Q::Owned: DynamicBundle,
+{
+    let mut scratch = World::new();
+    let mut map = EntityMap::new();
+    for (original, bundle) in Snapshot::<Q>::take(world) {
+        map.insert(original, scratch.spawn(bundle));
+    }
+    Fork {
+        world: scratch,
+        map,
+        _query: PhantomData,
+    }
+}
+
+impl<Q> Fork<Q>
+where
+    Q: OwnedQuery,
+    Q::Owned: DynamicBundle,
+{
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// The copy of `original` in the scratch world
+    pub fn get(&self, original: Entity) -> Option<Entity> {
+        self.map.get(original)
+    }
+
+    /// The original entity a scratch entity was copied from
+    pub fn original(&self, copy: Entity) -> Option<Entity> {
+        self.map.get_a(copy)
+    }
+
+    /// Drops the scratch world, leaving the original world unchanged
+    pub fn discard(self) {}
+
+    /// Writes the components of `Q`'s owned bundle back from each copy to its original entity,
+    /// returning the number of entities updated
+    ///
+    /// Entities spawned in the scratch world are ignored, as are copies that were despawned or no
+    /// longer match `Q`, and originals that were despawned since the fork.
+    pub fn merge(mut self, world: &mut World) -> usize {
+        let mut count = 0;
+        for (original, copy) in self.map.iter() {
+            if let Ok(item) = self.world.query_one_mut::<Q>(copy) {
+                if world.insert(original, Q::to_owned_item(&item)).is_ok() {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
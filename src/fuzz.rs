@@ -0,0 +1,98 @@
+use crate::Validate;
+use arbitrary::{Arbitrary, Unstructured};
+use hecs::{Bundle, Entity, Query, World};
+
+/// Spawns a world of arbitrary bundles from fuzzer input, runs a behavior on every entity
+/// matching `Q`, and checks the entities' invariants with [`Validate`]
+///
+/// This is intended to be called from a fuzz target, e.g. with cargo-fuzz. Bundle structs can
+/// implement [`Arbitrary`] with `#[provider(arbitrary)]`, and query structs implement [`Validate`]
+/// with `#[provider(validate = "...")]`. Worlds in which an entity is already invalid before the
+/// behavior runs are skipped, so that only violations introduced by the behavior are reported, as
+/// are inputs too short to build a world from.
+///
+/// # Panics
+///
+/// Panics with the validation error and the entity if an entity is invalid after the behavior
+/// has run, which the fuzzer reports as a crash.
+///
+/// ```
+/// use arbitrary::{Arbitrary, Unstructured};
+/// use hecs_component_provider::{
+///     default_trait_impl, fuzz_behavior, ComponentProvider, ComponentProviderMut,
+///     ValidationError
+/// };
+///
+/// struct Fuel(u8);
+///
+/// impl<'a> Arbitrary<'a> for Fuel {
+///     fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+///         u8::arbitrary(u).map(Fuel)
+///     }
+/// }
+///
+/// #[default_trait_impl]
+/// trait Burn: ComponentProviderMut<Fuel> {
+///     fn burn(&mut self) {
+///         let fuel: &mut Fuel = self.get_mut();
+///         fuel.0 = fuel.0.saturating_sub(1);
+///     }
+/// }
+///
+/// #[derive(hecs::Bundle, ComponentProvider)]
+/// #[provider(arbitrary)]
+/// struct RocketBundle {
+///     fuel: Fuel,
+/// }
+///
+/// fn check_fuel(rocket: &RocketQuery) -> Result<(), ValidationError> {
+///     if rocket.fuel.0 > 100 {
+///         return Err(ValidationError::new("too much fuel"));
+///     }
+///     Ok(())
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(validate = "check_fuel")]
+/// struct RocketQuery<'a> {
+///     fuel: &'a mut Fuel,
+/// }
+///
+/// // e.g. in `fuzz_target!(|data: &[u8]| { ... })`
+/// let data = [3, 50, 120, 7];
+/// fuzz_behavior::<RocketBundle, RocketQuery>(&data, |rocket| rocket.burn());
+/// ```
+pub fn fuzz_behavior<B, Q>(data: &[u8], mut behavior: impl for<'q> FnMut(&mut Q::Item<'q>))
+where
+    B: Bundle + for<'a> Arbitrary<'a> + 'static,
+    Q: Query,
+    for<'q> Q::Item<'q>: Validate,
+{
+    let mut u = Unstructured::new(data);
+    let bundles = match u
+        .arbitrary_iter::<B>()
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+    {
+        Ok(bundles) => bundles,
+        Err(_) => return,
+    };
+    let mut world = World::new();
+    world.spawn_batch(bundles);
+
+    if world
+        .query_mut::<Q>()
+        .into_iter()
+        .any(|item| item.validate().is_err())
+    {
+        return;
+    }
+    for (entity, mut item) in world.query_mut::<(Entity, Q)>() {
+        behavior(&mut item);
+        if let Err(error) = item.validate() {
+            panic!(
+                "entity {:?} is invalid after the behavior: {}",
+                entity, error
+            );
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use core::any::type_name;
+use core::fmt::{self, Debug, Write as _};
+use hecs::{Component, Entity, World};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+
+type RenderFn = fn(&World, Entity) -> Option<String>;
+
+/// Renders the components of selected entities to a canonical string and compares it against a
+/// stored snapshot file, for regression-testing how behaviors interact over several ticks
+///
+/// Only registered component types are rendered, with their `Debug` representation, in
+/// alphabetical order of their names. Entities are listed in the given order under a label chosen
+/// by the test, so the snapshot doesn't depend on entity ids.
+///
+/// [`assert_matches`](Self::assert_matches) writes the snapshot instead of comparing against it
+/// when the `BLESS` environment variable is set, e.g. `BLESS=1 cargo test`, which is how snapshots
+/// are created and updated after an intended change.
+///
+/// ```
+/// use hecs_component_provider::Golden;
+///
+/// #[derive(Debug)]
+/// struct Health(i32);
+/// #[derive(Debug)]
+/// struct Poisoned;
+///
+/// let mut world = hecs::World::new();
+/// let hero = world.spawn((Health(10), Poisoned));
+/// let goblin = world.spawn((Health(3),));
+///
+/// let mut golden = Golden::new();
+/// golden.register::<Health>().register::<Poisoned>();
+///
+/// assert_eq!(
+///     golden.render(&world, [("hero", hero), ("goblin", goblin)]),
+///     "hero\n  Health: Health(10)\n  Poisoned: Poisoned\n\
+///      goblin\n  Health: Health(3)\n"
+/// );
+///
+/// // in a test, e.g. `golden.assert_matches(&world, entities, "tests/snapshots/poison.txt")`
+/// # let path = std::env::temp_dir().join("hecs_component_provider_golden_doctest.txt");
+/// golden.bless(&world, [("hero", hero)], &path).unwrap();
+/// assert!(golden.check(&world, [("hero", hero)], &path).is_ok());
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Default)]
+pub struct Golden {
+    components: BTreeMap<String, RenderFn>,
+}
+
+fn render<T: Component + Debug>(world: &World, entity: Entity) -> Option<String> {
+    let component = world.get::<&T>(entity).ok()?;
+    Some(format!("{:?}", *component))
+}
+
+impl Golden {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `T` under its name without the module path, e.g. `"Health"`
+    pub fn register<T: Component + Debug>(&mut self) -> &mut Self {
+        let name = type_name::<T>().rsplit("::").next().unwrap();
+        self.register_as::<T>(name)
+    }
+
+    /// Registers `T` under `name`, replacing any type previously registered with that name
+    pub fn register_as<T: Component + Debug>(&mut self, name: &str) -> &mut Self {
+        self.components.insert(name.to_owned(), render::<T>);
+        self
+    }
+
+    /// Renders the registered components of each entity under its label
+    ///
+    /// Each entity's label is followed by one indented `Name: {:?}` line per registered component
+    /// it has, or by `  <despawned>` if it no longer exists.
+    pub fn render<'a>(
+        &self,
+        world: &World,
+        entities: impl IntoIterator<Item = (&'a str, Entity)>,
+    ) -> String {
+        let mut rendered = String::new();
+        for (label, entity) in entities {
+            rendered.push_str(label);
+            rendered.push('\n');
+            if !world.contains(entity) {
+                rendered.push_str("  <despawned>\n");
+                continue;
+            }
+            for (name, render) in &self.components {
+                if let Some(component) = render(world, entity) {
+                    writeln!(rendered, "  {}: {}", name, component).unwrap();
+                }
+            }
+        }
+        rendered
+    }
+
+    /// Compares the rendered entities against the snapshot stored at `path`
+    pub fn check<'a>(
+        &self,
+        world: &World,
+        entities: impl IntoIterator<Item = (&'a str, Entity)>,
+        path: impl AsRef<Path>,
+    ) -> Result<(), GoldenError> {
+        let path = path.as_ref();
+        let actual = self.render(world, entities);
+        let expected = match std::fs::read_to_string(path) {
+            Ok(expected) => expected.replace("\r\n", "\n"),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Err(GoldenError::Missing(path.to_owned()))
+            }
+            Err(error) => return Err(GoldenError::Io(error)),
+        };
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(GoldenError::Mismatch {
+                path: path.to_owned(),
+                expected,
+                actual,
+            })
+        }
+    }
+
+    /// Writes the rendered entities to `path` as the new snapshot, creating its directory if needed
+    pub fn bless<'a>(
+        &self,
+        world: &World,
+        entities: impl IntoIterator<Item = (&'a str, Entity)>,
+        path: impl AsRef<Path>,
+    ) -> Result<(), GoldenError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.render(world, entities))?;
+        Ok(())
+    }
+
+    /// Checks the rendered entities against the snapshot at `path`, or blesses them if the `BLESS`
+    /// environment variable is set
+    ///
+    /// # Panics
+    ///
+    /// Panics with the first differing line if the snapshot doesn't match, or if it can't be read
+    /// or written.
+    #[track_caller]
+    pub fn assert_matches<'a>(
+        &self,
+        world: &World,
+        entities: impl IntoIterator<Item = (&'a str, Entity)>,
+        path: impl AsRef<Path>,
+    ) {
+        let result = if std::env::var_os("BLESS").is_some() {
+            self.bless(world, entities, path)
+        } else {
+            self.check(world, entities, path)
+        };
+        if let Err(error) = result {
+            panic!("{}", error);
+        }
+    }
+}
+
+/// The error returned when a [`Golden`] snapshot doesn't match or can't be accessed
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(io::Error),
+    /// There is no snapshot at the path
+    Missing(PathBuf),
+    /// The rendered entities differ from the snapshot
+    Mismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl From<io::Error> for GoldenError {
+    fn from(error: io::Error) -> Self {
+        GoldenError::Io(error)
+    }
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Io(error) => write!(f, "failed to read or write snapshot: {}", error),
+            GoldenError::Missing(path) => write!(
+                f,
+                "no snapshot at `{}`, rerun with BLESS=1 to create it",
+                path.display()
+            ),
+            GoldenError::Mismatch {
+                path,
+                expected,
+                actual,
+            } => {
+                let mut expected_lines = expected.lines();
+                let mut actual_lines = actual.lines();
+                let mut line = 1;
+                let (expected_line, actual_line) = loop {
+                    match (expected_lines.next(), actual_lines.next()) {
+                        // the lines are equal, so the difference is in trailing newlines
+                        (None, None) => break (None, None),
+                        (e, a) if e != a => break (e, a),
+                        _ => line += 1,
+                    }
+                };
+                writeln!(
+                    f,
+                    "snapshot `{}` differs at line {}, rerun with BLESS=1 to update it",
+                    path.display(),
+                    line
+                )?;
+                writeln!(
+                    f,
+                    "expected: {}",
+                    expected_line.unwrap_or("<end of snapshot>")
+                )?;
+                write!(f, "  actual: {}", actual_line.unwrap_or("<end of output>"))
+            }
+        }
+    }
+}
+
+impl Error for GoldenError {}
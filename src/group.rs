@@ -0,0 +1,62 @@
+use core::ops::{BitOr, BitOrAssign};
+
+/// A bitmask of the groups, or layers, an entity belongs to
+///
+/// Behaviors restricted to groups with [`Schedule::in_groups`](crate::Schedule::in_groups) only
+/// run on entities sharing at least one group with them, even if other entities match their
+/// query. Entities without a `Group` component belong to [`Group::DEFAULT`].
+///
+/// ```
+/// use hecs_component_provider::Group;
+///
+/// const WORLD: Group = Group::DEFAULT;
+/// const UI: Group = Group::layer(1);
+///
+/// let overlay = WORLD | UI;
+/// assert!(overlay.intersects(UI));
+/// assert!(!WORLD.intersects(UI));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Group(pub u32);
+
+impl Group {
+    /// The group of entities without a `Group` component, which is layer 0
+    pub const DEFAULT: Group = Group(1);
+    pub const NONE: Group = Group(0);
+    pub const ALL: Group = Group(u32::MAX);
+
+    /// The group containing only `layer`, which must be less than 32
+    pub const fn layer(layer: u32) -> Group {
+        Group(1 << layer)
+    }
+
+    /// Whether this and `other` share at least one layer
+    pub const fn intersects(self, other: Group) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Whether every layer of `other` is in this group
+    pub const fn contains(self, other: Group) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Group::DEFAULT
+    }
+}
+
+impl BitOr for Group {
+    type Output = Group;
+
+    fn bitor(self, other: Group) -> Group {
+        Group(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Group {
+    fn bitor_assign(&mut self, other: Group) {
+        self.0 |= other.0;
+    }
+}
@@ -0,0 +1,297 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU64, Ordering};
+use hecs::Component;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Marks components that are modified through shared borrows, such as [`Atomic`] and [`Locked`]
+///
+/// The derived [`QueryAccess`](crate::QueryAccess) reports query fields borrowing these components,
+/// directly or through an `Option`, as
+/// [`interior_mutable`](crate::ComponentAccess::interior_mutable), however the type is named in
+/// the query. Custom wrappers around atomics or locks implement it to be reported the same way:
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, InteriorMutable, QueryAccess};
+/// use std::sync::atomic::AtomicU32;
+///
+/// struct Hits(AtomicU32);
+///
+/// impl InteriorMutable for Hits {}
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct TargetQuery<'a> {
+///     hits: &'a Hits,
+/// }
+///
+/// assert!(TargetQuery::access()[0].interior_mutable);
+/// ```
+pub trait InteriorMutable: Component {}
+
+/// A value that can be stored in an [`Atomic`] component as 64 bits
+///
+/// This is implemented for the primitive integer types, `bool`, `f32` and `f64`. Newtype
+/// components implement it by converting their inner value, so that each accumulator is its own
+/// component type:
+///
+/// ```
+/// use hecs_component_provider::AtomicValue;
+///
+/// #[derive(Clone, Copy)]
+/// struct Damage(f32);
+///
+/// impl AtomicValue for Damage {
+///     fn to_bits(self) -> u64 {
+///         AtomicValue::to_bits(self.0)
+///     }
+///
+///     fn from_bits(bits: u64) -> Self {
+///         Damage(AtomicValue::from_bits(bits))
+///     }
+/// }
+/// ```
+pub trait AtomicValue: Copy + Send + Sync + 'static {
+    fn to_bits(self) -> u64;
+
+    fn from_bits(bits: u64) -> Self;
+}
+
+macro_rules! impl_atomic_value {
+    ($($ty:ty => $bits:ty),*) => {
+        $(
+            impl AtomicValue for $ty {
+                fn to_bits(self) -> u64 {
+                    self as $bits as u64
+                }
+
+                fn from_bits(bits: u64) -> Self {
+                    bits as $bits as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_atomic_value!(
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, usize => usize,
+    i8 => i64, i16 => i64, i32 => i64, i64 => i64, isize => i64
+);
+
+impl AtomicValue for bool {
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        bits != 0
+    }
+}
+
+impl AtomicValue for f32 {
+    fn to_bits(self) -> u64 {
+        f32::to_bits(self) as u64
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        f32::from_bits(bits as u32)
+    }
+}
+
+impl AtomicValue for f64 {
+    fn to_bits(self) -> u64 {
+        f64::to_bits(self)
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        f64::from_bits(bits)
+    }
+}
+
+/// A component that can be modified through a shared borrow with atomic operations, e.g. an
+/// accumulator that read-only passes add to
+///
+/// Query fields of type `&'a Atomic<T>` borrow the component immutably, so behaviors modifying it
+/// don't conflict with each other and can run in the same batch of a
+/// [`ParallelRunner`](crate::ParallelRunner). The derived [`QueryAccess`](crate::QueryAccess)
+/// reports such fields as [`interior_mutable`](crate::ComponentAccess::interior_mutable).
+/// Modifications made on other threads are visible once those threads have been joined, e.g. after
+/// the runner returns.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, Atomic, ComponentProvider, ParallelRunner, QueryAccess
+/// };
+///
+/// struct Burning(u32);
+/// struct Poisoned(u32);
+///
+/// #[default_trait_impl]
+/// trait TakeDamage: ComponentProvider<Atomic<u32>> {
+///     fn take_damage(&self, amount: u32) {
+///         let damage: &Atomic<u32> = self.get();
+///         damage.update(|damage| damage + amount);
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct BurningQuery<'a> {
+///     burning: &'a Burning,
+///     damage: &'a Atomic<u32>,
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct PoisonedQuery<'a> {
+///     poisoned: &'a Poisoned,
+///     damage: &'a Atomic<u32>,
+/// }
+///
+/// assert!(BurningQuery::access()[1].interior_mutable);
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((Burning(3), Poisoned(1), Atomic::new(0u32)));
+///
+/// let mut runner = ParallelRunner::new(2);
+/// runner
+///     .add_behavior::<BurningQuery, _>("burn", |item| item.take_damage(item.burning.0))
+///     .add_behavior::<PoisonedQuery, _>("poison", |item| item.take_damage(item.poisoned.0));
+/// assert_eq!(runner.batches(), vec![vec!["burn", "poison"]]);
+/// runner.run(&mut world);
+///
+/// assert_eq!(world.get::<&Atomic<u32>>(entity).unwrap().load(), 4);
+/// ```
+pub struct Atomic<T: AtomicValue> {
+    bits: AtomicU64,
+    _value: PhantomData<T>,
+}
+
+impl<T: AtomicValue> Atomic<T> {
+    pub fn new(value: T) -> Self {
+        Atomic {
+            bits: AtomicU64::new(value.to_bits()),
+            _value: PhantomData,
+        }
+    }
+
+    pub fn load(&self) -> T {
+        T::from_bits(self.bits.load(Ordering::Acquire))
+    }
+
+    pub fn store(&self, value: T) {
+        self.bits.store(value.to_bits(), Ordering::Release);
+    }
+
+    /// Stores `value`, returning the previous value
+    pub fn swap(&self, value: T) -> T {
+        T::from_bits(self.bits.swap(value.to_bits(), Ordering::AcqRel))
+    }
+
+    /// Replaces the value with the result of `f`, returning the previous value
+    ///
+    /// `f` may be called several times if other threads modify the value concurrently, so it
+    /// should have no side effects.
+    pub fn update(&self, mut f: impl FnMut(T) -> T) -> T {
+        let previous = self
+            .bits
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |bits| {
+                Some(f(T::from_bits(bits)).to_bits())
+            })
+            .unwrap_or_else(|bits| bits);
+        T::from_bits(previous)
+    }
+
+    /// Takes the value, leaving `T::default()`, e.g. to consume the accumulated value once per
+    /// frame
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.swap(T::default())
+    }
+
+    pub fn into_inner(self) -> T {
+        T::from_bits(self.bits.into_inner())
+    }
+}
+
+impl<T: AtomicValue> InteriorMutable for Atomic<T> {}
+
+impl<T: AtomicValue + Default> Default for Atomic<T> {
+    fn default() -> Self {
+        Atomic::new(T::default())
+    }
+}
+
+impl<T: AtomicValue> From<T> for Atomic<T> {
+    fn from(value: T) -> Self {
+        Atomic::new(value)
+    }
+}
+
+impl<T: AtomicValue + fmt::Debug> fmt::Debug for Atomic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Atomic").field(&self.load()).finish()
+    }
+}
+
+/// A component that can be modified through a shared borrow by locking it, for values that
+/// don't fit in an [`Atomic`]
+///
+/// Like [`Atomic`], query fields of type `&'a Locked<T>` borrow the component immutably, so
+/// behaviors modifying it can run in parallel. Locks should only be held briefly, preferably with
+/// [`with`](Self::with), since a behavior waiting for the lock blocks its worker thread. A lock
+/// held by a behavior that panicked is released, and the value can still be accessed.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, Locked};
+///
+/// struct Hits(Vec<&'static str>);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct TargetQuery<'a> {
+///     hits: &'a Locked<Hits>,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let target = world.spawn((Locked::new(Hits(Vec::new())),));
+///
+/// for item in world.query::<TargetQuery>().iter() {
+///     let hits: &Locked<Hits> = item.get();
+///     hits.with(|hits| hits.0.push("arrow"));
+/// }
+/// assert_eq!(world.get::<&Locked<Hits>>(target).unwrap().lock().0, ["arrow"]);
+/// ```
+#[derive(Debug, Default)]
+pub struct Locked<T>(Mutex<T>);
+
+impl<T> Locked<T> {
+    pub fn new(value: T) -> Self {
+        Locked(Mutex::new(value))
+    }
+
+    /// Locks the value, blocking until other threads release it
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Calls `f` with the locked value, releasing the lock when it returns
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    /// The value of a uniquely borrowed component, which doesn't need to be locked
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Send + 'static> InteriorMutable for Locked<T> {}
+
+impl<T> From<T> for Locked<T> {
+    fn from(value: T) -> Self {
+        Locked::new(value)
+    }
+}
@@ -0,0 +1,117 @@
+use core::marker::PhantomData;
+use hecs::{BatchedIter, Entity, Query, QueryIter, World};
+
+/// The query items of the entities matching `Q`, borrowed from a world by
+/// [`WorldExt::behaviors`](crate::WorldExt::behaviors)
+///
+/// `Behaviors` implements [`IntoIterator`], so it can be used in a `for` loop or turned into an
+/// iterator to be combined with adapters such as `filter`, `take` and `zip`.
+///
+/// Creating a `Behaviors` each frame is cheap: hecs caches the archetypes matching each query type
+/// and only matches them again when the world's set of archetypes changes.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, ComponentProvider, ComponentProviderMut, WorldExt
+/// };
+///
+/// struct Fuel(u32);
+///
+/// #[default_trait_impl]
+/// trait Burn: ComponentProviderMut<Fuel> {
+///     fn burn(&mut self) {
+///         let fuel: &mut Fuel = self.get_mut();
+///         fuel.0 = fuel.0.saturating_sub(1);
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct FuelQuery<'a> {
+///     fuel: &'a mut Fuel,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// for fuel in [0, 5, 3] {
+///     world.spawn((Fuel(fuel),));
+/// }
+///
+/// // burn the fuel of at most one entity that has some left
+/// world
+///     .behaviors::<FuelQuery>()
+///     .into_iter()
+///     .filter(|item| item.fuel.0 > 0)
+///     .take(1)
+///     .for_each(|mut item| item.burn());
+///
+/// let total: u32 = world.behaviors::<&Fuel>().into_iter().map(|f| f.0).sum();
+/// assert_eq!(total, 7);
+/// ```
+pub struct Behaviors<'w, Q> {
+    world: &'w mut World,
+    _query: PhantomData<fn() -> Q>,
+}
+
+impl<'w, Q: Query> Behaviors<'w, Q> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        Behaviors {
+            world,
+            _query: PhantomData,
+        }
+    }
+
+    /// Iterates over the matching entities together with their query items
+    pub fn with_entities(self) -> QueryIter<'w, (Entity, Q)> {
+        self.world.query_mut::<(Entity, Q)>().into_iter()
+    }
+
+    /// Splits the matching entities into chunks of at most `chunk_size` entities, which never span
+    /// archetypes, each of which iterates over the query items
+    ///
+    /// The iterator and its chunks are `Send` when the query items are, which is the case for
+    /// queries of component references, so chunks can be processed on other threads, e.g. with
+    /// rayon's `par_bridge` or scoped threads.
+    ///
+    /// ```
+    /// use hecs_component_provider::WorldExt;
+    ///
+    /// struct Position(f32);
+    ///
+    /// let mut world = hecs::World::new();
+    /// for _ in 0..10 {
+    ///     world.spawn((Position(0.0),));
+    /// }
+    ///
+    /// let chunks: Vec<_> = world.behaviors::<&mut Position>().chunks(4).collect();
+    /// assert_eq!(chunks.len(), 3);
+    /// std::thread::scope(|scope| {
+    ///     for chunk in chunks {
+    ///         scope.spawn(move || chunk.for_each(|position| position.0 += 1.0));
+    ///     }
+    /// });
+    ///
+    /// assert!(world.query_mut::<&Position>().into_iter().all(|p| p.0 == 1.0));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn chunks(self, chunk_size: u32) -> BatchedIter<'w, Q> {
+        assert!(chunk_size > 0, "chunks must hold at least one entity");
+        self.world.query_mut::<Q>().into_iter_batched(chunk_size)
+    }
+
+    /// Splits the matching entities into one chunk per archetype, as with
+    /// [`chunks`](Self::chunks)
+    pub fn archetype_chunks(self) -> BatchedIter<'w, Q> {
+        self.chunks(u32::MAX)
+    }
+}
+
+impl<'w, Q: Query> IntoIterator for Behaviors<'w, Q> {
+    type Item = Q::Item<'w>;
+    type IntoIter = QueryIter<'w, Q>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.world.query_mut::<Q>().into_iter()
+    }
+}
@@ -0,0 +1,150 @@
+use hecs::{Entity, Query, World};
+use std::collections::HashMap;
+
+/// A one-to-one mapping between the entities of two worlds
+///
+/// This is typically maintained when spawning the counterpart of an entity in another world, such
+/// as a render world entity for each simulation entity, and used to join the worlds with
+/// [`run_behavior_joined`].
+#[derive(Clone, Debug, Default)]
+pub struct EntityMap {
+    forward: HashMap<Entity, Entity>,
+    backward: HashMap<Entity, Entity>,
+}
+
+impl EntityMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps `a` to `b`, replacing any existing mappings of either entity
+    pub fn insert(&mut self, a: Entity, b: Entity) {
+        self.remove(a);
+        self.remove_b(b);
+        self.forward.insert(a, b);
+        self.backward.insert(b, a);
+    }
+
+    /// Removes the mapping of `a`, returning the entity it was mapped to
+    pub fn remove(&mut self, a: Entity) -> Option<Entity> {
+        let b = self.forward.remove(&a)?;
+        self.backward.remove(&b);
+        Some(b)
+    }
+
+    /// Removes the mapping to `b`, returning the entity that was mapped to it
+    pub fn remove_b(&mut self, b: Entity) -> Option<Entity> {
+        let a = self.backward.remove(&b)?;
+        self.forward.remove(&a);
+        Some(a)
+    }
+
+    /// The entity `a` is mapped to
+    pub fn get(&self, a: Entity) -> Option<Entity> {
+        self.forward.get(&a).copied()
+    }
+
+    /// The entity that is mapped to `b`
+    pub fn get_a(&self, b: Entity) -> Option<Entity> {
+        self.backward.get(&b).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.forward.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    /// The pairs of mapped entities, in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.forward.iter().map(|(a, b)| (*a, *b))
+    }
+}
+
+/// Runs a behavior on pairs of entities in two worlds, joined by an [`EntityMap`]
+///
+/// For each entity matching `A` in `world_a` that is mapped to an entity matching `B` in
+/// `world_b`, `behavior` is called with both query items. Entities without a mapped counterpart,
+/// or whose counterpart doesn't match, are skipped. Returns the number of pairs processed.
+///
+/// The items can be combined into a single provider with
+/// [`gen_composed_component_providers`](crate::gen_composed_component_providers), so that
+/// behaviors requiring components from both worlds can be called on them.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, gen_composed_component_providers, run_behavior_joined,
+///     ComponentProvider, ComponentProviderMut, EntityMap
+/// };
+///
+/// struct Position(f32);
+/// struct Sprite { x: f32 }
+///
+/// #[default_trait_impl]
+/// trait ExtractSprite: ComponentProvider<Position> + ComponentProviderMut<Sprite> {
+///     fn extract_sprite(&mut self) {
+///         let &Position(x) = self.get();
+///         let sprite: &mut Sprite = self.get_mut();
+///         sprite.x = x;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct SimQuery<'a> {
+///     position: &'a Position,
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct RenderQuery<'a> {
+///     sprite: &'a mut Sprite,
+/// }
+///
+/// gen_composed_component_providers!(
+///     Extract<'a>(SimQuery<'a>, RenderQuery<'a>) {
+///         0 => [Position],
+///         1 => [mut Sprite],
+///     }
+/// );
+///
+/// let mut sim = hecs::World::new();
+/// let mut render = hecs::World::new();
+/// let mut map = EntityMap::new();
+/// let entity = sim.spawn((Position(4.0),));
+/// let sprite = render.spawn((Sprite { x: 0.0 },));
+/// map.insert(entity, sprite);
+/// sim.spawn((Position(1.0),));
+///
+/// let count = run_behavior_joined::<SimQuery, RenderQuery>(
+///     &mut sim,
+///     &mut render,
+///     &map,
+///     |sim, render| Extract(sim, render).extract_sprite(),
+/// );
+/// assert_eq!(count, 1);
+/// assert_eq!(render.get::<&Sprite>(sprite).unwrap().x, 4.0);
+/// ```
+pub fn run_behavior_joined<A, B>(
+    world_a: &mut World,
+    world_b: &mut World,
+    map: &EntityMap,
+    mut behavior: impl for<'a, 'b> FnMut(A::Item<'a>, B::Item<'b>),
+) -> usize
+where
+    A: Query,
+    B: Query,
+{
+    let mut count = 0;
+    for (entity, item) in world_a.query_mut::<(Entity, A)>() {
+        let other = match map.get(entity) {
+            Some(other) => other,
+            None => continue,
+        };
+        if let Ok(other_item) = world_b.query_one_mut::<B>(other) {
+            behavior(item, other_item);
+            count += 1;
+        }
+    }
+    count
+}
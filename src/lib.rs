@@ -32,7 +32,7 @@
 //!
 //! let dt = 0.1;
 //! # let mut found = false;
-//! for (_, mut entity) in world.query_mut::<MovableQuery>() {
+//! for mut entity in world.query_mut::<MovableQuery>() {
 //!     // apply the behavior to the entity
 //!     entity.apply_velocity(dt);
 //!
@@ -70,7 +70,7 @@
 //!
 //! let dt = 0.1;
 //! # let mut found = false;
-//! for (_, mut entity) in world.query_mut::<EnemyQuery>() {
+//! for mut entity in world.query_mut::<EnemyQuery>() {
 //!     // apply the behavior to the entity
 //!     entity.shoot_and_move(dt);
 //!
@@ -81,26 +81,271 @@
 //! }
 //! # assert!(found);
 //! ```
+//!
+//! # Optional features
+//!
+//! - `resources`: implements the provider traits for resources borrowed from the
+//!   [resources](https://docs.rs/resources) crate's `Resources` container.
+//! - `puffin`: wraps each behavior and system run by a [`Schedule`] in a
+//!   [puffin](https://docs.rs/puffin) profiler scope named after it.
+//! - `arbitrary`: adds `#[provider(arbitrary)]` for implementing the
+//!   [arbitrary](https://docs.rs/arbitrary) crate's `Arbitrary` trait for bundle structs, and
+//!   [`fuzz_behavior`] for fuzzing behaviors against their [`Validate`] invariants.
+//! - `proptest`: adds [`WorldStrategy`], which generates random worlds containing entities that
+//!   match given queries for [proptest](https://docs.rs/proptest) property tests.
+//! - `debug-borrows`: tracks the components borrowed through [`DynWorldProvider`] and
+//!   [`WorldFallback`] per entity, so that a conflicting borrow panics with the locations of both
+//!   borrows rather than only the second.
+//! - `mutation-log`: records the component type, entity, behavior and tick of each call to a
+//!   generated `get_mut` in a bounded [`MutationLog`] per [`Schedule`].
+
+mod access;
+mod behavior;
+mod buffered;
+mod bundle;
+mod checked;
+mod cold;
+mod compose;
+mod debug_borrows;
+mod dyn_world;
+mod dynamic;
+mod entity_commands;
+mod entity_set;
+mod fork;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+mod golden;
+mod group;
+mod interior;
+mod iter;
+mod join;
+mod many;
+mod message;
+mod metadata;
+mod mutation_log;
+mod parallel;
+mod params;
+mod predict;
+mod random_access;
+mod reflect;
+mod replication;
+#[cfg(feature = "resources")]
+mod resource;
+mod rng;
+mod rollback;
+mod runner;
+mod save;
+mod schedule;
+mod signal;
+mod snapshot;
+mod tag;
+mod tasks;
+mod teardown;
+mod trace;
+mod typed;
+mod union;
+mod validate;
+mod world;
+#[cfg(feature = "proptest")]
+mod world_strategy;
+
+pub use access::{Read, Write};
+pub use behavior::{BehaviorInfo, BehaviorRegistry};
+pub use buffered::{flip, BufferedProvider, DoubleBuffered};
+pub use bundle::{
+    demote, ensure, promote, remove, spawn_batch_with, BundleDiff, BundleRef, DiffBundle,
+    EnsureComponents, IntoEntityBuilder, Promote, RemoveBundle, SpawnDefault,
+};
+pub use checked::CheckedMut;
+pub use cold::Cold;
+pub use compose::{Chain, MapProvider};
+pub use dyn_world::{DynRef, DynRefMut, DynWorldProvider};
+pub use dynamic::{Dyn, DynamicProvider};
+pub use entity_commands::{EntityCommands, EntityCommandsProvider};
+pub use entity_set::EntitySet;
+pub use fork::{fork, Fork};
+#[cfg(feature = "arbitrary")]
+pub use fuzz::fuzz_behavior;
+pub use golden::{Golden, GoldenError};
+pub use group::Group;
+pub use interior::{Atomic, AtomicValue, InteriorMutable, Locked};
+pub use iter::Behaviors;
+pub use join::{run_behavior_joined, EntityMap};
+pub use many::{
+    ComponentProviderDisjoint, ComponentProviderMany, ComponentRefElement, ComponentRefTuple,
+    ComponentTuple,
+};
+pub use message::{Inbox, MessageProvider, Outbox};
+pub use metadata::{ComponentAccess, QueryAccess};
+pub use mutation_log::{Mutation, MutationLog};
+pub use parallel::{ParallelBackend, ParallelRunner, ScopedThreads};
+pub use params::{Params, ParamsError};
+pub use predict::{Correction, Prediction};
+pub use random_access::RandomAccessCache;
+pub use reflect::{EntityProxy, ProxyError, Reflect, ReflectRegistry};
+pub use replication::{
+    Quantization, ReplicatedBundle, ReplicatedComponent, Replication, ReplicationDescriptor,
+};
+pub use rng::{EntityRng, RngSeed};
+pub use rollback::RollbackBuffer;
+pub use runner::{
+    bind, run_behavior_in_set, run_behavior_sorted, run_with, run_with_entity, BindBehavior,
+    BoxedSystem, IncrementalRunner,
+};
+pub use save::{hash_world, SaveError, SaveGame, SavedComponent};
+pub use schedule::{
+    BehaviorStats, Clock, Commands, EntityPanic, ResumePoint, Schedule, Stage, Stats, Time,
+};
+pub use signal::{Connection, Signals};
+pub use snapshot::{OwnedQuery, Snapshot};
+pub use tag::{Tag, WithTag};
+pub use tasks::{AsyncCommands, AsyncRunner, BoxedTask, Spawner};
+pub use teardown::Teardown;
+pub use trace::{Trace, TraceEvent, TraceRun};
+pub use typed::{MissingComponents, TypedEntity, WorldExt};
+pub use validate::{Validate, ValidationError};
+pub use world::{WorldEntity, WorldFallback};
+#[cfg(feature = "proptest")]
+pub use world_strategy::{GeneratedWorld, WorldStrategy};
 
+#[doc(hidden)]
+pub use access::AccessWrapper;
+#[cfg(feature = "arbitrary")]
+#[doc(hidden)]
+pub use arbitrary;
+#[doc(hidden)]
+pub use bundle::{check_one, ensure_one, take_one, PromoteEntity};
 #[doc(hidden)]
 pub use gensym::gensym;
+#[doc(hidden)]
+pub use hecs;
+#[doc(hidden)]
+pub use metadata::{AccessProbe, InteriorAccessProbe, InteriorQuery, PlainAccessProbe};
+#[doc(hidden)]
+pub use mutation_log::record_mutation;
+#[doc(hidden)]
+pub use union::{union_access, union_borrows};
 
-pub trait ComponentProvider<Component> {
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't provide the component `{Component}`",
+    label = "missing component `{Component}`",
+    note = "add a `&'a {Component}` field to the query struct, or a field of type `{Component}` to the bundle struct",
+    note = "providers are generated by `#[derive(ComponentProvider)]` or `#[derive(SelfComponentProvider)]`"
+)]
+pub trait ComponentProvider<Component: ?Sized> {
     fn get(&self) -> &Component;
 }
 
-pub trait ComponentProviderMut<Component>: ComponentProvider<Component> {
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't provide mutable access to the component `{Component}`",
+    label = "missing mutable component `{Component}`",
+    note = "add a `&'a mut {Component}` field to the query struct, or a field of type `{Component}` to the bundle struct",
+    note = "providers are generated by `#[derive(ComponentProvider)]` or `#[derive(SelfComponentProvider)]`"
+)]
+pub trait ComponentProviderMut<Component: ?Sized>: ComponentProvider<Component> {
     fn get_mut(&mut self) -> &mut Component;
+
+    /// Borrows the component mutably through a guard that checks its invariants when dropped
+    ///
+    /// See [`CheckedMut`]. By default the guard doesn't check anything. Fields marked
+    /// `#[provider(assert = "...")]` are only checked when borrowed through this method, not
+    /// through [`get_mut`](Self::get_mut).
+    #[inline]
+    fn get_mut_checked(&mut self) -> CheckedMut<'_, Component> {
+        CheckedMut::unchecked(self.get_mut())
+    }
 }
 
-pub trait ComponentProviderOptional<Component> {
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't optionally provide the component `{Component}`",
+    label = "missing optional component `{Component}`",
+    note = "add an `Option<&'a {Component}>` field to the query struct",
+    note = "providers are generated by `#[derive(ComponentProvider)]`"
+)]
+pub trait ComponentProviderOptional<Component: ?Sized> {
     fn get_optional(&self) -> Option<&Component>;
 }
 
-pub trait ComponentProviderOptionalMut<Component>: ComponentProviderOptional<Component> {
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't optionally provide mutable access to the component `{Component}`",
+    label = "missing optional mutable component `{Component}`",
+    note = "add an `Option<&'a mut {Component}>` field to the query struct",
+    note = "providers are generated by `#[derive(ComponentProvider)]`"
+)]
+pub trait ComponentProviderOptionalMut<Component: ?Sized>:
+    ComponentProviderOptional<Component>
+{
     fn get_optional_mut(&mut self) -> Option<&mut Component>;
 }
 
+// Forward provider implementations through references, so that helper functions taking
+// `&impl Behavior` or `&mut impl Behavior` can be called with borrowed query items.
+
+impl<Component: ?Sized, P> ComponentProvider<Component> for &P
+where
+    P: ComponentProvider<Component> + ?Sized,
+{
+    #[inline]
+    fn get(&self) -> &Component {
+        (**self).get()
+    }
+}
+
+impl<Component: ?Sized, P> ComponentProvider<Component> for &mut P
+where
+    P: ComponentProvider<Component> + ?Sized,
+{
+    #[inline]
+    fn get(&self) -> &Component {
+        (**self).get()
+    }
+}
+
+impl<Component: ?Sized, P> ComponentProviderMut<Component> for &mut P
+where
+    P: ComponentProviderMut<Component> + ?Sized,
+{
+    #[inline]
+    fn get_mut(&mut self) -> &mut Component {
+        (**self).get_mut()
+    }
+
+    #[inline]
+    fn get_mut_checked(&mut self) -> CheckedMut<'_, Component> {
+        (**self).get_mut_checked()
+    }
+}
+
+impl<Component: ?Sized, P> ComponentProviderOptional<Component> for &P
+where
+    P: ComponentProviderOptional<Component> + ?Sized,
+{
+    #[inline]
+    fn get_optional(&self) -> Option<&Component> {
+        (**self).get_optional()
+    }
+}
+
+impl<Component: ?Sized, P> ComponentProviderOptional<Component> for &mut P
+where
+    P: ComponentProviderOptional<Component> + ?Sized,
+{
+    #[inline]
+    fn get_optional(&self) -> Option<&Component> {
+        (**self).get_optional()
+    }
+}
+
+impl<Component: ?Sized, P> ComponentProviderOptionalMut<Component> for &mut P
+where
+    P: ComponentProviderOptionalMut<Component> + ?Sized,
+{
+    #[inline]
+    fn get_optional_mut(&mut self) -> Option<&mut Component> {
+        (**self).get_optional_mut()
+    }
+}
+
 /// Attach to a component struct to implement [`ComponentProvider`] and [`ComponentProviderMut`] for the struct
 ///
 /// This allows behavior methods that require only a single component to be called on the struct
@@ -127,8 +372,64 @@ pub trait ComponentProviderOptionalMut<Component>: ComponentProviderOptional<Com
 /// let distance_squared = position.calculate_distance_squared_to(&other);
 /// assert_eq!(distance_squared, 52);
 /// ```
+///
+/// Wrapper components can additionally provide their [`Deref`](core::ops::Deref) target by adding
+/// `#[self_provider(deref)]`, or `#[self_provider(deref_mut)]` to also provide mutable access through
+/// [`DerefMut`](core::ops::DerefMut). Behaviors written for the inner type can then be called on the wrapper.
+///
+/// ```
+/// use hecs_component_provider::{default_trait_impl, ComponentProviderMut, SelfComponentProvider};
+/// use std::ops::{Deref, DerefMut};
+///
+/// #[derive(SelfComponentProvider)]
+/// struct Health(i32);
+///
+/// #[derive(SelfComponentProvider)]
+/// #[self_provider(deref_mut)]
+/// struct ShieldHealth(Health);
+///
+/// impl Deref for ShieldHealth {
+///     type Target = Health;
+///     fn deref(&self) -> &Health { &self.0 }
+/// }
+///
+/// impl DerefMut for ShieldHealth {
+///     fn deref_mut(&mut self) -> &mut Health { &mut self.0 }
+/// }
+///
+/// #[default_trait_impl]
+/// trait TakeDamage: ComponentProviderMut<Health> {
+///     fn take_damage(&mut self, amount: i32) {
+///         let health: &mut Health = self.get_mut();
+///         health.0 -= amount;
+///     }
+/// }
+///
+/// let mut shield = ShieldHealth(Health(10));
+/// shield.take_damage(3);
+/// assert_eq!(shield.0 .0, 7);
+/// ```
+///
+/// Components defined in other crates can't derive this trait, and Rust's orphan rules prevent
+/// implementing the provider traits for them directly. Wrap them in a local newtype with
+/// `#[self_provider(deref_mut)]` instead, so that behaviors written against the foreign type
+/// can still be called on the wrapper.
+///
+/// As with [`ComponentProvider`](macro@ComponentProvider), the generated getters are marked
+/// `#[inline]` unless `#[self_provider(no_inline)]` is added.
 pub use hecs_component_provider_macros::SelfComponentProvider;
 
+/// Attach to a struct to implement [`Reflect`](trait@Reflect) for it, exposing its fields by name
+///
+/// Fields of tuple structs are named by their index. The struct must not have generic parameters.
+/// See [`ReflectRegistry`] for accessing reflected components by name.
+pub use hecs_component_provider_macros::Reflect;
+
+/// Attach to a unit struct to implement [`Tag`](trait@Tag) for it
+///
+/// See [`Tag`](trait@Tag) for the generated methods.
+pub use hecs_component_provider_macros::Tag;
+
 /// Attach to a struct that derives [`hecs::Bundle`] or [`hecs::Query`] to generate component provider implementations for those structs.
 ///
 /// ```
@@ -148,7 +449,7 @@ pub use hecs_component_provider_macros::SelfComponentProvider;
 ///         let &Velocity(vx, vy) = self.get();
 ///         // or use fully qualified syntax:
 ///         assert!(matches!(ComponentProvider::<Velocity>::get(self), &Velocity(_, _)));
-
+///
 ///         let position: &mut Position = self.get_mut();
 ///         position.0 += vx;
 ///         position.1 += vy;
@@ -175,12 +476,401 @@ pub use hecs_component_provider_macros::SelfComponentProvider;
 /// spawn_entity.apply_velocity(); // uses ComponentProvider implementation on Bundle
 /// world.spawn(spawn_entity);
 ///
-/// for (_, mut entity) in world.query_mut::<MovableQuery>() {
+/// for mut entity in world.query_mut::<MovableQuery>() {
 ///     entity.apply_velocity(); // uses ComponentProvider implementation on Query
 ///     let position: &Position = entity.get();
 ///     assert_eq!(position, &Position(24, 36)); // apply_velocity has been applied twice by now
 /// }
 /// ```
+///
+/// Boxed unsized fields, such as `ai: Box<dyn Ai>` in a bundle or `ai: &'a mut Box<dyn Ai>` in a
+/// query, additionally provide the unsized value itself, i.e. `ComponentProvider<dyn Ai>`.
+/// The same applies to `Box<str>` and `Box<[T]>` fields.
+///
+/// Query fields of type [`hecs::Or<&L, &R>`](hecs::Or) implement [`ComponentProviderOptional`] for
+/// both `L` and `R`, and [`ComponentProviderOptionalMut`] for whichever side is borrowed mutably.
+///
+/// Fields can be gated with `#[cfg(...)]`, e.g. debug-only components. The compiler removes
+/// disabled fields before the derive runs, so no providers are generated for them.
+///
+/// # Splitting
+///
+/// `#[provider(split(PartA = [field_a], PartB = [field_b, field_c]))]` generates the structs `PartA`
+/// and `PartB`, each providing the components of its own fields, along with a
+/// `split(&mut self) -> (PartA<'_>, PartB<'_>)` method. This allows mutable access to
+/// disjoint sets of components to be passed to separate behaviors at the same time.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, ComponentProviderMut};
+///
+/// struct Position(i32);
+/// struct Velocity(i32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(split(PositionPart = [position], VelocityPart = [velocity]))]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a mut Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(1), Velocity(2)));
+///
+/// for mut entity in world.query_mut::<MovableQuery>() {
+///     let (mut position, mut velocity) = entity.split();
+///     let position: &mut Position = position.get_mut();
+///     let velocity: &mut Velocity = velocity.get_mut();
+///     position.0 += velocity.0;
+///     velocity.0 = 0;
+/// }
+/// ```
+///
+/// # Nested bundles
+///
+/// A bundle field whose type is itself a provider can forward the components it provides with
+/// `#[provider(nested(A, B))]`, which implements [`ComponentProvider`] and [`ComponentProviderMut`]
+/// for each listed component on the outer struct. The components must be listed because the
+/// derive can't see which components the field's type provides.
+///
+/// ```
+/// use hecs_component_provider::{default_trait_impl, ComponentProvider, ComponentProviderMut};
+///
+/// struct Position(i32);
+/// struct Velocity(i32);
+/// struct Health(i32);
+///
+/// #[default_trait_impl]
+/// trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+///     fn apply_velocity(&mut self) {
+///         let &Velocity(v) = self.get();
+///         let position: &mut Position = self.get_mut();
+///         position.0 += v;
+///     }
+/// }
+///
+/// #[derive(ComponentProvider)]
+/// struct PhysicsBundle {
+///     position: Position,
+///     velocity: Velocity,
+/// }
+///
+/// #[derive(ComponentProvider)]
+/// struct Player {
+///     health: Health,
+///     #[provider(nested(Position, Velocity))]
+///     physics: PhysicsBundle,
+/// }
+///
+/// let mut player = Player {
+///     health: Health(10),
+///     physics: PhysicsBundle { position: Position(0), velocity: Velocity(2) },
+/// };
+/// player.apply_velocity();
+/// assert_eq!(player.physics.position.0, 2);
+/// ```
+///
+/// # Owned bundles
+///
+/// `#[provider(owned(Name))]` on a query struct generates a bundle struct called `Name` holding a
+/// clone of each borrowed component, along with a `to_bundle` method on the query struct. This is
+/// useful for snapshotting or duplicating entities. `Entity` fields are skipped, and other field
+/// types such as `Option<&T>` aren't supported. The bundle derives [`hecs::Bundle`] and
+/// [`ComponentProvider`], and the query struct implements [`OwnedQuery`], so that all matching
+/// items can be copied at once into a [`Snapshot`].
+///
+/// ```
+/// use hecs_component_provider::ComponentProvider;
+///
+/// #[derive(Clone)]
+/// struct Position(i32);
+/// #[derive(Clone)]
+/// struct Velocity(i32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(owned(MovableBundle))]
+/// struct MovableQuery<'a> {
+///     entity: hecs::Entity,
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(1), Velocity(2)));
+///
+/// let bundles: Vec<MovableBundle> = world
+///     .query_mut::<MovableQuery>()
+///     .into_iter()
+///     .map(|item| item.to_bundle())
+///     .collect();
+/// for bundle in bundles {
+///     world.spawn(bundle);
+/// }
+/// assert_eq!(world.query_mut::<MovableQuery>().into_iter().count(), 2);
+/// ```
+///
+/// # Read-only queries
+///
+/// `#[provider(readonly_as = "Name")]` on a query struct generates a second query struct named
+/// `Name` with the same fields, where `&'a mut T` fields become `&'a T`, along with a `From`
+/// conversion from the original struct. Read-only queries can run alongside other queries reading
+/// the same components, e.g. with `World::query` instead of `World::query_mut`.
+///
+/// ```
+/// use hecs_component_provider::{default_trait_impl, ComponentProvider};
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// #[default_trait_impl]
+/// trait Speed: ComponentProvider<Velocity> {
+///     fn speed(&self) -> f32 {
+///         let velocity: &Velocity = self.get();
+///         velocity.0.abs()
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(readonly_as = "MovableQueryRead")]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a mut Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(0.0), Velocity(-2.0)));
+///
+/// let mut query = world.query::<MovableQueryRead>();
+/// let mut other = world.query::<&Velocity>();
+/// let total: f32 = query.iter().map(|item| item.speed()).sum();
+/// assert_eq!(total, other.iter().map(|v| v.0.abs()).sum::<f32>());
+/// ```
+///
+/// # Component aliases
+///
+/// A field marked `#[provider(alias(A))]` additionally provides `A` through the field type's
+/// [`AsRef<A>`] implementation, and mutably through [`AsMut<A>`] if the field is mutable. This
+/// bridges behaviors written against an old component type while entities are migrated to a new
+/// one.
+///
+/// ```
+/// use hecs_component_provider::{default_trait_impl, ComponentProvider, ComponentProviderMut};
+///
+/// struct Transform(i32);
+/// struct LocalTransform(Transform);
+///
+/// impl AsRef<Transform> for LocalTransform {
+///     fn as_ref(&self) -> &Transform { &self.0 }
+/// }
+///
+/// impl AsMut<Transform> for LocalTransform {
+///     fn as_mut(&mut self) -> &mut Transform { &mut self.0 }
+/// }
+///
+/// #[default_trait_impl]
+/// trait Translate: ComponentProviderMut<Transform> {
+///     fn translate(&mut self, offset: i32) {
+///         let transform: &mut Transform = self.get_mut();
+///         transform.0 += offset;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct LocalQuery<'a> {
+///     #[provider(alias(Transform))]
+///     transform: &'a mut LocalTransform,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((LocalTransform(Transform(1)),));
+/// for mut item in world.query_mut::<LocalQuery>() {
+///     item.translate(2);
+///     assert_eq!((item.transform.0).0, 3);
+/// }
+/// ```
+///
+/// # Field initializers
+///
+/// Bundle struct fields marked `#[provider(init = "expr")]` are initialized with the expression by
+/// a generated `new_partial` constructor, which takes the remaining fields as parameters in
+/// declaration order. The constructor has the struct's visibility and is only generated if at
+/// least one field has an initializer.
+///
+/// ```
+/// use hecs_component_provider::ComponentProvider;
+///
+/// struct Position(f32, f32);
+/// struct Velocity(f32, f32);
+/// struct Health(i32);
+///
+/// #[derive(hecs::Bundle, ComponentProvider)]
+/// struct Enemy {
+///     position: Position,
+///     #[provider(init = "Velocity(0.0, 0.0)")]
+///     velocity: Velocity,
+///     #[provider(init = "Health(100)")]
+///     health: Health,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let enemy = world.spawn(Enemy::new_partial(Position(1.0, 2.0)));
+/// assert_eq!(world.get::<&Health>(enemy).unwrap().0, 100);
+/// ```
+///
+/// # Builder methods
+///
+/// `#[provider(builder)]` on a bundle struct with named fields generates chainable `with_<field>`
+/// and `map_<field>` methods, which replace a field or transform it with a closure.
+///
+/// ```
+/// use hecs_component_provider::ComponentProvider;
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Position(f32);
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Speed(f32);
+///
+/// #[derive(Clone, ComponentProvider)]
+/// #[provider(builder)]
+/// struct Goblin {
+///     position: Position,
+///     speed: Speed,
+/// }
+///
+/// let prefab = Goblin { position: Position(0.0), speed: Speed(1.0) };
+/// let fast = prefab.clone().with_position(Position(5.0)).map_speed(|s| Speed(s.0 * 2.0));
+/// assert_eq!((fast.position, fast.speed), (Position(5.0), Speed(2.0)));
+/// ```
+///
+/// # Cold fields
+///
+/// Bundle struct fields marked `#[provider(cold)]` are added to entities boxed in [`Cold`], and
+/// query struct fields of type `&'a Cold<T>` or `&'a mut Cold<T>` provide `T` as well as
+/// `Cold<T>`. This keeps large, rarely accessed data out of the way of hot loops without changing
+/// the behaviors that use it.
+///
+/// # Replication
+///
+/// Bundle struct fields can be marked `#[provider(replicate)]` or `#[provider(server_only)]`,
+/// which is reported by the generated [`ReplicatedBundle`] implementation for networking code to
+/// respect. Unmarked fields aren't replicated. Fields can also be marked
+/// `#[provider(quantize(bits = 12, range = "-512.0..512.0"))]` to describe how their float values
+/// are packed, see [`Quantization`].
+///
+/// # Assertions
+///
+/// Fields marked `#[provider(assert = "self.0 >= 0.0")]` check the predicate, with `self` referring
+/// to the component, in debug builds whenever a guard returned by
+/// [`ComponentProviderMut::get_mut_checked`] is dropped. See [`CheckedMut`].
+///
+/// **Only `get_mut_checked` checks the predicate.** [`ComponentProviderMut::get_mut`] returns a
+/// plain reference, which can't run any code when the borrow ends, so modifications made through
+/// it, e.g. by existing behaviors, are never checked. Behaviors that should be checked have to
+/// call `get_mut_checked` instead.
+///
+/// # Validation
+///
+/// `#[provider(validate = "path::to::fn")]` on the struct implements [`Validate`] by calling the
+/// function with the struct, so that invariants across its components can be checked.
+///
+/// # Promotion
+///
+/// `#[provider(promote(Lower, extra = [a, b]))]` on a bundle struct implements [`Promote<Lower>`]
+/// for bundles that add the listed fields to the fields of `Lower`, so that entities can be
+/// converted between the two with [`promote`] and [`demote`].
+///
+/// # Fuzzing
+///
+/// With the `arbitrary` feature, `#[provider(arbitrary)]` on a bundle struct implements the
+/// arbitrary crate's `Arbitrary` trait by generating each field in turn, so that worlds of the
+/// bundle can be built from fuzzer input. See [`fuzz_behavior`].
+///
+/// # Dynamic mode
+///
+/// `#[provider(dynamic)]` implements [`DynamicProvider`] instead of a provider implementation per
+/// component, trading compile-time checks and some runtime cost for smaller code. See [`Dyn`]
+/// for calling behaviors on such structs. Dynamic mode can't be combined with nested fields,
+/// aliases or `split`.
+///
+/// # Inlining
+///
+/// Generated getters are marked `#[inline]`, so that they can be inlined across crates without
+/// link-time optimization. `#[provider(no_inline)]` on the struct leaves the hint out, e.g. to
+/// reduce code size.
+///
+/// # Thread safety
+///
+/// hecs requires components to be `Send + Sync + 'static`, so the derive checks this for each
+/// component of the struct and reports a violation at the offending field, rather than wherever the
+/// struct is later spawned or queried. `#[provider(non_send)]` on the struct skips the check, for
+/// structs that provide components to behaviors without being added to a world.
+///
+/// ```compile_fail
+/// use hecs_component_provider::ComponentProvider;
+/// use std::rc::Rc;
+///
+/// struct Health(i32);
+///
+/// #[derive(ComponentProvider)]
+/// struct SharedBundle {
+///     health: Rc<Health>, // error: `Rc<Health>` cannot be sent between threads safely
+/// }
+/// ```
+///
+/// # Unsupported structs
+///
+/// The derive reports unsupported structs at the offending item with a suggested fix. Query
+/// structs have exactly one lifetime and bundle structs none:
+///
+/// ```compile_fail
+/// # use hecs_component_provider::ComponentProvider;
+/// # struct Position(i32);
+/// #[derive(ComponentProvider)]
+/// struct PairQuery<'a, 'b> { // error: must have <= 1 lifetime parameter
+///     first: &'a Position,
+///     second: &'b Position,
+/// }
+/// ```
+///
+/// Neither may have type parameters:
+///
+/// ```compile_fail
+/// # use hecs_component_provider::ComponentProvider;
+/// #[derive(ComponentProvider)]
+/// struct GenericBundle<T> { // error: must have no type parameters
+///     value: T,
+/// }
+/// ```
+///
+/// Enums and unions aren't supported:
+///
+/// ```compile_fail
+/// # use hecs_component_provider::ComponentProvider;
+/// # struct Position(i32);
+/// #[derive(ComponentProvider)]
+/// enum Marker { // error: derive(ComponentProvider) may only be applied to structs
+///     At(Position),
+/// }
+/// ```
+///
+/// ```compile_fail
+/// # use hecs_component_provider::ComponentProvider;
+/// #[derive(ComponentProvider)]
+/// union Bits { // error: derive(ComponentProvider) may only be applied to structs
+///     value: u32,
+/// }
+/// ```
+///
+/// Trait object components are stored boxed, so query fields borrow the box:
+///
+/// ```compile_fail
+/// # use hecs_component_provider::ComponentProvider;
+/// trait Shape: Send + Sync {}
+///
+/// #[derive(ComponentProvider)]
+/// struct ShapeQuery<'a> {
+///     shape: &'a dyn Shape, // error: trait object components must be boxed
+/// }
+/// ```
 pub use hecs_component_provider_macros::ComponentProvider;
 
 /// Implement the attached trait for all types that implement the trait's supertraits
@@ -208,13 +898,69 @@ pub use hecs_component_provider_macros::ComponentProvider;
 /// # position.move_right();
 /// # assert_eq!(position.0, 2);
 /// ```
+///
+/// `#[default_trait_impl(info = fn_name)]` additionally generates a function called `fn_name`
+/// returning a [`BehaviorInfo`] that describes the trait, which can be added to a
+/// [`BehaviorRegistry`].
+///
+/// `#[default_trait_impl(warn_unused)]` warns about provider supertraits whose component type is
+/// never named in the trait's methods, since each unneeded requirement shrinks the set of
+/// entities the behavior applies to. The check is a heuristic: a component that is only used
+/// through type inference should be named with a type annotation, e.g.
+/// `let position: &Position = self.get();`. Options can be combined, e.g.
+/// `#[default_trait_impl(warn_unused, info = move_right_info)]`.
 pub use hecs_component_provider_macros::default_trait_impl;
 
+/// Turn the attached function into a test that runs a behavior on freshly spawned entities
+///
+/// `spawn(...)` lists the bundles to spawn into a new world, and `run(Query, closure)` runs the
+/// closure on each entity matching the query, as with [`run_with`]. Each parameter of the function
+/// is then bound to a view of the entity spawned from the bundle at the same position, fetched with
+/// the parameter's type as the query, so it can be any read-only query, e.g. `&Position` or a query
+/// struct with shared fields. Entities can be left without a parameter. The world is available to
+/// the body as `world`.
+///
+/// ```
+/// use hecs_component_provider::{
+///     behavior_test, default_trait_impl, ComponentProvider, ComponentProviderMut
+/// };
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// #[default_trait_impl]
+/// trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+///     fn apply_velocity(&mut self, dt: f32) {
+///         let &Velocity(v) = self.get();
+///         let position: &mut Position = self.get_mut();
+///         position.0 += v * dt;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// #[behavior_test(
+///     spawn((Position(1.0), Velocity(2.0)), (Position(1.0),)),
+///     run(MovableQuery, |item| item.apply_velocity(0.5)),
+/// )]
+/// fn applies_velocity(moving: &Position, still: &Position) {
+///     assert_eq!(moving.0, 2.0);
+///     assert_eq!(still.0, 1.0);
+/// }
+/// # fn main() {}
+/// ```
+pub use hecs_component_provider_macros::behavior_test;
+
 /// Prepare a tuple query that includes component provider implementations for the returned entities
 ///
 /// The first argument to the macro is the name of the query type that you would like to generate,
 /// which can then be passed to the query methods on [`hecs::World`].
 /// The second argument is the tuple of components that the query will return.
+/// Elements may include [`hecs::Or`], e.g. `Or<&Fire, &Ice>`, which provide optional access to both sides.
 ///
 /// ```
 /// use hecs_component_provider::{
@@ -231,7 +977,7 @@ pub use hecs_component_provider_macros::default_trait_impl;
 ///
 /// gen_tuple_query_component_providers!(MovableQuery, (&mut Position, &Velocity));
 ///
-/// for (_, mut entity) in world.query_mut::<MovableQuery>() {
+/// for mut entity in world.query_mut::<MovableQuery>() {
 ///     assert_eq!(entity.get_mut(), &mut Position(10, 20));
 ///
 ///     // bind with let to disambiguate between component providers for Position and Velocity:
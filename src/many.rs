@@ -0,0 +1,187 @@
+use crate::ComponentProvider;
+use core::any::{type_name, TypeId};
+
+/// Provides raw access to a component that is stored separately from every other component
+/// the implementor provides through this trait
+///
+/// This is implemented by `derive(ComponentProvider)` for each field of a bundle or query struct,
+/// and allows [`ComponentProviderMany::get_many_mut`] to hand out several references at once.
+///
+/// # Safety
+///
+/// For distinct `Component` types, `get_ptr` must return pointers to non-overlapping memory.
+/// The returned pointer must be valid for reads, and for writes if the implementor also implements
+/// [`ComponentProviderMut<Component>`](crate::ComponentProviderMut), for as long as `this` is.
+pub unsafe trait ComponentProviderDisjoint<Component: ?Sized>:
+    ComponentProvider<Component>
+{
+    /// # Safety
+    ///
+    /// `this` must be valid for reads and writes.
+    unsafe fn get_ptr(this: *mut Self) -> *mut Component;
+}
+
+/// A tuple of components that can be fetched together with [`ComponentProviderMany::get_many`]
+pub trait ComponentTuple<'a, P: ?Sized> {
+    type Refs;
+
+    fn fetch(provider: &'a P) -> Self::Refs;
+}
+
+/// A tuple of `&Component` and `&mut Component` references that can be fetched together with
+/// [`ComponentProviderMany::get_many_mut`]
+pub trait ComponentRefTuple<'a, P: ?Sized>: Sized {
+    #[doc(hidden)]
+    fn fetch(provider: &'a mut P) -> Self;
+}
+
+/// An element of a [`ComponentRefTuple`], either `&Component` or `&mut Component`
+pub trait ComponentRefElement<'a, P: ?Sized>: Sized {
+    #[doc(hidden)]
+    const MUTABLE: bool;
+
+    #[doc(hidden)]
+    fn component_type() -> (TypeId, &'static str);
+
+    #[doc(hidden)]
+    unsafe fn from_provider(provider: *mut P) -> Self;
+}
+
+impl<'a, P, C> ComponentRefElement<'a, P> for &'a C
+where
+    P: ComponentProviderDisjoint<C> + ?Sized,
+    C: ?Sized + 'static,
+{
+    const MUTABLE: bool = false;
+
+    fn component_type() -> (TypeId, &'static str) {
+        (TypeId::of::<C>(), type_name::<C>())
+    }
+
+    unsafe fn from_provider(provider: *mut P) -> Self {
+        unsafe { &*P::get_ptr(provider) }
+    }
+}
+
+impl<'a, P, C> ComponentRefElement<'a, P> for &'a mut C
+where
+    P: ComponentProviderDisjoint<C> + crate::ComponentProviderMut<C> + ?Sized,
+    C: ?Sized + 'static,
+{
+    const MUTABLE: bool = true;
+
+    fn component_type() -> (TypeId, &'static str) {
+        (TypeId::of::<C>(), type_name::<C>())
+    }
+
+    unsafe fn from_provider(provider: *mut P) -> Self {
+        unsafe { &mut *P::get_ptr(provider) }
+    }
+}
+
+fn assert_disjoint(elements: &[(bool, (TypeId, &'static str))]) {
+    for (i, (mutable, (type_id, name))) in elements.iter().enumerate() {
+        for (other_mutable, (other_type_id, _)) in &elements[i + 1..] {
+            assert!(
+                !((*mutable || *other_mutable) && type_id == other_type_id),
+                "get_many_mut: component `{}` is borrowed mutably more than once",
+                name
+            );
+        }
+    }
+}
+
+/// Fetch several components from a provider in a single call
+///
+/// This is implemented for all types, and is available for tuples of up to eight components.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, ComponentProviderMany};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Position(i32);
+/// #[derive(Debug, PartialEq)]
+/// struct Velocity(i32);
+/// struct Mass(i32);
+///
+/// #[derive(hecs::Bundle, ComponentProvider)]
+/// struct Entity {
+///     position: Position,
+///     velocity: Velocity,
+///     mass: Mass,
+/// }
+///
+/// let mut entity = Entity { position: Position(1), velocity: Velocity(2), mass: Mass(3) };
+///
+/// let (velocity, mass) = entity.get_many::<(Velocity, Mass)>();
+/// assert_eq!(velocity.0 * mass.0, 6);
+///
+/// let (position, velocity) = entity.get_many_mut::<(&mut Position, &Velocity)>();
+/// position.0 += velocity.0;
+/// assert_eq!(entity.position, Position(3));
+/// ```
+pub trait ComponentProviderMany {
+    /// Returns shared references to each component in the tuple `C`
+    fn get_many<'a, C>(&'a self) -> C::Refs
+    where
+        C: ComponentTuple<'a, Self>,
+    {
+        C::fetch(self)
+    }
+
+    /// Returns a tuple of shared and mutable references, e.g. `(&mut Position, &Velocity)`
+    ///
+    /// Only components provided through [`ComponentProviderDisjoint`] can be fetched this way.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component type is requested mutably and also appears elsewhere in the tuple.
+    fn get_many_mut<'a, R>(&'a mut self) -> R
+    where
+        R: ComponentRefTuple<'a, Self>,
+    {
+        R::fetch(self)
+    }
+}
+
+impl<T: ?Sized> ComponentProviderMany for T {}
+
+macro_rules! impl_component_tuples {
+    ($($name:ident),+) => {
+        impl<'a, P, $($name),+> ComponentTuple<'a, P> for ($($name,)+)
+        where
+            P: $(ComponentProvider<$name> +)+ ?Sized,
+            $($name: 'a,)+
+        {
+            type Refs = ($(&'a $name,)+);
+
+            fn fetch(provider: &'a P) -> Self::Refs {
+                ($(ComponentProvider::<$name>::get(provider),)+)
+            }
+        }
+
+        impl<'a, P, $($name),+> ComponentRefTuple<'a, P> for ($($name,)+)
+        where
+            P: ?Sized,
+            $($name: ComponentRefElement<'a, P>,)+
+        {
+            fn fetch(provider: &'a mut P) -> Self {
+                assert_disjoint(&[$(($name::MUTABLE, $name::component_type())),+]);
+                let provider: *mut P = provider;
+                // SAFETY: each element refers to a distinct component type, which
+                // ComponentProviderDisjoint guarantees to be stored in non-overlapping memory,
+                // and the provider is mutably borrowed for the lifetime of the references
+                unsafe { ($($name::from_provider(provider),)+) }
+            }
+        }
+    };
+}
+
+impl_component_tuples!(A);
+impl_component_tuples!(A, B);
+impl_component_tuples!(A, B, C);
+impl_component_tuples!(A, B, C, D);
+impl_component_tuples!(A, B, C, D, E);
+impl_component_tuples!(A, B, C, D, E, F);
+impl_component_tuples!(A, B, C, D, E, F, G);
+impl_component_tuples!(A, B, C, D, E, F, G, H);
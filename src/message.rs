@@ -0,0 +1,166 @@
+use crate::{ComponentProvider, ComponentProviderMut};
+use hecs::{Component, Entity, World};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::vec;
+
+/// A component holding the messages of type `M` delivered to its entity by an [`Outbox`]
+///
+/// Messages are kept in delivery order until they are drained, so a behavior that reads them
+/// through [`MessageProvider`] sees each message once if it drains them every run.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Inbox<M> {
+    messages: Vec<M>,
+}
+
+impl<M> Inbox<M> {
+    pub fn new() -> Self {
+        Inbox {
+            messages: Vec::new(),
+        }
+    }
+
+    pub fn messages(&self) -> &[M] {
+        &self.messages
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn push(&mut self, message: M) {
+        self.messages.push(message);
+    }
+
+    /// Removes and returns every message, in delivery order
+    pub fn drain(&mut self) -> vec::Drain<'_, M> {
+        self.messages.drain(..)
+    }
+}
+
+impl<M> Default for Inbox<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Gives behaviors access to the messages of type `M` addressed to their entity
+///
+/// This is implemented for every type providing [`Inbox<M>`] mutably.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, ComponentProvider, ComponentProviderMut, Inbox, MessageProvider,
+///     Outbox, Schedule, Stage,
+/// };
+///
+/// struct Damage(i32);
+/// struct Health(i32);
+///
+/// #[default_trait_impl]
+/// trait TakeDamage: ComponentProviderMut<Health> + MessageProvider<Damage> {
+///     fn take_damage(&mut self) {
+///         let damage: i32 = self.drain_messages().map(|d| d.0).sum();
+///         let health: &mut Health = self.get_mut();
+///         health.0 -= damage;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct TargetQuery<'a> {
+///     health: &'a mut Health,
+///     inbox: &'a mut Inbox<Damage>,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let target = world.spawn((Health(10), Inbox::<Damage>::new()));
+///
+/// let outbox = Outbox::<Damage>::new();
+/// let mut schedule = Schedule::new();
+/// schedule.add_system("attack", {
+///     let outbox = outbox.clone();
+///     move |_| outbox.send(target, Damage(3))
+/// });
+/// schedule.add_system_to_stage(Stage::PostUpdate, "deliver", move |world| {
+///     outbox.deliver(world);
+/// });
+/// schedule.add_behavior_to_stage::<TargetQuery, _>(
+///     Stage::PostUpdate,
+///     "take_damage",
+///     |mut item| item.take_damage(),
+/// );
+/// schedule.run(&mut world);
+/// schedule.run(&mut world);
+///
+/// assert_eq!(world.get::<&Health>(target).unwrap().0, 4);
+/// ```
+pub trait MessageProvider<M>: ComponentProviderMut<Inbox<M>> {
+    /// The messages delivered since they were last drained
+    fn messages(&self) -> &[M] {
+        ComponentProvider::<Inbox<M>>::get(self).messages()
+    }
+
+    fn drain_messages(&mut self) -> vec::Drain<'_, M> {
+        self.get_mut().drain()
+    }
+}
+
+impl<M, P: ComponentProviderMut<Inbox<M>> + ?Sized> MessageProvider<M> for P {}
+
+/// A shared queue of messages of type `M` addressed to entities, which are moved to the entities'
+/// [`Inbox`] components by [`deliver`](Self::deliver)
+///
+/// Behaviors can't borrow other entities' components while they are being queried, so they send
+/// messages through an `Outbox` captured by their closure instead, and the messages are delivered
+/// at a flush point, such as a system in a later [`Stage`](crate::Stage) of a
+/// [`Schedule`](crate::Schedule).
+pub struct Outbox<M>(Rc<RefCell<Vec<(Entity, M)>>>);
+
+impl<M> Outbox<M> {
+    pub fn new() -> Self {
+        Outbox(Rc::new(RefCell::new(Vec::new())))
+    }
+
+    pub fn send(&self, target: Entity, message: M) {
+        self.0.borrow_mut().push((target, message));
+    }
+
+    /// The number of messages waiting to be delivered
+    pub fn pending(&self) -> usize {
+        self.0.borrow().len()
+    }
+}
+
+impl<M: Component> Outbox<M> {
+    /// Moves every sent message to its target's [`Inbox`], in the order they were sent,
+    /// returning the number of messages delivered
+    ///
+    /// Messages to entities without an `Inbox<M>`, including despawned ones, are dropped.
+    pub fn deliver(&self, world: &mut World) -> usize {
+        let messages = core::mem::take(&mut *self.0.borrow_mut());
+        let mut delivered = 0;
+        for (target, message) in messages {
+            if let Ok(mut inbox) = world.get::<&mut Inbox<M>>(target) {
+                inbox.push(message);
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+}
+
+impl<M> Clone for Outbox<M> {
+    fn clone(&self) -> Self {
+        Outbox(self.0.clone())
+    }
+}
+
+impl<M> Default for Outbox<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
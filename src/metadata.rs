@@ -0,0 +1,223 @@
+use crate::InteriorMutable;
+use core::any::{type_name, TypeId};
+use core::marker::PhantomData;
+use hecs::{Component, Entity, Or};
+
+/// Describes how a query accesses a single component type
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ComponentAccess {
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    /// Whether the component is borrowed mutably
+    pub mutable: bool,
+    /// Whether an entity must have the component to match the query
+    pub required: bool,
+    /// Whether the component is modified through a shared borrow, such as an [`Atomic`] or
+    /// [`Locked`] component, which doesn't conflict with other shared borrows
+    ///
+    /// Derived impls set this for fields borrowing [`InteriorMutable`] components.
+    ///
+    /// [`Atomic`]: crate::Atomic
+    /// [`Locked`]: crate::Locked
+    pub interior_mutable: bool,
+}
+
+impl ComponentAccess {
+    #[doc(hidden)]
+    pub fn new<T: ?Sized + 'static>(mutable: bool, required: bool) -> Self {
+        ComponentAccess {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            mutable,
+            required,
+            interior_mutable: false,
+        }
+    }
+
+    pub fn read<T: Component>() -> Self {
+        Self::new::<T>(false, true)
+    }
+
+    pub fn write<T: Component>() -> Self {
+        ComponentAccess {
+            mutable: true,
+            ..Self::read::<T>()
+        }
+    }
+
+    fn optional(self) -> Self {
+        ComponentAccess {
+            required: false,
+            ..self
+        }
+    }
+}
+
+fn interior_access(access: Vec<ComponentAccess>) -> Vec<ComponentAccess> {
+    access
+        .into_iter()
+        .map(|access| ComponentAccess {
+            interior_mutable: true,
+            ..access
+        })
+        .collect()
+}
+
+/// Implemented by query fields that borrow an [`InteriorMutable`] component, directly or through
+/// an `Option`
+#[doc(hidden)]
+pub trait InteriorQuery {}
+
+impl<T: InteriorMutable> InteriorQuery for &T {}
+
+impl<T: InteriorMutable> InteriorQuery for &mut T {}
+
+impl<Q: InteriorQuery> InteriorQuery for Option<Q> {}
+
+/// Used by derived [`QueryAccess`] impls to mark the accesses of fields implementing
+/// [`InteriorQuery`] as interior mutable, which can't be expressed with a blanket impl
+///
+/// Calling `(&&AccessProbe::<Q>(PhantomData)).access()` with both probe traits in scope resolves
+/// to [`InteriorAccessProbe`] if `Q` implements [`InteriorQuery`], since its receiver matches
+/// without dereferencing, and to [`PlainAccessProbe`] otherwise.
+#[doc(hidden)]
+pub struct AccessProbe<Q>(pub PhantomData<Q>);
+
+#[doc(hidden)]
+pub trait InteriorAccessProbe {
+    fn access(&self) -> Vec<ComponentAccess>;
+}
+
+impl<Q: QueryAccess + InteriorQuery> InteriorAccessProbe for &AccessProbe<Q> {
+    fn access(&self) -> Vec<ComponentAccess> {
+        interior_access(Q::access())
+    }
+}
+
+#[doc(hidden)]
+pub trait PlainAccessProbe {
+    fn access(&self) -> Vec<ComponentAccess>;
+}
+
+impl<Q: QueryAccess> PlainAccessProbe for AccessProbe<Q> {
+    fn access(&self) -> Vec<ComponentAccess> {
+        Q::access()
+    }
+}
+
+/// Lists the components accessed by a query
+///
+/// This is implemented by `derive(ComponentProvider)` for query structs whose fields all implement
+/// it, and for references, `Option`, [`Or`], [`Entity`] and tuples of up to eight queries.
+///
+/// The components on either side of an [`Or`] are reported as optional, since neither is required
+/// by itself.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, QueryAccess};
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: Option<&'a Velocity>,
+/// }
+///
+/// let access = MovableQuery::access();
+/// assert_eq!(access.len(), 2);
+/// assert!(access[0].mutable && access[0].required);
+/// assert!(!access[1].mutable && !access[1].required);
+/// ```
+pub trait QueryAccess {
+    fn access() -> Vec<ComponentAccess>;
+}
+
+impl<T: Component> QueryAccess for &T {
+    fn access() -> Vec<ComponentAccess> {
+        vec![ComponentAccess::read::<T>()]
+    }
+}
+
+impl<T: Component> QueryAccess for &mut T {
+    fn access() -> Vec<ComponentAccess> {
+        vec![ComponentAccess::write::<T>()]
+    }
+}
+
+impl<Q: QueryAccess> QueryAccess for Option<Q> {
+    fn access() -> Vec<ComponentAccess> {
+        Q::access()
+            .into_iter()
+            .map(ComponentAccess::optional)
+            .collect()
+    }
+}
+
+impl<L: QueryAccess, R: QueryAccess> QueryAccess for Or<L, R> {
+    fn access() -> Vec<ComponentAccess> {
+        L::access()
+            .into_iter()
+            .chain(R::access())
+            .map(ComponentAccess::optional)
+            .collect()
+    }
+}
+
+impl QueryAccess for Entity {
+    fn access() -> Vec<ComponentAccess> {
+        Vec::new()
+    }
+}
+
+macro_rules! impl_query_access_tuples {
+    ($($name:ident),*) => {
+        impl<$($name: QueryAccess),*> QueryAccess for ($($name,)*) {
+            fn access() -> Vec<ComponentAccess> {
+                #[allow(unused_mut)]
+                let mut access = Vec::new();
+                $(access.extend($name::access());)*
+                access
+            }
+        }
+    };
+}
+
+impl_query_access_tuples!();
+impl_query_access_tuples!(A);
+impl_query_access_tuples!(A, B);
+impl_query_access_tuples!(A, B, C);
+impl_query_access_tuples!(A, B, C, D);
+impl_query_access_tuples!(A, B, C, D, E);
+impl_query_access_tuples!(A, B, C, D, E, F);
+impl_query_access_tuples!(A, B, C, D, E, F, G);
+impl_query_access_tuples!(A, B, C, D, E, F, G, H);
+
+/// Combines accesses to the same component, which is mutable, interior mutable or required if any
+/// access is
+pub(crate) fn merge_access(
+    access: impl IntoIterator<Item = ComponentAccess>,
+) -> Vec<ComponentAccess> {
+    let mut merged: Vec<ComponentAccess> = Vec::new();
+    for access in access {
+        match merged.iter_mut().find(|a| a.type_id == access.type_id) {
+            Some(existing) => {
+                existing.mutable |= access.mutable;
+                existing.interior_mutable |= access.interior_mutable;
+                existing.required |= access.required;
+            }
+            None => merged.push(access),
+        }
+    }
+    merged
+}
+
+/// Whether two queries can't run at the same time, because one borrows a component mutably that
+/// the other also accesses
+pub(crate) fn conflicts(a: &[ComponentAccess], b: &[ComponentAccess]) -> bool {
+    a.iter().any(|a| {
+        b.iter()
+            .any(|b| a.type_id == b.type_id && (a.mutable || b.mutable))
+    })
+}
@@ -0,0 +1,244 @@
+use hecs::Entity;
+#[cfg(feature = "mutation-log")]
+use std::cell::RefCell;
+#[cfg(feature = "mutation-log")]
+use std::collections::VecDeque;
+#[cfg(feature = "mutation-log")]
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A mutable access to a component through a generated provider, recorded in a [`MutationLog`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Mutation {
+    /// The type name of the component
+    pub component: &'static str,
+    /// The entity being processed by the [`Schedule`](crate::Schedule), if the access happened in
+    /// a behavior
+    pub entity: Option<Entity>,
+    /// The behavior or system that was running, if the access happened in a schedule
+    pub behavior: Option<&'static str>,
+    /// The schedule's [`tick`](crate::Schedule::tick) when the access happened
+    pub tick: Option<u64>,
+}
+
+/// A bounded log of the most recent calls to generated `get_mut` and `get_mut_checked` methods, for
+/// finding out which behavior modified a component
+///
+/// Recording requires the `mutation-log` feature, and costs a lock per access, so it's intended
+/// for debug builds. Without the feature nothing is recorded and logs are always empty.
+///
+/// Each [`Schedule`](crate::Schedule) records the mutations made by its behaviors and systems in
+/// its own [`mutation_log`](crate::Schedule::mutation_log), tagged with the running behavior, the
+/// entity being processed and the schedule's tick. Mutations made outside schedules, e.g. on the
+/// threads of a [`ParallelRunner`](crate::ParallelRunner), are recorded in the
+/// [`global`](Self::global) log. Logs keep the latest
+/// [`DEFAULT_CAPACITY`](Self::DEFAULT_CAPACITY) mutations unless resized with
+/// [`set_capacity`](Self::set_capacity).
+///
+/// ```
+/// # #[cfg(feature = "mutation-log")]
+/// # {
+/// use hecs_component_provider::{ComponentProvider, ComponentProviderMut, Schedule};
+///
+/// struct Charge(u32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct BatteryQuery<'a> {
+///     charge: &'a mut Charge,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let battery = world.spawn((Charge(3),));
+///
+/// let mut schedule = Schedule::new();
+/// schedule.add_behavior::<BatteryQuery, _>("drain", |mut battery| {
+///     let charge: &mut Charge = battery.get_mut();
+///     charge.0 -= 1;
+/// });
+/// schedule.run(&mut world);
+///
+/// let mutations = schedule.mutation_log().entries_for(battery);
+/// assert_eq!(mutations.len(), 1);
+/// assert_eq!(mutations[0].behavior, Some("drain"));
+/// assert_eq!(mutations[0].tick, Some(0));
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MutationLog {
+    #[cfg(feature = "mutation-log")]
+    log: Arc<Mutex<Log>>,
+}
+
+#[cfg(feature = "mutation-log")]
+struct Log {
+    capacity: usize,
+    entries: VecDeque<Mutation>,
+}
+
+#[cfg(feature = "mutation-log")]
+#[derive(Clone, Default)]
+struct Context {
+    log: Option<MutationLog>,
+    entity: Option<Entity>,
+    behavior: Option<&'static str>,
+    tick: Option<u64>,
+}
+
+#[cfg(feature = "mutation-log")]
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+#[cfg(feature = "mutation-log")]
+impl Default for Log {
+    fn default() -> Self {
+        Log {
+            capacity: MutationLog::DEFAULT_CAPACITY,
+            entries: VecDeque::new(),
+        }
+    }
+}
+
+impl MutationLog {
+    /// The number of mutations kept by default
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The log of mutations made outside schedules, which is shared by all threads
+    pub fn global() -> &'static MutationLog {
+        #[cfg(feature = "mutation-log")]
+        {
+            static GLOBAL: OnceLock<MutationLog> = OnceLock::new();
+            GLOBAL.get_or_init(MutationLog::new)
+        }
+        #[cfg(not(feature = "mutation-log"))]
+        &MutationLog {}
+    }
+
+    /// The recorded mutations, from oldest to newest
+    pub fn entries(&self) -> Vec<Mutation> {
+        #[cfg(feature = "mutation-log")]
+        {
+            let log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+            log.entries.iter().copied().collect()
+        }
+        #[cfg(not(feature = "mutation-log"))]
+        Vec::new()
+    }
+
+    /// The recorded mutations of `entity`'s components, from oldest to newest
+    ///
+    /// Entities are only unique within a world, so this should be used with the log of a schedule
+    /// that runs a single world.
+    pub fn entries_for(&self, entity: Entity) -> Vec<Mutation> {
+        let mut entries = self.entries();
+        entries.retain(|m| m.entity == Some(entity));
+        entries
+    }
+
+    /// Removes all recorded mutations
+    pub fn clear(&self) {
+        #[cfg(feature = "mutation-log")]
+        self.log
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .clear();
+    }
+
+    /// Sets the number of mutations kept, dropping the oldest ones if there are more
+    pub fn set_capacity(&self, capacity: usize) {
+        #[cfg(feature = "mutation-log")]
+        {
+            let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+            log.capacity = capacity;
+            let excess = log.entries.len().saturating_sub(capacity);
+            log.entries.drain(..excess);
+        }
+        #[cfg(not(feature = "mutation-log"))]
+        let _ = capacity;
+    }
+
+    #[cfg(feature = "mutation-log")]
+    fn push(&self, mutation: Mutation) {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        if log.capacity == 0 {
+            return;
+        }
+        if log.entries.len() >= log.capacity {
+            log.entries.pop_front();
+        }
+        log.entries.push_back(mutation);
+    }
+}
+
+/// Called by generated mutable getters, recording a mutation of `T` in the log of the running
+/// schedule, or the global log outside schedules
+#[doc(hidden)]
+#[inline]
+pub fn record_mutation<T: ?Sized>() {
+    #[cfg(feature = "mutation-log")]
+    CONTEXT.with(|c| {
+        let context = c.borrow();
+        let log = context
+            .log
+            .as_ref()
+            .unwrap_or_else(|| MutationLog::global());
+        log.push(Mutation {
+            component: core::any::type_name::<T>(),
+            entity: context.entity,
+            behavior: context.behavior,
+            tick: context.tick,
+        });
+    });
+}
+
+/// Records the mutations made on this thread in `log`, tagged with a running behavior, until
+/// dropped
+pub(crate) struct BehaviorScope {
+    #[cfg(feature = "mutation-log")]
+    previous: Context,
+}
+
+#[inline]
+pub(crate) fn enter_behavior(
+    log: &MutationLog,
+    behavior: &'static str,
+    tick: u64,
+) -> BehaviorScope {
+    #[cfg(feature = "mutation-log")]
+    {
+        let previous = CONTEXT.with(|c| {
+            c.replace(Context {
+                log: Some(log.clone()),
+                entity: None,
+                behavior: Some(behavior),
+                tick: Some(tick),
+            })
+        });
+        BehaviorScope { previous }
+    }
+    #[cfg(not(feature = "mutation-log"))]
+    {
+        let _ = (log, behavior, tick);
+        BehaviorScope {}
+    }
+}
+
+/// Tags the mutations recorded on this thread with the entity being processed
+#[inline]
+pub(crate) fn set_entity(entity: Entity) {
+    #[cfg(feature = "mutation-log")]
+    CONTEXT.with(|c| c.borrow_mut().entity = Some(entity));
+    #[cfg(not(feature = "mutation-log"))]
+    let _ = entity;
+}
+
+#[cfg(feature = "mutation-log")]
+impl Drop for BehaviorScope {
+    fn drop(&mut self) {
+        CONTEXT.with(|c| *c.borrow_mut() = core::mem::take(&mut self.previous));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::metadata::conflicts;
+use crate::{ComponentAccess, QueryAccess, Trace, TraceEvent};
+use hecs::{Query, World};
+use std::panic;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+/// Runs behaviors in parallel, on a fixed number of scoped threads by default
+///
+/// Behaviors are grouped into batches using the components their queries access: a behavior
+/// joins the batch after the last one containing a behavior it conflicts with, i.e. one that
+/// accesses a component the other borrows mutably. Batches run one after another in order, and
+/// the behaviors within a batch are handed to a [`ParallelBackend`] to run concurrently.
+/// Conflicting behaviors therefore always run in registration order.
+///
+/// [`new`](Self::new) uses the [`ScopedThreads`] backend, which doesn't depend on a thread pool
+/// crate. The crate has no rayon backend; one can be provided with
+/// [`with_backend`](Self::with_backend) and reuses the same batching.
+///
+/// Behaviors must be `Send`, since they may run on a different thread each time.
+///
+/// ```
+/// use hecs_component_provider::ParallelRunner;
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+/// struct Health(i32);
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(0.0), Velocity(1.0), Health(10)));
+///
+/// let mut runner = ParallelRunner::new(2);
+/// runner
+///     .add_behavior::<(&mut Position, &Velocity), _>("move", |(p, v)| p.0 += v.0)
+///     .add_behavior::<&mut Health, _>("regenerate", |h| h.0 += 1)
+///     .add_behavior::<&Position, _>("report", |p| assert_eq!(p.0, 1.0));
+///
+/// // `move` and `regenerate` access different components, but `report` reads the position
+/// assert_eq!(
+///     runner.batches(),
+///     vec![vec!["move", "regenerate"], vec!["report"]]
+/// );
+/// assert_eq!(runner.run(&mut world), 3);
+/// ```
+pub struct ParallelRunner<B = ScopedThreads> {
+    backend: B,
+    behaviors: Vec<ParallelBehavior>,
+    n_batches: usize,
+    trace: Option<Trace>,
+}
+
+/// Runs the behaviors of a batch of a [`ParallelRunner`] concurrently
+///
+/// The runner only puts behaviors that don't conflict in the same batch, so a backend is free to
+/// run the jobs of a batch in any order and on any threads, e.g. on a thread pool. Each job runs
+/// one behavior for every matching entity and returns the number of entities processed.
+///
+/// ```
+/// use hecs_component_provider::{ParallelBackend, ParallelRunner};
+///
+/// // runs every job on the calling thread, e.g. for debugging
+/// struct Sequential;
+///
+/// impl ParallelBackend for Sequential {
+///     fn run_batch(&mut self, jobs: &mut [&mut (dyn FnMut() -> usize + Send)]) -> usize {
+///         jobs.iter_mut().map(|job| job()).sum()
+///     }
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((1u32,));
+///
+/// let mut runner = ParallelRunner::with_backend(Sequential);
+/// runner.add_behavior::<&mut u32, _>("increment", |n| *n += 1);
+/// assert_eq!(runner.run(&mut world), 1);
+/// ```
+pub trait ParallelBackend {
+    /// Runs every job once and returns the sum of their results
+    ///
+    /// A panic in a job should be propagated once the other jobs have finished.
+    fn run_batch(&mut self, jobs: &mut [&mut (dyn FnMut() -> usize + Send)]) -> usize;
+}
+
+/// A [`ParallelBackend`] that spreads the jobs of each batch over up to `workers` threads with
+/// [`std::thread::scope`]
+pub struct ScopedThreads {
+    workers: usize,
+}
+
+impl ScopedThreads {
+    /// Creates a backend that uses at most `workers` threads, e.g. from
+    /// [`std::thread::available_parallelism`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn new(workers: usize) -> Self {
+        assert!(workers > 0, "a parallel runner needs at least one worker");
+        ScopedThreads { workers }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+}
+
+impl ParallelBackend for ScopedThreads {
+    fn run_batch(&mut self, jobs: &mut [&mut (dyn FnMut() -> usize + Send)]) -> usize {
+        let n_lanes = self.workers.min(jobs.len());
+        if n_lanes <= 1 {
+            return jobs.iter_mut().map(|job| job()).sum();
+        }
+
+        let mut lanes: Vec<Vec<&mut (dyn FnMut() -> usize + Send)>> =
+            (0..n_lanes).map(|_| Vec::new()).collect();
+        for (i, job) in jobs.iter_mut().enumerate() {
+            lanes[i % n_lanes].push(&mut **job);
+        }
+        thread::scope(|scope| {
+            let handles: Vec<_> = lanes
+                .into_iter()
+                .map(|lane| scope.spawn(move || lane.into_iter().map(|job| job()).sum::<usize>()))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .sum()
+        })
+    }
+}
+
+struct ParallelBehavior {
+    name: &'static str,
+    access: Vec<ComponentAccess>,
+    batch: usize,
+    // runs the behavior for every matching entity, returning the number of entities processed
+    run: Box<dyn FnMut(&World) -> usize + Send>,
+    #[cfg(feature = "puffin")]
+    scope_id: Option<puffin::ScopeId>,
+}
+
+impl ParallelBehavior {
+    fn run(&mut self, world: &World) -> usize {
+        #[cfg(feature = "puffin")]
+        let _scope =
+            crate::schedule::profile_scope(&mut self.scope_id, self.name, "ParallelRunner::run");
+        (self.run)(world)
+    }
+}
+
+impl ParallelRunner {
+    /// Creates a runner that uses at most `workers` threads, e.g. from
+    /// [`std::thread::available_parallelism`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn new(workers: usize) -> Self {
+        Self::with_backend(ScopedThreads::new(workers))
+    }
+
+    pub fn workers(&self) -> usize {
+        self.backend.workers()
+    }
+}
+
+impl<B: ParallelBackend> ParallelRunner<B> {
+    /// Creates a runner that runs the behaviors of each batch with `backend`
+    pub fn with_backend(backend: B) -> Self {
+        ParallelRunner {
+            backend,
+            behaviors: Vec::new(),
+            n_batches: 0,
+            trace: None,
+        }
+    }
+
+    /// Registers a behavior that is called for each entity matching `Q`
+    pub fn add_behavior<Q, F>(&mut self, name: &'static str, mut f: F) -> &mut Self
+    where
+        Q: Query + QueryAccess,
+        F: for<'q> FnMut(Q::Item<'q>) + Send + 'static,
+    {
+        let access = Q::access();
+        let batch = self
+            .behaviors
+            .iter()
+            .filter(|b| conflicts(&b.access, &access))
+            .map(|b| b.batch + 1)
+            .max()
+            .unwrap_or(0);
+        self.n_batches = self.n_batches.max(batch + 1);
+        self.behaviors.push(ParallelBehavior {
+            name,
+            access,
+            batch,
+            run: Box::new(move |world| {
+                let mut count = 0;
+                for item in world.query::<Q>().iter() {
+                    f(item);
+                    count += 1;
+                }
+                count
+            }),
+            #[cfg(feature = "puffin")]
+            scope_id: None,
+        });
+        self
+    }
+
+    /// The names of the behaviors in each batch, in the order the batches run
+    pub fn batches(&self) -> Vec<Vec<&'static str>> {
+        (0..self.n_batches)
+            .map(|batch| {
+                self.behaviors
+                    .iter()
+                    .filter(|b| b.batch == batch)
+                    .map(|b| b.name)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Runs every behavior once, returning the total number of entities processed
+    ///
+    /// A panic in a behavior is propagated once the other behaviors in its batch have finished,
+    /// if the backend does so.
+    pub fn run(&mut self, world: &mut World) -> usize {
+        let world = &*world;
+        let run_start = Instant::now();
+        let tracing = self.trace.is_some();
+        if let Some(trace) = &mut self.trace {
+            trace.begin_run();
+        }
+        let mut count = 0;
+        for batch in 0..self.n_batches {
+            let behaviors: Vec<_> = self
+                .behaviors
+                .iter_mut()
+                .filter(|b| b.batch == batch)
+                .collect();
+            let mut spans: Vec<Option<JobSpan>> = behaviors.iter().map(|_| None).collect();
+            {
+                let mut jobs: Vec<_> = behaviors
+                    .into_iter()
+                    .zip(&mut spans)
+                    .map(|(b, span)| {
+                        move || {
+                            if !tracing {
+                                return b.run(world);
+                            }
+                            let start = Instant::now();
+                            let entities = b.run(world);
+                            *span = Some(JobSpan {
+                                name: b.name,
+                                thread: thread::current().id(),
+                                start,
+                                duration: start.elapsed(),
+                                entities,
+                            });
+                            entities
+                        }
+                    })
+                    .collect();
+                let mut jobs: Vec<&mut (dyn FnMut() -> usize + Send)> =
+                    jobs.iter_mut().map(|job| job as _).collect();
+                count += self.backend.run_batch(&mut jobs);
+            }
+            if let Some(trace) = &mut self.trace {
+                record_batch(
+                    trace,
+                    batch,
+                    run_start,
+                    spans.into_iter().flatten().collect(),
+                );
+            }
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.end_run(true);
+        }
+        count
+    }
+
+    /// Enables or disables recording a [`Trace`] of the batches and lanes of each run
+    ///
+    /// Disabling tracing discards the recorded trace.
+    pub fn set_trace(&mut self, enabled: bool) -> &mut Self {
+        if !enabled {
+            self.trace = None;
+        } else if self.trace.is_none() {
+            self.trace = Some(Trace::default());
+        }
+        self
+    }
+
+    /// The trace recorded since tracing was enabled or the trace was last taken
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Takes the recorded trace, continuing to record into an empty trace
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.as_mut().map(core::mem::take)
+    }
+}
+
+// when and where a behavior ran, recorded by its job while tracing
+struct JobSpan {
+    name: &'static str,
+    thread: ThreadId,
+    start: Instant,
+    duration: Duration,
+    entities: usize,
+}
+
+// records the behaviors of a batch in the order they started, numbering the threads they ran on
+// as lanes, since backends don't report which thread runs each job
+fn record_batch(trace: &mut Trace, batch: usize, run_start: Instant, mut spans: Vec<JobSpan>) {
+    spans.sort_by_key(|span| span.start);
+    let mut threads = Vec::new();
+    for span in spans {
+        let lane = match threads.iter().position(|&thread| thread == span.thread) {
+            Some(lane) => lane,
+            None => {
+                threads.push(span.thread);
+                threads.len() - 1
+            }
+        };
+        trace.record(TraceEvent::ParallelBehavior {
+            name: span.name,
+            batch,
+            lane,
+            offset: span.start - run_start,
+            duration: span.duration,
+            entities: span.entities,
+        });
+    }
+}
@@ -0,0 +1,152 @@
+use crate::ComponentProvider;
+use core::fmt;
+use core::ops::Deref;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+type ParseFn<T> = dyn Fn(&str) -> Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Tuning parameters loaded from a file, which are reloaded when the file changes
+///
+/// The file format is chosen by the parse function, e.g. `ron::from_str` or `toml::from_str`
+/// for types implementing `serde::Deserialize`. [`reload_if_changed`](Self::reload_if_changed)
+/// checks the file's modification time and should be called once per frame, or less often.
+///
+/// `Params<T>` provides `T`, so it can be composed with query items using
+/// [`gen_composed_component_providers`](crate::gen_composed_component_providers) and accessed by
+/// behaviors through `ComponentProvider<T>`.
+///
+/// ```
+/// use hecs_component_provider::Params;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct MoveParams { speed: f32 }
+///
+/// let path = std::env::temp_dir().join("hecs_component_provider_params_doctest.txt");
+/// std::fs::write(&path, "2.5").unwrap();
+///
+/// let mut params = Params::load(&path, |s| s.trim().parse().map(|speed| MoveParams { speed }))
+///     .unwrap();
+/// assert_eq!(params.speed, 2.5);
+/// assert_eq!(params.reload_if_changed().unwrap(), false);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct Params<T> {
+    path: PathBuf,
+    parse: Box<ParseFn<T>>,
+    value: T,
+    modified: Option<SystemTime>,
+    version: u64,
+}
+
+impl<T> Params<T> {
+    /// Loads the parameters from the file at `path`
+    pub fn load<E>(
+        path: impl AsRef<Path>,
+        parse: impl Fn(&str) -> Result<T, E> + 'static,
+    ) -> Result<Self, ParamsError>
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let parse: Box<ParseFn<T>> = Box::new(move |s| parse(s).map_err(Into::into));
+        let modified = modified(&path)?;
+        let value = read(&path, &*parse)?;
+        Ok(Params {
+            path,
+            parse,
+            value,
+            modified,
+            version: 0,
+        })
+    }
+
+    /// Reloads the parameters if the file was modified since it was last read, returning whether
+    /// they were reloaded
+    ///
+    /// If the file can't be read or parsed, the current parameters are kept and the error is
+    /// returned. The file will be read again on the next call if it is modified again.
+    pub fn reload_if_changed(&mut self) -> Result<bool, ParamsError> {
+        let modified = modified(&self.path)?;
+        if modified == self.modified {
+            return Ok(false);
+        }
+        self.modified = modified;
+        self.value = read(&self.path, &*self.parse)?;
+        self.version += 1;
+        Ok(true)
+    }
+
+    /// The number of times the parameters have been reloaded
+    ///
+    /// Comparing this with a previously observed value detects changes.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+}
+
+fn modified(path: &Path) -> Result<Option<SystemTime>, ParamsError> {
+    // some platforms don't support modification times, in which case changes aren't detected
+    Ok(fs::metadata(path)?.modified().ok())
+}
+
+fn read<T>(path: &Path, parse: &ParseFn<T>) -> Result<T, ParamsError> {
+    let contents = fs::read_to_string(path)?;
+    parse(&contents).map_err(ParamsError::Parse)
+}
+
+impl<T> Deref for Params<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> ComponentProvider<T> for Params<T> {
+    fn get(&self) -> &T {
+        &self.value
+    }
+}
+
+/// The error returned when [`Params`] can't be loaded
+#[derive(Debug)]
+pub enum ParamsError {
+    Io(io::Error),
+    Parse(Box<dyn Error + Send + Sync>),
+}
+
+impl From<io::Error> for ParamsError {
+    fn from(error: io::Error) -> Self {
+        ParamsError::Io(error)
+    }
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamsError::Io(error) => write!(f, "failed to read params: {}", error),
+            ParamsError::Parse(error) => write!(f, "failed to parse params: {}", error),
+        }
+    }
+}
+
+impl Error for ParamsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParamsError::Io(error) => Some(error),
+            ParamsError::Parse(error) => Some(&**error),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{BundleDiff, DiffBundle, OwnedQuery, Snapshot};
+use hecs::{DynamicBundle, Entity, World};
+use std::collections::VecDeque;
+
+/// The locally predicted states and buffered inputs of a client since the last authoritative
+/// state, for reconciling predictions with the server
+///
+/// After applying the local input for a tick, call [`record`](Self::record). When the
+/// authoritative state for a tick arrives, [`reconcile`](Self::reconcile) compares it with the
+/// state predicted for that tick. If they differ, the authoritative state is applied and the inputs
+/// buffered since are applied again through the caller's behaviors, which must be deterministic.
+///
+/// Authoritative states refer to entities by their handles in the client's world, so entities
+/// received from the server should be mapped first, e.g. with an [`EntityMap`](crate::EntityMap).
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, Prediction};
+///
+/// #[derive(Clone, PartialEq)]
+/// struct Position(i32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(owned(PositionBundle))]
+/// struct PositionQuery<'a> {
+///     position: &'a mut Position,
+/// }
+///
+/// fn apply_input(world: &mut hecs::World, input: &i32) {
+///     for item in world.query_mut::<PositionQuery>() {
+///         item.position.0 += input;
+///     }
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let player = world.spawn((Position(0),));
+/// let mut prediction = Prediction::<PositionQuery, i32>::new();
+/// for (tick, input) in [(0, 1), (1, 1), (2, 1)] {
+///     apply_input(&mut world, &input);
+///     prediction.record(tick, input, &world);
+/// }
+///
+/// // the server moved the player further than predicted at tick 0
+/// let authoritative = vec![(player, PositionBundle { position: Position(5) })];
+/// let corrections = prediction.reconcile(&mut world, 0, authoritative, apply_input);
+/// assert_eq!(corrections.len(), 1);
+/// assert!(corrections[0].diff.as_ref().unwrap().is_changed("position"));
+/// // ticks 1 and 2 were predicted again from the corrected state
+/// assert_eq!(world.get::<&Position>(player).unwrap().0, 7);
+/// assert_eq!(prediction.pending(), 2);
+/// ```
+pub struct Prediction<Q: OwnedQuery, I> {
+    // sorted by tick
+    history: VecDeque<PredictedTick<Q, I>>,
+}
+
+struct PredictedTick<Q: OwnedQuery, I> {
+    tick: u64,
+    input: I,
+    state: Snapshot<Q>,
+}
+
+/// An entity whose authoritative state differed from its predicted state, returned by
+/// [`Prediction::reconcile`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Correction {
+    pub entity: Entity,
+    /// The fields that differed, or `None` if the entity wasn't predicted to match the query
+    pub diff: Option<BundleDiff>,
+}
+
+impl<Q: OwnedQuery, I> Default for Prediction<Q, I> {
+    fn default() -> Self {
+        Prediction {
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl<Q, I> Prediction<Q, I>
+where
+    Q: OwnedQuery,
+    Q::Owned: DynamicBundle + DiffBundle,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of ticks predicted since the last reconciled tick
+    pub fn pending(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Records the input applied for `tick` and the state it was predicted to produce
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick` isn't later than the last recorded tick, or if a component accessed by
+    /// `Q` is already borrowed mutably.
+    pub fn record(&mut self, tick: u64, input: I, world: &World) {
+        assert!(
+            self.history.back().is_none_or(|last| last.tick < tick),
+            "ticks must be recorded in increasing order"
+        );
+        self.history.push_back(PredictedTick {
+            tick,
+            input,
+            state: Snapshot::take(world),
+        });
+    }
+
+    /// Compares the authoritative state at the end of `tick` with the predicted state, returning
+    /// the entities that differ
+    ///
+    /// If any differ, the authoritative components are inserted into the world, respawning
+    /// entities that were predicted to be despawned, and `apply` is called with each input
+    /// recorded after `tick` to predict those ticks again. Predictions up to and including `tick`
+    /// are then discarded. If `tick` wasn't recorded, every authoritative entity is corrected.
+    pub fn reconcile(
+        &mut self,
+        world: &mut World,
+        tick: u64,
+        authoritative: impl IntoIterator<Item = (Entity, Q::Owned)>,
+        mut apply: impl FnMut(&mut World, &I),
+    ) -> Vec<Correction> {
+        while self.history.front().is_some_and(|p| p.tick < tick) {
+            self.history.pop_front();
+        }
+        let predicted = self
+            .history
+            .front()
+            .filter(|p| p.tick == tick)
+            .map(|p| &p.state);
+
+        let authoritative: Vec<_> = authoritative.into_iter().collect();
+        let corrections: Vec<_> = authoritative
+            .iter()
+            .filter_map(|(entity, bundle)| {
+                let diff = predicted
+                    .and_then(|state| state.get(*entity))
+                    .map(|predicted| predicted.diff(bundle));
+                if diff.as_ref().is_some_and(|diff| !diff.has_changes()) {
+                    return None;
+                }
+                Some(Correction {
+                    entity: *entity,
+                    diff,
+                })
+            })
+            .collect();
+        if predicted.is_some() {
+            self.history.pop_front();
+        }
+        if corrections.is_empty() {
+            return corrections;
+        }
+
+        for (entity, bundle) in authoritative {
+            if world.contains(entity) {
+                world.insert(entity, bundle).expect("entity exists");
+            } else {
+                world.spawn_at(entity, bundle);
+            }
+        }
+        for predicted in &mut self.history {
+            apply(world, &predicted.input);
+            predicted.state = Snapshot::take(world);
+        }
+        corrections
+    }
+}
@@ -0,0 +1,78 @@
+use hecs::{PreparedQuery, PreparedQueryBorrow, PreparedView, Query, World};
+
+/// Caches the archetypes matching `Q` across frames, for behaviors that look up many entities by
+/// id, e.g. to follow references to their targets
+///
+/// Each [`World::query_one`] finds which components of the entity's archetype `Q` needs and
+/// borrows them, which dominates when a behavior makes many lookups per frame. The cache keeps
+/// this per-archetype state until a new archetype is added to the world, and lookups through a
+/// view only resolve the entity's current location, so entities can be spawned, despawned and
+/// moved between existing archetypes without invalidating it. Views borrow the components of
+/// every matching archetype once, so a single view should be used for all lookups in a pass rather
+/// than one per lookup.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, RandomAccessCache};
+///
+/// struct Position(i32);
+/// struct Target(hecs::Entity);
+/// struct Aim(i32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct TargetPositionQuery<'a> {
+///     position: &'a Position,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let enemy = world.spawn((Position(5),));
+/// let turret = world.spawn((Target(enemy), Aim(0)));
+///
+/// let mut targets = RandomAccessCache::<TargetPositionQuery>::new();
+/// // e.g. every frame
+/// {
+///     let mut borrow = targets.borrow(&world);
+///     let mut view = borrow.view();
+///     for (target, aim) in world.query::<(&Target, &mut Aim)>().iter() {
+///         if let Some(target) = view.get_mut(target.0) {
+///             let position: &Position = target.get();
+///             aim.0 = position.0;
+///         }
+///     }
+/// }
+/// assert_eq!(world.get::<&Aim>(turret).unwrap().0, 5);
+/// ```
+pub struct RandomAccessCache<Q: Query> {
+    prepared: PreparedQuery<Q>,
+}
+
+impl<Q: Query> Default for RandomAccessCache<Q> {
+    fn default() -> Self {
+        RandomAccessCache {
+            prepared: PreparedQuery::new(),
+        }
+    }
+}
+
+impl<Q: Query> RandomAccessCache<Q> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Borrows the components of `Q` in `world` with dynamic borrow checking, so that other
+    /// queries can run alongside the lookups
+    ///
+    /// Lookups are made through [`PreparedQueryBorrow::view`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the components are already borrowed incompatibly.
+    pub fn borrow<'q>(&'q mut self, world: &'q World) -> PreparedQueryBorrow<'q, Q> {
+        self.prepared.query(world)
+    }
+
+    /// A view for looking up entities of a uniquely borrowed world, which avoids dynamic borrow
+    /// checking
+    pub fn view_mut<'q>(&'q mut self, world: &'q mut World) -> PreparedView<'q, Q> {
+        self.prepared.view_mut(world)
+    }
+}
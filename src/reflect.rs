@@ -0,0 +1,232 @@
+use core::any::{type_name, Any};
+use core::fmt;
+use hecs::{Component, Entity, World};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Access to a struct's fields by name, implemented by `derive(Reflect)`
+///
+/// Fields of tuple structs are named by their index, e.g. `"0"`.
+pub trait Reflect: Any {
+    /// The names of the fields, in declaration order
+    fn field_names(&self) -> &'static [&'static str];
+
+    fn field_type_name(&self, name: &str) -> Option<&'static str>;
+
+    fn field(&self, name: &str) -> Option<&dyn Any>;
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut dyn Any>;
+}
+
+type AccessFn = for<'a> fn(&'a mut World, Entity) -> Option<&'a mut dyn Reflect>;
+
+/// Component types that can be accessed by name through an [`EntityProxy`]
+///
+/// ```
+/// use hecs_component_provider::{Reflect, ReflectRegistry};
+///
+/// #[derive(Reflect)]
+/// struct Health { current: i32, max: i32 }
+///
+/// #[derive(Reflect)]
+/// struct Speed(f32);
+///
+/// let mut registry = ReflectRegistry::new();
+/// registry.register::<Health>().register_as::<Speed>("speed");
+///
+/// let mut world = hecs::World::new();
+/// let player = world.spawn((Health { current: 5, max: 10 }, Speed(1.5)));
+///
+/// // e.g. `set player Health.current 10` typed into a console
+/// let mut proxy = registry.proxy(&mut world, player);
+/// proxy.set_field("Health", "current", 10).unwrap();
+/// assert_eq!(proxy.get_field::<i32>("Health", "current").unwrap(), 10);
+/// assert_eq!(proxy.get_field::<f32>("speed", "0").unwrap(), 1.5);
+/// assert_eq!(proxy.component_names(), ["Health", "speed"]);
+/// ```
+#[derive(Default)]
+pub struct ReflectRegistry {
+    components: BTreeMap<String, AccessFn>,
+}
+
+fn access<T: Component + Reflect>(world: &mut World, entity: Entity) -> Option<&mut dyn Reflect> {
+    let component = world.query_one_mut::<&mut T>(entity).ok()?;
+    Some(component)
+}
+
+impl ReflectRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `T` under its name without the module path, e.g. `"Health"`
+    pub fn register<T: Component + Reflect>(&mut self) -> &mut Self {
+        let name = type_name::<T>().rsplit("::").next().unwrap();
+        self.register_as::<T>(name)
+    }
+
+    /// Registers `T` under `name`, replacing any type previously registered with that name
+    pub fn register_as<T: Component + Reflect>(&mut self, name: &str) -> &mut Self {
+        self.components.insert(name.to_owned(), access::<T>);
+        self
+    }
+
+    /// The registered component names, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.components.keys().map(String::as_str)
+    }
+
+    /// A proxy for accessing the registered components of `entity` by name
+    pub fn proxy<'a>(&'a self, world: &'a mut World, entity: Entity) -> EntityProxy<'a> {
+        EntityProxy {
+            registry: self,
+            world,
+            entity,
+        }
+    }
+}
+
+/// Reads and writes the fields of an entity's components by name, for dynamic callers such as
+/// consoles and scripts
+///
+/// Created with [`ReflectRegistry::proxy`].
+pub struct EntityProxy<'a> {
+    registry: &'a ReflectRegistry,
+    world: &'a mut World,
+    entity: Entity,
+}
+
+impl<'a> EntityProxy<'a> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// The names of the registered components the entity has, in alphabetical order
+    pub fn component_names(&mut self) -> Vec<&'a str> {
+        let registry = self.registry;
+        registry
+            .components
+            .iter()
+            .filter(|(_, access)| access(self.world, self.entity).is_some())
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// The names of the fields of the entity's component called `component`
+    pub fn field_names(&mut self, component: &str) -> Result<&'static [&'static str], ProxyError> {
+        Ok(self.component(component)?.field_names())
+    }
+
+    /// Calls `f` with the field, returning its result
+    pub fn with_field<R>(
+        &mut self,
+        component: &str,
+        field: &str,
+        f: impl FnOnce(&mut dyn Any) -> R,
+    ) -> Result<R, ProxyError> {
+        let reflect = self.component(component)?;
+        let value = reflect
+            .field_mut(field)
+            .ok_or_else(|| ProxyError::UnknownField {
+                component: component.to_owned(),
+                field: field.to_owned(),
+            })?;
+        Ok(f(value))
+    }
+
+    /// Returns a copy of the field, which must have type `V`
+    pub fn get_field<V: Any + Clone>(
+        &mut self,
+        component: &str,
+        field: &str,
+    ) -> Result<V, ProxyError> {
+        self.with_field(component, field, |value| value.downcast_ref::<V>().cloned())?
+            .ok_or_else(|| self.wrong_type::<V>(component, field))
+    }
+
+    /// Replaces the field's value, which must have type `V`
+    pub fn set_field<V: Any>(
+        &mut self,
+        component: &str,
+        field: &str,
+        value: V,
+    ) -> Result<(), ProxyError> {
+        let set = self.with_field(component, field, |current| {
+            current.downcast_mut::<V>().map(|current| *current = value)
+        })?;
+        set.ok_or_else(|| self.wrong_type::<V>(component, field))
+    }
+
+    fn component(&mut self, component: &str) -> Result<&mut dyn Reflect, ProxyError> {
+        let access = self
+            .registry
+            .components
+            .get(component)
+            .ok_or_else(|| ProxyError::UnknownComponent(component.to_owned()))?;
+        let entity = self.entity;
+        access(self.world, entity).ok_or_else(|| ProxyError::MissingComponent {
+            entity,
+            component: component.to_owned(),
+        })
+    }
+
+    fn wrong_type<V>(&mut self, component: &str, field: &str) -> ProxyError {
+        let actual = self
+            .component(component)
+            .ok()
+            .and_then(|reflect| reflect.field_type_name(field))
+            .unwrap_or("?");
+        ProxyError::WrongType {
+            component: component.to_owned(),
+            field: field.to_owned(),
+            expected: type_name::<V>(),
+            actual,
+        }
+    }
+}
+
+/// The error returned when an [`EntityProxy`] can't access a field
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProxyError {
+    /// No component type is registered with the name
+    UnknownComponent(String),
+    /// The entity doesn't exist or doesn't have the component
+    MissingComponent { entity: Entity, component: String },
+    /// The component has no field with the name
+    UnknownField { component: String, field: String },
+    /// The field's type differs from the type it was accessed as
+    WrongType {
+        component: String,
+        field: String,
+        expected: &'static str,
+        actual: &'static str,
+    },
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyError::UnknownComponent(component) => {
+                write!(f, "no component named `{}` is registered", component)
+            }
+            ProxyError::MissingComponent { entity, component } => {
+                write!(f, "entity {:?} has no `{}` component", entity, component)
+            }
+            ProxyError::UnknownField { component, field } => {
+                write!(f, "component `{}` has no field `{}`", component, field)
+            }
+            ProxyError::WrongType {
+                component,
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "field `{}.{}` has type `{}`, not `{}`",
+                component, field, actual, expected
+            ),
+        }
+    }
+}
+
+impl Error for ProxyError {}
@@ -0,0 +1,200 @@
+use core::any::{type_name, TypeId};
+
+/// Lists which components of a bundle struct are sent to clients
+///
+/// This is implemented by `derive(ComponentProvider)` for bundle structs. Fields marked
+/// `#[provider(replicate)]` are replicated, while fields marked `#[provider(server_only)]` or left
+/// unmarked are not, so new fields aren't sent to clients by accident. Nested bundles contribute
+/// the components of their own descriptors.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, ReplicatedBundle};
+/// use std::any::TypeId;
+///
+/// struct Position(f32);
+/// struct Health(i32);
+/// struct AiState(u8);
+///
+/// #[derive(ComponentProvider)]
+/// struct Monster {
+///     #[provider(replicate)]
+///     position: Position,
+///     #[provider(replicate)]
+///     health: Health,
+///     #[provider(server_only)]
+///     ai: AiState,
+/// }
+///
+/// let replication = Monster::replication();
+/// assert!(replication.is_replicated(TypeId::of::<Health>()));
+/// assert!(!replication.is_replicated(TypeId::of::<AiState>()));
+/// assert_eq!(replication.replicated().map(|c| c.path.as_str()).collect::<Vec<_>>(), ["position", "health"]);
+/// ```
+pub trait ReplicatedBundle {
+    fn replication() -> ReplicationDescriptor;
+}
+
+/// Whether a component is sent to clients
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Replication {
+    Replicate,
+    ServerOnly,
+}
+
+/// A component of a bundle struct and whether it is replicated
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplicatedComponent {
+    /// The field name, prefixed with the names of the fields of any enclosing nested bundles,
+    /// e.g. `physics.position`
+    pub path: String,
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    pub replication: Replication,
+    /// How to pack the component's float values, set with `#[provider(quantize(...))]`
+    pub quantization: Option<Quantization>,
+}
+
+/// The result of [`ReplicatedBundle::replication`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplicationDescriptor {
+    components: Vec<ReplicatedComponent>,
+}
+
+impl ReplicationDescriptor {
+    #[doc(hidden)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[doc(hidden)]
+    pub fn push<T: ?Sized + 'static>(
+        &mut self,
+        path: &str,
+        replication: Replication,
+        quantization: Option<Quantization>,
+    ) {
+        self.components.push(ReplicatedComponent {
+            path: path.to_string(),
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            replication,
+            quantization,
+        });
+    }
+
+    #[doc(hidden)]
+    pub fn push_nested(&mut self, name: &str, descriptor: ReplicationDescriptor) {
+        for component in descriptor.components {
+            self.components.push(ReplicatedComponent {
+                path: format!("{}.{}", name, component.path),
+                ..component
+            });
+        }
+    }
+
+    /// Every component in declaration order
+    pub fn components(&self) -> &[ReplicatedComponent] {
+        &self.components
+    }
+
+    /// The components that are sent to clients
+    pub fn replicated(&self) -> impl Iterator<Item = &ReplicatedComponent> + '_ {
+        self.components
+            .iter()
+            .filter(|c| c.replication == Replication::Replicate)
+    }
+
+    /// Whether the bundle has a replicated component of type `type_id`
+    pub fn is_replicated(&self, type_id: TypeId) -> bool {
+        self.replicated().any(|c| c.type_id == type_id)
+    }
+}
+
+/// Packs floats in a range into a fixed number of bits, set on bundle struct fields with
+/// `#[provider(quantize(bits = 12, range = "-512.0..512.0"))]`
+///
+/// Values are rounded to the nearest of `2^bits` evenly spaced steps and clamped to the range.
+/// Deltas of quantized values against a baseline the client already has are usually small, and
+/// compress well with variable-length integer encodings.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, ReplicatedBundle};
+///
+/// struct Position(f32);
+///
+/// #[derive(ComponentProvider)]
+/// struct Projectile {
+///     #[provider(replicate, quantize(bits = 12, range = "-512.0..512.0"))]
+///     position: Position,
+/// }
+///
+/// let quantization = Projectile::replication().components()[0].quantization.unwrap();
+/// let packed = quantization.encode(100.0);
+/// assert!(packed < 1 << 12);
+/// assert!((quantization.decode(packed) - 100.0).abs() <= quantization.step() / 2.0);
+///
+/// let delta = quantization.delta(100.0, 101.0);
+/// assert_eq!(delta, 4);
+/// assert_eq!(quantization.apply_delta(100.0, delta), quantization.decode(quantization.encode(101.0)));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quantization {
+    bits: u32,
+    min: f32,
+    max: f32,
+}
+
+impl Quantization {
+    /// # Panics
+    ///
+    /// Panics if `bits` isn't between 1 and 32, or `min` isn't less than `max`.
+    pub fn new(bits: u32, min: f32, max: f32) -> Self {
+        assert!((1..=32).contains(&bits), "bits must be between 1 and 32");
+        assert!(min < max, "the quantization range must not be empty");
+        Quantization { bits, min, max }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn min(&self) -> f32 {
+        self.min
+    }
+
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    fn max_value(&self) -> u32 {
+        u32::MAX >> (32 - self.bits)
+    }
+
+    /// The distance between adjacent quantized values
+    pub fn step(&self) -> f32 {
+        ((self.max as f64 - self.min as f64) / self.max_value() as f64) as f32
+    }
+
+    pub fn encode(&self, value: f32) -> u32 {
+        let t = (value as f64 - self.min as f64) / (self.max as f64 - self.min as f64);
+        // NaN is packed as the minimum
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        (t * self.max_value() as f64).round() as u32
+    }
+
+    pub fn decode(&self, packed: u32) -> f32 {
+        let t = packed.min(self.max_value()) as f64 / self.max_value() as f64;
+        (self.min as f64 + t * (self.max as f64 - self.min as f64)) as f32
+    }
+
+    /// The difference between the quantized `value` and the quantized `baseline`
+    pub fn delta(&self, baseline: f32, value: f32) -> i64 {
+        self.encode(value) as i64 - self.encode(baseline) as i64
+    }
+
+    /// Reconstructs a value from its [`delta`](Self::delta) against `baseline`
+    pub fn apply_delta(&self, baseline: f32, delta: i64) -> f32 {
+        let packed = (self.encode(baseline) as i64 + delta).clamp(0, self.max_value() as i64);
+        self.decode(packed as u32)
+    }
+}
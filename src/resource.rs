@@ -0,0 +1,72 @@
+use crate::{ComponentProvider, ComponentProviderMut};
+use resources::{Ref, RefMut, Resource};
+
+/// Provides a resource borrowed from a [`resources::Resources`] container
+///
+/// Borrowed resources can be composed with query items using
+/// [`gen_composed_component_providers`](crate::gen_composed_component_providers), so behaviors
+/// access them through `ComponentProvider<T>` like any other component.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, gen_composed_component_providers, ComponentProvider,
+///     ComponentProviderMut
+/// };
+/// use resources::{Ref, Resources};
+///
+/// struct Gravity(f32);
+/// struct Velocity(f32);
+///
+/// #[default_trait_impl]
+/// trait Fall: ComponentProviderMut<Velocity> + ComponentProvider<Gravity> {
+///     fn fall(&mut self) {
+///         let &Gravity(g) = self.get();
+///         let velocity: &mut Velocity = self.get_mut();
+///         velocity.0 -= g;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct FallQuery<'a> {
+///     velocity: &'a mut Velocity,
+/// }
+///
+/// gen_composed_component_providers!(
+///     FallWithGravity<'a>(FallQuery<'a>, &'a Ref<'a, Gravity>) {
+///         0 => [mut Velocity],
+///         1 => [Gravity],
+///     }
+/// );
+///
+/// let mut resources = Resources::default();
+/// resources.insert(Gravity(9.8));
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((Velocity(0.0),));
+///
+/// let gravity = resources.get::<Gravity>().unwrap();
+/// for item in world.query_mut::<FallQuery>() {
+///     FallWithGravity(item, &gravity).fall();
+/// }
+/// assert_eq!(world.get::<&Velocity>(entity).unwrap().0, -9.8);
+/// ```
+impl<T: Resource> ComponentProvider<T> for Ref<'_, T> {
+    #[inline]
+    fn get(&self) -> &T {
+        self
+    }
+}
+
+impl<T: Resource> ComponentProvider<T> for RefMut<'_, T> {
+    #[inline]
+    fn get(&self) -> &T {
+        self
+    }
+}
+
+impl<T: Resource> ComponentProviderMut<T> for RefMut<'_, T> {
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        self
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{ComponentProvider, ComponentProviderMut};
+use core::ops::Range;
+use hecs::Entity;
+
+/// A world-wide seed from which deterministic per-entity random number generators are derived
+///
+/// The generator for an entity depends only on the seed, the current tick and the entity's id, so
+/// replays and lockstep simulations produce the same values regardless of iteration order or
+/// which other entities drew random numbers. Call [`advance`](Self::advance) once per tick.
+///
+/// An [`EntityRng`] provides itself, so behaviors can require
+/// `ComponentProviderMut<EntityRng>` and receive it by composing it with a query item using
+/// [`gen_composed_component_providers`](crate::gen_composed_component_providers).
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, gen_composed_component_providers, ComponentProvider,
+///     ComponentProviderMut, EntityRng, RngSeed
+/// };
+///
+/// struct Position(f64);
+///
+/// #[default_trait_impl]
+/// trait Wander: ComponentProviderMut<Position> + ComponentProviderMut<EntityRng> {
+///     fn wander(&mut self) {
+///         let rng: &mut EntityRng = self.get_mut();
+///         let step = rng.next_f64() - 0.5;
+///         let position: &mut Position = self.get_mut();
+///         position.0 += step;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct WanderQuery<'a> {
+///     entity: hecs::Entity,
+///     position: &'a mut Position,
+/// }
+///
+/// gen_composed_component_providers!(
+///     WanderWithRng<'a>(WanderQuery<'a>, EntityRng) {
+///         0 => [mut Position],
+///         1 => [mut EntityRng],
+///     }
+/// );
+///
+/// let simulate = || {
+///     let mut world = hecs::World::new();
+///     world.spawn((Position(0.0),));
+///     let mut seed = RngSeed::new(42);
+///     for _ in 0..10 {
+///         for item in world.query_mut::<WanderQuery>() {
+///             let rng = seed.rng_for(item.entity);
+///             WanderWithRng(item, rng).wander();
+///         }
+///         seed.advance();
+///     }
+///     world.query_mut::<&Position>().into_iter().map(|p| p.0).sum::<f64>()
+/// };
+/// assert_eq!(simulate(), simulate());
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RngSeed {
+    seed: u64,
+    tick: u64,
+}
+
+impl RngSeed {
+    pub fn new(seed: u64) -> Self {
+        RngSeed { seed, tick: 0 }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Moves to the next tick, changing the generators derived for every entity
+    pub fn advance(&mut self) {
+        self.tick += 1;
+    }
+
+    /// Returns the generator for `entity` in the current tick
+    pub fn rng_for(&self, entity: Entity) -> EntityRng {
+        let state = mix(mix(self.seed) ^ self.tick) ^ entity.to_bits().get();
+        EntityRng { state: mix(state) }
+    }
+}
+
+/// A small deterministic random number generator, created by [`RngSeed::rng_for`]
+///
+/// This is not suitable for cryptographic use.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct EntityRng {
+    state: u64,
+}
+
+impl EntityRng {
+    pub fn from_seed(seed: u64) -> Self {
+        EntityRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // splitmix64
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.state)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a value in `0.0..1.0`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Returns a value in `0.0..1.0`
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+
+    /// Returns a value in `range`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is empty.
+    pub fn range(&mut self, range: Range<u64>) -> u64 {
+        assert!(range.start < range.end, "cannot sample from an empty range");
+        let span = range.end - range.start;
+        // widening multiply, whose bias is negligible for spans much smaller than 2^64
+        let offset = ((self.next_u64() as u128 * span as u128) >> 64) as u64;
+        range.start + offset
+    }
+
+    /// Returns true with probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl ComponentProvider<EntityRng> for EntityRng {
+    fn get(&self) -> &EntityRng {
+        self
+    }
+}
+
+impl ComponentProviderMut<EntityRng> for EntityRng {
+    fn get_mut(&mut self) -> &mut EntityRng {
+        self
+    }
+}
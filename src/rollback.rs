@@ -0,0 +1,178 @@
+use crate::{OwnedQuery, Schedule, Snapshot};
+use hecs::{DynamicBundle, Entity, World};
+use std::collections::{HashSet, VecDeque};
+
+/// A ring buffer of per-tick snapshots of the components in a query's owned bundle, for rolling
+/// back and resimulating when late inputs arrive
+///
+/// Call [`save`](Self::save) after each tick. When an input for an earlier tick arrives,
+/// [`resimulate`](Self::resimulate) restores the world to that tick and runs the schedule again up
+/// to the present, which produces the corrected state as long as the schedule is deterministic,
+/// e.g. by drawing random numbers from [`RngSeed`](crate::RngSeed).
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, RollbackBuffer, Schedule};
+/// use std::{cell::RefCell, collections::HashMap, rc::Rc};
+///
+/// #[derive(Clone)]
+/// struct Position(i32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(owned(PositionBundle))]
+/// struct PositionQuery<'a> {
+///     position: &'a mut Position,
+/// }
+///
+/// // inputs by tick, with missing inputs predicted as 0
+/// let inputs = Rc::new(RefCell::new(HashMap::<u64, i32>::new()));
+/// let tick = Rc::new(RefCell::new(0));
+///
+/// let mut schedule = Schedule::new();
+/// schedule.add_system("move", {
+///     let (inputs, tick) = (inputs.clone(), tick.clone());
+///     move |world| {
+///         let input = inputs.borrow().get(&*tick.borrow()).copied().unwrap_or(0);
+///         for item in world.query_mut::<PositionQuery>() {
+///             item.position.0 += input;
+///         }
+///     }
+/// });
+///
+/// let mut world = hecs::World::new();
+/// let player = world.spawn((Position(0),));
+/// let mut rollback = RollbackBuffer::<PositionQuery>::new(8);
+/// for _ in 0..3 {
+///     *tick.borrow_mut() = rollback.next_tick();
+///     schedule.run(&mut world);
+///     rollback.save(&world);
+/// }
+/// assert_eq!(world.get::<&Position>(player).unwrap().0, 0);
+///
+/// // the input for tick 1 arrives late, so resimulate ticks 1 and 2
+/// inputs.borrow_mut().insert(1, 5);
+/// rollback.resimulate(&mut world, &mut schedule, 2, |tick_number| {
+///     *tick.borrow_mut() = tick_number;
+/// });
+/// assert_eq!(world.get::<&Position>(player).unwrap().0, 5);
+/// ```
+pub struct RollbackBuffer<Q: OwnedQuery> {
+    capacity: usize,
+    frames: VecDeque<Snapshot<Q>>,
+    // the tick of the next saved frame
+    next_tick: u64,
+}
+
+impl<Q> RollbackBuffer<Q>
+where
+    Q: OwnedQuery,
+    Q::Owned: DynamicBundle,
+{
+    /// Creates a buffer keeping the last `capacity` ticks
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "a rollback buffer must keep at least one tick"
+        );
+        RollbackBuffer {
+            capacity,
+            frames: VecDeque::with_capacity(capacity),
+            next_tick: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of saved ticks that can be rolled back to
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The tick that the next call to [`save`](Self::save) records, counting from 0
+    pub fn next_tick(&self) -> u64 {
+        self.next_tick
+    }
+
+    /// Records the state of the world at the end of the current tick, dropping the oldest saved
+    /// tick if the buffer is full
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component accessed by `Q` is already borrowed mutably.
+    pub fn save(&mut self, world: &World) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(Snapshot::take(world));
+        self.next_tick += 1;
+    }
+
+    /// Restores the world to its state `n` ticks before the last saved tick, discarding the
+    /// ticks after it, and returns whether enough ticks were saved
+    ///
+    /// The components of the owned bundle are reinserted on every entity in the saved state,
+    /// respawning entities with the same handle if they were despawned since. Entities matching
+    /// `Q` that aren't in the saved state are assumed to have been spawned since, and despawned.
+    pub fn rollback(&mut self, world: &mut World, n: usize) -> bool {
+        if n >= self.frames.len() {
+            return false;
+        }
+        self.frames.truncate(self.frames.len() - n);
+        self.next_tick -= n as u64;
+        let frame = self.frames.pop_back().expect("at least one frame remains");
+
+        let saved: HashSet<Entity> = frame.entities().iter().copied().collect();
+        let spawned: Vec<Entity> = world
+            .query_mut::<(Entity, Q)>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .filter(|entity| !saved.contains(entity))
+            .collect();
+        for entity in spawned {
+            world.despawn(entity).expect("entity was just queried");
+        }
+        for (entity, bundle) in frame {
+            if world.contains(entity) {
+                world.insert(entity, bundle).expect("entity exists");
+            } else {
+                world.spawn_at(entity, bundle);
+            }
+        }
+
+        // the bundles were moved into the world, so copy them again to keep the restored tick
+        self.frames.push_back(Snapshot::take(world));
+        true
+    }
+
+    /// Rolls back `n` ticks and runs `schedule` once for each of them, saving each resimulated
+    /// tick, and returns whether enough ticks were saved
+    ///
+    /// `before_tick` is called with the number of each tick before it is run, to make the inputs
+    /// for that tick available to the schedule.
+    pub fn resimulate(
+        &mut self,
+        world: &mut World,
+        schedule: &mut Schedule,
+        n: usize,
+        mut before_tick: impl FnMut(u64),
+    ) -> bool {
+        if !self.rollback(world, n) {
+            return false;
+        }
+        for _ in 0..n {
+            before_tick(self.next_tick);
+            schedule.run(world);
+            self.save(world);
+        }
+        true
+    }
+}
@@ -0,0 +1,365 @@
+use crate::EntitySet;
+use core::marker::PhantomData;
+use hecs::{Entity, Query, World};
+
+/// Runs a behavior on at most a fixed number of entities matching `Q` per call
+///
+/// Each pass collects the matching entities once, ordered by [`Entity::to_bits`] so that the order
+/// is deterministic for a given world, and later calls continue where the previous one stopped.
+/// Entities despawned or no longer matching during a pass are skipped, and entities spawned during
+/// a pass are visited in the next one. Once every entity of the pass has been visited, the next
+/// call starts a new pass. This amortizes expensive work, such as initializing assets, over
+/// several frames.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, IncrementalRunner};
+///
+/// struct Mesh { baked: bool }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MeshQuery<'a> {
+///     mesh: &'a mut Mesh,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// for _ in 0..5 {
+///     world.spawn((Mesh { baked: false },));
+/// }
+///
+/// let mut runner = IncrementalRunner::<MeshQuery>::new(2);
+/// assert_eq!(runner.run(&mut world, |item| item.mesh.baked = true), 2);
+/// assert_eq!(runner.run(&mut world, |item| item.mesh.baked = true), 2);
+/// assert_eq!(runner.run(&mut world, |item| item.mesh.baked = true), 1);
+/// assert!(world.query_mut::<&Mesh>().into_iter().all(|m| m.baked));
+/// ```
+pub struct IncrementalRunner<Q> {
+    n_per_tick: usize,
+    // the entities of the current pass, sorted by their bits
+    pass: Vec<Entity>,
+    // the index in `pass` of the next entity to visit
+    next: usize,
+    _query: PhantomData<fn() -> Q>,
+}
+
+impl<Q: Query> IncrementalRunner<Q> {
+    pub fn new(n_per_tick: usize) -> Self {
+        IncrementalRunner {
+            n_per_tick,
+            pass: Vec::new(),
+            next: 0,
+            _query: PhantomData,
+        }
+    }
+
+    /// Calls `f` for up to `n_per_tick` matching entities that haven't been visited in the current
+    /// pass, returning the number of entities processed
+    pub fn run<F>(&mut self, world: &mut World, mut f: F) -> usize
+    where
+        F: for<'q> FnMut(Q::Item<'q>),
+    {
+        if self.is_pass_complete() {
+            self.pass.clear();
+            self.pass.extend(world.query_mut::<Entity>().with::<Q>());
+            self.pass.sort_unstable_by_key(|e| e.to_bits());
+            self.next = 0;
+        }
+
+        let mut count = 0;
+        while count < self.n_per_tick && self.next < self.pass.len() {
+            let entity = self.pass[self.next];
+            self.next += 1;
+            if let Ok(item) = world.query_one_mut::<Q>(entity) {
+                f(item);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Whether the next call to [`run`](Self::run) starts a new pass
+    pub fn is_pass_complete(&self) -> bool {
+        self.next >= self.pass.len()
+    }
+
+    /// Starts a new pass on the next call to [`run`](Self::run)
+    pub fn reset(&mut self) {
+        self.pass.clear();
+        self.next = 0;
+    }
+}
+
+/// Runs a behavior on every entity matching `Q`, in ascending order of the key returned by
+/// `key_fn`
+///
+/// Entities with equal keys are processed in order of their ids, so that the order is
+/// deterministic for a given world. Returns the number of entities processed.
+///
+/// ```
+/// use hecs_component_provider::{run_behavior_sorted, ComponentProvider};
+///
+/// struct Initiative(i32);
+/// struct Name(&'static str);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct TurnQuery<'a> {
+///     initiative: &'a Initiative,
+///     name: &'a Name,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Initiative(3), Name("goblin")));
+/// world.spawn((Initiative(1), Name("knight")));
+/// world.spawn((Initiative(2), Name("wizard")));
+///
+/// let mut turns = Vec::new();
+/// run_behavior_sorted::<TurnQuery, _>(
+///     &mut world,
+///     |item| -item.initiative.0,
+///     |item| turns.push(item.name.0),
+/// );
+/// assert_eq!(turns, ["goblin", "wizard", "knight"]);
+/// ```
+pub fn run_behavior_sorted<Q, K>(
+    world: &mut World,
+    mut key_fn: impl for<'q> FnMut(&Q::Item<'q>) -> K,
+    mut behavior: impl for<'q> FnMut(Q::Item<'q>),
+) -> usize
+where
+    Q: Query,
+    K: Ord,
+{
+    let mut entities: Vec<(K, u64, Entity)> = world
+        .query_mut::<(Entity, Q)>()
+        .into_iter()
+        .map(|(entity, item)| (key_fn(&item), entity.to_bits().get(), entity))
+        .collect();
+    entities.sort_unstable_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+    for &(_, _, entity) in &entities {
+        if let Ok(item) = world.query_one_mut::<Q>(entity) {
+            behavior(item);
+        }
+    }
+    entities.len()
+}
+
+/// Runs a behavior on the entities in `set` that match `Q`, in order of their ids
+///
+/// Only the members of the set are visited, each by a direct lookup, so the cost depends on the
+/// size of the set rather than the number of entities matching `Q`. Members that have been
+/// despawned or don't match are skipped. Returns the number of entities processed.
+///
+/// ```
+/// use hecs_component_provider::{run_behavior_in_set, ComponentProvider, EntitySet};
+///
+/// struct Position(i32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct PositionQuery<'a> {
+///     position: &'a mut Position,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let near = world.spawn((Position(0),));
+/// let far = world.spawn((Position(0),));
+///
+/// let mut active_chunk = EntitySet::new();
+/// active_chunk.insert(near);
+///
+/// let count = run_behavior_in_set::<PositionQuery>(&mut world, &active_chunk, |item| {
+///     item.position.0 += 1;
+/// });
+/// assert_eq!(count, 1);
+/// assert_eq!(world.get::<&Position>(near).unwrap().0, 1);
+/// assert_eq!(world.get::<&Position>(far).unwrap().0, 0);
+/// ```
+pub fn run_behavior_in_set<Q: Query>(
+    world: &mut World,
+    set: &EntitySet,
+    mut behavior: impl for<'q> FnMut(Q::Item<'q>),
+) -> usize {
+    let mut count = 0;
+    for entity in set.iter() {
+        if let Ok(item) = world.query_one_mut::<Q>(entity) {
+            behavior(item);
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Runs a closure on every entity matching `Q`, returning the number of entities processed
+///
+/// The closure receives the query item mutably, so behavior methods taking `&mut self` can be
+/// called on it, and one-off logic can use the item's providers without defining a behavior trait.
+///
+/// ```
+/// use hecs_component_provider::{run_with, ComponentProvider, ComponentProviderMut};
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(0.0), Velocity(2.0)));
+///
+/// run_with::<MovableQuery>(&mut world, |item| {
+///     let &Velocity(v) = item.get();
+///     let position: &mut Position = item.get_mut();
+///     position.0 += v;
+/// });
+/// assert_eq!(world.query_mut::<&Position>().into_iter().next().unwrap().0, 2.0);
+/// ```
+pub fn run_with<Q: Query>(world: &mut World, mut f: impl for<'q> FnMut(&mut Q::Item<'q>)) -> usize {
+    run_with_entity::<Q>(world, |_, item| f(item))
+}
+
+/// Runs a closure on every entity matching `Q` as with [`run_with`], also passing the entity
+///
+/// ```
+/// use hecs_component_provider::run_with_entity;
+///
+/// struct Health(i32);
+///
+/// let mut world = hecs::World::new();
+/// let dead = world.spawn((Health(0),));
+/// world.spawn((Health(5),));
+///
+/// let mut despawn = Vec::new();
+/// run_with_entity::<&Health>(&mut world, |entity, health| {
+///     if health.0 <= 0 {
+///         despawn.push(entity);
+///     }
+/// });
+/// assert_eq!(despawn, [dead]);
+/// ```
+pub fn run_with_entity<Q: Query>(
+    world: &mut World,
+    mut f: impl for<'q> FnMut(Entity, &mut Q::Item<'q>),
+) -> usize {
+    let mut count = 0;
+    for (entity, mut item) in world.query_mut::<(Entity, Q)>() {
+        f(entity, &mut item);
+        count += 1;
+    }
+    count
+}
+
+/// A system that can be registered in a [`Schedule`](crate::Schedule)
+pub type BoxedSystem = Box<dyn FnMut(&mut World)>;
+
+/// Binds the parameters of a behavior, returning a system that calls `f` with each item matching
+/// `Q` and the parameters
+///
+/// This replaces a hand-written closure per registration of a behavior that takes arguments.
+/// Parameters that change between runs, such as the elapsed time, can be read through shared
+/// handles like [`Clock`](crate::Clock).
+///
+/// ```
+/// use hecs_component_provider::{
+///     bind, default_trait_impl, ComponentProvider, ComponentProviderMut, Schedule
+/// };
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+/// struct MoveConfig { max_speed: f32 }
+///
+/// #[default_trait_impl]
+/// trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+///     fn apply_velocity(&mut self, dt: f32, config: &MoveConfig) {
+///         let &Velocity(v) = self.get();
+///         let position: &mut Position = self.get_mut();
+///         position.0 += v.min(config.max_speed) * dt;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(0.0), Velocity(4.0)));
+///
+/// let mut schedule = Schedule::new();
+/// let args = (schedule.clock(), MoveConfig { max_speed: 3.0 });
+/// schedule.add_system(
+///     "move",
+///     bind::<MovableQuery, _, _>(args, |mut item, (clock, config)| {
+///         item.apply_velocity(clock.get().scaled_delta(), config)
+///     }),
+/// );
+/// schedule.set_delta(0.5);
+/// schedule.run(&mut world);
+///
+/// assert_eq!(world.query_mut::<&Position>().into_iter().next().unwrap().0, 1.5);
+/// ```
+pub fn bind<Q, A, F>(args: A, mut f: F) -> BoxedSystem
+where
+    Q: Query,
+    A: 'static,
+    F: for<'q> FnMut(Q::Item<'q>, &A) + 'static,
+{
+    Box::new(move |world| {
+        for item in world.query_mut::<Q>() {
+            f(item, &args);
+        }
+    })
+}
+
+/// Provides [`bind`] as a method of behavior functions
+///
+/// `Q` can't be inferred from the behavior's signature, so it's named when binding:
+///
+/// ```
+/// use hecs_component_provider::{BindBehavior, Clock, ComponentProvider, Schedule};
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// fn apply_velocity(item: MovableQuery, clock: &Clock) {
+///     item.position.0 += item.velocity.0 * clock.get().scaled_delta();
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(0.0), Velocity(4.0)));
+///
+/// let mut schedule = Schedule::new();
+/// let clock = schedule.clock();
+/// schedule.add_system("move", apply_velocity.bind::<MovableQuery>(clock));
+/// schedule.set_delta(0.5);
+/// schedule.run(&mut world);
+///
+/// assert_eq!(world.query_mut::<&Position>().into_iter().next().unwrap().0, 2.0);
+/// ```
+pub trait BindBehavior<A>: Sized {
+    /// Binds the parameters of the behavior, see [`bind`]
+    fn bind<Q>(self, args: A) -> BoxedSystem
+    where
+        Q: Query,
+        Self: for<'q> FnMut(Q::Item<'q>, &A) + 'static,
+        A: 'static;
+}
+
+impl<F, A> BindBehavior<A> for F {
+    fn bind<Q>(self, args: A) -> BoxedSystem
+    where
+        Q: Query,
+        Self: for<'q> FnMut(Q::Item<'q>, &A) + 'static,
+        A: 'static,
+    {
+        bind::<Q, A, F>(args, self)
+    }
+}
@@ -0,0 +1,394 @@
+use core::convert::TryFrom;
+use core::fmt;
+use hecs::{Component, Entity, EntityBuilder, EntityRef, World};
+use std::error::Error;
+use std::io::{self, Read, Write};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+type EncodeFn = dyn Fn(EntityRef<'_>) -> Option<Result<Vec<u8>, BoxError>>;
+type DecodeFn = dyn Fn(&[u8], &mut EntityBuilder) -> Result<(), BoxError>;
+type MigrateFn = dyn Fn(&mut Vec<SavedComponent>);
+type EncodedEntity = (Entity, Vec<(&'static str, Vec<u8>)>);
+
+const MAGIC: &[u8; 4] = b"HCPS";
+
+/// Saves and loads the registered components of a world, with a format version and migrations
+/// for loading saves from older versions
+///
+/// Each component type is registered with a name and functions to encode and decode it, e.g.
+/// `bincode::serialize` and `bincode::deserialize` for types implementing serde's traits. Saves
+/// store components by name, so components whose names aren't registered, e.g. because the
+/// type was removed, are skipped when loading. Entities keep their handles, so components
+/// referring to other entities remain valid.
+///
+/// When the format changes, increase the version and add a migration that rewrites the saved
+/// components of each entity from the previous version.
+///
+/// ```
+/// use hecs_component_provider::SaveGame;
+/// use std::convert::TryInto;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Health(i32);
+/// #[derive(Debug, PartialEq)]
+/// struct Name(String);
+///
+/// let mut save_game = SaveGame::new(2);
+/// save_game
+///     .register::<Health, _, _>(
+///         "health",
+///         |health| Ok::<_, std::io::Error>(health.0.to_le_bytes().to_vec()),
+///         |bytes| bytes.try_into().map(|bytes| Health(i32::from_le_bytes(bytes))),
+///     )
+///     .register::<Name, _, _>(
+///         "name",
+///         |name| Ok::<_, std::io::Error>(name.0.clone().into_bytes()),
+///         |bytes| String::from_utf8(bytes.to_vec()).map(Name),
+///     )
+///     // version 1 called the health component "hp"
+///     .add_migration(1, |components| {
+///         for component in components {
+///             if component.name == "hp" {
+///                 component.name = "health".to_string();
+///             }
+///         }
+///     });
+///
+/// let mut world = hecs::World::new();
+/// let hero = world.spawn((Health(10), Name("hero".to_string())));
+///
+/// let mut bytes = Vec::new();
+/// save_game.save(&world, &mut bytes).unwrap();
+/// let loaded = save_game.load(&bytes[..]).unwrap();
+/// assert_eq!(*loaded.get::<&Health>(hero).unwrap(), Health(10));
+/// assert_eq!(loaded.get::<&Name>(hero).unwrap().0, "hero");
+/// ```
+pub struct SaveGame {
+    version: u32,
+    components: Vec<RegisteredComponent>,
+    // sorted by the version they migrate from
+    migrations: Vec<(u32, Box<MigrateFn>)>,
+}
+
+struct RegisteredComponent {
+    name: &'static str,
+    encode: Box<EncodeFn>,
+    decode: Box<DecodeFn>,
+}
+
+/// A component of an entity as stored in a save, passed to migrations
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SavedComponent {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl SaveGame {
+    /// Creates a save format with the given version, which is written to saves
+    pub fn new(version: u32) -> Self {
+        SaveGame {
+            version,
+            components: Vec::new(),
+            migrations: Vec::new(),
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Registers the component type `T` under `name`
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component has already been registered under `name`.
+    pub fn register<T, EncodeError, DecodeError>(
+        &mut self,
+        name: &'static str,
+        encode: impl Fn(&T) -> Result<Vec<u8>, EncodeError> + 'static,
+        decode: impl Fn(&[u8]) -> Result<T, DecodeError> + 'static,
+    ) -> &mut Self
+    where
+        T: Component,
+        EncodeError: Into<BoxError>,
+        DecodeError: Into<BoxError>,
+    {
+        assert!(
+            self.components.iter().all(|c| c.name != name),
+            "a component named `{}` has already been registered",
+            name
+        );
+        self.components.push(RegisteredComponent {
+            name,
+            encode: Box::new(move |entity| {
+                let component = entity.get::<&T>()?;
+                Some(encode(&component).map_err(Into::into))
+            }),
+            decode: Box::new(move |bytes, builder| {
+                builder.add(decode(bytes).map_err(Into::into)?);
+                Ok(())
+            }),
+        });
+        self
+    }
+
+    /// Adds a migration that rewrites the components of each entity in saves from version
+    /// `from_version` to the next version
+    ///
+    /// When loading a save, the migrations from its version onwards are applied in order.
+    pub fn add_migration(
+        &mut self,
+        from_version: u32,
+        migrate: impl Fn(&mut Vec<SavedComponent>) + 'static,
+    ) -> &mut Self {
+        let index = self
+            .migrations
+            .iter()
+            .position(|(version, _)| *version > from_version)
+            .unwrap_or(self.migrations.len());
+        self.migrations
+            .insert(index, (from_version, Box::new(migrate)));
+        self
+    }
+
+    /// Writes the registered components of every entity to `writer`
+    ///
+    /// Entities without registered components aren't saved.
+    pub fn save(&self, world: &World, mut writer: impl Write) -> Result<(), SaveError> {
+        let entities = self.encode(world)?;
+        writer.write_all(MAGIC)?;
+        write_u32(&mut writer, self.version)?;
+        write_len(&mut writer, entities.len())?;
+        write_entities(&mut writer, entities)?;
+        Ok(())
+    }
+
+    // encodes the registered components of each entity that has any
+    fn encode(&self, world: &World) -> Result<Vec<EncodedEntity>, SaveError> {
+        let mut entities = Vec::new();
+        for entity in world.iter() {
+            let mut components = Vec::new();
+            for registered in &self.components {
+                if let Some(data) = (registered.encode)(entity) {
+                    let data = data.map_err(|error| SaveError::Component {
+                        name: registered.name.to_string(),
+                        error,
+                    })?;
+                    components.push((registered.name, data));
+                }
+            }
+            if !components.is_empty() {
+                entities.push((entity.entity(), components));
+            }
+        }
+        Ok(entities)
+    }
+
+    /// Reads a world from `reader`, migrating it from the save's version if it is older
+    ///
+    /// Components whose names aren't registered are skipped.
+    pub fn load(&self, mut reader: impl Read) -> Result<World, SaveError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SaveError::InvalidFormat);
+        }
+        let version = read_u32(&mut reader)?;
+        if version > self.version {
+            return Err(SaveError::UnsupportedVersion {
+                found: version,
+                supported: self.version,
+            });
+        }
+
+        let mut world = World::new();
+        let mut builder = EntityBuilder::new();
+        for _ in 0..read_u32(&mut reader)? {
+            let mut bits = [0; 8];
+            reader.read_exact(&mut bits)?;
+            let entity =
+                Entity::from_bits(u64::from_le_bytes(bits)).ok_or(SaveError::InvalidFormat)?;
+
+            let mut components = Vec::new();
+            for _ in 0..read_u32(&mut reader)? {
+                let name = String::from_utf8(read_bytes(&mut reader)?)
+                    .map_err(|_| SaveError::InvalidFormat)?;
+                let data = read_bytes(&mut reader)?;
+                components.push(SavedComponent { name, data });
+            }
+            for (_, migrate) in self.migrations.iter().filter(|(from, _)| *from >= version) {
+                migrate(&mut components);
+            }
+
+            for component in components {
+                let registered = self.components.iter().find(|c| c.name == component.name);
+                if let Some(registered) = registered {
+                    (registered.decode)(&component.data, &mut builder).map_err(|error| {
+                        SaveError::Component {
+                            name: component.name,
+                            error,
+                        }
+                    })?;
+                }
+            }
+            world.spawn_at(entity, builder.build());
+        }
+        Ok(world)
+    }
+}
+
+/// Hashes the registered components of every entity in `world`, for detecting when the worlds of
+/// peers in a lockstep simulation have diverged
+///
+/// The hash covers the same data as [`SaveGame::save`], in a canonical order: entities are sorted
+/// by handle and their components by registered name, so it doesn't depend on the order in which
+/// entities are stored or components were registered. It uses FNV-1a, whose result is the same on
+/// every platform and compiler version, so peers can exchange it each tick and compare. Entities
+/// without registered components aren't hashed.
+///
+/// ```
+/// use hecs_component_provider::{hash_world, SaveGame};
+///
+/// struct Position(i32);
+///
+/// let mut registry = SaveGame::new(1);
+/// registry.register::<Position, _, _>(
+///     "position",
+///     |position| Ok::<_, std::io::Error>(position.0.to_le_bytes().to_vec()),
+///     |_| Err("hashing only"),
+/// );
+///
+/// let mut local = hecs::World::new();
+/// let mut remote = hecs::World::new();
+/// let player = local.spawn((Position(1),));
+/// remote.spawn((Position(1),));
+/// assert_eq!(hash_world(&local, &registry).unwrap(), hash_world(&remote, &registry).unwrap());
+///
+/// local.get::<&mut Position>(player).unwrap().0 += 1;
+/// assert_ne!(hash_world(&local, &registry).unwrap(), hash_world(&remote, &registry).unwrap());
+/// ```
+pub fn hash_world(world: &World, registry: &SaveGame) -> Result<u64, SaveError> {
+    let mut entities = registry.encode(world)?;
+    entities.sort_unstable_by_key(|(entity, _)| entity.to_bits());
+    for (_, components) in &mut entities {
+        components.sort_unstable_by_key(|(name, _)| *name);
+    }
+    let mut hasher = Fnv1a(0xcbf2_9ce4_8422_2325);
+    write_entities(&mut hasher, entities)?;
+    Ok(hasher.0)
+}
+
+// a 64-bit FNV-1a hasher, which unlike std's hashers is specified to be stable
+struct Fnv1a(u64);
+
+impl Write for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn write_entities(writer: &mut impl Write, entities: Vec<EncodedEntity>) -> io::Result<()> {
+    for (entity, components) in entities {
+        writer.write_all(&entity.to_bits().get().to_le_bytes())?;
+        write_len(writer, components.len())?;
+        for (name, data) in components {
+            write_bytes(writer, name.as_bytes())?;
+            write_bytes(writer, &data)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_len(writer: &mut impl Write, len: usize) -> io::Result<()> {
+    let len = u32::try_from(len)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many items to save"))?;
+    write_u32(writer, len)
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    write_len(writer, bytes.len())?;
+    writer.write_all(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u32(reader)? as usize;
+    let mut bytes = Vec::new();
+    // read incrementally, so a corrupt length doesn't allocate up front
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+/// The error returned when a [`SaveGame`] can't be saved or loaded
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    /// The data isn't a save
+    InvalidFormat,
+    /// The save was written by a newer version
+    UnsupportedVersion {
+        found: u32,
+        supported: u32,
+    },
+    /// A component couldn't be encoded or decoded
+    Component {
+        name: String,
+        error: BoxError,
+    },
+}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "failed to read or write save: {}", error),
+            SaveError::InvalidFormat => write!(f, "invalid save format"),
+            SaveError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "save version {} is newer than the supported version {}",
+                found, supported
+            ),
+            SaveError::Component { name, error } => {
+                write!(
+                    f,
+                    "failed to encode or decode component `{}`: {}",
+                    name, error
+                )
+            }
+        }
+    }
+}
+
+impl Error for SaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SaveError::Io(error) => Some(error),
+            SaveError::Component { error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
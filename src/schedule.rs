@@ -0,0 +1,1250 @@
+use crate::{
+    mutation_log, ComponentProvider, EntityCommands, Group, MutationLog, QueryAccess, Teardown,
+    Trace, TraceEvent,
+};
+use hecs::{CommandBuffer, Component, DynamicBundle, Entity, Query, World};
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Runs registered behaviors over a world stage by stage, recording statistics
+///
+/// A behavior is a closure called for each entity matching its query, which typically calls
+/// methods of a behavior trait on the query item. A system is a closure called once per run with
+/// the whole world.
+///
+/// Behaviors are registered to a [`Stage`], [`Stage::Update`] by default, and run in stage order
+/// and then in registration order. At the end of each stage, structural changes recorded through
+/// [`Commands`] are applied to the world.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, ComponentProvider, ComponentProviderMut, Schedule
+/// };
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// #[default_trait_impl]
+/// trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+///     fn apply_velocity(&mut self) {
+///         let &Velocity(v) = self.get();
+///         let position: &mut Position = self.get_mut();
+///         position.0 += v;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(0.0), Velocity(1.0)));
+/// world.spawn((Position(0.0),));
+///
+/// let mut schedule = Schedule::new();
+/// schedule.add_behavior::<MovableQuery, _>("apply_velocity", |mut e| e.apply_velocity());
+/// schedule.run(&mut world);
+///
+/// let stats = schedule.stats().get("apply_velocity").unwrap();
+/// assert_eq!(stats.entities, 1);
+/// assert_eq!(stats.frames, 1);
+/// ```
+pub struct Schedule {
+    stages: Vec<Stage>,
+    // sorted by stage and then registration order
+    behaviors: Vec<Behavior>,
+    stats: Stats,
+    resume: Option<ResumePoint>,
+    commands: Commands,
+    catch_panics: bool,
+    panics: Vec<EntityPanic>,
+    clock: Clock,
+    trace: Option<Trace>,
+    teardown: Teardown,
+    // the number of completed frames
+    tick: u64,
+    mutation_log: MutationLog,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            stages: vec![Stage::PreUpdate, Stage::Update, Stage::PostUpdate],
+            behaviors: Vec::new(),
+            stats: Stats::default(),
+            resume: None,
+            commands: Commands::default(),
+            catch_panics: false,
+            panics: Vec::new(),
+            clock: Clock::default(),
+            trace: None,
+            teardown: Teardown::default(),
+            tick: 0,
+            mutation_log: MutationLog::default(),
+        }
+    }
+}
+
+/// A named group of behaviors in a [`Schedule`], after which recorded [`Commands`] are applied
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Stage {
+    PreUpdate,
+    Update,
+    PostUpdate,
+    /// A stage added with [`Schedule::add_stage_before`] or [`Schedule::add_stage_after`]
+    Custom(&'static str),
+}
+
+struct Behavior {
+    stage: Stage,
+    // processes entities from the cursor until done or the deadline passes, returning the number
+    // of entities processed and the cursor to resume from if the deadline passed
+    run: Box<RunFn>,
+    mutable: bool,
+    // whether the query borrows `Group` mutably, in which case it can't be restricted to groups
+    writes_group: bool,
+    // systems don't have queries, so they aren't reported as matching no entities
+    system: bool,
+    run_criteria: Option<Box<RunCriteriaFn>>,
+    run_when_paused: bool,
+    groups: Option<Group>,
+    throttle: Option<Throttle>,
+    // registered with puffin the first time the behavior runs while profiling is on
+    #[cfg(feature = "puffin")]
+    scope_id: Option<puffin::ScopeId>,
+}
+
+/// Limits how often a behavior runs, counting only the runs in which it would otherwise run
+enum Throttle {
+    Ticks { every: u32, elapsed: u32 },
+    Rate { period: f32, accumulator: f32 },
+}
+
+impl Throttle {
+    /// Advances the throttle by one run of the schedule, returning whether the behavior runs
+    fn tick(&mut self, delta: f32) -> bool {
+        match self {
+            Throttle::Ticks { every, elapsed } => {
+                let run = *elapsed == 0;
+                *elapsed = (*elapsed + 1) % *every;
+                run
+            }
+            Throttle::Rate {
+                period,
+                accumulator,
+            } => {
+                *accumulator += delta;
+                if *accumulator < *period {
+                    return false;
+                }
+                // at most one run per frame, dropping whole periods that were missed
+                *accumulator %= *period;
+                true
+            }
+        }
+    }
+}
+
+type RunFn = dyn FnMut(&mut World, RunContext<'_>) -> (usize, Option<Cursor>);
+type RunCriteriaFn = dyn FnMut(&World) -> bool;
+
+/// The position of an entity within a query, as the index of its archetype among the non-empty
+/// archetypes matching the query and its index within the archetype
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct Cursor {
+    archetype: usize,
+    index: usize,
+}
+
+/// Where a budgeted run of a [`Schedule`] stopped, and the next run will continue
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ResumePoint {
+    /// The index of the behavior in execution order
+    pub behavior: usize,
+    /// The index of the archetype among the non-empty archetypes matching the behavior's query
+    pub archetype: usize,
+    /// The index of the entity within the archetype
+    pub index: usize,
+}
+
+struct RunContext<'a> {
+    start: Cursor,
+    deadline: Option<Instant>,
+    // entities outside these groups are skipped
+    groups: Option<Group>,
+    // collects panics caught from behavior calls when panic isolation is enabled
+    panics: Option<&'a mut Vec<EntityPanic>>,
+}
+
+/// A panic caught from a behavior call by a [`Schedule`] with panic isolation enabled
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EntityPanic {
+    pub behavior: &'static str,
+    pub entity: Entity,
+    pub message: String,
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+// opens a puffin scope named after a behavior, which closes when dropped
+#[cfg(feature = "puffin")]
+pub(crate) fn profile_scope(
+    scope_id: &mut Option<puffin::ScopeId>,
+    name: &'static str,
+    function_name: &'static str,
+) -> Option<puffin::ProfilerScope> {
+    if !puffin::are_scopes_on() {
+        return None;
+    }
+    let scope_id = *scope_id.get_or_insert_with(|| {
+        puffin::ThreadProfiler::call(|tp| {
+            tp.register_named_scope(name, function_name, file!(), line!())
+        })
+    });
+    Some(puffin::ProfilerScope::new(scope_id, ""))
+}
+
+fn expired(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a custom stage that runs directly before `existing`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `existing` hasn't been added or `stage` already has.
+    pub fn add_stage_before(&mut self, existing: Stage, stage: Stage) -> &mut Self {
+        let index = self.stage_index(existing);
+        self.insert_stage(index, stage)
+    }
+
+    /// Adds a custom stage that runs directly after `existing`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `existing` hasn't been added or `stage` already has.
+    pub fn add_stage_after(&mut self, existing: Stage, stage: Stage) -> &mut Self {
+        let index = self.stage_index(existing) + 1;
+        self.insert_stage(index, stage)
+    }
+
+    fn insert_stage(&mut self, index: usize, stage: Stage) -> &mut Self {
+        assert!(
+            !self.stages.contains(&stage),
+            "stage `{:?}` has already been added",
+            stage
+        );
+        self.stages.insert(index, stage);
+        self
+    }
+
+    fn stage_index(&self, stage: Stage) -> usize {
+        self.stages
+            .iter()
+            .position(|s| *s == stage)
+            .unwrap_or_else(|| panic!("stage `{:?}` has not been added", stage))
+    }
+
+    /// Stages in execution order
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// A handle for recording structural changes, which are applied at the end of each stage
+    pub fn commands(&self) -> Commands {
+        self.commands.clone()
+    }
+
+    /// Registers a behavior in [`Stage::Update`] that is called for each entity matching `Q`
+    pub fn add_behavior<Q, F>(&mut self, name: &'static str, f: F) -> &mut Self
+    where
+        Q: Query + QueryAccess,
+        F: for<'q> FnMut(Q::Item<'q>) + 'static,
+    {
+        self.add_behavior_to_stage::<Q, F>(Stage::Update, name, f)
+    }
+
+    /// Registers a behavior in `stage` that is called for each entity matching `Q`
+    pub fn add_behavior_to_stage<Q, F>(
+        &mut self,
+        stage: Stage,
+        name: &'static str,
+        mut f: F,
+    ) -> &mut Self
+    where
+        Q: Query + QueryAccess,
+        F: for<'q> FnMut(Q::Item<'q>) + 'static,
+    {
+        let access = Q::access();
+        let mutable = access.iter().any(|a| a.mutable || a.interior_mutable);
+        let writes_group = access
+            .iter()
+            .any(|a| a.mutable && a.type_id == TypeId::of::<Group>());
+        self.push(
+            name,
+            Behavior {
+                stage,
+                run: Box::new(move |world, mut context| {
+                    let RunContext {
+                        start,
+                        deadline,
+                        groups,
+                        ..
+                    } = context;
+                    let mut count = 0;
+                    let mut query = world.query::<(Entity, Q)>();
+                    // batches are yielded for the non-empty archetypes matching the query, in the
+                    // order of `World::archetypes`, so that their groups can be read by column
+                    let archetypes = world
+                        .archetypes()
+                        .filter(|a| !a.is_empty() && a.satisfies::<Q>());
+                    for (archetype, (batch, components)) in query
+                        .iter_batched(u32::MAX)
+                        .zip(archetypes)
+                        .enumerate()
+                        .skip(start.archetype)
+                    {
+                        // groups are only read by behaviors restricted to them, which don't borrow
+                        // `Group` mutably, so that other behaviors may
+                        let group_column = match groups {
+                            Some(groups) => match components.get::<&Group>() {
+                                Some(column) => Some(column),
+                                None if Group::DEFAULT.intersects(groups) => None,
+                                None => continue,
+                            },
+                            None => None,
+                        };
+                        let skip = if archetype == start.archetype {
+                            start.index
+                        } else {
+                            0
+                        };
+                        for (index, (entity, item)) in batch.enumerate().skip(skip) {
+                            if let (Some(groups), Some(column)) = (groups, &group_column) {
+                                if !column[index].intersects(groups) {
+                                    continue;
+                                }
+                            }
+                            // always make progress, even if the deadline has already passed
+                            if count > 0 && expired(deadline) {
+                                return (count, Some(Cursor { archetype, index }));
+                            }
+                            mutation_log::set_entity(entity);
+                            match context.panics {
+                                Some(ref mut panics) => {
+                                    let result = panic::catch_unwind(AssertUnwindSafe(|| f(item)));
+                                    if let Err(payload) = result {
+                                        panics.push(EntityPanic {
+                                            behavior: name,
+                                            entity,
+                                            message: panic_message(&*payload),
+                                        });
+                                    }
+                                }
+                                None => f(item),
+                            }
+                            count += 1;
+                        }
+                    }
+                    (count, None)
+                }),
+                mutable,
+                writes_group,
+                system: false,
+                run_criteria: None,
+                run_when_paused: false,
+                groups: None,
+                throttle: None,
+                #[cfg(feature = "puffin")]
+                scope_id: None,
+            },
+        )
+    }
+
+    /// Registers a teardown behavior that is called for entities matching `Q` before they are
+    /// despawned through [`Commands::despawn`]
+    ///
+    /// Teardown behaviors run when the commands are applied at the end of a stage, after the other
+    /// recorded changes, and may record further commands, which are applied in the same stage. See
+    /// [`Teardown`].
+    ///
+    /// ```
+    /// use hecs_component_provider::{ComponentProvider, Schedule};
+    ///
+    /// struct Health(i32);
+    /// struct Owner(hecs::Entity);
+    /// struct LostMinions(u32);
+    ///
+    /// #[derive(hecs::Query, ComponentProvider)]
+    /// struct MinionQuery<'a> {
+    ///     entity: hecs::Entity,
+    ///     health: &'a Health,
+    /// }
+    ///
+    /// #[derive(hecs::Query, ComponentProvider)]
+    /// struct OwnedQuery<'a> {
+    ///     owner: &'a Owner,
+    /// }
+    ///
+    /// let mut world = hecs::World::new();
+    /// let master = world.spawn((LostMinions(0),));
+    /// world.spawn((Health(0), Owner(master)));
+    ///
+    /// let mut schedule = Schedule::new();
+    /// let commands = schedule.commands();
+    /// schedule.add_behavior::<MinionQuery, _>("despawn_dead", move |item| {
+    ///     if item.health.0 <= 0 {
+    ///         commands.despawn(item.entity);
+    ///     }
+    /// });
+    /// let commands = schedule.commands();
+    /// schedule.add_teardown::<OwnedQuery, _>(move |item| {
+    ///     let owner = item.owner.0;
+    ///     commands.queue(move |world| world.get::<&mut LostMinions>(owner).unwrap().0 += 1);
+    /// });
+    /// schedule.run(&mut world);
+    ///
+    /// assert_eq!(world.len(), 1);
+    /// assert_eq!(world.get::<&LostMinions>(master).unwrap().0, 1);
+    /// ```
+    pub fn add_teardown<Q, F>(&mut self, f: F) -> &mut Self
+    where
+        Q: Query,
+        F: for<'q> FnMut(Q::Item<'q>) + 'static,
+    {
+        self.teardown.add::<Q, F>(f);
+        self
+    }
+
+    /// Registers a system in [`Stage::Update`] that is called once per run with the whole world
+    pub fn add_system<F>(&mut self, name: &'static str, f: F) -> &mut Self
+    where
+        F: FnMut(&mut World) + 'static,
+    {
+        self.add_system_to_stage(Stage::Update, name, f)
+    }
+
+    /// Registers a system in `stage` that is called once per run with the whole world
+    pub fn add_system_to_stage<F>(
+        &mut self,
+        stage: Stage,
+        name: &'static str,
+        mut f: F,
+    ) -> &mut Self
+    where
+        F: FnMut(&mut World) + 'static,
+    {
+        self.push(
+            name,
+            Behavior {
+                stage,
+                run: Box::new(move |world, _| {
+                    f(world);
+                    (0, None)
+                }),
+                mutable: false,
+                writes_group: false,
+                system: true,
+                run_criteria: None,
+                run_when_paused: false,
+                groups: None,
+                throttle: None,
+                #[cfg(feature = "puffin")]
+                scope_id: None,
+            },
+        )
+    }
+
+    /// Sets a condition that is evaluated each run before the behavior or system named `name`,
+    /// which is skipped when the condition returns false
+    ///
+    /// Skipped behaviors don't record statistics for that run.
+    ///
+    /// ```
+    /// use hecs_component_provider::Schedule;
+    /// use std::{cell::Cell, rc::Rc};
+    ///
+    /// #[derive(Clone, Copy, PartialEq)]
+    /// enum GameState { Playing, Paused }
+    ///
+    /// let state = Rc::new(Cell::new(GameState::Paused));
+    /// let mut schedule = Schedule::new();
+    /// schedule.add_system("simulate", |_| {});
+    /// schedule.run_if("simulate", {
+    ///     let state = state.clone();
+    ///     move |_| state.get() == GameState::Playing
+    /// });
+    ///
+    /// let mut world = hecs::World::new();
+    /// schedule.run(&mut world);
+    /// assert_eq!(schedule.stats().get("simulate").unwrap().frames, 0);
+    ///
+    /// state.set(GameState::Playing);
+    /// schedule.run(&mut world);
+    /// assert_eq!(schedule.stats().get("simulate").unwrap().frames, 1);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if no behavior or system named `name` has been registered.
+    pub fn run_if<F>(&mut self, name: &str, condition: F) -> &mut Self
+    where
+        F: FnMut(&World) -> bool + 'static,
+    {
+        let index = self.behavior_index(name);
+        self.behaviors[index].run_criteria = Some(Box::new(condition));
+        self
+    }
+
+    /// Keeps the behavior or system named `name` running while the schedule is paused, e.g. for
+    /// user interface behaviors
+    ///
+    /// # Panics
+    ///
+    /// Panics if no behavior or system named `name` has been registered.
+    pub fn run_when_paused(&mut self, name: &str) -> &mut Self {
+        let index = self.behavior_index(name);
+        self.behaviors[index].run_when_paused = true;
+        self
+    }
+
+    /// Restricts the behavior named `name` to entities in at least one of `groups`
+    ///
+    /// Entities without a [`Group`] component are in [`Group::DEFAULT`]. Groups are checked once
+    /// per archetype, skipping archetypes without a `Group` component unless `groups` contains
+    /// [`Group::DEFAULT`], and reading the groups of the others as a column. Groups have no effect
+    /// on systems.
+    ///
+    /// ```
+    /// use hecs_component_provider::{ComponentProvider, Group, Schedule};
+    ///
+    /// const UI: Group = Group::layer(1);
+    ///
+    /// struct Position(f32);
+    ///
+    /// #[derive(hecs::Query, ComponentProvider)]
+    /// struct PositionQuery<'a> {
+    ///     position: &'a mut Position,
+    /// }
+    ///
+    /// let mut world = hecs::World::new();
+    /// let ball = world.spawn((Position(0.0),));
+    /// let button = world.spawn((Position(0.0), UI));
+    ///
+    /// let mut schedule = Schedule::new();
+    /// schedule
+    ///     .add_behavior::<PositionQuery, _>("gravity", |item| item.position.0 -= 1.0)
+    ///     .in_groups("gravity", Group::DEFAULT);
+    /// schedule.run(&mut world);
+    ///
+    /// assert_eq!(world.get::<&Position>(ball).unwrap().0, -1.0);
+    /// assert_eq!(world.get::<&Position>(button).unwrap().0, 0.0);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if no behavior or system named `name` has been registered, or if the behavior's
+    /// query borrows `Group` mutably, since the groups are read while the query is borrowed.
+    pub fn in_groups(&mut self, name: &str, groups: Group) -> &mut Self {
+        let index = self.behavior_index(name);
+        assert!(
+            !self.behaviors[index].writes_group,
+            "behavior `{}` borrows `Group` mutably, so it can't be restricted to groups",
+            name
+        );
+        self.behaviors[index].groups = Some(groups);
+        self
+    }
+
+    /// Runs the behavior or system named `name` only every `ticks` runs of the schedule, starting
+    /// with the next one
+    ///
+    /// Runs in which the behavior is skipped because the schedule is paused or its
+    /// [`run_if`](Self::run_if) condition is false aren't counted.
+    ///
+    /// ```
+    /// use hecs_component_provider::Schedule;
+    ///
+    /// let mut schedule = Schedule::new();
+    /// schedule.add_system("autosave", |_| {}).run_every("autosave", 3);
+    ///
+    /// let mut world = hecs::World::new();
+    /// for _ in 0..7 {
+    ///     schedule.run(&mut world);
+    /// }
+    /// assert_eq!(schedule.stats().get("autosave").unwrap().frames, 3);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ticks` is zero, or if no behavior or system named `name` has been registered.
+    pub fn run_every(&mut self, name: &str, ticks: u32) -> &mut Self {
+        assert!(ticks > 0, "a behavior can't run every 0 ticks");
+        let index = self.behavior_index(name);
+        self.behaviors[index].throttle = Some(Throttle::Ticks {
+            every: ticks,
+            elapsed: 0,
+        });
+        self
+    }
+
+    /// Runs the behavior or system named `name` at most `hz` times per second of
+    /// [`Time::scaled_delta`], starting with the next run of the schedule
+    ///
+    /// Time accumulates across runs, so the behavior keeps its rate regardless of the frame rate,
+    /// as long as the frame rate is higher. It runs at most once per run of the schedule. Like
+    /// [`run_every`](Self::run_every), runs in which the behavior is skipped for other reasons
+    /// aren't counted.
+    ///
+    /// ```
+    /// use hecs_component_provider::Schedule;
+    ///
+    /// let mut schedule = Schedule::new();
+    /// schedule.add_system("replan", |_| {}).run_at_rate("replan", 5.0);
+    ///
+    /// // one second at 60 frames per second
+    /// let mut world = hecs::World::new();
+    /// schedule.set_delta(1.0 / 60.0);
+    /// for _ in 0..60 {
+    ///     schedule.run(&mut world);
+    /// }
+    /// assert_eq!(schedule.stats().get("replan").unwrap().frames, 5);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hz` isn't positive and finite, or if no behavior or system named `name` has
+    /// been registered.
+    pub fn run_at_rate(&mut self, name: &str, hz: f32) -> &mut Self {
+        assert!(
+            hz > 0.0 && hz.is_finite(),
+            "a behavior's rate must be positive"
+        );
+        let index = self.behavior_index(name);
+        let period = 1.0 / hz;
+        self.behaviors[index].throttle = Some(Throttle::Rate {
+            period,
+            // the first run isn't delayed
+            accumulator: period,
+        });
+        self
+    }
+
+    fn behavior_index(&self, name: &str) -> usize {
+        self.stats
+            .behaviors
+            .iter()
+            .position(|b| b.name == name)
+            .unwrap_or_else(|| panic!("no behavior named `{}` has been registered", name))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the behavior's stage hasn't been added.
+    fn push(&mut self, name: &'static str, behavior: Behavior) -> &mut Self {
+        let stage = self.stage_index(behavior.stage);
+        let index = self
+            .behaviors
+            .iter()
+            .position(|b| self.stage_index(b.stage) > stage)
+            .unwrap_or(self.behaviors.len());
+        self.stats
+            .behaviors
+            .insert(index, BehaviorStats::new(name, behavior.system));
+        self.behaviors.insert(index, behavior);
+        self
+    }
+
+    /// Runs every registered behavior once
+    ///
+    /// If a previous [`run_with_budget`](Self::run_with_budget) stopped early, this continues from
+    /// its resume point.
+    pub fn run(&mut self, world: &mut World) {
+        self.run_until(world, None);
+    }
+
+    /// Runs behaviors until the frame completes or `budget` is exhausted, returning whether the
+    /// frame completed
+    ///
+    /// When the budget runs out, the position of the next entity is recorded as a
+    /// [`ResumePoint`] and the next run continues from there. At least one entity is processed per
+    /// call, and systems are never interrupted.
+    ///
+    /// Resume points refer to positions in query iteration order, so spawning or despawning
+    /// entities between runs may cause entities to be skipped or processed twice in that frame.
+    pub fn run_with_budget(&mut self, world: &mut World, budget: Duration) -> bool {
+        self.run_until(world, Some(Instant::now() + budget))
+    }
+
+    /// The position a budgeted run stopped at, if the last frame is incomplete
+    pub fn resume_point(&self) -> Option<ResumePoint> {
+        self.resume
+    }
+
+    fn run_until(&mut self, world: &mut World, deadline: Option<Instant>) -> bool {
+        if let Some(trace) = &mut self.trace {
+            trace.begin_run();
+        }
+        let complete = self.run_behaviors(world, deadline, Instant::now());
+        if let Some(trace) = &mut self.trace {
+            trace.end_run(complete);
+        }
+        if complete {
+            self.tick += 1;
+        }
+        complete
+    }
+
+    /// The number of frames completed, which is the index of the frame that the next run executes
+    /// or continues
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The mutations made by this schedule's behaviors and systems, which requires the
+    /// `mutation-log` feature
+    pub fn mutation_log(&self) -> &MutationLog {
+        &self.mutation_log
+    }
+
+    fn run_behaviors(
+        &mut self,
+        world: &mut World,
+        deadline: Option<Instant>,
+        run_start: Instant,
+    ) -> bool {
+        let resume = self.resume.take();
+        let first = resume.map_or(0, |r| r.behavior);
+        let mut cursor = resume.map_or(Cursor::default(), |r| Cursor {
+            archetype: r.archetype,
+            index: r.index,
+        });
+
+        for i in first..self.behaviors.len() {
+            if i > first && expired(deadline) {
+                self.resume = Some(ResumePoint {
+                    behavior: i,
+                    archetype: 0,
+                    index: 0,
+                });
+                return false;
+            }
+
+            let time = self.clock.get();
+            let behavior = &mut self.behaviors[i];
+            // a behavior that stopped partway has already processed an entity, so its cursor is
+            // never the default and its run criteria were checked when it started
+            let resumed = cursor != Cursor::default();
+            let should_run = resumed
+                || ((!time.paused || behavior.run_when_paused)
+                    && behavior
+                        .run_criteria
+                        .as_mut()
+                        .is_none_or(|condition| condition(world))
+                    && behavior
+                        .throttle
+                        .as_mut()
+                        .is_none_or(|throttle| throttle.tick(time.scaled_delta())));
+
+            if should_run {
+                #[cfg(feature = "puffin")]
+                let _scope = profile_scope(
+                    &mut behavior.scope_id,
+                    self.stats.behaviors[i].name,
+                    "Schedule::run",
+                );
+                let _mutations = mutation_log::enter_behavior(
+                    &self.mutation_log,
+                    self.stats.behaviors[i].name,
+                    self.tick,
+                );
+                let start = Instant::now();
+                let context = RunContext {
+                    start: cursor,
+                    deadline,
+                    groups: behavior.groups,
+                    panics: if self.catch_panics {
+                        Some(&mut self.panics)
+                    } else {
+                        None
+                    },
+                };
+                let (entities, stopped) = (behavior.run)(world, context);
+                let duration = start.elapsed();
+                self.stats.behaviors[i].record(duration, entities, behavior.mutable);
+                if let Some(trace) = &mut self.trace {
+                    trace.record(TraceEvent::Behavior {
+                        name: self.stats.behaviors[i].name,
+                        stage: behavior.stage,
+                        offset: start - run_start,
+                        duration,
+                        entities,
+                        skipped: false,
+                    });
+                }
+
+                if let Some(stopped) = stopped {
+                    self.resume = Some(ResumePoint {
+                        behavior: i,
+                        archetype: stopped.archetype,
+                        index: stopped.index,
+                    });
+                    return false;
+                }
+            } else if let Some(trace) = &mut self.trace {
+                trace.record(TraceEvent::Behavior {
+                    name: self.stats.behaviors[i].name,
+                    stage: behavior.stage,
+                    offset: run_start.elapsed(),
+                    duration: Duration::default(),
+                    entities: 0,
+                    skipped: true,
+                });
+            }
+            cursor = Cursor::default();
+
+            let stage = self.behaviors[i].stage;
+            if self.behaviors.get(i + 1).is_none_or(|b| b.stage != stage) {
+                if let Some(trace) = &mut self.trace {
+                    trace.record(TraceEvent::Flush {
+                        stage,
+                        offset: run_start.elapsed(),
+                    });
+                }
+                self.apply_commands(world);
+            }
+        }
+        true
+    }
+
+    // applies the recorded commands, tearing down despawned entities until no more are recorded
+    fn apply_commands(&mut self, world: &mut World) {
+        loop {
+            self.commands.buffer.borrow_mut().run_on(world);
+            let despawned = std::mem::take(&mut *self.commands.despawned.borrow_mut());
+            if despawned.is_empty() {
+                break;
+            }
+            for entity in despawned {
+                // the entity may have been despawned by an earlier command
+                let _ = self.teardown.despawn(world, entity);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// A handle for reading the schedule's [`Time`] from behaviors
+    pub fn clock(&self) -> Clock {
+        self.clock.clone()
+    }
+
+    /// Sets the unscaled time elapsed since the previous frame, typically before each run
+    pub fn set_delta(&mut self, delta: f32) -> &mut Self {
+        self.clock.update(|time| time.delta = delta);
+        self
+    }
+
+    /// Sets the factor applied to [`Time::scaled_delta`], e.g. `0.5` for slow motion
+    pub fn set_time_scale(&mut self, scale: f32) -> &mut Self {
+        self.clock.update(|time| time.scale = scale);
+        self
+    }
+
+    /// Pauses or unpauses the schedule
+    ///
+    /// While paused, only behaviors registered with [`run_when_paused`](Self::run_when_paused) run,
+    /// and [`Time::scaled_delta`] is zero.
+    pub fn set_paused(&mut self, paused: bool) -> &mut Self {
+        self.clock.update(|time| time.paused = paused);
+        self
+    }
+
+    /// Enables or disables panic isolation for behaviors
+    ///
+    /// When enabled, a panic in a behavior call is caught and recorded with the entity being
+    /// processed, and the behavior continues with the remaining entities. The panic hook still
+    /// runs, so panics are printed as usual. Panics in systems are not caught.
+    ///
+    /// Components that were being modified when the panic occurred may be left in an inconsistent
+    /// state.
+    ///
+    /// ```
+    /// use hecs_component_provider::{ComponentProvider, Schedule};
+    ///
+    /// struct Health(i32);
+    ///
+    /// #[derive(hecs::Query, ComponentProvider)]
+    /// struct HealthQuery<'a> {
+    ///     health: &'a mut Health,
+    /// }
+    ///
+    /// let mut world = hecs::World::new();
+    /// let corrupt = world.spawn((Health(-1),));
+    /// world.spawn((Health(10),));
+    ///
+    /// let mut schedule = Schedule::new();
+    /// schedule.set_catch_panics(true);
+    /// schedule.add_behavior::<HealthQuery, _>("heal", |item| {
+    ///     assert!(item.health.0 >= 0, "negative health");
+    ///     item.health.0 += 1;
+    /// });
+    /// # let hook = std::panic::take_hook();
+    /// # std::panic::set_hook(Box::new(|_| {}));
+    /// schedule.run(&mut world);
+    /// # std::panic::set_hook(hook);
+    ///
+    /// let panics = schedule.take_panics();
+    /// assert_eq!(panics.len(), 1);
+    /// assert_eq!(panics[0].entity, corrupt);
+    /// assert_eq!(panics[0].message, "negative health");
+    /// ```
+    pub fn set_catch_panics(&mut self, catch_panics: bool) -> &mut Self {
+        self.catch_panics = catch_panics;
+        self
+    }
+
+    /// Panics caught since they were last taken
+    pub fn panics(&self) -> &[EntityPanic] {
+        &self.panics
+    }
+
+    pub fn take_panics(&mut self) -> Vec<EntityPanic> {
+        core::mem::take(&mut self.panics)
+    }
+
+    /// Enables or disables recording a [`Trace`] of the execution order of each run
+    ///
+    /// Disabling tracing discards the recorded trace.
+    pub fn set_trace(&mut self, enabled: bool) -> &mut Self {
+        if !enabled {
+            self.trace = None;
+        } else if self.trace.is_none() {
+            self.trace = Some(Trace::default());
+        }
+        self
+    }
+
+    /// The trace recorded since tracing was enabled or the trace was last taken
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Takes the recorded trace, continuing to record into an empty trace
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.as_mut().map(core::mem::take)
+    }
+}
+
+/// Statistics recorded by a [`Schedule`] for each of its behaviors
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    behaviors: Vec<BehaviorStats>,
+}
+
+impl Stats {
+    /// Statistics for each behavior, in execution order
+    pub fn behaviors(&self) -> &[BehaviorStats] {
+        &self.behaviors
+    }
+
+    /// Statistics for the first behavior registered with `name`
+    pub fn get(&self, name: &str) -> Option<&BehaviorStats> {
+        self.behaviors.iter().find(|b| b.name == name)
+    }
+
+    /// Behaviors whose queries have matched no entities for at least `frames` consecutive runs
+    ///
+    /// This usually means a component was renamed or is never inserted, so that the behavior
+    /// silently does nothing.
+    ///
+    /// ```
+    /// use hecs_component_provider::{ComponentProvider, Schedule};
+    ///
+    /// struct Position(f32);
+    ///
+    /// #[derive(hecs::Query, ComponentProvider)]
+    /// struct PositionQuery<'a> {
+    ///     position: &'a mut Position,
+    /// }
+    ///
+    /// let mut world = hecs::World::new();
+    /// let mut schedule = Schedule::new();
+    /// schedule.add_behavior::<PositionQuery, _>("move", |item| item.position.0 += 1.0);
+    /// for _ in 0..60 {
+    ///     schedule.run(&mut world);
+    /// }
+    ///
+    /// let unmatched: Vec<_> = schedule.stats().unmatched(60).map(|b| b.name).collect();
+    /// assert_eq!(unmatched, ["move"]);
+    /// ```
+    pub fn unmatched(&self, frames: u64) -> impl Iterator<Item = &BehaviorStats> + '_ {
+        self.behaviors
+            .iter()
+            .filter(move |b| b.empty_frames > 0 && b.empty_frames >= frames)
+    }
+
+    /// Writes the statistics as CSV, with one row per behavior and durations in microseconds
+    pub fn write_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writeln!(
+            writer,
+            "name,frames,duration_us,total_duration_us,entities,mutations"
+        )?;
+        for b in &self.behaviors {
+            writeln!(
+                writer,
+                "\"{}\",{},{},{},{},{}",
+                b.name.replace('"', "\"\""),
+                b.frames,
+                b.duration.as_micros(),
+                b.total_duration.as_micros(),
+                b.entities,
+                b.mutations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Statistics for a single behavior
+///
+/// Apart from `frames` and `total_duration`, the values describe the most recent run. When a
+/// budgeted run stops partway through a behavior, each part is recorded as a separate run.
+#[derive(Clone, Debug)]
+pub struct BehaviorStats {
+    pub name: &'static str,
+    /// The number of runs recorded
+    pub frames: u64,
+    pub duration: Duration,
+    pub total_duration: Duration,
+    /// The number of entities matched by the behavior's query
+    pub entities: usize,
+    /// The number of entities the behavior was given mutable access to, including access to
+    /// [`interior_mutable`](crate::ComponentAccess::interior_mutable) components
+    ///
+    /// This is an upper bound, since a behavior may leave mutably borrowed components unchanged.
+    pub mutations: usize,
+    /// The number of consecutive runs in which the behavior's query matched no entities
+    ///
+    /// This is always zero for systems.
+    pub empty_frames: u64,
+    is_system: bool,
+}
+
+impl BehaviorStats {
+    fn new(name: &'static str, is_system: bool) -> Self {
+        BehaviorStats {
+            name,
+            frames: 0,
+            duration: Duration::default(),
+            total_duration: Duration::default(),
+            entities: 0,
+            mutations: 0,
+            empty_frames: 0,
+            is_system,
+        }
+    }
+
+    fn record(&mut self, duration: Duration, entities: usize, mutable: bool) {
+        self.frames += 1;
+        self.duration = duration;
+        self.total_duration += duration;
+        self.entities = entities;
+        self.mutations = if mutable { entities } else { 0 };
+        if entities > 0 || self.is_system {
+            self.empty_frames = 0;
+        } else {
+            self.empty_frames += 1;
+        }
+    }
+}
+
+/// A shared [`CommandBuffer`] whose commands a [`Schedule`] applies at the end of each stage
+///
+/// Behaviors can't modify the structure of the world while it is being queried, so they record
+/// spawns, insertions and removals through a `Commands` handle captured by their closure instead.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, Schedule, Stage};
+///
+/// struct Health(i32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct HealthQuery<'a> {
+///     entity: hecs::Entity,
+///     health: &'a Health,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Health(0),));
+/// world.spawn((Health(5),));
+///
+/// let mut schedule = Schedule::new();
+/// let commands = schedule.commands();
+/// schedule.add_behavior::<HealthQuery, _>("despawn_dead", move |item| {
+///     if item.health.0 <= 0 {
+///         commands.despawn(item.entity);
+///     }
+/// });
+/// schedule.add_system_to_stage(Stage::PostUpdate, "check", |world| {
+///     assert_eq!(world.len(), 1);
+/// });
+/// schedule.run(&mut world);
+/// ```
+#[derive(Clone, Default)]
+pub struct Commands {
+    buffer: Rc<RefCell<CommandBuffer>>,
+    // despawned after the buffer is applied, so that teardown behaviors can run first
+    despawned: Rc<RefCell<Vec<Entity>>>,
+}
+
+impl Commands {
+    /// A handle for recording changes to `entity` alone
+    pub fn entity(&self, entity: Entity) -> EntityCommands {
+        EntityCommands::new(entity, self.clone())
+    }
+
+    pub fn spawn(&self, components: impl DynamicBundle) {
+        self.buffer.borrow_mut().spawn(components);
+    }
+
+    pub fn insert(&self, entity: Entity, components: impl DynamicBundle) {
+        self.buffer.borrow_mut().insert(entity, components);
+    }
+
+    pub fn insert_one(&self, entity: Entity, component: impl Component) {
+        self.buffer.borrow_mut().insert_one(entity, component);
+    }
+
+    pub fn remove_one<T: Component>(&self, entity: Entity) {
+        self.buffer.borrow_mut().remove_one::<T>(entity);
+    }
+
+    /// Despawns `entity` after the other recorded changes have been applied, running the
+    /// schedule's teardown behaviors first
+    pub fn despawn(&self, entity: Entity) {
+        self.despawned.borrow_mut().push(entity);
+    }
+
+    /// Records an arbitrary change to the world
+    pub fn queue(&self, f: impl FnOnce(&mut World) + Send + Sync + 'static) {
+        self.buffer.borrow_mut().queue(f);
+    }
+}
+
+/// The frame time of a [`Schedule`], with its time scale and pause state
+///
+/// `Time` provides itself, so behaviors can require `ComponentProvider<Time>` and receive it by
+/// composing it with a query item using
+/// [`gen_composed_component_providers`](crate::gen_composed_component_providers).
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, Schedule};
+///
+/// struct Position(f32);
+/// struct Spinner(f32);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct PositionQuery<'a> {
+///     position: &'a mut Position,
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct SpinnerQuery<'a> {
+///     spinner: &'a mut Spinner,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(0.0),));
+/// world.spawn((Spinner(0.0),));
+///
+/// let mut schedule = Schedule::new();
+/// let clock = schedule.clock();
+/// schedule.add_behavior::<PositionQuery, _>("simulate", move |item| {
+///     item.position.0 += clock.get().scaled_delta();
+/// });
+/// let clock = schedule.clock();
+/// schedule.add_behavior::<SpinnerQuery, _>("ui_spinner", move |item| {
+///     item.spinner.0 += clock.get().delta;
+/// });
+/// schedule.run_when_paused("ui_spinner");
+///
+/// schedule.set_delta(1.0).set_time_scale(0.5);
+/// schedule.run(&mut world);
+/// schedule.set_paused(true);
+/// schedule.run(&mut world);
+///
+/// assert_eq!(world.query_mut::<&Position>().into_iter().next().unwrap().0, 0.5);
+/// assert_eq!(world.query_mut::<&Spinner>().into_iter().next().unwrap().0, 2.0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Time {
+    /// The unscaled time elapsed since the previous frame
+    pub delta: f32,
+    pub scale: f32,
+    pub paused: bool,
+}
+
+impl Time {
+    /// The elapsed time multiplied by the time scale, or zero while paused
+    pub fn scaled_delta(&self) -> f32 {
+        if self.paused {
+            0.0
+        } else {
+            self.delta * self.scale
+        }
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Time {
+            delta: 0.0,
+            scale: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl ComponentProvider<Time> for Time {
+    fn get(&self) -> &Time {
+        self
+    }
+}
+
+/// A shared handle to the [`Time`] of a [`Schedule`]
+#[derive(Clone, Default)]
+pub struct Clock(Rc<Cell<Time>>);
+
+impl Clock {
+    pub fn get(&self) -> Time {
+        self.0.get()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Time)) {
+        let mut time = self.0.get();
+        f(&mut time);
+        self.0.set(time);
+    }
+}
@@ -0,0 +1,180 @@
+use hecs::{Entity, Query, World};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Connections from entities emitting signals of type `S` to entities subscribed to them
+///
+/// Emitted signals are queued, and [`dispatch`](Self::dispatch) calls a behavior for each
+/// subscriber of each signal, typically from a system at a flush point of a
+/// [`Schedule`](crate::Schedule). Connections are identified by a [`Connection`] handle, which
+/// stays valid until it is disconnected. Connections whose source or subscriber has been
+/// despawned are removed by `dispatch`, after any signals the source emitted before it was
+/// despawned have been delivered.
+///
+/// `Signals` is a shared handle, so clones can be captured by behaviors to emit signals.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, Schedule, Signals, Stage};
+///
+/// struct OnDeath;
+/// struct Health(i32);
+/// struct Mourning(bool);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct HealthQuery<'a> {
+///     entity: hecs::Entity,
+///     health: &'a Health,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let king = world.spawn((Health(0),));
+/// let knight = world.spawn((Mourning(false),));
+///
+/// let signals = Signals::<OnDeath>::new();
+/// signals.connect(king, knight);
+///
+/// let mut schedule = Schedule::new();
+/// let (commands, emitter) = (schedule.commands(), signals.clone());
+/// schedule.add_behavior::<HealthQuery, _>("die", move |item| {
+///     if item.health.0 <= 0 {
+///         emitter.emit(item.entity, OnDeath);
+///         commands.despawn(item.entity);
+///     }
+/// });
+/// schedule.add_system_to_stage(Stage::PostUpdate, "mourn", move |world| {
+///     signals.dispatch::<&mut Mourning, _>(world, |mourning, _source, _signal| {
+///         mourning.0 = true;
+///     });
+///     // the king's connections are cleaned up once its death has been delivered
+///     assert_eq!(signals.len(), 0);
+/// });
+/// schedule.run(&mut world);
+///
+/// assert!(world.get::<&Mourning>(knight).unwrap().0);
+/// ```
+pub struct Signals<S>(Rc<RefCell<SignalState<S>>>);
+
+struct SignalState<S> {
+    next_id: u64,
+    connections: Vec<Subscription>,
+    emitted: Vec<(Entity, S)>,
+}
+
+#[derive(Clone, Copy)]
+struct Subscription {
+    connection: Connection,
+    source: Entity,
+    subscriber: Entity,
+}
+
+/// A handle to a connection made by [`Signals::connect`]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Connection(u64);
+
+impl<S> Signals<S> {
+    pub fn new() -> Self {
+        Signals(Rc::new(RefCell::new(SignalState {
+            next_id: 0,
+            connections: Vec::new(),
+            emitted: Vec::new(),
+        })))
+    }
+
+    /// Subscribes `subscriber` to the signals emitted by `source`
+    ///
+    /// An entity can be connected to the same source more than once, in which case it receives
+    /// each signal once per connection.
+    pub fn connect(&self, source: Entity, subscriber: Entity) -> Connection {
+        let mut state = self.0.borrow_mut();
+        let connection = Connection(state.next_id);
+        state.next_id += 1;
+        state.connections.push(Subscription {
+            connection,
+            source,
+            subscriber,
+        });
+        connection
+    }
+
+    /// Removes a connection, returning whether it was still connected
+    pub fn disconnect(&self, connection: Connection) -> bool {
+        let mut state = self.0.borrow_mut();
+        let len = state.connections.len();
+        state.connections.retain(|s| s.connection != connection);
+        state.connections.len() != len
+    }
+
+    pub fn is_connected(&self, connection: Connection) -> bool {
+        self.0
+            .borrow()
+            .connections
+            .iter()
+            .any(|s| s.connection == connection)
+    }
+
+    /// The entities subscribed to `source`, in the order they were connected
+    pub fn subscribers(&self, source: Entity) -> Vec<Entity> {
+        self.0
+            .borrow()
+            .connections
+            .iter()
+            .filter(|s| s.source == source)
+            .map(|s| s.subscriber)
+            .collect()
+    }
+
+    /// The number of connections
+    pub fn len(&self) -> usize {
+        self.0.borrow().connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().connections.is_empty()
+    }
+
+    /// Queues `signal` for delivery to the subscribers of `source`
+    pub fn emit(&self, source: Entity, signal: S) {
+        self.0.borrow_mut().emitted.push((source, signal));
+    }
+
+    /// Calls `f` with each subscriber's `Q` item, the source and the signal, for every signal
+    /// emitted since the last dispatch, returning the number of calls
+    ///
+    /// Signals are delivered in the order they were emitted, and to subscribers in the order they
+    /// were connected. Subscribers that don't match `Q` are skipped. Afterwards, connections
+    /// involving despawned entities are removed.
+    pub fn dispatch<Q, F>(&self, world: &mut World, mut f: F) -> usize
+    where
+        Q: Query,
+        F: for<'q> FnMut(Q::Item<'q>, Entity, &S),
+    {
+        // take the queue first, so that `f` can emit further signals for the next dispatch
+        let emitted = core::mem::take(&mut self.0.borrow_mut().emitted);
+        let mut calls = 0;
+        for (source, signal) in &emitted {
+            for subscriber in self.subscribers(*source) {
+                if let Ok(item) = world.query_one_mut::<Q>(subscriber) {
+                    f(item, *source, signal);
+                    calls += 1;
+                }
+            }
+        }
+        self.0
+            .borrow_mut()
+            .connections
+            .retain(|s| world.contains(s.source) && world.contains(s.subscriber));
+        calls
+    }
+}
+
+impl<S> Clone for Signals<S> {
+    fn clone(&self) -> Self {
+        Signals(self.0.clone())
+    }
+}
+
+impl<S> Default for Signals<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
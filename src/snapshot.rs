@@ -0,0 +1,169 @@
+use hecs::{Entity, Query, World};
+
+/// A query whose items can be cloned into an owned bundle, implemented by
+/// `derive(ComponentProvider)` with `#[provider(owned(Name))]`
+pub trait OwnedQuery: Query {
+    type Owned: 'static;
+
+    fn to_owned_item(item: &Self::Item<'_>) -> Self::Owned;
+}
+
+/// Owned copies of the components of every entity matching a query, stored contiguously
+///
+/// Taking a snapshot iterates the query once. Passes that read the same data several times can
+/// then iterate the snapshot, which is laid out as an array of owned bundles in query order,
+/// instead of querying the world again. The bundles implement the provider traits, so behaviors
+/// can be called on them, but changes aren't written back to the world.
+///
+/// ```
+/// use hecs_component_provider::{default_trait_impl, ComponentProvider, Snapshot};
+///
+/// #[derive(Clone)]
+/// struct Position(f32);
+/// #[derive(Clone)]
+/// struct Mass(f32);
+///
+/// #[default_trait_impl]
+/// trait Weight: ComponentProvider<Position> + ComponentProvider<Mass> {
+///     fn weighted_position(&self) -> f32 {
+///         let position: &Position = self.get();
+///         let mass: &Mass = self.get();
+///         position.0 * mass.0
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(owned(BodyBundle))]
+/// struct BodyQuery<'a> {
+///     position: &'a Position,
+///     mass: &'a Mass,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(1.0), Mass(2.0)));
+/// world.spawn((Position(3.0), Mass(2.0)));
+///
+/// let bodies = Snapshot::<BodyQuery>::take(&world);
+/// let total_mass: f32 = bodies.items().iter().map(|b| b.mass.0).sum();
+/// let center: f32 = bodies.items().iter().map(|b| b.weighted_position()).sum::<f32>() / total_mass;
+/// assert_eq!(center, 2.0);
+/// ```
+pub struct Snapshot<Q: OwnedQuery> {
+    entities: Vec<Entity>,
+    items: Vec<Q::Owned>,
+}
+
+impl<Q: OwnedQuery> Snapshot<Q> {
+    /// Copies the components of every entity matching `Q`
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component accessed by `Q` is already borrowed mutably.
+    pub fn take(world: &World) -> Self {
+        let mut query = world.query::<(Entity, Q)>();
+        let iter = query.iter();
+        let mut entities = Vec::with_capacity(iter.len());
+        let mut items = Vec::with_capacity(iter.len());
+        for (entity, item) in iter {
+            entities.push(entity);
+            items.push(Q::to_owned_item(&item));
+        }
+        Snapshot { entities, items }
+    }
+
+    /// Copies the components of the entities matching `Q` for which `filter` returns true
+    ///
+    /// This is used to build a snapshot per observer, such as a networked client, containing only
+    /// the entities relevant to it. The filter receives the query item, so it can be written in
+    /// terms of read-only component providers.
+    ///
+    /// ```
+    /// use hecs_component_provider::{ComponentProvider, Snapshot};
+    ///
+    /// #[derive(Clone)]
+    /// struct Position(f32);
+    ///
+    /// #[derive(hecs::Query, ComponentProvider)]
+    /// #[provider(owned(PositionBundle))]
+    /// struct PositionQuery<'a> {
+    ///     position: &'a Position,
+    /// }
+    ///
+    /// fn in_range(observer: f32) -> impl FnMut(hecs::Entity, &PositionQuery) -> bool {
+    ///     move |_, item| {
+    ///         let position: &Position = item.get();
+    ///         (position.0 - observer).abs() < 10.0
+    ///     }
+    /// }
+    ///
+    /// let mut world = hecs::World::new();
+    /// world.spawn((Position(0.0),));
+    /// world.spawn((Position(100.0),));
+    ///
+    /// assert_eq!(Snapshot::<PositionQuery>::take_filtered(&world, in_range(5.0)).len(), 1);
+    /// assert_eq!(Snapshot::<PositionQuery>::take_filtered(&world, in_range(50.0)).len(), 0);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component accessed by `Q` is already borrowed mutably.
+    pub fn take_filtered(
+        world: &World,
+        mut filter: impl for<'q> FnMut(Entity, &Q::Item<'q>) -> bool,
+    ) -> Self {
+        let mut query = world.query::<(Entity, Q)>();
+        let mut entities = Vec::new();
+        let mut items = Vec::new();
+        for (entity, item) in query.iter() {
+            if filter(entity, &item) {
+                entities.push(entity);
+                items.push(Q::to_owned_item(&item));
+            }
+        }
+        Snapshot { entities, items }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The entities the items were copied from, in the same order as [`items`](Self::items)
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn items(&self) -> &[Q::Owned] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut [Q::Owned] {
+        &mut self.items
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Entity, &Q::Owned)> + '_ {
+        self.entities.iter().copied().zip(&self.items)
+    }
+
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = (Entity, &mut Q::Owned)> + '_ {
+        self.entities.iter().copied().zip(&mut self.items)
+    }
+
+    /// The item copied from `entity`, found by linear search
+    pub fn get(&self, entity: Entity) -> Option<&Q::Owned> {
+        let index = self.entities.iter().position(|e| *e == entity)?;
+        Some(&self.items[index])
+    }
+}
+
+impl<Q: OwnedQuery> IntoIterator for Snapshot<Q> {
+    type Item = (Entity, Q::Owned);
+    type IntoIter = core::iter::Zip<std::vec::IntoIter<Entity>, std::vec::IntoIter<Q::Owned>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entities.into_iter().zip(self.items)
+    }
+}
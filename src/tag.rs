@@ -0,0 +1,52 @@
+use hecs::{Component, ComponentError, Entity, NoSuchEntity, With, World};
+
+/// A marker component without any data, implemented by `derive(Tag)`
+///
+/// Tags are used to select entities in queries rather than to store state, so they only need to
+/// be checked, inserted and removed.
+///
+/// ```
+/// use hecs_component_provider::{Tag, WithTag};
+///
+/// #[derive(Tag)]
+/// struct Enemy;
+///
+/// struct Position(i32);
+///
+/// let mut world = hecs::World::new();
+/// let goblin = world.spawn((Position(1),));
+/// let knight = world.spawn((Position(2),));
+///
+/// Enemy::insert_tag(&mut world, goblin).unwrap();
+/// assert!(Enemy::has_tag(&world, goblin));
+/// assert!(!Enemy::has_tag(&world, knight));
+///
+/// let enemies: Vec<_> = world.query_mut::<WithTag<Enemy>>().into_iter().collect();
+/// assert_eq!(enemies, [goblin]);
+///
+/// Enemy::remove_tag(&mut world, goblin).unwrap();
+/// assert!(!Enemy::has_tag(&world, goblin));
+/// ```
+pub trait Tag: Component + Sized {
+    #[doc(hidden)]
+    const TAG: Self;
+
+    fn has_tag(world: &World, entity: Entity) -> bool {
+        world.satisfies::<&Self>(entity)
+    }
+
+    /// Adds the tag to `entity`, which has no effect if it already has it
+    fn insert_tag(world: &mut World, entity: Entity) -> Result<(), NoSuchEntity> {
+        world.insert_one(entity, Self::TAG)
+    }
+
+    /// Removes the tag from `entity`, failing if it doesn't have it
+    fn remove_tag(world: &mut World, entity: Entity) -> Result<(), ComponentError> {
+        world.remove_one::<Self>(entity).map(drop)
+    }
+}
+
+/// A query filter for entities with the tag `T`, which yields the query `Q`
+///
+/// `Q` defaults to [`Entity`], so that `WithTag<T>` yields the tagged entities.
+pub type WithTag<T, Q = Entity> = With<Q, &'static T>;
@@ -0,0 +1,196 @@
+use core::future::Future;
+use core::pin::Pin;
+use hecs::{CommandBuffer, Component, DynamicBundle, Entity, Query, World};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A boxed future spawned by an [`AsyncRunner`]
+pub type BoxedTask = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Hands tasks started by an [`AsyncRunner`] to an async runtime
+///
+/// This is implemented for closures, so a runtime can be plugged in without a wrapper type, e.g.
+/// `|task| { tokio::spawn(task); }` or `|task| { async_std::task::spawn(task); }`.
+pub trait Spawner {
+    fn spawn(&self, task: BoxedTask);
+}
+
+impl<F: Fn(BoxedTask)> Spawner for F {
+    fn spawn(&self, task: BoxedTask) {
+        self(task)
+    }
+}
+
+/// Starts async tasks for entities and applies the changes they record to the world
+///
+/// A task can't borrow the world while it is suspended, so the behavior closure copies what it
+/// needs out of the query item and returns a future, which is driven to completion by the
+/// runtime behind the [`Spawner`], possibly over several frames. The future records its changes
+/// through the [`AsyncCommands`] it is given, and they are applied when
+/// [`apply`](Self::apply) is next called, typically once per frame.
+///
+/// ```
+/// use hecs_component_provider::{AsyncRunner, BoxedTask, ComponentProvider};
+/// use std::sync::{Arc, Mutex};
+///
+/// struct AssetPath(&'static str);
+/// struct Loaded(usize);
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct AssetQuery<'a> {
+///     entity: hecs::Entity,
+///     path: &'a AssetPath,
+/// }
+///
+/// async fn load(path: &'static str) -> usize {
+///     path.len()
+/// }
+///
+/// // a stand-in for an async runtime, which collects the tasks to run later
+/// let tasks = Arc::new(Mutex::new(Vec::<BoxedTask>::new()));
+/// let queue = tasks.clone();
+/// let mut runner = AsyncRunner::new(move |task| queue.lock().unwrap().push(task));
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((AssetPath("tree.png"),));
+///
+/// runner.spawn_behavior::<AssetQuery, _, _>(&mut world, |item, commands| {
+///     let (entity, path) = (item.entity, item.path.0);
+///     async move {
+///         let size = load(path).await;
+///         commands.insert_one(entity, Loaded(size));
+///     }
+/// });
+/// assert_eq!(runner.pending(), 1);
+///
+/// // poll the tasks as the runtime would
+/// struct NoopWaker;
+///
+/// impl std::task::Wake for NoopWaker {
+///     fn wake(self: Arc<Self>) {}
+/// }
+///
+/// let waker = std::task::Waker::from(Arc::new(NoopWaker));
+/// let mut context = std::task::Context::from_waker(&waker);
+/// for mut task in tasks.lock().unwrap().drain(..) {
+///     assert!(task.as_mut().poll(&mut context).is_ready());
+/// }
+/// assert_eq!(runner.pending(), 0);
+///
+/// runner.apply(&mut world);
+/// assert_eq!(world.get::<&Loaded>(entity).unwrap().0, 8);
+/// ```
+pub struct AsyncRunner<S> {
+    spawner: S,
+    commands: AsyncCommands,
+    pending: Arc<AtomicUsize>,
+}
+
+impl<S: Spawner> AsyncRunner<S> {
+    pub fn new(spawner: S) -> Self {
+        AsyncRunner {
+            spawner,
+            commands: AsyncCommands::default(),
+            pending: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// A handle for recording changes from tasks started outside
+    /// [`spawn_behavior`](Self::spawn_behavior)
+    pub fn commands(&self) -> AsyncCommands {
+        self.commands.clone()
+    }
+
+    /// Calls `f` for each entity matching `Q` and spawns the future it returns, returning the
+    /// number of tasks started
+    pub fn spawn_behavior<Q, F, Fut>(&mut self, world: &mut World, mut f: F) -> usize
+    where
+        Q: Query,
+        F: for<'q> FnMut(Q::Item<'q>, AsyncCommands) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut count = 0;
+        for item in world.query_mut::<Q>() {
+            let task = f(item, self.commands.clone());
+            self.spawn(task);
+            count += 1;
+        }
+        count
+    }
+
+    /// Spawns a single task, which is counted as pending until it completes or is dropped
+    pub fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let guard = PendingGuard::new(self.pending.clone());
+        self.spawner.spawn(Box::pin(async move {
+            let _guard = guard;
+            task.await
+        }));
+    }
+
+    /// The number of spawned tasks that haven't completed yet
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Acquire)
+    }
+
+    /// Applies the changes recorded by tasks since the last call
+    pub fn apply(&mut self, world: &mut World) {
+        // swap the buffer out so that tasks can keep recording while it is applied
+        let mut buffer = std::mem::take(&mut *self.commands.buffer());
+        buffer.run_on(world);
+    }
+}
+
+// counts a task as pending until it finishes, including if it is cancelled by the runtime
+struct PendingGuard(Arc<AtomicUsize>);
+
+impl PendingGuard {
+    fn new(pending: Arc<AtomicUsize>) -> Self {
+        pending.fetch_add(1, Ordering::AcqRel);
+        PendingGuard(pending)
+    }
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A thread-safe [`CommandBuffer`] shared by the tasks of an [`AsyncRunner`]
+///
+/// This mirrors [`Commands`](crate::Commands), but can be moved into futures that run on other
+/// threads.
+#[derive(Clone, Default)]
+pub struct AsyncCommands(Arc<Mutex<CommandBuffer>>);
+
+impl AsyncCommands {
+    fn buffer(&self) -> MutexGuard<'_, CommandBuffer> {
+        // a task that panicked while recording leaves the buffer usable
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn spawn(&self, components: impl DynamicBundle) {
+        self.buffer().spawn(components);
+    }
+
+    pub fn insert(&self, entity: Entity, components: impl DynamicBundle) {
+        self.buffer().insert(entity, components);
+    }
+
+    pub fn insert_one(&self, entity: Entity, component: impl Component) {
+        self.buffer().insert_one(entity, component);
+    }
+
+    pub fn remove_one<T: Component>(&self, entity: Entity) {
+        self.buffer().remove_one::<T>(entity);
+    }
+
+    pub fn despawn(&self, entity: Entity) {
+        self.buffer().despawn(entity);
+    }
+
+    /// Records an arbitrary change to the world
+    pub fn queue(&self, f: impl FnOnce(&mut World) + Send + Sync + 'static) {
+        self.buffer().queue(f);
+    }
+}
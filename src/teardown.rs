@@ -0,0 +1,86 @@
+use hecs::{Entity, NoSuchEntity, Query, World};
+
+type TeardownFn = Box<dyn FnMut(&mut World, Entity)>;
+
+/// Behaviors that run on an entity right before it is despawned, e.g. to release handles or
+/// notify owners
+///
+/// hecs drops the components of despawned entities without running any behavior, so cleanup has
+/// to happen wherever entities are despawned. Registering it here and despawning through
+/// [`despawn`](Self::despawn) keeps it in one place. Entities despawned through
+/// [`Commands`](crate::Commands) run the teardown behaviors registered with
+/// [`Schedule::add_teardown`](crate::Schedule::add_teardown).
+///
+/// Each teardown behavior is called if the entity matches its query, in registration order.
+/// Entities despawned directly with [`World::despawn`] are not torn down.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, ComponentProvider, ComponentProviderMut, Teardown
+/// };
+/// use std::{cell::RefCell, rc::Rc};
+///
+/// struct SoundHandle(u32);
+///
+/// #[default_trait_impl]
+/// trait ReleaseSound: ComponentProviderMut<SoundHandle> {
+///     fn release_sound(&mut self, released: &mut Vec<u32>) {
+///         let handle: &SoundHandle = self.get();
+///         released.push(handle.0);
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct SoundQuery<'a> {
+///     handle: &'a mut SoundHandle,
+/// }
+///
+/// let released = Rc::new(RefCell::new(Vec::new()));
+/// let mut teardown = Teardown::new();
+/// teardown.add::<SoundQuery, _>({
+///     let released = released.clone();
+///     move |mut item| item.release_sound(&mut released.borrow_mut())
+/// });
+///
+/// let mut world = hecs::World::new();
+/// let explosion = world.spawn((SoundHandle(7),));
+/// teardown.despawn(&mut world, explosion).unwrap();
+///
+/// assert!(!world.contains(explosion));
+/// assert_eq!(*released.borrow(), [7]);
+/// ```
+#[derive(Default)]
+pub struct Teardown {
+    behaviors: Vec<TeardownFn>,
+}
+
+impl Teardown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a behavior that is called for entities matching `Q` before they are despawned
+    pub fn add<Q, F>(&mut self, mut f: F) -> &mut Self
+    where
+        Q: Query,
+        F: for<'q> FnMut(Q::Item<'q>) + 'static,
+    {
+        self.behaviors.push(Box::new(move |world, entity| {
+            if let Ok(item) = world.query_one_mut::<Q>(entity) {
+                f(item);
+            }
+        }));
+        self
+    }
+
+    /// Runs the teardown behaviors matching `entity` and then despawns it
+    pub fn despawn(&mut self, world: &mut World, entity: Entity) -> Result<(), NoSuchEntity> {
+        if !world.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        for behavior in &mut self.behaviors {
+            behavior(world, entity);
+        }
+        world.despawn(entity)
+    }
+}
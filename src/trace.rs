@@ -0,0 +1,183 @@
+use crate::Stage;
+use std::io;
+use std::time::Duration;
+
+/// A record of the order in which a [`Schedule`](crate::Schedule) ran its behaviors and applied
+/// commands, enabled with [`Schedule::set_trace`](crate::Schedule::set_trace)
+///
+/// Each call to [`run`](crate::Schedule::run) or
+/// [`run_with_budget`](crate::Schedule::run_with_budget) is recorded as a [`TraceRun`].
+/// A [`ParallelRunner`](crate::ParallelRunner) records its runs the same way with
+/// [`ParallelRunner::set_trace`](crate::ParallelRunner::set_trace), showing the batches and
+/// lanes its behaviors ran in.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    runs: Vec<TraceRun>,
+}
+
+/// The events of a single run of a [`Schedule`](crate::Schedule) or
+/// [`ParallelRunner`](crate::ParallelRunner)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TraceRun {
+    /// Whether the run completed the frame, rather than stopping when its budget ran out
+    pub complete: bool,
+    /// The events in execution order
+    pub events: Vec<TraceEvent>,
+}
+
+/// An event recorded in a [`TraceRun`]
+///
+/// Offsets are measured from the start of the run.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceEvent {
+    /// A behavior or system was run, or skipped because of its run criteria or because the
+    /// schedule is paused
+    Behavior {
+        name: &'static str,
+        stage: Stage,
+        offset: Duration,
+        duration: Duration,
+        /// The number of entities processed
+        entities: usize,
+        skipped: bool,
+    },
+    /// A behavior was run by a [`ParallelRunner`](crate::ParallelRunner)
+    ParallelBehavior {
+        name: &'static str,
+        /// The index of the batch the behavior ran in
+        batch: usize,
+        /// The thread the behavior ran on, numbered within its batch in the order the threads
+        /// started running behaviors
+        lane: usize,
+        offset: Duration,
+        duration: Duration,
+        /// The number of entities processed
+        entities: usize,
+    },
+    /// Commands recorded during a stage were applied to the world
+    Flush { stage: Stage, offset: Duration },
+}
+
+impl Trace {
+    /// Runs in the order they were recorded
+    pub fn runs(&self) -> &[TraceRun] {
+        &self.runs
+    }
+
+    pub(crate) fn begin_run(&mut self) {
+        self.runs.push(TraceRun::default());
+    }
+
+    pub(crate) fn record(&mut self, event: TraceEvent) {
+        if let Some(run) = self.runs.last_mut() {
+            run.events.push(event);
+        }
+    }
+
+    pub(crate) fn end_run(&mut self, complete: bool) {
+        if let Some(run) = self.runs.last_mut() {
+            run.complete = complete;
+        }
+    }
+
+    /// Writes the trace as a JSON array of runs, with offsets and durations in microseconds
+    ///
+    /// ```
+    /// use hecs_component_provider::Schedule;
+    ///
+    /// let mut schedule = Schedule::new();
+    /// schedule.set_trace(true);
+    /// schedule.add_system("tick", |_| {});
+    /// schedule.run(&mut hecs::World::new());
+    ///
+    /// let mut json = Vec::new();
+    /// schedule.trace().unwrap().write_json(&mut json).unwrap();
+    /// let json = String::from_utf8(json).unwrap();
+    /// assert!(json.starts_with(r#"[{"complete":true,"events":[{"type":"behavior","name":"tick","#));
+    /// ```
+    pub fn write_json(&self, mut writer: impl io::Write) -> io::Result<()> {
+        write!(writer, "[")?;
+        for (i, run) in self.runs.iter().enumerate() {
+            if i > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{{\"complete\":{},\"events\":[", run.complete)?;
+            for (j, event) in run.events.iter().enumerate() {
+                if j > 0 {
+                    write!(writer, ",")?;
+                }
+                match event {
+                    TraceEvent::Behavior {
+                        name,
+                        stage,
+                        offset,
+                        duration,
+                        entities,
+                        skipped,
+                    } => write!(
+                        writer,
+                        "{{\"type\":\"behavior\",\"name\":{},\"stage\":{},\"offset_us\":{},\
+                         \"duration_us\":{},\"entities\":{},\"skipped\":{}}}",
+                        json_string(name),
+                        json_string(&stage_name(*stage)),
+                        offset.as_micros(),
+                        duration.as_micros(),
+                        entities,
+                        skipped
+                    )?,
+                    TraceEvent::ParallelBehavior {
+                        name,
+                        batch,
+                        lane,
+                        offset,
+                        duration,
+                        entities,
+                    } => write!(
+                        writer,
+                        "{{\"type\":\"parallel_behavior\",\"name\":{},\"batch\":{},\"lane\":{},\
+                         \"offset_us\":{},\"duration_us\":{},\"entities\":{}}}",
+                        json_string(name),
+                        batch,
+                        lane,
+                        offset.as_micros(),
+                        duration.as_micros(),
+                        entities
+                    )?,
+                    TraceEvent::Flush { stage, offset } => write!(
+                        writer,
+                        "{{\"type\":\"flush\",\"stage\":{},\"offset_us\":{}}}",
+                        json_string(&stage_name(*stage)),
+                        offset.as_micros()
+                    )?,
+                }
+            }
+            write!(writer, "]}}")?;
+        }
+        writeln!(writer, "]")
+    }
+}
+
+fn stage_name(stage: Stage) -> String {
+    match stage {
+        Stage::Custom(name) => name.to_string(),
+        stage => format!("{:?}", stage),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
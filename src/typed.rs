@@ -0,0 +1,352 @@
+use crate::{Behaviors, ComponentAccess, QueryAccess};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use hecs::{Entity, Query, QueryIter, QueryOneError, World};
+
+/// An entity handle that was validated to satisfy the query `Q`
+///
+/// Storing a `TypedEntity<Q>` instead of a plain [`Entity`] records which components the entity is
+/// expected to have, e.g. "an entity that can run the enemy behaviors". The query item is fetched
+/// again on each access through [`with`](Self::with) or [`with_mut`](Self::with_mut).
+///
+/// Components can still be removed after validation, so access returns an error if the entity was
+/// despawned or no longer satisfies `Q`.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, ComponentProvider, ComponentProviderMut, TypedEntity
+/// };
+///
+/// struct Health(i32);
+///
+/// #[default_trait_impl]
+/// trait TakeDamage: ComponentProviderMut<Health> {
+///     fn take_damage(&mut self, amount: i32) {
+///         let health: &mut Health = self.get_mut();
+///         health.0 -= amount;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct EnemyQuery<'a> {
+///     health: &'a mut Health,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((Health(10),));
+/// let boss = TypedEntity::<EnemyQuery>::new(&world, entity).unwrap();
+///
+/// boss.with_mut(&mut world, |mut enemy| enemy.take_damage(3)).unwrap();
+/// assert_eq!(boss.with(&world, |enemy| enemy.health.0), Ok(7));
+///
+/// let bystander = world.spawn(("bystander",));
+/// assert!(TypedEntity::<EnemyQuery>::new(&world, bystander).is_none());
+/// ```
+pub struct TypedEntity<Q> {
+    entity: Entity,
+    _query: PhantomData<fn() -> Q>,
+}
+
+impl<Q: Query> TypedEntity<Q> {
+    /// Returns a typed handle if `entity` exists and satisfies `Q`
+    pub fn new(world: &World, entity: Entity) -> Option<Self> {
+        if world.satisfies::<Q>(entity) {
+            Some(Self::new_unchecked(entity))
+        } else {
+            None
+        }
+    }
+
+    /// Returns a typed handle without validating `entity`
+    ///
+    /// This is not unsafe, since access is checked again on every fetch.
+    pub fn new_unchecked(entity: Entity) -> Self {
+        TypedEntity {
+            entity,
+            _query: PhantomData,
+        }
+    }
+
+    /// Fetches the query item and passes it to `f`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the fetch would conflict with an existing borrow of the entity's components.
+    pub fn with<R>(
+        &self,
+        world: &World,
+        f: impl FnOnce(Q::Item<'_>) -> R,
+    ) -> Result<R, QueryOneError> {
+        let mut query = world.query_one::<Q>(self.entity);
+        let item = query.get()?;
+        Ok(f(item))
+    }
+
+    /// Fetches the query item from a uniquely borrowed world and passes it to `f`
+    pub fn with_mut<R>(
+        &self,
+        world: &mut World,
+        f: impl FnOnce(Q::Item<'_>) -> R,
+    ) -> Result<R, QueryOneError> {
+        world.query_one_mut::<Q>(self.entity).map(f)
+    }
+}
+
+impl<Q> TypedEntity<Q> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+impl<Q> From<TypedEntity<Q>> for Entity {
+    fn from(typed: TypedEntity<Q>) -> Self {
+        typed.entity
+    }
+}
+
+// implemented manually to avoid requiring the traits on Q
+
+impl<Q> Clone for TypedEntity<Q> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Q> Copy for TypedEntity<Q> {}
+
+impl<Q> PartialEq for TypedEntity<Q> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entity == other.entity
+    }
+}
+
+impl<Q> Eq for TypedEntity<Q> {}
+
+impl<Q> Hash for TypedEntity<Q> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.entity.hash(state);
+    }
+}
+
+impl<Q> fmt::Debug for TypedEntity<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TypedEntity").field(&self.entity).finish()
+    }
+}
+
+/// Extension methods for validating entities against queries and iterating over them
+pub trait WorldExt {
+    /// Returns a typed handle if `entity` satisfies `Q`, or the components it is missing otherwise
+    ///
+    /// ```
+    /// use hecs_component_provider::{ComponentProvider, WorldExt};
+    ///
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    ///
+    /// #[derive(hecs::Query, ComponentProvider)]
+    /// struct MovableQuery<'a> {
+    ///     position: &'a mut Position,
+    ///     velocity: &'a Velocity,
+    /// }
+    ///
+    /// let mut world = hecs::World::new();
+    /// let entity = world.spawn((Position(0.0),));
+    ///
+    /// let error = world.typed::<MovableQuery>(entity).unwrap_err();
+    /// assert_eq!(error.components().len(), 1);
+    /// assert!(error.components()[0].type_name.ends_with("Velocity"));
+    ///
+    /// world.insert_one(entity, Velocity(1.0)).unwrap();
+    /// assert!(world.typed::<MovableQuery>(entity).is_ok());
+    /// ```
+    fn typed<Q: Query + QueryAccess>(
+        &self,
+        entity: Entity,
+    ) -> Result<TypedEntity<Q>, MissingComponents>;
+
+    /// Iterates over the entities matching `Q` together with their query items, on which behavior
+    /// methods can be called directly
+    ///
+    /// A behavior trait can't be a type parameter, so the behavior isn't named here: calling its
+    /// methods on the items requires `Q`'s item to implement it, which is checked at the call.
+    ///
+    /// ```
+    /// use hecs_component_provider::{
+    ///     default_trait_impl, ComponentProvider, ComponentProviderMut, WorldExt
+    /// };
+    ///
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    ///
+    /// #[default_trait_impl]
+    /// trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+    ///     fn apply_velocity(&mut self, dt: f32) {
+    ///         let &Velocity(v) = self.get();
+    ///         let position: &mut Position = self.get_mut();
+    ///         position.0 += v * dt;
+    ///     }
+    /// }
+    ///
+    /// #[derive(hecs::Query, ComponentProvider)]
+    /// struct MovableQuery<'a> {
+    ///     position: &'a mut Position,
+    ///     velocity: &'a Velocity,
+    /// }
+    ///
+    /// let mut world = hecs::World::new();
+    /// let entity = world.spawn((Position(0.0), Velocity(2.0)));
+    ///
+    /// for (e, mut b) in world.iter_behavior::<MovableQuery>() {
+    ///     b.apply_velocity(0.5);
+    ///     assert_eq!(e, entity);
+    /// }
+    /// assert_eq!(world.get::<&Position>(entity).unwrap().0, 1.0);
+    /// ```
+    fn iter_behavior<Q: Query>(&mut self) -> QueryIter<'_, (Entity, Q)>;
+
+    /// Borrows the query items of the entities matching `Q` as an [`IntoIterator`], see
+    /// [`Behaviors`]
+    fn behaviors<Q: Query>(&mut self) -> Behaviors<'_, Q>;
+
+    /// Lists the entities that have some but not all of the components required by `Q`, with the
+    /// components each of them is missing
+    ///
+    /// These entities are skipped by behaviors running on `Q`, which is often unintended after a
+    /// component was renamed or removed from a spawn function. Components that are optional in
+    /// `Q` aren't considered. The component types of each archetype are checked once, so the cost
+    /// depends on the number of archetypes and near misses rather than the size of the world.
+    ///
+    /// ```
+    /// use hecs_component_provider::{ComponentProvider, WorldExt};
+    ///
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    ///
+    /// #[derive(hecs::Query, ComponentProvider)]
+    /// struct MovableQuery<'a> {
+    ///     position: &'a mut Position,
+    ///     velocity: &'a Velocity,
+    /// }
+    ///
+    /// let mut world = hecs::World::new();
+    /// world.spawn((Position(0.0), Velocity(1.0)));
+    /// let stuck = world.spawn((Position(0.0),));
+    /// world.spawn(("unrelated",));
+    ///
+    /// let near_misses = world.near_misses::<MovableQuery>();
+    /// assert_eq!(near_misses.len(), 1);
+    /// assert_eq!(near_misses[0].entity(), stuck);
+    /// assert_eq!(
+    ///     near_misses[0].to_string(),
+    ///     format!(
+    ///         "entity {:?} does not satisfy the query, missing `{}`",
+    ///         stuck,
+    ///         std::any::type_name::<Velocity>()
+    ///     )
+    /// );
+    /// ```
+    fn near_misses<Q: QueryAccess>(&self) -> Vec<MissingComponents>;
+}
+
+impl WorldExt for World {
+    fn typed<Q: Query + QueryAccess>(
+        &self,
+        entity: Entity,
+    ) -> Result<TypedEntity<Q>, MissingComponents> {
+        if self.satisfies::<Q>(entity) {
+            return Ok(TypedEntity::new_unchecked(entity));
+        }
+
+        let required = Q::access().into_iter().filter(|a| a.required);
+        let (exists, components) = match self.entity(entity) {
+            Ok(entity_ref) => (
+                true,
+                required
+                    .filter(|a| !entity_ref.component_types().any(|t| t == a.type_id))
+                    .collect(),
+            ),
+            Err(_) => (false, required.collect()),
+        };
+        Err(MissingComponents {
+            entity,
+            exists,
+            components,
+        })
+    }
+
+    fn iter_behavior<Q: Query>(&mut self) -> QueryIter<'_, (Entity, Q)> {
+        self.query_mut::<(Entity, Q)>().into_iter()
+    }
+
+    fn behaviors<Q: Query>(&mut self) -> Behaviors<'_, Q> {
+        Behaviors::new(self)
+    }
+
+    fn near_misses<Q: QueryAccess>(&self) -> Vec<MissingComponents> {
+        let required: Vec<_> = Q::access().into_iter().filter(|a| a.required).collect();
+        let mut near_misses = Vec::new();
+        // entities of an archetype share their component types, so each archetype is only
+        // checked once
+        for archetype in self.archetypes().filter(|a| !a.is_empty()) {
+            let (present, missing): (Vec<_>, Vec<_>) = required
+                .iter()
+                .partition(|a| archetype.has_dynamic(a.type_id));
+            if present.is_empty() || missing.is_empty() {
+                continue;
+            }
+            let missing: Vec<_> = missing.into_iter().copied().collect();
+            near_misses.extend(archetype.ids().iter().map(|&id| MissingComponents {
+                // SAFETY: the ids of an archetype belong to the live entities stored in it
+                entity: unsafe { self.find_entity_from_id(id) },
+                exists: true,
+                components: missing.clone(),
+            }));
+        }
+        near_misses
+    }
+}
+
+/// The error returned by [`WorldExt::typed`] when an entity doesn't satisfy a query
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MissingComponents {
+    entity: Entity,
+    exists: bool,
+    components: Vec<ComponentAccess>,
+}
+
+impl MissingComponents {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Whether the entity exists in the world
+    pub fn exists(&self) -> bool {
+        self.exists
+    }
+
+    /// The required components that the entity lacks
+    ///
+    /// This may be empty if the query failed on a condition that isn't a single required
+    /// component, such as both sides of an [`Or`](hecs::Or) being absent.
+    pub fn components(&self) -> &[ComponentAccess] {
+        &self.components
+    }
+}
+
+impl fmt::Display for MissingComponents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.exists {
+            return write!(f, "entity {:?} does not exist", self.entity);
+        }
+        write!(f, "entity {:?} does not satisfy the query", self.entity)?;
+        for (i, component) in self.components.iter().enumerate() {
+            let separator = if i == 0 { ", missing " } else { ", " };
+            write!(f, "{}`{}`", separator, component.type_name)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingComponents {}
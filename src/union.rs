@@ -0,0 +1,330 @@
+use crate::ComponentAccess;
+use core::any::TypeId;
+
+/// Generate an enum query matching entities that satisfy any of several queries
+///
+/// Each variant wraps the item of a query struct with a single lifetime. An entity matching more
+/// than one of the queries is yielded as the first matching variant, and only that query's
+/// components are borrowed, so the queries may borrow the same components mutably.
+///
+/// The enum implements each provider trait for the components provided by all of its variants,
+/// so behaviors that only need the shared components can be called on it directly, while the
+/// variants remain available for kind-specific logic. [`QueryAccess`](crate::QueryAccess) reports
+/// the components of every variant, which are required only if every variant requires them.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, gen_union_query, ComponentProvider, ComponentProviderMut
+/// };
+///
+/// struct Position(f32);
+/// struct Score(u32);
+/// struct Aggression(f32);
+///
+/// #[default_trait_impl]
+/// trait Drift: ComponentProviderMut<Position> {
+///     fn drift(&mut self) {
+///         let position: &mut Position = self.get_mut();
+///         position.0 += 1.0;
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct PlayerQuery<'a> {
+///     position: &'a mut Position,
+///     score: &'a Score,
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MonsterQuery<'a> {
+///     position: &'a mut Position,
+///     aggression: &'a Aggression,
+/// }
+///
+/// gen_union_query!(Actor, Player => PlayerQuery, Monster => MonsterQuery);
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Position(0.0), Score(10)));
+/// world.spawn((Position(0.0), Aggression(0.5)));
+/// world.spawn((Position(0.0),));
+///
+/// let mut monsters = 0;
+/// for mut actor in world.query_mut::<Actor>() {
+///     actor.drift();
+///     if let Actor::Monster(_) = actor {
+///         monsters += 1;
+///     }
+/// }
+/// assert_eq!(monsters, 1);
+/// assert_eq!(world.query_mut::<&Position>().into_iter().filter(|p| p.0 == 1.0).count(), 2);
+/// ```
+#[macro_export]
+macro_rules! gen_union_query {
+    (
+        $vis:vis $name:ident,
+        $first_variant:ident => $first_query:ident
+        $(, $variant:ident => $query:ident)* $(,)?
+    ) => {
+        $vis enum $name<'a> {
+            $first_variant($first_query<'a>),
+            $($variant($query<'a>),)*
+        }
+
+        const _: () = {
+            use $crate::hecs::{Access, Archetype, EntityMeta, Fetch, Query};
+
+            #[derive(Clone)]
+            pub enum UnionFetch {
+                $first_variant(<$first_query<'static> as Query>::Fetch),
+                $($variant(<$query<'static> as Query>::Fetch),)*
+            }
+
+            #[derive(Clone, Copy)]
+            pub enum UnionState {
+                $first_variant(<<$first_query<'static> as Query>::Fetch as Fetch>::State),
+                $($variant(<<$query<'static> as Query>::Fetch as Fetch>::State),)*
+            }
+
+            unsafe impl Fetch for UnionFetch {
+                type State = UnionState;
+
+                fn dangling() -> Self {
+                    UnionFetch::$first_variant(
+                        <<$first_query<'static> as Query>::Fetch as Fetch>::dangling(),
+                    )
+                }
+
+                fn access(archetype: &Archetype) -> Option<Access> {
+                    // consistent with `prepare`, which selects the first matching query
+                    <<$first_query<'static> as Query>::Fetch as Fetch>::access(archetype)
+                        $(.or_else(|| <<$query<'static> as Query>::Fetch as Fetch>::access(archetype)))*
+                }
+
+                fn borrow(archetype: &Archetype, state: Self::State) {
+                    match state {
+                        UnionState::$first_variant(state) => {
+                            <<$first_query<'static> as Query>::Fetch as Fetch>::borrow(archetype, state)
+                        }
+                        $(UnionState::$variant(state) => {
+                            <<$query<'static> as Query>::Fetch as Fetch>::borrow(archetype, state)
+                        })*
+                    }
+                }
+
+                fn prepare(archetype: &Archetype) -> Option<Self::State> {
+                    if let Some(state) =
+                        <<$first_query<'static> as Query>::Fetch as Fetch>::prepare(archetype)
+                    {
+                        return Some(UnionState::$first_variant(state));
+                    }
+                    $(
+                        if let Some(state) =
+                            <<$query<'static> as Query>::Fetch as Fetch>::prepare(archetype)
+                        {
+                            return Some(UnionState::$variant(state));
+                        }
+                    )*
+                    None
+                }
+
+                fn execute(archetype: &Archetype, state: Self::State) -> Self {
+                    match state {
+                        UnionState::$first_variant(state) => UnionFetch::$first_variant(
+                            <<$first_query<'static> as Query>::Fetch as Fetch>::execute(archetype, state),
+                        ),
+                        $(UnionState::$variant(state) => UnionFetch::$variant(
+                            <<$query<'static> as Query>::Fetch as Fetch>::execute(archetype, state),
+                        ),)*
+                    }
+                }
+
+                fn release(archetype: &Archetype, state: Self::State) {
+                    match state {
+                        UnionState::$first_variant(state) => {
+                            <<$first_query<'static> as Query>::Fetch as Fetch>::release(archetype, state)
+                        }
+                        $(UnionState::$variant(state) => {
+                            <<$query<'static> as Query>::Fetch as Fetch>::release(archetype, state)
+                        })*
+                    }
+                }
+
+                fn for_each_borrow(f: impl FnMut(::core::any::TypeId, bool)) {
+                    let mut branches = ::std::vec::Vec::new();
+                    let mut branch = ::std::vec::Vec::new();
+                    <<$first_query<'static> as Query>::Fetch as Fetch>::for_each_borrow(
+                        |id, unique| branch.push((id, unique)),
+                    );
+                    branches.push(branch);
+                    $(
+                        let mut branch = ::std::vec::Vec::new();
+                        <<$query<'static> as Query>::Fetch as Fetch>::for_each_borrow(
+                            |id, unique| branch.push((id, unique)),
+                        );
+                        branches.push(branch);
+                    )*
+                    $crate::union_borrows(branches, f);
+                }
+            }
+
+            impl<'a> Query for $name<'a> {
+                type Item<'q> = $name<'q>;
+                type Fetch = UnionFetch;
+
+                unsafe fn get<'q>(meta: &[EntityMeta], fetch: &Self::Fetch, n: usize) -> $name<'q> {
+                    match fetch {
+                        UnionFetch::$first_variant(fetch) => $name::$first_variant(
+                            <$first_query<'static> as Query>::get(meta, fetch, n),
+                        ),
+                        $(UnionFetch::$variant(fetch) => $name::$variant(
+                            <$query<'static> as Query>::get(meta, fetch, n),
+                        ),)*
+                    }
+                }
+            }
+
+            impl<'a> $crate::QueryAccess for $name<'a>
+            where
+                $first_query<'a>: $crate::QueryAccess,
+                $($query<'a>: $crate::QueryAccess,)*
+            {
+                fn access() -> ::std::vec::Vec<$crate::ComponentAccess> {
+                    $crate::union_access(::std::vec![
+                        <$first_query<'a> as $crate::QueryAccess>::access(),
+                        $(<$query<'a> as $crate::QueryAccess>::access(),)*
+                    ])
+                }
+            }
+
+            impl<'a, C: ?Sized> $crate::ComponentProvider<C> for $name<'a>
+            where
+                $first_query<'a>: $crate::ComponentProvider<C>,
+                $($query<'a>: $crate::ComponentProvider<C>,)*
+            {
+                #[inline]
+                fn get(&self) -> &C {
+                    match self {
+                        $name::$first_variant(item) => $crate::ComponentProvider::<C>::get(item),
+                        $($name::$variant(item) => $crate::ComponentProvider::<C>::get(item),)*
+                    }
+                }
+            }
+
+            impl<'a, C: ?Sized> $crate::ComponentProviderMut<C> for $name<'a>
+            where
+                $first_query<'a>: $crate::ComponentProviderMut<C>,
+                $($query<'a>: $crate::ComponentProviderMut<C>,)*
+            {
+                #[inline]
+                fn get_mut(&mut self) -> &mut C {
+                    match self {
+                        $name::$first_variant(item) => $crate::ComponentProviderMut::<C>::get_mut(item),
+                        $($name::$variant(item) => $crate::ComponentProviderMut::<C>::get_mut(item),)*
+                    }
+                }
+
+                #[inline]
+                fn get_mut_checked(&mut self) -> $crate::CheckedMut<'_, C> {
+                    match self {
+                        $name::$first_variant(item) => {
+                            $crate::ComponentProviderMut::<C>::get_mut_checked(item)
+                        }
+                        $($name::$variant(item) => {
+                            $crate::ComponentProviderMut::<C>::get_mut_checked(item)
+                        })*
+                    }
+                }
+            }
+
+            impl<'a, C: ?Sized> $crate::ComponentProviderOptional<C> for $name<'a>
+            where
+                $first_query<'a>: $crate::ComponentProviderOptional<C>,
+                $($query<'a>: $crate::ComponentProviderOptional<C>,)*
+            {
+                #[inline]
+                fn get_optional(&self) -> ::core::option::Option<&C> {
+                    match self {
+                        $name::$first_variant(item) => {
+                            $crate::ComponentProviderOptional::<C>::get_optional(item)
+                        }
+                        $($name::$variant(item) => {
+                            $crate::ComponentProviderOptional::<C>::get_optional(item)
+                        })*
+                    }
+                }
+            }
+
+            impl<'a, C: ?Sized> $crate::ComponentProviderOptionalMut<C> for $name<'a>
+            where
+                $first_query<'a>: $crate::ComponentProviderOptionalMut<C>,
+                $($query<'a>: $crate::ComponentProviderOptionalMut<C>,)*
+            {
+                #[inline]
+                fn get_optional_mut(&mut self) -> ::core::option::Option<&mut C> {
+                    match self {
+                        $name::$first_variant(item) => {
+                            $crate::ComponentProviderOptionalMut::<C>::get_optional_mut(item)
+                        }
+                        $($name::$variant(item) => {
+                            $crate::ComponentProviderOptionalMut::<C>::get_optional_mut(item)
+                        })*
+                    }
+                }
+            }
+        };
+    };
+}
+
+/// Reports the borrows of a union query's variants to `f`, for hecs's check that a query doesn't
+/// borrow a component uniquely more than once
+///
+/// Only one variant is borrowed for each archetype, so a component borrowed by several variants
+/// is reported once, as unique if any variant borrows it uniquely. Repeated borrows within a
+/// variant are still reported.
+#[doc(hidden)]
+pub fn union_borrows(branches: Vec<Vec<(TypeId, bool)>>, mut f: impl FnMut(TypeId, bool)) {
+    for (i, branch) in branches.iter().enumerate() {
+        for &(id, unique) in branch {
+            let earlier = branches[..i]
+                .iter()
+                .flatten()
+                .any(|&(other, _)| other == id);
+            if earlier {
+                continue;
+            }
+            let unique = unique
+                || branches[i + 1..]
+                    .iter()
+                    .flatten()
+                    .any(|&(other, unique)| other == id && unique);
+            f(id, unique);
+        }
+    }
+}
+
+/// Combines the accesses of a union query's variants
+///
+/// A component is accessed mutably if any variant does, and is required only if every variant
+/// requires it.
+#[doc(hidden)]
+pub fn union_access(branches: Vec<Vec<ComponentAccess>>) -> Vec<ComponentAccess> {
+    let mut merged: Vec<ComponentAccess> = Vec::new();
+    for branch in &branches {
+        for access in branch {
+            let required = branches
+                .iter()
+                .all(|b| b.iter().any(|a| a.type_id == access.type_id && a.required));
+            match merged.iter_mut().find(|a| a.type_id == access.type_id) {
+                Some(existing) => {
+                    existing.mutable |= access.mutable;
+                    existing.interior_mutable |= access.interior_mutable;
+                }
+                None => merged.push(ComponentAccess {
+                    required,
+                    ..*access
+                }),
+            }
+        }
+    }
+    merged
+}
@@ -0,0 +1,74 @@
+use core::fmt;
+use std::error::Error;
+
+/// Checks invariants across the components of a provider
+///
+/// This is implemented by `derive(ComponentProvider)` for structs marked
+/// `#[provider(validate = "path::to::fn")]`, where the function takes `&Self`, or any provider
+/// through a generic parameter, and returns `Result<(), ValidationError>`. Calling it after
+/// behaviors have run, e.g. in a `debug_assert!`, catches components that have become
+/// inconsistent with each other.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, Validate, ValidationError};
+///
+/// struct Health(i32);
+/// struct MaxHealth(i32);
+///
+/// fn check_health<P>(item: &P) -> Result<(), ValidationError>
+/// where
+///     P: ComponentProvider<Health> + ComponentProvider<MaxHealth>,
+/// {
+///     let &Health(health) = item.get();
+///     let &MaxHealth(max) = item.get();
+///     if health > max {
+///         return Err(ValidationError::new(format!("health {} exceeds max {}", health, max)));
+///     }
+///     Ok(())
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// #[provider(validate = "check_health")]
+/// struct HealthQuery<'a> {
+///     health: &'a mut Health,
+///     max_health: &'a MaxHealth,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// world.spawn((Health(5), MaxHealth(10)));
+///
+/// for item in world.query_mut::<HealthQuery>() {
+///     item.health.0 += 10;
+///     let error = item.validate().unwrap_err();
+///     assert_eq!(error.to_string(), "health 15 exceeds max 10");
+/// }
+/// ```
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationError>;
+}
+
+/// The error returned when a [`Validate`] check fails
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidationError {
+    message: String,
+}
+
+impl ValidationError {
+    pub fn new(message: impl Into<String>) -> Self {
+        ValidationError {
+            message: message.into(),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for ValidationError {}
@@ -0,0 +1,269 @@
+use crate::debug_borrows::{self, TrackedBorrow};
+use crate::{
+    CheckedMut, ComponentProvider, ComponentProviderMut, ComponentProviderOptional,
+    ComponentProviderOptionalMut,
+};
+use core::any::TypeId;
+use core::cell::RefCell;
+use hecs::{Component, Entity, World};
+
+/// Wraps a query item so that optional components it lacks are looked up in the world
+///
+/// The wrapped provider's [`ComponentProvider`] and [`ComponentProviderMut`] implementations are
+/// forwarded, while [`ComponentProviderOptional`] is implemented for every component type by
+/// fetching it from the entity in the world on first access. This lets behaviors use rarely needed
+/// components without adding them to every query.
+///
+/// Fetched components stay borrowed until the wrapper is dropped.
+///
+/// # Panics
+///
+/// Optional access panics if the component is already borrowed mutably, e.g. by the wrapped query.
+/// With the `debug-borrows` feature, a conflicting borrow of the same component of the same entity
+/// through a [`DynWorldProvider`](crate::DynWorldProvider) panics with the locations of both
+/// borrows.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, ComponentProvider, ComponentProviderMut, ComponentProviderOptional,
+///     WorldFallback
+/// };
+///
+/// struct Health(i32);
+/// struct Armor(i32);
+///
+/// #[default_trait_impl]
+/// trait TakeDamage: ComponentProviderMut<Health> + ComponentProviderOptional<Armor> {
+///     fn take_damage(&mut self, amount: i32) {
+///         let armor = self.get_optional().map_or(0, |a: &Armor| a.0);
+///         let health: &mut Health = self.get_mut();
+///         health.0 -= (amount - armor).max(0);
+///     }
+/// }
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct HealthQuery<'a> {
+///     health: &'a mut Health,
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let entity = world.spawn((Health(10), Armor(3)));
+///
+/// for (entity, item) in world.query::<(hecs::Entity, HealthQuery)>().iter() {
+///     let mut item = WorldFallback::new(&world, entity, item);
+///     item.take_damage(5);
+///     assert_eq!(item.item().health.0, 8);
+/// }
+/// ```
+pub struct WorldFallback<'w, P> {
+    item: P,
+    world: &'w World,
+    entity: Entity,
+    borrows: RefCell<Vec<FallbackBorrow<'w>>>,
+}
+
+struct FallbackBorrow<'w> {
+    type_id: TypeId,
+    ptr: *const u8,
+    // keeps the dynamic borrow of the component alive
+    _guard: Box<dyn Guard + 'w>,
+    _tracked: TrackedBorrow,
+}
+
+trait Guard {}
+impl<T> Guard for T {}
+
+impl<'w, P> WorldFallback<'w, P> {
+    pub fn new(world: &'w World, entity: Entity, item: P) -> Self {
+        WorldFallback {
+            item,
+            world,
+            entity,
+            borrows: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn world(&self) -> &'w World {
+        self.world
+    }
+
+    pub fn item(&self) -> &P {
+        &self.item
+    }
+
+    pub fn item_mut(&mut self) -> &mut P {
+        &mut self.item
+    }
+
+    pub fn into_inner(self) -> P {
+        self.item
+    }
+}
+
+impl<'w, C: ?Sized, P> ComponentProvider<C> for WorldFallback<'w, P>
+where
+    P: ComponentProvider<C>,
+{
+    fn get(&self) -> &C {
+        self.item.get()
+    }
+}
+
+impl<'w, C: ?Sized, P> ComponentProviderMut<C> for WorldFallback<'w, P>
+where
+    P: ComponentProviderMut<C>,
+{
+    fn get_mut(&mut self) -> &mut C {
+        self.item.get_mut()
+    }
+
+    fn get_mut_checked(&mut self) -> CheckedMut<'_, C> {
+        self.item.get_mut_checked()
+    }
+}
+
+impl<'w, C: Component, P> ComponentProviderOptional<C> for WorldFallback<'w, P> {
+    #[track_caller]
+    fn get_optional(&self) -> Option<&C> {
+        let type_id = TypeId::of::<C>();
+        let mut borrows = self.borrows.borrow_mut();
+        let ptr = match borrows.iter().find(|b| b.type_id == type_id) {
+            Some(borrow) => borrow.ptr,
+            None => {
+                let tracked = debug_borrows::track(
+                    self.world,
+                    self.entity,
+                    type_id,
+                    core::any::type_name::<C>(),
+                    false,
+                );
+                let guard = self.world.get::<&C>(self.entity).ok()?;
+                let ptr = &*guard as *const C as *const u8;
+                borrows.push(FallbackBorrow {
+                    type_id,
+                    ptr,
+                    _guard: Box::new(guard),
+                    _tracked: tracked,
+                });
+                ptr
+            }
+        };
+        // SAFETY: the component is kept borrowed by its guard until self is dropped, and the world
+        // can't be structurally modified while it is shared with self
+        Some(unsafe { &*(ptr as *const C) })
+    }
+}
+
+/// An entity together with exclusive access to its world, which provides every component of the
+/// entity by looking it up at runtime
+///
+/// This lets behaviors be called outside of queries, e.g. in event handlers. All of the provider
+/// traits are implemented for every component type: the optional providers return `None` if the
+/// entity doesn't have the component, while [`ComponentProvider::get`] and
+/// [`ComponentProviderMut::get_mut`] panic.
+///
+/// The world is borrowed mutably for as long as the `WorldEntity` exists, so the borrow checker
+/// prevents components from being accessed through the world while they are provided, and
+/// behaviors follow the usual rules: any number of components can be borrowed immutably at once,
+/// but a mutable borrow requires exclusive access to the `WorldEntity`. Each access costs a lookup
+/// of the entity and its component.
+///
+/// # Panics
+///
+/// [`ComponentProvider::get`] and [`ComponentProviderMut::get_mut`] panic if the entity has been
+/// despawned or doesn't have the component.
+///
+/// ```
+/// use hecs_component_provider::{
+///     default_trait_impl, ComponentProvider, ComponentProviderMut, ComponentProviderOptional,
+///     WorldEntity
+/// };
+///
+/// struct Ammo(u32);
+/// struct Silenced;
+///
+/// #[default_trait_impl]
+/// trait Shoot: ComponentProviderMut<Ammo> + ComponentProviderOptional<Silenced> {
+///     fn shoot(&mut self) -> bool {
+///         let ammo: &mut Ammo = self.get_mut();
+///         ammo.0 -= 1;
+///         ComponentProviderOptional::<Silenced>::get_optional(self).is_none()
+///     }
+/// }
+///
+/// let mut world = hecs::World::new();
+/// let gun = world.spawn((Ammo(3),));
+///
+/// // e.g. in an input event handler
+/// let loud = WorldEntity::new(&mut world, gun).shoot();
+/// assert!(loud);
+/// assert_eq!(world.get::<&Ammo>(gun).unwrap().0, 2);
+/// ```
+pub struct WorldEntity<'w> {
+    // private, so that components can't be borrowed through the world while they are provided
+    world: &'w mut World,
+    entity: Entity,
+}
+
+impl<'w> WorldEntity<'w> {
+    pub fn new(world: &'w mut World, entity: Entity) -> Self {
+        WorldEntity { world, entity }
+    }
+
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.world
+    }
+
+    pub fn into_world(self) -> &'w mut World {
+        self.world
+    }
+
+    fn missing<C>(&self) -> ! {
+        panic!(
+            "entity {:?} doesn't have the component `{}`",
+            self.entity,
+            core::any::type_name::<C>()
+        )
+    }
+}
+
+impl<'w, C: Component> ComponentProvider<C> for WorldEntity<'w> {
+    fn get(&self) -> &C {
+        self.get_optional().unwrap_or_else(|| self.missing::<C>())
+    }
+}
+
+impl<'w, C: Component> ComponentProviderMut<C> for WorldEntity<'w> {
+    fn get_mut(&mut self) -> &mut C {
+        if self.world.satisfies::<&C>(self.entity) {
+            return self.get_optional_mut().unwrap();
+        }
+        self.missing::<C>()
+    }
+}
+
+impl<'w, C: Component> ComponentProviderOptional<C> for WorldEntity<'w> {
+    fn get_optional(&self) -> Option<&C> {
+        let guard = self.world.get::<&C>(self.entity).ok()?;
+        let ptr = &*guard as *const C;
+        drop(guard);
+        // SAFETY: the world is borrowed mutably by self and can't be reached through a shared
+        // reference to it, so the component can't be borrowed mutably or moved while the returned
+        // reference is alive
+        Some(unsafe { &*ptr })
+    }
+}
+
+impl<'w, C: Component> ComponentProviderOptionalMut<C> for WorldEntity<'w> {
+    fn get_optional_mut(&mut self) -> Option<&mut C> {
+        self.world.query_one_mut::<&mut C>(self.entity).ok()
+    }
+}
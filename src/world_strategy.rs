@@ -0,0 +1,224 @@
+use crate::{ComponentAccess, QueryAccess};
+use core::any::TypeId;
+use core::fmt;
+use hecs::{Component, EntityBuilder, World};
+use proptest::collection::{vec, SizeRange};
+use proptest::prelude::*;
+
+/// Generates random worlds for property tests, containing entities that match given queries
+/// alongside noise entities with random sets of components
+///
+/// Each component type that may appear is registered with a strategy for its values. Entities
+/// generated for a query have every component the query requires and a random subset of the
+/// other registered components, so the property is exercised across many archetypes. Noise
+/// entities have a random subset of all registered components, and may match the query by
+/// chance. Entities are spawned in random order.
+///
+/// ```
+/// use hecs_component_provider::{ComponentProvider, WorldStrategy};
+/// use proptest::prelude::*;
+///
+/// #[derive(Clone, Debug)]
+/// struct Position(i32);
+/// #[derive(Clone, Debug)]
+/// struct Velocity(i32);
+/// #[derive(Clone, Debug)]
+/// struct Frozen;
+///
+/// #[derive(hecs::Query, ComponentProvider)]
+/// struct MovableQuery<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+/// }
+///
+/// let worlds = WorldStrategy::new()
+///     .component((-100..100).prop_map(Position))
+///     .component((-10..10).prop_map(Velocity))
+///     .component(Just(Frozen))
+///     .matching::<MovableQuery>(3..10)
+///     .noise(0..5)
+///     .strategy();
+///
+/// proptest!(|(generated in worlds)| {
+///     let mut world = generated.build();
+///     prop_assert!(world.query_mut::<MovableQuery>().into_iter().count() >= 3);
+/// });
+/// ```
+pub struct WorldStrategy {
+    components: Vec<ComponentStrategy>,
+    // the required component types and number of entities of each group of matching entities
+    matching: Vec<(Vec<ComponentAccess>, SizeRange)>,
+    noise: SizeRange,
+}
+
+struct ComponentStrategy {
+    type_id: TypeId,
+    values: BoxedStrategy<GeneratedComponent>,
+}
+
+impl Default for WorldStrategy {
+    fn default() -> Self {
+        WorldStrategy {
+            components: Vec::new(),
+            matching: Vec::new(),
+            noise: SizeRange::from(0),
+        }
+    }
+}
+
+impl WorldStrategy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the strategy generating values of component `T`, replacing any previously
+    /// registered for it
+    pub fn component<T: Component + Clone + fmt::Debug>(
+        mut self,
+        values: impl Strategy<Value = T> + 'static,
+    ) -> Self {
+        let type_id = TypeId::of::<T>();
+        self.components.retain(|c| c.type_id != type_id);
+        self.components.push(ComponentStrategy {
+            type_id,
+            values: values
+                .prop_map(|value| GeneratedComponent(Box::new(value)))
+                .boxed(),
+        });
+        self
+    }
+
+    /// Adds `count` entities matching `Q`
+    pub fn matching<Q: QueryAccess>(self, count: impl Into<SizeRange>) -> Self {
+        self.matching_access(Q::access(), count)
+    }
+
+    /// Adds `count` entities that have every component required by `access`, e.g. the access of a
+    /// [`BehaviorInfo`](crate::BehaviorInfo)
+    pub fn matching_access(
+        mut self,
+        access: impl IntoIterator<Item = ComponentAccess>,
+        count: impl Into<SizeRange>,
+    ) -> Self {
+        let required = access.into_iter().filter(|a| a.required).collect();
+        self.matching.push((required, count.into()));
+        self
+    }
+
+    /// Sets the number of noise entities, which is zero by default
+    pub fn noise(mut self, count: impl Into<SizeRange>) -> Self {
+        self.noise = count.into();
+        self
+    }
+
+    /// The strategy generating worlds
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component required by a matching query has no registered strategy.
+    pub fn strategy(&self) -> BoxedStrategy<GeneratedWorld> {
+        let mut groups = Vec::new();
+        for (required, count) in &self.matching {
+            if let Some(missing) = required
+                .iter()
+                .find(|a| !self.components.iter().any(|c| c.type_id == a.type_id))
+            {
+                panic!(
+                    "component `{}` is required by a matching query but has no registered strategy",
+                    missing.type_name
+                );
+            }
+            let required: Vec<_> = required.iter().map(|a| a.type_id).collect();
+            groups.push(vec(self.entity(&required), count.clone()).boxed());
+        }
+        groups.push(vec(self.entity(&[]), self.noise.clone()).boxed());
+
+        groups
+            .prop_map(|groups| groups.into_iter().flatten().collect::<Vec<_>>())
+            .prop_shuffle()
+            .prop_map(|entities| GeneratedWorld { entities })
+            .boxed()
+    }
+
+    // an entity with the required components and a random subset of the others
+    fn entity(&self, required: &[TypeId]) -> BoxedStrategy<Vec<GeneratedComponent>> {
+        let components: Vec<_> = self
+            .components
+            .iter()
+            .map(|c| {
+                if required.contains(&c.type_id) {
+                    c.values.clone().prop_map(Some).boxed()
+                } else {
+                    proptest::option::of(c.values.clone()).boxed()
+                }
+            })
+            .collect();
+        components
+            .prop_map(|components| components.into_iter().flatten().collect())
+            .boxed()
+    }
+}
+
+/// A world generated by a [`WorldStrategy`], which can be spawned with [`build`](Self::build)
+#[derive(Clone, Debug)]
+pub struct GeneratedWorld {
+    entities: Vec<Vec<GeneratedComponent>>,
+}
+
+impl GeneratedWorld {
+    /// Spawns the generated entities into a new world
+    pub fn build(&self) -> World {
+        let mut world = World::new();
+        let mut builder = EntityBuilder::new();
+        for entity in &self.entities {
+            for component in entity {
+                component.0.add_to(&mut builder);
+            }
+            world.spawn(builder.build());
+        }
+        world
+    }
+
+    /// The number of generated entities
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+struct GeneratedComponent(Box<dyn AnyComponent>);
+
+trait AnyComponent: 'static {
+    fn add_to(&self, builder: &mut EntityBuilder);
+    fn clone_box(&self) -> Box<dyn AnyComponent>;
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+impl<T: Component + Clone + fmt::Debug> AnyComponent for T {
+    fn add_to(&self, builder: &mut EntityBuilder) {
+        builder.add(self.clone());
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyComponent> {
+        Box::new(self.clone())
+    }
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl Clone for GeneratedComponent {
+    fn clone(&self) -> Self {
+        GeneratedComponent(self.0.clone_box())
+    }
+}
+
+impl fmt::Debug for GeneratedComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
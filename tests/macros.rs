@@ -34,10 +34,8 @@ fn query_component_provider_basic_test() {
 
     let mut world = World::new();
     let id = world.spawn((123, MyComponent(456), "abc"));
-    let mut query = world
-        .query_one::<MyQuery>(id)
-        .expect("Entity should be returned");
-    let entity = query.get().unwrap();
+    let mut query = world.query_one::<MyQuery>(id);
+    let entity = query.get().expect("Entity should be returned");
 
     let integer: &i32 = entity.get();
     assert_eq!(*integer, 123);
@@ -95,7 +93,7 @@ fn query_component_provider_complex_test() {
 
     let mut query = world.query::<MyQuery>();
     let mut query_iter = query.iter();
-    let (_, mut entity) = query_iter
+    let mut entity = query_iter
         .next()
         .expect("At least one entity should be returned");
     assert!(
@@ -187,7 +185,7 @@ fn gen_tuple_query_component_providers_test() {
 
     let mut query = world.query::<MyQuery>();
     let mut query_iter = query.iter();
-    let (_, mut entity) = query_iter
+    let mut entity = query_iter
         .next()
         .expect("At least one entity should be returned");
     assert!(
@@ -213,3 +211,2024 @@ fn gen_tuple_query_component_providers_test() {
     let string: &&str = entity.get();
     assert_eq!(*string, "abc");
 }
+
+#[test]
+fn gen_composed_component_providers_test() {
+    use hecs_component_provider::{
+        gen_composed_component_providers, ComponentProvider, ComponentProviderMut,
+    };
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct MyComponent(i32);
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    struct Bundle {
+        integer: i32,
+        component: MyComponent,
+    }
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    struct Resources {
+        string: String,
+    }
+
+    gen_composed_component_providers!(Composed(Bundle, Resources) {
+        0 => [mut i32, MyComponent],
+        1 => [String],
+    });
+
+    let mut entity = Composed(
+        Bundle {
+            integer: 123,
+            component: MyComponent(456),
+        },
+        Resources {
+            string: "abc".to_string(),
+        },
+    );
+
+    let integer: &mut i32 = entity.get_mut();
+    *integer += 1;
+    assert_eq!(entity.0.integer, 124);
+
+    let component: &MyComponent = entity.get();
+    assert_eq!(*component, MyComponent(456));
+
+    let string: &String = entity.get();
+    assert_eq!(*string, "abc");
+}
+
+#[test]
+fn gen_mapped_component_providers_test() {
+    use hecs_component_provider::{
+        gen_mapped_component_providers, ComponentProvider, ComponentProviderMut, MapProvider,
+    };
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Name(Label);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Health(i32);
+
+    struct Stats {
+        health: Health,
+    }
+
+    impl AsRef<Health> for Stats {
+        fn as_ref(&self) -> &Health {
+            &self.health
+        }
+    }
+
+    impl AsMut<Health> for Stats {
+        fn as_mut(&mut self) -> &mut Health {
+            &mut self.health
+        }
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Label(String);
+
+    impl AsRef<Label> for Name {
+        fn as_ref(&self) -> &Label {
+            &self.0
+        }
+    }
+
+    gen_mapped_component_providers!(mut Stats => Health, Name => Label);
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    struct Bundle {
+        name: Name,
+        stats: Stats,
+    }
+
+    let mut entity = MapProvider(Bundle {
+        name: Name(Label("abc".to_string())),
+        stats: Stats { health: Health(3) },
+    });
+
+    let health: &mut Health = entity.get_mut();
+    health.0 -= 1;
+    assert_eq!(entity.0.stats.health, Health(2));
+
+    let label: &Label = entity.get();
+    assert_eq!(label.0, "abc");
+}
+
+#[test]
+fn self_component_provider_deref_test() {
+    use hecs_component_provider::{ComponentProvider, SelfComponentProvider};
+    use std::ops::Deref;
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Inner(i32);
+
+    #[derive(SelfComponentProvider)]
+    #[self_provider(deref)]
+    struct Wrapper(Inner);
+
+    impl Deref for Wrapper {
+        type Target = Inner;
+        fn deref(&self) -> &Inner {
+            &self.0
+        }
+    }
+
+    let wrapper = Wrapper(Inner(123));
+
+    let inner: &Inner = wrapper.get();
+    assert_eq!(*inner, Inner(123));
+
+    let wrapper: &Wrapper = wrapper.get();
+    assert_eq!(wrapper.0, Inner(123));
+}
+
+#[test]
+fn trait_object_component_provider_test() {
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMut};
+
+    trait Ai: Send + Sync {
+        fn think(&mut self) -> i32;
+    }
+
+    struct Counter(i32);
+
+    impl Ai for Counter {
+        fn think(&mut self) -> i32 {
+            self.0 += 1;
+            self.0
+        }
+    }
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    struct Bundle {
+        ai: Box<dyn Ai>,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct MyQuery<'a> {
+        ai: &'a mut Box<dyn Ai>,
+    }
+
+    let mut entity = Bundle {
+        ai: Box::new(Counter(0)),
+    };
+
+    let ai: &mut dyn Ai = entity.get_mut();
+    assert_eq!(ai.think(), 1);
+
+    let mut world = World::new();
+    world.spawn(entity);
+
+    for mut entity in world.query_mut::<MyQuery>() {
+        let _: &dyn Ai = entity.get();
+        let ai: &mut dyn Ai = entity.get_mut();
+        assert_eq!(ai.think(), 2);
+    }
+}
+
+#[test]
+fn unsized_component_provider_test() {
+    use hecs_component_provider::{default_trait_impl, ComponentProvider, ComponentProviderMut};
+
+    #[default_trait_impl]
+    trait Greet: ComponentProvider<str> {
+        fn greet(&self) -> String {
+            let name: &str = self.get();
+            format!("hello {}", name)
+        }
+    }
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    struct Bundle {
+        name: Box<str>,
+        scores: Box<[i32]>,
+    }
+
+    let mut entity = Bundle {
+        name: "abc".into(),
+        scores: vec![1, 2, 3].into_boxed_slice(),
+    };
+
+    assert_eq!(entity.greet(), "hello abc");
+
+    let provider: &dyn ComponentProvider<str> = &entity;
+    assert_eq!(provider.greet(), "hello abc");
+
+    let scores: &mut [i32] = entity.get_mut();
+    scores[0] = 4;
+    assert_eq!(&*entity.scores, &[4, 2, 3]);
+}
+
+#[test]
+fn or_component_provider_test() {
+    use hecs::Or;
+    use hecs_component_provider::{
+        gen_tuple_query_component_providers, ComponentProvider, ComponentProviderOptional,
+        ComponentProviderOptionalMut,
+    };
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Fire(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Ice(i32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct ElementQuery<'a> {
+        element: Or<&'a mut Fire, &'a Ice>,
+    }
+
+    gen_tuple_query_component_providers!(ElementTupleQuery, (&i32, Or<&mut Fire, &Ice>));
+
+    let mut world = World::new();
+    let fire = world.spawn((1, Fire(10)));
+    let ice = world.spawn((2, Ice(20)));
+
+    let mut query = world.query_one::<ElementQuery>(fire);
+    let mut entity = query.get().unwrap();
+    let ice_component: Option<&Ice> = entity.get_optional();
+    assert_eq!(ice_component, None);
+    let fire_component: Option<&mut Fire> = entity.get_optional_mut();
+    fire_component.unwrap().0 += 1;
+    assert_eq!(entity.get_optional(), Some(&Fire(11)));
+    drop(query);
+
+    let mut query = world.query_one::<ElementTupleQuery>(ice);
+    let entity = query.get().unwrap();
+    let fire_component: Option<&Fire> = entity.get_optional();
+    assert_eq!(fire_component, None);
+    assert_eq!(entity.get_optional(), Some(&Ice(20)));
+}
+
+#[test]
+fn split_component_provider_test() {
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMut};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Drag(i32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(split(PositionPart = [position], VelocityPart = [velocity, drag]))]
+    struct MovableQuery<'a> {
+        position: &'a mut Position,
+        velocity: &'a mut Velocity,
+        drag: Option<&'a Drag>,
+    }
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    #[provider(split(Left = [a], Right = [b]))]
+    struct Bundle {
+        a: Position,
+        b: Velocity,
+    }
+
+    fn step(
+        position: &mut impl ComponentProviderMut<Position>,
+        velocity: &impl ComponentProvider<Velocity>,
+    ) {
+        let &Velocity(v) = velocity.get();
+        position.get_mut().0 += v;
+    }
+
+    let mut world = World::new();
+    world.spawn((Position(0), Velocity(2), Drag(1)));
+
+    for mut entity in world.query_mut::<MovableQuery>() {
+        let (mut position, mut velocity) = entity.split();
+        let drag = velocity.drag.map_or(0, |d| d.0);
+        let velocity_component: &mut Velocity = velocity.get_mut();
+        velocity_component.0 -= drag;
+        step(&mut position, &velocity);
+        assert_eq!(*entity.position, Position(1));
+    }
+
+    let mut bundle = Bundle {
+        a: Position(0),
+        b: Velocity(3),
+    };
+    let (mut left, right) = bundle.split();
+    step(&mut left, &right);
+    assert_eq!(bundle.a, Position(3));
+}
+
+#[test]
+fn access_wrapper_component_provider_test() {
+    use hecs_component_provider::{
+        default_trait_impl, ComponentProvider, ComponentProviderMany, ComponentProviderMut, Read,
+        Write,
+    };
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Velocity(i32);
+
+    #[default_trait_impl]
+    trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+        fn apply_velocity(&mut self) {
+            let &Velocity(v) = self.get();
+            let position: &mut Position = self.get_mut();
+            position.0 += v;
+        }
+    }
+
+    #[derive(ComponentProvider)]
+    struct Bundle {
+        position: Write<Position>,
+        velocity: Read<Velocity>,
+    }
+
+    let mut entity = Bundle {
+        position: Write::new(Position(1)),
+        velocity: Read::new(Velocity(2)),
+    };
+    entity.apply_velocity();
+    assert_eq!(*entity.position, Position(3));
+
+    let (position, velocity) = entity.get_many_mut::<(&mut Position, &Velocity)>();
+    position.0 += velocity.0;
+    assert_eq!(entity.position.into_inner(), Position(5));
+}
+
+#[test]
+fn access_wrapper_bundle_spawn_test() {
+    use hecs_component_provider::{
+        ensure, remove, ComponentProvider, IntoEntityBuilder, Read, Write,
+    };
+
+    #[derive(Debug, Default, Eq, PartialEq)]
+    struct Health(i32);
+    #[derive(Debug, Default, Eq, PartialEq)]
+    struct Armor(i32);
+
+    #[derive(ComponentProvider)]
+    struct Bundle {
+        health: Write<Health>,
+        armor: Read<Armor>,
+    }
+
+    let mut world = World::new();
+    let bundle = Bundle {
+        health: Write::new(Health(10)),
+        armor: Read::new(Armor(2)),
+    };
+    let entity = world.spawn(bundle.into_entity_builder().build());
+    assert_eq!(*world.get::<&Health>(entity).unwrap(), Health(10));
+    assert!(world.get::<&Write<Health>>(entity).is_err());
+    assert_eq!(world.query::<(&Health, &Armor)>().iter().count(), 1);
+
+    let bundle = remove::<Bundle>(&mut world, entity).unwrap();
+    assert_eq!(*bundle.health, Health(10));
+    assert_eq!(*bundle.armor, Armor(2));
+
+    let added = ensure::<Bundle>(&mut world, entity).unwrap();
+    assert_eq!(added.len(), 2);
+    assert_eq!(*world.get::<&Health>(entity).unwrap(), Health(0));
+}
+
+#[test]
+fn qualified_access_wrapper_name_test() {
+    use hecs_component_provider::ComponentProvider;
+
+    mod journal {
+        #[derive(Debug, Eq, PartialEq)]
+        pub struct Read<T>(pub T);
+    }
+
+    #[derive(ComponentProvider)]
+    struct Bundle {
+        last_read: journal::Read<u32>,
+    }
+
+    let entity = Bundle {
+        last_read: journal::Read(7),
+    };
+    let last_read: &journal::Read<u32> = entity.get();
+    assert_eq!(*last_read, journal::Read(7));
+}
+
+#[test]
+fn nested_bundle_component_provider_test() {
+    use hecs_component_provider::{
+        default_trait_impl, ComponentProvider, ComponentProviderMut, SelfComponentProvider,
+    };
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, Eq, PartialEq, SelfComponentProvider)]
+    struct Health(i32);
+
+    #[default_trait_impl]
+    trait PlayerBehaviors:
+        ComponentProviderMut<Position> + ComponentProvider<Velocity> + ComponentProviderMut<Health>
+    {
+        fn update(&mut self) {
+            let &Velocity(v) = self.get();
+            let position: &mut Position = self.get_mut();
+            position.0 += v;
+            let health: &mut Health = self.get_mut();
+            health.0 -= 1;
+        }
+    }
+
+    #[derive(ComponentProvider)]
+    struct PhysicsBundle {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    #[derive(ComponentProvider)]
+    struct Player(
+        #[provider(nested(Position, Velocity))] PhysicsBundle,
+        #[provider(nested(Health))] Health,
+    );
+
+    let mut player = Player(
+        PhysicsBundle {
+            position: Position(1),
+            velocity: Velocity(2),
+        },
+        Health(10),
+    );
+    player.update();
+    assert_eq!(player.0.position, Position(3));
+    assert_eq!(player.1, Health(9));
+}
+
+#[test]
+fn nested_bundle_entity_builder_test() {
+    use hecs_component_provider::{ComponentProvider, IntoEntityBuilder};
+    use std::rc::Rc;
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Health(i32);
+
+    #[derive(ComponentProvider)]
+    struct PhysicsBundle {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    #[derive(ComponentProvider)]
+    struct CharacterBundle {
+        #[provider(nested(Position, Velocity))]
+        physics: PhysicsBundle,
+    }
+
+    #[derive(ComponentProvider)]
+    struct Player {
+        health: Health,
+        #[provider(nested(Position, Velocity))]
+        character: CharacterBundle,
+    }
+
+    // bundles with fields that aren't components still derive the providers
+    #[derive(ComponentProvider)]
+    #[provider(non_send)]
+    struct Local {
+        shared: Rc<Health>,
+    }
+    let local = Local {
+        shared: Rc::new(Health(1)),
+    };
+    let _: &Rc<Health> = local.get();
+
+    let player = Player {
+        health: Health(10),
+        character: CharacterBundle {
+            physics: PhysicsBundle {
+                position: Position(1),
+                velocity: Velocity(2),
+            },
+        },
+    };
+    let mut world = World::new();
+    let entity = world.spawn(player.into_entity_builder().build());
+
+    let mut query = world.query_one::<(&Position, &Velocity, &Health)>(entity);
+    assert_eq!(
+        query.get().unwrap(),
+        (&Position(1), &Velocity(2), &Health(10))
+    );
+    drop(query);
+    assert!(!world.satisfies::<&PhysicsBundle>(entity));
+}
+
+#[test]
+fn spawn_default_test() {
+    use hecs_component_provider::{ComponentProvider, SpawnDefault};
+
+    #[derive(Debug, Default, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Default, Eq, PartialEq)]
+    struct Velocity(i32);
+
+    #[derive(Default, ComponentProvider)]
+    struct PhysicsBundle {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    #[derive(Default, ComponentProvider)]
+    struct Projectile {
+        #[provider(nested(Position, Velocity))]
+        physics: PhysicsBundle,
+    }
+
+    let projectile = Projectile::default_with(|p| p.physics.velocity.0 = 3);
+    assert_eq!(projectile.physics.velocity, Velocity(3));
+
+    let mut world = World::new();
+    let entity = Projectile::spawn_default_with(&mut world, |p| p.physics.position.0 = 1);
+    let default = Projectile::spawn_default(&mut world);
+    assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(1));
+    assert_eq!(*world.get::<&Velocity>(default).unwrap(), Velocity(0));
+}
+
+#[test]
+fn spawn_batch_with_test() {
+    use hecs_component_provider::{spawn_batch_with, ComponentProvider, ComponentProviderMut};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Velocity(i32);
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    struct Projectile {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    let mut world = World::new();
+    let mut calls = 0;
+    let projectiles = (0..3).map(|i| Projectile {
+        position: Position(i),
+        velocity: Velocity(i),
+    });
+    let entities = spawn_batch_with(&mut world, projectiles, |projectile| {
+        let position: &mut Position = projectile.get_mut();
+        position.0 *= 10;
+        calls += 1;
+    });
+
+    assert_eq!(calls, 3);
+    assert_eq!(entities.len(), 3);
+    assert_eq!(*world.get::<&Position>(entities[2]).unwrap(), Position(20));
+    assert_eq!(*world.get::<&Velocity>(entities[2]).unwrap(), Velocity(2));
+    assert!(spawn_batch_with(&mut world, Vec::<Projectile>::new(), |_| {}).is_empty());
+}
+
+#[test]
+fn bundle_diff_test() {
+    use hecs_component_provider::{ComponentProvider, DiffBundle};
+
+    #[derive(Clone, PartialEq)]
+    struct Position(i32);
+    #[derive(Clone, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Clone, PartialEq)]
+    struct Health(i32);
+
+    #[derive(Clone, ComponentProvider)]
+    struct PhysicsBundle {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    #[derive(Clone, ComponentProvider)]
+    struct Player(
+        Health,
+        #[provider(nested(Position, Velocity))] PhysicsBundle,
+    );
+
+    let before = Player(
+        Health(10),
+        PhysicsBundle {
+            position: Position(0),
+            velocity: Velocity(1),
+        },
+    );
+    let mut after = before.clone();
+    assert!(!before.diff(&after).has_changes());
+
+    after.1.position.0 += 1;
+    let diff = before.diff(&after);
+    assert!(diff.has_changes());
+    assert_eq!(
+        diff.fields().collect::<Vec<_>>(),
+        [("0", false), ("1.position", true), ("1.velocity", false)]
+    );
+}
+
+#[test]
+fn bundle_into_builder_test() {
+    use hecs::EntityBuilder;
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMut};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Shield(i32);
+
+    #[derive(ComponentProvider)]
+    struct Knight {
+        position: Position,
+    }
+
+    let mut knight = Knight {
+        position: Position(0),
+    };
+    let position: &mut Position = knight.get_mut();
+    position.0 = 4;
+
+    let mut builder: EntityBuilder = knight.into();
+    builder.add(Shield(2));
+
+    let mut world = World::new();
+    let entity = world.spawn(builder.build());
+    assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(4));
+    assert_eq!(*world.get::<&Shield>(entity).unwrap(), Shield(2));
+}
+
+#[test]
+fn query_to_owned_bundle_test() {
+    use hecs::Entity;
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMut};
+
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    struct Name(String);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(owned(NamedBundle))]
+    struct NamedQuery<'a> {
+        entity: Entity,
+        position: &'a mut Position,
+        name: &'a Name,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(owned(PositionTuple))]
+    struct PositionTupleQuery<'a>(&'a Position);
+
+    let mut world = World::new();
+    let original = world.spawn((Position(1), Name("a".to_owned())));
+
+    let item = world.query_one_mut::<NamedQuery>(original).unwrap();
+    assert_eq!(item.entity, original);
+    let mut bundle = item.to_bundle();
+    let position: &mut Position = bundle.get_mut();
+    position.0 = 5;
+    assert_eq!(bundle.name, Name("a".to_owned()));
+
+    let copy = world.spawn(bundle);
+    assert_eq!(*world.get::<&Position>(original).unwrap(), Position(1));
+    assert_eq!(*world.get::<&Position>(copy).unwrap(), Position(5));
+    assert_eq!(*world.get::<&Name>(copy).unwrap(), Name("a".to_owned()));
+
+    let tuple = world
+        .query_one_mut::<PositionTupleQuery>(copy)
+        .unwrap()
+        .to_bundle();
+    assert_eq!(tuple.0, Position(5));
+}
+
+#[test]
+fn cfg_field_component_provider_test() {
+    use hecs_component_provider::{ComponentProvider, DiffBundle};
+
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    struct DebugName(&'static str);
+
+    #[derive(Clone, hecs::Bundle, ComponentProvider)]
+    struct Spawned {
+        position: Position,
+        #[cfg(not(test))]
+        debug_name: DebugName,
+        #[cfg(test)]
+        name: DebugName,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(owned(OwnedItem), readonly_as = "ReadOnlyItem")]
+    struct Item<'a> {
+        position: &'a mut Position,
+        #[cfg(not(test))]
+        debug_name: &'a DebugName,
+        #[cfg(test)]
+        name: &'a DebugName,
+    }
+
+    let bundle = Spawned {
+        position: Position(1),
+        name: DebugName("a"),
+    };
+    assert!(!bundle.diff(&bundle.clone()).has_changes());
+
+    let mut world = World::new();
+    let entity = world.spawn(bundle);
+    let item = world.query_one_mut::<Item>(entity).unwrap();
+    let name: &DebugName = item.get();
+    assert_eq!(*name, DebugName("a"));
+    assert_eq!(item.to_bundle().position, Position(1));
+
+    let item = ReadOnlyItem::from(item);
+    let position: &Position = item.get();
+    assert_eq!(*position, Position(1));
+}
+
+#[test]
+fn ensure_components_test() {
+    use hecs_component_provider::{ensure, ComponentProvider};
+    use std::any::TypeId;
+
+    #[derive(Debug, Default, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Default, Eq, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, Default, Eq, PartialEq)]
+    struct Health(i32);
+
+    #[derive(ComponentProvider)]
+    struct PhysicsBundle {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    #[derive(ComponentProvider)]
+    struct Player {
+        health: Health,
+        #[provider(nested(Position, Velocity))]
+        physics: PhysicsBundle,
+    }
+
+    let mut world = World::new();
+    let entity = world.spawn((Velocity(2),));
+
+    let added = ensure::<Player>(&mut world, entity).unwrap();
+    let added: Vec<TypeId> = added.iter().map(|access| access.type_id).collect();
+    assert_eq!(added, [TypeId::of::<Health>(), TypeId::of::<Position>()]);
+    assert_eq!(*world.get::<&Velocity>(entity).unwrap(), Velocity(2));
+    assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(0));
+    assert!(ensure::<Player>(&mut world, entity).unwrap().is_empty());
+
+    world.despawn(entity).unwrap();
+    assert!(ensure::<Player>(&mut world, entity).is_err());
+}
+
+#[test]
+fn remove_bundle_test() {
+    use hecs_component_provider::{remove, ComponentProvider};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Health(i32);
+
+    #[derive(ComponentProvider)]
+    struct PhysicsBundle(Position, Velocity);
+
+    #[derive(ComponentProvider)]
+    struct Player {
+        health: Health,
+        #[provider(nested(Position, Velocity))]
+        physics: PhysicsBundle,
+    }
+
+    let mut world = World::new();
+    let partial = world.spawn((Health(1), Position(2)));
+    assert!(remove::<Player>(&mut world, partial).is_err());
+    assert_eq!(*world.get::<&Health>(partial).unwrap(), Health(1));
+    assert_eq!(*world.get::<&Position>(partial).unwrap(), Position(2));
+
+    let entity = world.spawn((Health(3), Position(4), Velocity(5), true));
+    let player = remove::<Player>(&mut world, entity).unwrap();
+    assert_eq!(player.health, Health(3));
+    assert_eq!(player.physics.0, Position(4));
+    assert_eq!(player.physics.1, Velocity(5));
+    assert!(world.satisfies::<&bool>(entity));
+    assert!(!world.satisfies::<&Health>(entity));
+}
+
+#[test]
+fn promote_test() {
+    use hecs_component_provider::{demote, promote, Cold, ComponentProvider};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Health(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Hunger(i32);
+
+    #[derive(ComponentProvider)]
+    struct Corpse {
+        position: Position,
+    }
+
+    #[derive(ComponentProvider)]
+    #[provider(promote(Corpse, extra = [health, hunger]))]
+    struct Creature {
+        health: Health,
+        position: Position,
+        #[provider(cold)]
+        hunger: Hunger,
+    }
+
+    let mut world = World::new();
+    let entity = world.spawn((Position(2), true));
+    let archetypes = world.archetypes().len();
+    promote::<Corpse, Creature>(&mut world, entity, |corpse| {
+        assert_eq!(corpse.entity(), entity);
+        let position: &Position = corpse.get();
+        (Health(10), Hunger(position.0))
+    })
+    .unwrap();
+    assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(2));
+    assert_eq!(*world.get::<&Health>(entity).unwrap(), Health(10));
+    assert!(world.satisfies::<&Cold<Hunger>>(entity));
+    assert!(world.satisfies::<&bool>(entity));
+    // the entity moved straight to its new archetype
+    assert_eq!(world.archetypes().len(), archetypes + 1);
+
+    let extra = demote::<Creature, Corpse>(&mut world, entity).unwrap();
+    assert_eq!(extra, (Health(10), Hunger(2)));
+    assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(2));
+    assert!(!world.satisfies::<&Health>(entity));
+    assert!(world.satisfies::<&bool>(entity));
+    assert_eq!(world.archetypes().len(), archetypes + 1);
+
+    // the entity is no longer a creature, so it's left unchanged
+    assert!(demote::<Creature, Corpse>(&mut world, entity).is_err());
+    let empty = world.spawn((true,));
+    assert!(promote::<Corpse, Creature>(&mut world, empty, |_| unreachable!()).is_err());
+    assert!(!world.satisfies::<&Health>(empty));
+
+    // cold components of the lower bundle are provided through their box
+    #[derive(ComponentProvider)]
+    #[provider(promote(Creature, extra = [title]))]
+    struct Hero {
+        health: Health,
+        position: Position,
+        #[provider(cold)]
+        hunger: Hunger,
+        title: &'static str,
+    }
+
+    promote::<Corpse, Creature>(&mut world, entity, |_| (Health(5), Hunger(3))).unwrap();
+    promote::<Creature, Hero>(&mut world, entity, |creature| {
+        let hunger: &Hunger = creature.get();
+        (if hunger.0 > 2 {
+            "the hungry"
+        } else {
+            "the fed"
+        },)
+    })
+    .unwrap();
+    assert_eq!(*world.get::<&&str>(entity).unwrap(), "the hungry");
+}
+
+#[test]
+fn tag_test() {
+    use hecs_component_provider::{Tag, WithTag};
+
+    #[derive(Tag)]
+    struct Frozen;
+
+    struct Position(i32);
+
+    let mut world = World::new();
+    let a = world.spawn((Position(1),));
+    let b = world.spawn((Position(2),));
+
+    Frozen::insert_tag(&mut world, a).unwrap();
+    Frozen::insert_tag(&mut world, a).unwrap();
+    assert!(Frozen::has_tag(&world, a));
+    assert!(!Frozen::has_tag(&world, b));
+
+    let frozen: Vec<i32> = world
+        .query_mut::<WithTag<Frozen, &Position>>()
+        .into_iter()
+        .map(|position| position.0)
+        .collect();
+    assert_eq!(frozen, [1]);
+
+    Frozen::remove_tag(&mut world, a).unwrap();
+    assert!(Frozen::remove_tag(&mut world, a).is_err());
+    world.despawn(b).unwrap();
+    assert!(Frozen::insert_tag(&mut world, b).is_err());
+    assert!(!Frozen::has_tag(&world, b));
+}
+
+#[test]
+fn component_alias_test() {
+    use hecs_component_provider::{default_trait_impl, ComponentProvider, ComponentProviderMut};
+
+    struct Transform(i32);
+    struct LocalTransform(Transform);
+    struct Velocity(i32);
+
+    impl AsRef<Transform> for LocalTransform {
+        fn as_ref(&self) -> &Transform {
+            &self.0
+        }
+    }
+
+    impl AsMut<Transform> for LocalTransform {
+        fn as_mut(&mut self) -> &mut Transform {
+            &mut self.0
+        }
+    }
+
+    #[default_trait_impl]
+    trait Move: ComponentProviderMut<Transform> + ComponentProvider<Velocity> {
+        fn move_by_velocity(&mut self) {
+            let &Velocity(v) = self.get();
+            let transform: &mut Transform = self.get_mut();
+            transform.0 += v;
+        }
+    }
+
+    #[default_trait_impl]
+    trait ReadTransform: ComponentProvider<Transform> {
+        fn transform(&self) -> i32 {
+            let transform: &Transform = self.get();
+            transform.0
+        }
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct LocalMoveQuery<'a> {
+        #[provider(alias(Transform))]
+        transform: &'a mut LocalTransform,
+        velocity: &'a Velocity,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct LocalReadQuery<'a>(#[provider(alias(Transform))] &'a LocalTransform);
+
+    #[derive(ComponentProvider)]
+    struct LocalBundle {
+        #[provider(alias(Transform))]
+        transform: LocalTransform,
+        velocity: Velocity,
+    }
+
+    let mut world = World::new();
+    let entity = world.spawn((LocalTransform(Transform(1)), Velocity(2)));
+    for mut item in world.query_mut::<LocalMoveQuery>() {
+        item.move_by_velocity();
+        let _: &LocalTransform = item.get();
+    }
+    assert_eq!(
+        world
+            .query_one_mut::<LocalReadQuery>(entity)
+            .unwrap()
+            .transform(),
+        3
+    );
+
+    let mut bundle = LocalBundle {
+        transform: LocalTransform(Transform(0)),
+        velocity: Velocity(5),
+    };
+    bundle.move_by_velocity();
+    assert_eq!(bundle.transform(), 5);
+}
+
+#[test]
+fn behavior_info_test() {
+    use hecs_component_provider::{
+        default_trait_impl, BehaviorRegistry, ComponentProvider, ComponentProviderMut,
+        ComponentProviderOptional,
+    };
+    use std::any::TypeId;
+
+    struct Health(i32);
+    struct Armor(i32);
+
+    #[default_trait_impl(info = take_damage_info)]
+    trait TakeDamage: ComponentProvider<Health> + ComponentProviderMut<Health> {
+        fn take_damage(&mut self, amount: i32) {
+            let health: &mut Health = self.get_mut();
+            health.0 -= amount;
+        }
+
+        fn is_dead(&self) -> bool {
+            let health: &Health = self.get();
+            health.0 <= 0
+        }
+    }
+
+    #[default_trait_impl(info = hit_info)]
+    trait Hit: TakeDamage + ComponentProviderOptional<Armor> {
+        fn hit(&mut self, amount: i32) {
+            let armor = self.get_optional().map_or(0, |armor: &Armor| armor.0);
+            self.take_damage(amount - armor);
+        }
+    }
+
+    #[derive(ComponentProvider)]
+    struct Knight {
+        health: Health,
+        armor: Armor,
+    }
+
+    impl ComponentProviderOptional<Armor> for Knight {
+        fn get_optional(&self) -> Option<&Armor> {
+            Some(&self.armor)
+        }
+    }
+
+    let mut knight = Knight {
+        health: Health(5),
+        armor: Armor(1),
+    };
+    knight.hit(5);
+    assert!(!knight.is_dead());
+
+    let mut registry = BehaviorRegistry::new();
+    registry.register(hit_info());
+    registry.register(take_damage_info());
+    registry.register(take_damage_info());
+    assert_eq!(registry.behaviors().len(), 2);
+
+    let take_damage = registry.get("TakeDamage").unwrap();
+    assert_eq!(take_damage.methods(), ["take_damage", "is_dead"]);
+    assert_eq!(take_damage.access().len(), 1);
+    assert!(take_damage.access()[0].mutable && take_damage.access()[0].required);
+
+    let hit = registry.get("Hit").unwrap();
+    assert_eq!(hit.dependencies(), ["TakeDamage"]);
+    let access = registry.access("Hit");
+    let access: Vec<_> = access
+        .iter()
+        .map(|a| (a.type_id, a.mutable, a.required))
+        .collect();
+    assert_eq!(
+        access,
+        [
+            (TypeId::of::<Armor>(), false, false),
+            (TypeId::of::<Health>(), true, true)
+        ]
+    );
+    assert!(registry.get("Unknown").is_none());
+    assert!(registry.access("Unknown").is_empty());
+}
+
+#[test]
+fn export_dot_test() {
+    use hecs_component_provider::{
+        default_trait_impl, BehaviorRegistry, ComponentProvider, ComponentProviderMut,
+        ComponentProviderOptional,
+    };
+
+    struct Position;
+    struct Velocity;
+    struct Boost;
+
+    #[default_trait_impl(info = apply_velocity_info)]
+    trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {}
+
+    #[default_trait_impl(info = boosted_info)]
+    trait Boosted: ApplyVelocity + ComponentProviderOptional<Boost> {
+        fn is_boosted(&self) -> bool {
+            let boost: Option<&Boost> = self.get_optional();
+            boost.is_some()
+        }
+    }
+
+    #[derive(ComponentProvider)]
+    struct Ship {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    impl ComponentProviderOptional<Boost> for Ship {
+        fn get_optional(&self) -> Option<&Boost> {
+            None
+        }
+    }
+
+    let ship = Ship {
+        position: Position,
+        velocity: Velocity,
+    };
+    assert!(!ship.is_boosted());
+
+    let mut registry = BehaviorRegistry::new();
+    registry.register(apply_velocity_info());
+    registry.register(boosted_info());
+    let dot = registry.export_dot();
+
+    let node = |name: &str| {
+        let full = format!("export_dot_test::{}", name);
+        let line = dot
+            .lines()
+            .find(|l| l.contains(&full) && l.contains("label=\"") && !l.contains("->"))
+            .unwrap_or_else(|| panic!("missing node for {}", name));
+        line.trim().split(' ').next().unwrap().to_owned()
+    };
+    let position = node("Position");
+    let velocity = node("Velocity");
+    let boost = node("Boost");
+
+    for edge in [
+        format!(
+            "\"ApplyVelocity\" -> {} [label=\"write\", style=solid];",
+            position
+        ),
+        format!(
+            "{} -> \"ApplyVelocity\" [label=\"read\", style=solid];",
+            velocity
+        ),
+        format!("{} -> \"Boosted\" [label=\"read\", style=dashed];", boost),
+        "\"Boosted\" -> \"ApplyVelocity\" [style=bold];".to_owned(),
+    ] {
+        assert!(dot.contains(&edge), "missing `{}` in:\n{}", edge, dot);
+    }
+    assert!(dot.contains("label=\"Position\""));
+}
+
+#[test]
+fn warn_unused_supertrait_test() {
+    use hecs_component_provider::{
+        default_trait_impl, ComponentProvider, ComponentProviderMut, SelfComponentProvider,
+    };
+
+    #[derive(SelfComponentProvider)]
+    struct Counter(i32);
+
+    // every component is named, so no warnings are emitted, which `-D warnings` checks
+    #[default_trait_impl(warn_unused, info = increment_info)]
+    trait Increment: ComponentProvider<Counter> + ComponentProviderMut<Counter> {
+        fn increment(&mut self) {
+            let counter: &mut Counter = self.get_mut();
+            counter.0 += 1;
+        }
+    }
+
+    let mut counter = Counter(0);
+    counter.increment();
+    assert_eq!(counter.0, 1);
+    assert_eq!(increment_info().name(), "Increment");
+}
+
+#[test]
+fn no_inline_test() {
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMut, SelfComponentProvider};
+
+    #[derive(Debug, PartialEq, SelfComponentProvider)]
+    #[self_provider(no_inline)]
+    struct Position(i32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(no_inline)]
+    struct PositionQuery<'a> {
+        position: &'a mut Position,
+    }
+
+    let mut world = World::new();
+    world.spawn((Position(1),));
+    for mut item in world.query_mut::<PositionQuery>() {
+        let position: &mut Position = item.get_mut();
+        let position: &mut Position = position.get_mut();
+        position.0 += 1;
+    }
+    let position = world.query_mut::<&Position>().into_iter().next().unwrap();
+    assert_eq!(*position, Position(2));
+}
+
+#[test]
+fn dynamic_provider_test() {
+    use hecs_component_provider::{
+        default_trait_impl, ComponentProvider, ComponentProviderMut, ComponentProviderOptional,
+        Dyn, DynamicProvider,
+    };
+    use std::any::TypeId;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, PartialEq)]
+    struct Boost(i32);
+
+    #[default_trait_impl]
+    trait Move: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+        fn move_by_velocity(&mut self) {
+            let &Velocity(v) = self.get();
+            let position: &mut Position = self.get_mut();
+            position.0 += v;
+        }
+    }
+
+    #[default_trait_impl]
+    trait BoostedMove: Move + ComponentProviderOptional<Boost> {
+        fn boosted_move(&mut self) {
+            let boost = self.get_optional().map_or(1, |b: &Boost| b.0);
+            for _ in 0..boost {
+                self.move_by_velocity();
+            }
+        }
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(dynamic)]
+    struct MoveQuery<'a> {
+        position: &'a mut Position,
+        velocity: &'a Velocity,
+        boost: Option<&'a Boost>,
+    }
+
+    #[derive(ComponentProvider)]
+    #[provider(dynamic)]
+    struct MoveBundle(Position, Velocity);
+
+    let mut world = World::new();
+    let boosted = world.spawn((Position(0), Velocity(1), Boost(3)));
+    let plain = world.spawn((Position(0), Velocity(1)));
+    for item in world.query_mut::<MoveQuery>() {
+        Dyn(item).boosted_move();
+    }
+    assert_eq!(*world.get::<&Position>(boosted).unwrap(), Position(3));
+    assert_eq!(*world.get::<&Position>(plain).unwrap(), Position(1));
+
+    let mut item = world.query_one_mut::<MoveQuery>(plain).unwrap();
+    assert!(item.get_dyn(TypeId::of::<Velocity>()).is_some());
+    assert!(item.get_dyn_mut(TypeId::of::<Velocity>()).is_none());
+    assert!(item.get_dyn(TypeId::of::<Boost>()).is_none());
+
+    let mut bundle = Dyn(MoveBundle(Position(0), Velocity(2)));
+    bundle.move_by_velocity();
+    assert_eq!((bundle.0).0, Position(2));
+    let velocity: &mut Velocity = bundle.get_mut();
+    velocity.0 = 5;
+    assert_eq!((bundle.0).1, Velocity(5));
+    let boost: Option<&Boost> = bundle.get_optional();
+    assert!(boost.is_none());
+}
+
+#[test]
+fn replication_test() {
+    use hecs_component_provider::{ComponentProvider, ReplicatedBundle, Replication};
+    use std::any::TypeId;
+
+    struct Position(f32);
+    struct Velocity(f32);
+    struct Health(i32);
+    struct PathfindingCache(Vec<u32>);
+
+    #[derive(ComponentProvider)]
+    struct PhysicsBundle {
+        #[provider(replicate)]
+        position: Position,
+        velocity: Velocity,
+    }
+
+    #[derive(ComponentProvider)]
+    struct Npc {
+        #[provider(replicate)]
+        health: Health,
+        #[provider(server_only)]
+        cache: PathfindingCache,
+        #[provider(nested(Position, Velocity))]
+        physics: PhysicsBundle,
+    }
+
+    let npc = Npc {
+        health: Health(3),
+        cache: PathfindingCache(vec![1]),
+        physics: PhysicsBundle {
+            position: Position(0.0),
+            velocity: Velocity(1.0),
+        },
+    };
+    assert_eq!(npc.health.0 + npc.cache.0.len() as i32, 4);
+    assert_eq!(npc.physics.position.0 + npc.physics.velocity.0, 1.0);
+
+    let replication = Npc::replication();
+    let components: Vec<_> = replication
+        .components()
+        .iter()
+        .map(|c| (c.path.as_str(), c.replication))
+        .collect();
+    assert_eq!(
+        components,
+        [
+            ("health", Replication::Replicate),
+            ("cache", Replication::ServerOnly),
+            ("physics.position", Replication::Replicate),
+            ("physics.velocity", Replication::ServerOnly),
+        ]
+    );
+    assert!(replication.is_replicated(TypeId::of::<Position>()));
+    assert!(!replication.is_replicated(TypeId::of::<Velocity>()));
+    assert_eq!(replication.replicated().count(), 2);
+    assert!(replication.components()[1]
+        .type_name
+        .ends_with("PathfindingCache"));
+}
+
+#[test]
+fn quantize_test() {
+    use hecs_component_provider::{ComponentProvider, Quantization, ReplicatedBundle};
+
+    struct Angle(f32);
+    struct Health(f32);
+
+    #[derive(ComponentProvider)]
+    struct Turret {
+        #[provider(replicate, quantize(bits = 8, range = "0.0..360.0"))]
+        angle: Angle,
+        #[provider(replicate)]
+        health: Health,
+    }
+
+    let turret = Turret {
+        angle: Angle(90.0),
+        health: Health(1.0),
+    };
+    assert_eq!(turret.angle.0 + turret.health.0, 91.0);
+
+    let replication = Turret::replication();
+    let angle = replication.components()[0].quantization.unwrap();
+    assert_eq!(angle, Quantization::new(8, 0.0, 360.0));
+    assert_eq!(replication.components()[1].quantization, None);
+
+    assert_eq!(angle.encode(0.0), 0);
+    assert_eq!(angle.encode(360.0), 255);
+    assert_eq!(angle.encode(-10.0), 0);
+    assert_eq!(angle.encode(1000.0), 255);
+    assert_eq!(angle.encode(f32::NAN), 0);
+    for value in [0.0, 45.0, 90.5, 359.0] {
+        assert!((angle.decode(angle.encode(value)) - value).abs() <= angle.step() / 2.0);
+    }
+    assert_eq!(angle.delta(90.0, 90.0), 0);
+    assert_eq!(angle.apply_delta(350.0, 100), 360.0);
+
+    let full = Quantization::new(32, -1.0, 1.0);
+    assert_eq!(full.encode(1.0), u32::MAX);
+    assert_eq!(full.decode(u32::MAX), 1.0);
+}
+
+#[test]
+fn qualified_cold_name_test() {
+    use hecs_component_provider::ComponentProvider;
+
+    mod storage {
+        #[derive(Debug, Eq, PartialEq)]
+        pub struct Cold<T>(pub T);
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct ArchiveQuery<'a> {
+        archive: &'a storage::Cold<u32>,
+    }
+
+    let mut world = World::new();
+    world.spawn((storage::Cold(7u32),));
+    for item in world.query_mut::<ArchiveQuery>() {
+        let archive: &storage::Cold<u32> = item.get();
+        assert_eq!(*archive, storage::Cold(7));
+    }
+}
+
+#[test]
+fn cold_field_test() {
+    use hecs_component_provider::{
+        default_trait_impl, ensure, remove, Cold, ComponentProvider, ComponentProviderMut,
+        IntoEntityBuilder, ReplicatedBundle,
+    };
+    use std::any::TypeId;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Health(i32);
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct Biography(String);
+
+    #[default_trait_impl]
+    trait Describe: ComponentProvider<Health> + ComponentProvider<Biography> {
+        fn describe(&self) -> String {
+            let health: &Health = self.get();
+            let biography: &Biography = self.get();
+            format!("{} ({})", biography.0, health.0)
+        }
+    }
+
+    #[default_trait_impl]
+    trait Rename: ComponentProviderMut<Biography> {
+        fn rename(&mut self, name: &str) {
+            let biography: &mut Biography = self.get_mut();
+            biography.0 = name.to_string();
+        }
+    }
+
+    #[derive(Default, ComponentProvider)]
+    struct Villager {
+        health: Health,
+        #[provider(cold, replicate)]
+        biography: Biography,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct DescribeQuery<'a> {
+        health: &'a Health,
+        biography: &'a Cold<Biography>,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct RenameQuery<'a> {
+        biography: &'a mut Cold<Biography>,
+    }
+
+    let villager = Villager {
+        health: Health(3),
+        biography: Biography("farmer".to_string()),
+    };
+    // behaviors work on the bundle itself, which stores the field unboxed
+    assert_eq!(villager.describe(), "farmer (3)");
+
+    let mut world = World::new();
+    let entity = world.spawn(villager.into_entity_builder().build());
+    assert!(world.get::<&Biography>(entity).is_err());
+
+    for mut item in world.query_mut::<RenameQuery>() {
+        item.rename("miller");
+    }
+    let descriptions: Vec<_> = world
+        .query_mut::<DescribeQuery>()
+        .into_iter()
+        .map(|item| item.describe())
+        .collect();
+    assert_eq!(descriptions, ["miller (3)"]);
+
+    let blank = world.spawn((Health(1),));
+    let added = ensure::<Villager>(&mut world, blank).unwrap();
+    assert_eq!(added[0].type_id, TypeId::of::<Cold<Biography>>());
+
+    let removed = remove::<Villager>(&mut world, entity).unwrap();
+    assert_eq!(removed.biography, Biography("miller".to_string()));
+    assert!(world.get::<&Cold<Biography>>(entity).is_err());
+
+    assert!(Villager::replication().is_replicated(TypeId::of::<Cold<Biography>>()));
+    assert_eq!(
+        Cold::new(Biography::default()).into_inner(),
+        Biography::default()
+    );
+}
+
+#[test]
+fn readonly_query_test() {
+    use hecs::{Entity, Or};
+    use hecs_component_provider::{
+        default_trait_impl, ComponentProvider, ComponentProviderMut, ComponentProviderOptional,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    struct Velocity(i32);
+    struct Boost(i32);
+    struct Mass(i32);
+    struct Charge(i32);
+
+    #[default_trait_impl]
+    trait Report: ComponentProvider<Position> + ComponentProviderOptional<Boost> {
+        fn report(&self) -> i32 {
+            let position: &Position = self.get();
+            let boost: Option<&Boost> = self.get_optional();
+            position.0 + boost.map_or(0, |b| b.0)
+        }
+    }
+
+    #[default_trait_impl]
+    trait Step: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+        fn step(&mut self) {
+            let &Velocity(v) = self.get();
+            let position: &mut Position = self.get_mut();
+            position.0 += v;
+        }
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(readonly_as = "PhysicsQueryRead")]
+    struct PhysicsQuery<'a> {
+        entity: Entity,
+        position: &'a mut Position,
+        velocity: &'a Velocity,
+        boost: Option<&'a mut Boost>,
+        weight: Or<&'a mut Mass, &'a Charge>,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(readonly_as = "PairRead")]
+    struct Pair<'a>(&'a mut Position, &'a Velocity);
+
+    let mut world = World::new();
+    let a = world.spawn((Position(0), Velocity(1), Boost(10), Mass(1)));
+    world.spawn((Position(5), Velocity(1), Charge(2)));
+
+    let reports: Vec<_> = world
+        .query_mut::<PhysicsQuery>()
+        .into_iter()
+        .map(|mut item| {
+            item.step();
+            let weight = match &item.weight {
+                Or::Left(m) => m.0,
+                Or::Right(c) => c.0,
+                Or::Both(m, c) => m.0 + c.0,
+            };
+            let item = PhysicsQueryRead::from(item);
+            (item.entity, item.report() + weight)
+        })
+        .collect();
+    assert!(reports.contains(&(a, 12)));
+    assert_eq!(reports.len(), 2);
+
+    // read-only queries can be borrowed alongside each other
+    let mut first = world.query::<PhysicsQueryRead>();
+    let mut second = world.query::<PairRead>();
+    let total: i32 = first.iter().map(|item| item.report()).sum();
+    let positions: i32 = second.iter().map(|item| item.0 .0 + item.1 .0 - 1).sum();
+    assert_eq!(total, 11 + 6);
+    assert_eq!(positions, 1 + 6);
+    drop((first, second));
+
+    for item in world.query_mut::<Pair>() {
+        let read = PairRead::from(item);
+        assert!(read.0 .0 > 0);
+    }
+}
+
+#[test]
+fn reflect_test() {
+    use hecs_component_provider::{ProxyError, Reflect, ReflectRegistry};
+
+    #[derive(Reflect)]
+    struct Stats {
+        name: String,
+        level: u32,
+    }
+    #[derive(Reflect)]
+    struct Speed(f32, f32);
+    #[derive(Reflect)]
+    struct Marker;
+
+    let stats = Stats {
+        name: "knight".to_owned(),
+        level: 3,
+    };
+    assert_eq!(stats.field_names(), ["name", "level"]);
+    assert_eq!(stats.field_type_name("level"), Some("u32"));
+    assert!(stats.field("strength").is_none());
+    assert_eq!(Speed(1.0, 2.0).field_names(), ["0", "1"]);
+    assert!(Marker.field_names().is_empty());
+
+    let mut registry = ReflectRegistry::new();
+    registry
+        .register::<Stats>()
+        .register::<Speed>()
+        .register::<Marker>();
+    assert_eq!(
+        registry.names().collect::<Vec<_>>(),
+        ["Marker", "Speed", "Stats"]
+    );
+
+    let mut world = hecs::World::new();
+    let entity = world.spawn((stats, Speed(1.0, 2.0)));
+
+    let mut proxy = registry.proxy(&mut world, entity);
+    assert_eq!(proxy.entity(), entity);
+    assert_eq!(proxy.component_names(), ["Speed", "Stats"]);
+    assert_eq!(proxy.field_names("Speed").unwrap(), ["0", "1"]);
+    proxy
+        .set_field("Stats", "name", "paladin".to_owned())
+        .unwrap();
+    proxy.set_field("Speed", "1", 4.0f32).unwrap();
+    let doubled = proxy
+        .with_field("Stats", "level", |level| {
+            let level = level.downcast_mut::<u32>().unwrap();
+            *level *= 2;
+            *level
+        })
+        .unwrap();
+    assert_eq!(doubled, 6);
+
+    assert_eq!(
+        proxy.get_field::<u32>("Marker", "0"),
+        Err(ProxyError::MissingComponent {
+            entity,
+            component: "Marker".to_owned(),
+        })
+    );
+    assert_eq!(
+        proxy.get_field::<u32>("Health", "current"),
+        Err(ProxyError::UnknownComponent("Health".to_owned()))
+    );
+    assert!(matches!(
+        proxy.get_field::<u32>("Stats", "strength"),
+        Err(ProxyError::UnknownField { .. })
+    ));
+    let error = proxy.set_field("Stats", "level", 7i64).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "field `Stats.level` has type `u32`, not `i64`"
+    );
+
+    let stats = world.get::<&Stats>(entity).unwrap();
+    assert_eq!((stats.name.as_str(), stats.level), ("paladin", 6));
+    assert_eq!(world.get::<&Speed>(entity).unwrap().1, 4.0);
+}
+
+#[test]
+fn raw_identifier_fields_test() {
+    use hecs_component_provider::{
+        default_trait_impl, ComponentProvider, ComponentProviderMut, DiffBundle, Reflect,
+        ReflectRegistry,
+    };
+
+    #[derive(Clone, Debug, Eq, PartialEq, Reflect)]
+    struct TypeTag {
+        r#type: u32,
+    }
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Clone, hecs::Bundle, ComponentProvider)]
+    struct Tagged {
+        r#type: TypeTag,
+        r#move: Position,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(
+        owned(TaggedOwned),
+        readonly_as = "TaggedRead",
+        split(TypePart = [r#type], MovePart = [r#move])
+    )]
+    struct TaggedQuery<'a> {
+        r#type: &'a TypeTag,
+        r#move: &'a mut Position,
+    }
+
+    #[default_trait_impl(info = r#match_info)]
+    trait r#Match: ComponentProvider<TypeTag> {
+        fn r#match(&self, tag: u32) -> bool {
+            let tag_type: &TypeTag = self.get();
+            tag_type.r#type == tag
+        }
+    }
+
+    let bundle = Tagged {
+        r#type: TypeTag { r#type: 3 },
+        r#move: Position(0),
+    };
+    assert!(bundle.r#match(3));
+    assert_eq!(
+        bundle.diff(&bundle.clone()).fields().collect::<Vec<_>>(),
+        [("type", false), ("move", false)]
+    );
+
+    let mut world = World::new();
+    let entity = world.spawn(bundle);
+    let mut item = world.query_one_mut::<TaggedQuery>(entity).unwrap();
+    let (type_part, mut move_part) = item.split();
+    assert!(type_part.r#match(3));
+    let position: &mut Position = move_part.get_mut();
+    position.0 = 1;
+    assert_eq!(item.to_bundle().r#move, Position(1));
+    let item = TaggedRead::from(item);
+    assert!(item.r#match(3));
+
+    let info = r#match_info();
+    assert_eq!(info.name(), "Match");
+    assert_eq!(info.methods(), ["match"]);
+
+    let mut registry = ReflectRegistry::new();
+    registry.register::<TypeTag>();
+    let mut proxy = registry.proxy(&mut world, entity);
+    assert_eq!(proxy.field_names("TypeTag").unwrap(), ["type"]);
+    assert_eq!(proxy.get_field::<u32>("TypeTag", "type").unwrap(), 3);
+}
+
+#[test]
+fn impl_component_provider_test() {
+    use hecs_component_provider::{
+        impl_component_provider, ComponentProvider, ComponentProviderMut,
+    };
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Name(&'static str);
+
+    struct View<'a> {
+        position: &'a mut Position,
+        names: (Name, Name),
+    }
+
+    impl_component_provider!(View<'a> {
+        mut Position => self.position,
+        Name => self.names.1,
+    });
+
+    let mut position = Position(1);
+    let mut view = View {
+        position: &mut position,
+        names: (Name("a"), Name("b")),
+    };
+    let name: &Name = view.get();
+    assert_eq!(*name, Name("b"));
+    let position: &mut Position = view.get_mut();
+    position.0 = 2;
+    assert_eq!(*view.position, Position(2));
+}
+
+#[test]
+fn field_init_test() {
+    use hecs_component_provider::ComponentProvider;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Tags(Vec<&'static str>);
+
+    #[derive(ComponentProvider)]
+    struct Unit {
+        #[provider(init = "Tags(vec![\"unit\"])")]
+        tags: Tags,
+        position: Position,
+        name: &'static str,
+    }
+
+    #[derive(ComponentProvider)]
+    struct Marker(#[provider(init = "Position(-1)")] Position, Tags);
+
+    let unit = Unit::new_partial(Position(2), "knight");
+    assert_eq!(unit.tags, Tags(vec!["unit"]));
+    assert_eq!(unit.position, Position(2));
+    assert_eq!(unit.name, "knight");
+
+    let marker = Marker::new_partial(Tags(Vec::new()));
+    assert_eq!(marker.0, Position(-1));
+}
+
+#[test]
+fn validate_test() {
+    use hecs_component_provider::{ComponentProvider, Validate, ValidationError};
+
+    struct Health(i32);
+    struct MaxHealth(i32);
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    #[provider(validate = "Unit::check")]
+    struct Unit {
+        health: Health,
+        max_health: MaxHealth,
+    }
+
+    impl Unit {
+        fn check(&self) -> Result<(), ValidationError> {
+            if self.health.0 > self.max_health.0 {
+                return Err(ValidationError::new("health exceeds max"));
+            }
+            Ok(())
+        }
+    }
+
+    fn non_negative<P: ComponentProvider<Health>>(item: &P) -> Result<(), ValidationError> {
+        let health: &Health = item.get();
+        if health.0 < 0 {
+            return Err(ValidationError::new(format!(
+                "negative health {}",
+                health.0
+            )));
+        }
+        Ok(())
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(validate = "non_negative")]
+    struct HealthQuery<'a> {
+        health: &'a mut Health,
+    }
+
+    let mut unit = Unit {
+        health: Health(5),
+        max_health: MaxHealth(5),
+    };
+    assert_eq!(unit.validate(), Ok(()));
+    unit.health.0 = 6;
+    assert_eq!(unit.validate().unwrap_err().message(), "health exceeds max");
+
+    let mut world = World::new();
+    world.spawn((Health(1),));
+    for item in world.query_mut::<HealthQuery>() {
+        item.health.0 -= 2;
+        assert_eq!(
+            item.validate().unwrap_err().to_string(),
+            "negative health -1"
+        );
+    }
+}
+
+#[test]
+fn assert_field_test() {
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMut};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[derive(Debug, PartialEq)]
+    struct Health(i32);
+    struct Name(&'static str);
+
+    #[derive(ComponentProvider)]
+    struct Unit {
+        #[provider(assert = "self.0 >= 0")]
+        health: Health,
+        #[provider(assert = "!self.0.is_empty()")]
+        name: Name,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct HealthQuery<'a> {
+        #[provider(assert = "(0..=100).contains(&self.0)")]
+        health: &'a mut Health,
+    }
+
+    let mut unit = Unit {
+        health: Health(1),
+        name: Name("a"),
+    };
+    ComponentProviderMut::<Health>::get_mut_checked(&mut unit).0 -= 1;
+    assert_eq!(unit.health, Health(0));
+    ComponentProviderMut::<Name>::get_mut(&mut unit).0 = "";
+
+    let mut world = World::new();
+    world.spawn((Health(100),));
+    let mut item = world.query_mut::<HealthQuery>().into_iter().next().unwrap();
+    item.get_mut_checked().0 -= 1;
+    assert_eq!(*item.health, Health(99));
+
+    if cfg!(debug_assertions) {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            ComponentProviderMut::<Health>::get_mut_checked(&mut unit).0 -= 1;
+        }));
+        let message = *result.unwrap_err().downcast::<&str>().unwrap();
+        assert_eq!(message, "assertion `self.0 >= 0` failed for field `health`");
+
+        let result = catch_unwind(AssertUnwindSafe(|| item.get_mut_checked().0 = 101));
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn bundle_builder_test() {
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMut};
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Default, PartialEq)]
+    struct Velocity(i32);
+
+    #[derive(Default, ComponentProvider)]
+    struct PhysicsBundle {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    #[derive(Default, ComponentProvider)]
+    #[provider(builder)]
+    struct Projectile {
+        #[provider(nested(Position, Velocity))]
+        physics: PhysicsBundle,
+        r#type: u8,
+    }
+
+    let mut projectile = Projectile::default()
+        .with_type(2)
+        .map_physics(|physics| PhysicsBundle {
+            velocity: Velocity(3),
+            ..physics
+        })
+        .map_type(|t| t + 1);
+    let position: &mut Position = projectile.get_mut();
+    position.0 = 1;
+    assert_eq!(projectile.r#type, 3);
+    assert_eq!(projectile.physics.position, Position(1));
+    assert_eq!(projectile.physics.velocity, Velocity(3));
+}
+
+mod behavior_test_harness {
+    use hecs_component_provider::{
+        behavior_test, default_trait_impl, ComponentProvider, ComponentProviderMut,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Health(i32);
+    struct Poisoned;
+
+    #[default_trait_impl]
+    trait TakePoisonDamage: ComponentProviderMut<Health> + ComponentProvider<Poisoned> {
+        fn take_poison_damage(&mut self) {
+            let health: &mut Health = self.get_mut();
+            health.0 -= 1;
+        }
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct PoisonedQuery<'a> {
+        health: &'a mut Health,
+        poisoned: &'a Poisoned,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct HealthView<'a> {
+        health: &'a Health,
+    }
+
+    #[behavior_test(
+        spawn((Health(10), Poisoned), (Health(10),), (Poisoned,)),
+        run(PoisonedQuery, |item| item.take_poison_damage()),
+    )]
+    fn behavior_test_test(poisoned: HealthView, healthy: &Health) {
+        let health: &Health = poisoned.get();
+        assert_eq!(*health, Health(9));
+        assert_eq!(*healthy, Health(10));
+        assert_eq!(world.len(), 3);
+    }
+
+    #[behavior_test(spawn((Poisoned,)), run(PoisonedQuery, |item| item.take_poison_damage()))]
+    #[should_panic(expected = "entity 0 doesn't match the query `&Health`")]
+    fn behavior_test_mismatch_test(_: &Health) {}
+}
+
+#[test]
+#[cfg(feature = "arbitrary")]
+fn arbitrary_bundle_test() {
+    use arbitrary::{Arbitrary, Unstructured};
+    use hecs_component_provider::{fuzz_behavior, ComponentProvider, ValidationError};
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[derive(Debug, PartialEq)]
+    struct Speed(u8);
+
+    impl<'a> Arbitrary<'a> for Speed {
+        fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+            u8::arbitrary(u).map(Speed)
+        }
+    }
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    #[provider(arbitrary)]
+    struct CarBundle(Speed, bool);
+
+    fn check_speed(car: &CarQuery) -> Result<(), ValidationError> {
+        if car.speed.0 > 200 {
+            return Err(ValidationError::new("too fast"));
+        }
+        Ok(())
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(validate = "check_speed")]
+    struct CarQuery<'a> {
+        speed: &'a mut Speed,
+    }
+
+    let CarBundle(speed, braking) = CarBundle::arbitrary(&mut Unstructured::new(&[7, 1])).unwrap();
+    assert_eq!(speed, Speed(7));
+    assert!(braking);
+
+    // worlds with entities that are invalid before the behavior runs are skipped
+    let data = [1, 250, 0, 1, 5, 0, 0];
+    fuzz_behavior::<CarBundle, CarQuery>(&data, |_| {});
+
+    let data = [1, 0, 150, 0];
+    fuzz_behavior::<CarBundle, CarQuery>(&data, |car| car.speed.0 = car.speed.0.saturating_add(10));
+    let error = panic::catch_unwind(AssertUnwindSafe(|| {
+        fuzz_behavior::<CarBundle, CarQuery>(&data, |car| car.speed.0 = 255)
+    }))
+    .unwrap_err();
+    let message = error.downcast::<String>().unwrap();
+    assert!(
+        message.ends_with("is invalid after the behavior: too fast"),
+        "{}",
+        message
+    );
+}
@@ -0,0 +1,1333 @@
+use hecs::World;
+
+#[test]
+fn reference_forwarding_test() {
+    use hecs_component_provider::{default_trait_impl, ComponentProvider, ComponentProviderMut};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32, i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Velocity(i32, i32);
+
+    #[default_trait_impl]
+    trait ApplyVelocity: ComponentProviderMut<Position> + ComponentProvider<Velocity> {
+        fn apply_velocity(&mut self) {
+            let &Velocity(vx, vy) = self.get();
+            let position: &mut Position = self.get_mut();
+            position.0 += vx;
+            position.1 += vy;
+        }
+    }
+
+    fn read_position(entity: &impl ComponentProvider<Position>) -> (i32, i32) {
+        let position: &Position = entity.get();
+        (position.0, position.1)
+    }
+
+    fn apply_twice(mut entity: impl ApplyVelocity) {
+        entity.apply_velocity();
+        entity.apply_velocity();
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct MovableQuery<'a> {
+        position: &'a mut Position,
+        velocity: &'a Velocity,
+    }
+
+    let mut world = World::new();
+    world.spawn((Position(10, 20), Velocity(1, 2)));
+
+    for mut entity in world.query_mut::<MovableQuery>() {
+        apply_twice(&mut entity);
+        assert_eq!(read_position(&&entity), (12, 24));
+        assert_eq!(read_position(&&mut entity), (12, 24));
+    }
+}
+
+#[test]
+fn chain_test() {
+    use hecs_component_provider::{
+        Chain, ComponentProvider, ComponentProviderOptional, ComponentProviderOptionalMut,
+    };
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Speed(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Armor(i32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct OverrideQuery<'a> {
+        speed: Option<&'a mut Speed>,
+        armor: Option<&'a mut Armor>,
+    }
+
+    let mut world = World::new();
+    let template = world.spawn((Speed(1), Armor(10)));
+    let entity = world.spawn((Speed(5),));
+
+    let mut template_query = world.query_one::<OverrideQuery>(template);
+    let mut template = template_query.get().unwrap();
+    let mut entity_query = world.query_one::<OverrideQuery>(entity);
+    let entity = entity_query.get().unwrap();
+
+    let mut chain = Chain(entity, &mut template);
+    assert_eq!(chain.get_optional(), Some(&Speed(5)));
+    assert_eq!(chain.get_optional(), Some(&Armor(10)));
+
+    let armor: Option<&mut Armor> = chain.get_optional_mut();
+    armor.unwrap().0 += 1;
+    let speed: Option<&mut Speed> = chain.get_optional_mut();
+    speed.unwrap().0 += 1;
+
+    assert_eq!(chain.1.armor, Some(&mut Armor(11)));
+    assert_eq!(chain.0.speed, Some(&mut Speed(6)));
+    assert_eq!(chain.1.speed, Some(&mut Speed(1)));
+}
+
+#[test]
+fn mutable_reference_behavior_test() {
+    use hecs_component_provider::{default_trait_impl, ComponentProviderMut};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Health(i32);
+
+    #[default_trait_impl]
+    trait TakeDamage: ComponentProviderMut<Health> {
+        fn take_damage(&mut self, amount: i32) {
+            let health: &mut Health = self.get_mut();
+            health.0 -= amount;
+        }
+    }
+
+    fn take_damage_by_value(mut entity: impl TakeDamage) {
+        entity.take_damage(1);
+    }
+
+    fn take_damage_by_reference(entity: &mut impl TakeDamage) {
+        entity.take_damage(2);
+    }
+
+    fn take_damage_generic<T: TakeDamage>(mut entity: T) {
+        entity.take_damage(3);
+    }
+
+    #[derive(hecs::Query, hecs_component_provider::ComponentProvider)]
+    struct HealthQuery<'a> {
+        health: &'a mut Health,
+    }
+
+    let mut world = World::new();
+    world.spawn((Health(10),));
+
+    for mut entity in world.query_mut::<HealthQuery>() {
+        take_damage_by_value(&mut entity);
+        take_damage_by_reference(&mut entity);
+        take_damage_generic(&mut &mut entity);
+        assert_eq!(*entity.health, Health(4));
+    }
+}
+
+#[test]
+fn get_many_test() {
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMany};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Acceleration(i32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct MovableQuery<'a> {
+        position: &'a mut Position,
+        velocity: &'a mut Velocity,
+        acceleration: &'a Acceleration,
+    }
+
+    let mut world = World::new();
+    world.spawn((Position(0), Velocity(1), Acceleration(2)));
+
+    for mut entity in world.query_mut::<MovableQuery>() {
+        let (position, velocity, acceleration) =
+            entity.get_many_mut::<(&mut Position, &mut Velocity, &Acceleration)>();
+        velocity.0 += acceleration.0;
+        position.0 += velocity.0;
+
+        let (position, velocity) = entity.get_many::<(Position, Velocity)>();
+        assert_eq!((position, velocity), (&Position(3), &Velocity(3)));
+    }
+}
+
+#[test]
+#[should_panic(expected = "borrowed mutably more than once")]
+fn get_many_mut_aliasing_test() {
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMany};
+
+    struct Position;
+
+    #[derive(hecs::Bundle, ComponentProvider)]
+    struct Bundle {
+        position: Position,
+    }
+
+    let mut entity = Bundle { position: Position };
+    let _ = entity.get_many_mut::<(&mut Position, &Position)>();
+}
+
+#[test]
+fn world_fallback_test() {
+    use hecs_component_provider::{ComponentProvider, ComponentProviderOptional, WorldFallback};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Name(&'static str);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Title(&'static str);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct NameQuery<'a> {
+        name: &'a Name,
+    }
+
+    let mut world = World::new();
+    let titled = world.spawn((Name("a"), Title("Sir")));
+    let untitled = world.spawn((Name("b"),));
+
+    let mut query = world.query_one::<NameQuery>(titled);
+    let item = WorldFallback::new(&world, titled, query.get().unwrap());
+    let name: &Name = item.get();
+    assert_eq!(name, &Name("a"));
+    assert_eq!(item.get_optional(), Some(&Title("Sir")));
+    assert_eq!(item.get_optional(), Some(&Title("Sir")));
+    drop(item);
+    drop(query);
+
+    let mut query = world.query_one::<NameQuery>(untitled);
+    let item = WorldFallback::new(&world, untitled, query.get().unwrap());
+    let title: Option<&Title> = item.get_optional();
+    assert_eq!(title, None);
+}
+
+#[test]
+fn typed_entity_test() {
+    use hecs::QueryOneError;
+    use hecs_component_provider::{ComponentProvider, TypedEntity};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, Eq, PartialEq)]
+    struct Velocity(i32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct MovableQuery<'a> {
+        position: &'a mut Position,
+        velocity: &'a Velocity,
+    }
+
+    let mut world = World::new();
+    let movable = world.spawn((Position(0), Velocity(2)));
+    let fixed = world.spawn((Position(0),));
+
+    assert!(TypedEntity::<MovableQuery>::new(&world, fixed).is_none());
+    let typed = TypedEntity::<MovableQuery>::new(&world, movable).unwrap();
+    assert_eq!(typed.entity(), movable);
+
+    let moved = typed.with_mut(&mut world, |item| {
+        item.position.0 += item.velocity.0;
+        item.position.0
+    });
+    assert_eq!(moved, Ok(2));
+    assert_eq!(typed.with(&world, |item| item.position.0), Ok(2));
+
+    world.remove_one::<Velocity>(movable).unwrap();
+    assert_eq!(typed.with(&world, |_| ()), Err(QueryOneError::Unsatisfied));
+    world.despawn(movable).unwrap();
+    assert_eq!(
+        typed.with_mut(&mut world, |_| ()),
+        Err(QueryOneError::NoSuchEntity)
+    );
+}
+
+#[test]
+fn world_typed_test() {
+    use hecs_component_provider::{ComponentProvider, WorldExt};
+
+    struct Position;
+    struct Velocity;
+    struct Frozen;
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct MovableQuery<'a> {
+        position: &'a mut Position,
+        velocity: &'a Velocity,
+        frozen: Option<&'a Frozen>,
+    }
+
+    let mut world = World::new();
+    let empty = world.spawn(());
+    let movable = world.spawn((Position, Velocity));
+
+    let error = world.typed::<MovableQuery>(empty).unwrap_err();
+    assert!(error.exists());
+    let missing: Vec<_> = error.components().iter().map(|c| c.type_name).collect();
+    assert_eq!(missing.len(), 2);
+    assert!(missing[0].ends_with("Position") && missing[1].ends_with("Velocity"));
+    assert!(error.to_string().contains("missing `"));
+
+    let typed = world.typed::<MovableQuery>(movable).unwrap();
+    assert_eq!(typed.entity(), movable);
+
+    let matching: Vec<_> = world
+        .iter_behavior::<MovableQuery>()
+        .map(|(entity, item)| (entity, item.frozen.is_some()))
+        .collect();
+    assert_eq!(matching, [(movable, false)]);
+
+    world.despawn(movable).unwrap();
+    let error = world.typed::<MovableQuery>(movable).unwrap_err();
+    assert!(!error.exists());
+    assert_eq!(error.components().len(), 2);
+}
+
+#[test]
+fn near_misses_test() {
+    use hecs_component_provider::{ComponentProvider, WorldExt};
+
+    struct Position;
+    struct Velocity;
+    struct Mass;
+    struct Frozen;
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct PhysicsQuery<'a> {
+        position: &'a mut Position,
+        velocity: &'a Velocity,
+        mass: &'a Mass,
+        frozen: Option<&'a Frozen>,
+    }
+
+    let mut world = World::new();
+    world.spawn((Position, Velocity, Mass));
+    world.spawn((Frozen,));
+    world.spawn(());
+    let no_mass = world.spawn((Position, Velocity, Frozen));
+    let only_mass = world.spawn((Mass,));
+    // reuses the id of the despawned entity with a new generation
+    let despawned = world.spawn((Mass,));
+    world.despawn(despawned).unwrap();
+    let respawned = world.spawn((Mass,));
+
+    let mut near_misses = world.near_misses::<PhysicsQuery>();
+    near_misses.sort_by_key(|m| m.entity().id());
+    let entities: Vec<_> = near_misses.iter().map(|m| m.entity()).collect();
+    assert_eq!(entities, [no_mass, only_mass, respawned]);
+    assert_ne!(respawned, despawned);
+    assert_eq!(near_misses[0].components().len(), 1);
+    assert!(near_misses[0].components()[0].type_name.ends_with("Mass"));
+    assert_eq!(near_misses[1].components().len(), 2);
+
+    assert!(world.near_misses::<&Position>().is_empty());
+}
+
+#[test]
+fn union_query_test() {
+    use hecs_component_provider::{
+        gen_union_query, ComponentProvider, ComponentProviderMut, QueryAccess,
+    };
+    use std::any::TypeId;
+
+    struct Position(i32);
+    struct Score(u32);
+    struct Aggression;
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct PlayerQuery<'a> {
+        position: &'a mut Position,
+        score: &'a mut Score,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct MonsterQuery<'a> {
+        position: &'a mut Position,
+        aggression: &'a Aggression,
+        score: Option<&'a Score>,
+    }
+
+    gen_union_query!(Actor, Player => PlayerQuery, Monster => MonsterQuery);
+
+    let mut world = World::new();
+    let player = world.spawn((Position(0), Score(3)));
+    let monster = world.spawn((Position(0), Aggression));
+    // matches both queries, so is yielded as the first variant
+    let both = world.spawn((Position(0), Score(1), Aggression));
+    world.spawn((Position(0),));
+
+    let mut kinds = Vec::new();
+    for (entity, mut actor) in world.query_mut::<(hecs::Entity, Actor)>() {
+        let position: &mut Position = actor.get_mut();
+        position.0 += 1;
+        match actor {
+            Actor::Player(item) => kinds.push((entity, "player", Some(item.score.0))),
+            Actor::Monster(item) => kinds.push((entity, "monster", item.score.map(|s| s.0))),
+        }
+    }
+    kinds.sort_by_key(|(e, _, _)| e.id());
+    assert_eq!(
+        kinds,
+        [
+            (player, "player", Some(3)),
+            (monster, "monster", None),
+            (both, "player", Some(1)),
+        ]
+    );
+    assert_eq!(
+        world
+            .query_mut::<&Position>()
+            .into_iter()
+            .filter(|p| p.0 == 1)
+            .count(),
+        3
+    );
+
+    let access = Actor::access();
+    let find = |id| access.iter().find(|a| a.type_id == id).unwrap();
+    assert_eq!(access.len(), 3);
+    assert!(find(TypeId::of::<Position>()).required);
+    assert!(find(TypeId::of::<Position>()).mutable);
+    assert!(!find(TypeId::of::<Score>()).required);
+    assert!(find(TypeId::of::<Score>()).mutable);
+    assert!(!find(TypeId::of::<Aggression>()).required);
+}
+
+#[test]
+fn behaviors_adapter_test() {
+    use hecs_component_provider::WorldExt;
+
+    struct Score(u32);
+
+    let mut world = World::new();
+    let entities: Vec<_> = (0..4).map(|i| world.spawn((Score(i),))).collect();
+
+    let bonuses = [10, 20];
+    for (score, bonus) in world.behaviors::<&mut Score>().into_iter().zip(bonuses) {
+        score.0 += bonus;
+    }
+    let mut scores: Vec<_> = world
+        .behaviors::<&Score>()
+        .with_entities()
+        .map(|(entity, score)| (entity, score.0))
+        .collect();
+    scores.sort_unstable();
+    assert_eq!(scores.len(), entities.len());
+    assert_eq!(scores.iter().map(|s| s.1).sum::<u32>(), 6 + 30);
+    assert!(scores.iter().all(|(entity, _)| entities.contains(entity)));
+}
+
+#[test]
+fn behavior_chunks_test() {
+    use hecs_component_provider::{ComponentProvider, WorldExt};
+
+    struct Position(i32);
+    struct Frozen;
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct PositionQuery<'a> {
+        position: &'a mut Position,
+    }
+
+    fn assert_send<T: Send>(value: T) -> T {
+        value
+    }
+
+    let mut world = World::new();
+    for _ in 0..5 {
+        world.spawn((Position(0),));
+    }
+    for _ in 0..3 {
+        world.spawn((Position(0), Frozen));
+    }
+
+    let sizes: Vec<usize> = world
+        .behaviors::<PositionQuery>()
+        .archetype_chunks()
+        .map(|chunk| chunk.count())
+        .collect();
+    assert_eq!(sizes.iter().sum::<usize>(), 8);
+    assert_eq!(sizes.len(), 2);
+
+    // chunks never span archetypes
+    let chunks = assert_send(world.behaviors::<PositionQuery>().chunks(2));
+    let mut sizes: Vec<usize> = std::thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .map(|chunk| scope.spawn(move || chunk.map(|item| item.position.0 += 1).count()))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    sizes.sort_unstable();
+    assert_eq!(sizes, [1, 1, 2, 2, 2]);
+    assert!(world.query_mut::<&Position>().into_iter().all(|p| p.0 == 1));
+}
+
+#[test]
+fn world_entity_test() {
+    use hecs_component_provider::{
+        ComponentProvider, ComponentProviderMut, ComponentProviderOptional,
+        ComponentProviderOptionalMut, WorldEntity,
+    };
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+    struct Frozen;
+
+    fn step(item: &mut (impl ComponentProviderMut<Position> + ComponentProvider<Velocity>)) {
+        let &Velocity(v) = item.get();
+        let position: &mut Position = item.get_mut();
+        position.0 += v;
+    }
+
+    let mut world = World::new();
+    let entity = world.spawn((Position(1), Velocity(2)));
+
+    let mut item = WorldEntity::new(&mut world, entity);
+    assert_eq!(item.entity(), entity);
+    step(&mut item);
+
+    // several components can be borrowed at once
+    let position: &Position = item.get();
+    let velocity: &Velocity = item.get();
+    assert_eq!((position, velocity), (&Position(3), &Velocity(2)));
+    assert!(ComponentProviderOptional::<Frozen>::get_optional(&item).is_none());
+    assert!(ComponentProviderOptionalMut::<Frozen>::get_optional_mut(&mut item).is_none());
+
+    item.world_mut().insert_one(entity, Frozen).unwrap();
+    assert!(ComponentProviderOptional::<Frozen>::get_optional(&item).is_some());
+
+    let world = item.into_world();
+    world.remove_one::<Velocity>(entity).unwrap();
+    let mut item = WorldEntity::new(world, entity);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _: &mut Velocity = item.get_mut();
+    }));
+    let message = *result.unwrap_err().downcast::<String>().unwrap();
+    assert!(message.contains("doesn't have the component"));
+    assert!(message.contains("Velocity"));
+}
+
+#[test]
+fn dyn_world_provider_test() {
+    use hecs_component_provider::DynWorldProvider;
+    use std::any::TypeId;
+    use std::panic::{self, AssertUnwindSafe};
+
+    struct Position(i32);
+    struct Velocity(i32);
+    struct Unregistered;
+
+    let mut world = World::new();
+    let a = world.spawn((Position(1), Velocity(2)));
+    let b = world.spawn((Position(3),));
+    let empty = world.spawn((Unregistered,));
+
+    let mut provider = DynWorldProvider::new(&world);
+    provider.register::<Position>().register::<Velocity>();
+    let (position, velocity) = (TypeId::of::<Position>(), TypeId::of::<Velocity>());
+    assert!(provider.is_registered(position));
+    assert!(!provider.is_registered(TypeId::of::<Unregistered>()));
+    assert!(provider.type_name(velocity).unwrap().ends_with("Velocity"));
+    assert!(provider.get(empty, position).is_none());
+
+    let panic_message = |f: &dyn Fn()| {
+        let payload = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+        *payload.downcast::<String>().unwrap()
+    };
+
+    {
+        // shared borrows of one type, alongside a mutable borrow of another
+        let a_position = provider.get(a, position).unwrap();
+        let b_position = provider.get(b, position).unwrap();
+        let mut a_velocity = provider.get_mut(a, velocity).unwrap();
+        a_velocity.downcast_mut::<Velocity>().unwrap().0 +=
+            a_position.downcast_ref::<Position>().unwrap().0
+                + b_position.downcast_ref::<Position>().unwrap().0;
+
+        let message = panic_message(&|| drop(provider.get_mut(b, position)));
+        assert!(message.contains("Position") && message.contains("borrowed immutably"));
+        let message = panic_message(&|| drop(provider.get(a, velocity)));
+        assert!(message.contains("Velocity") && message.contains("already borrowed mutably"));
+    }
+
+    // borrows end when the guards are dropped
+    let mut a_position = provider.get_mut(a, position).unwrap();
+    a_position.downcast_mut::<Position>().unwrap().0 = 10;
+    drop(a_position);
+    assert_eq!(
+        provider
+            .get(a, velocity)
+            .unwrap()
+            .downcast_ref::<Velocity>()
+            .unwrap()
+            .0,
+        6
+    );
+
+    let message = panic_message(&|| drop(provider.get(empty, TypeId::of::<Unregistered>())));
+    assert!(message.contains("isn't registered"));
+    assert_eq!(provider.world().get::<&Position>(a).unwrap().0, 10);
+}
+
+#[test]
+#[cfg(feature = "debug-borrows")]
+#[should_panic(expected = "can't be borrowed mutably at tests/providers.rs")]
+fn debug_borrows_test() {
+    use hecs_component_provider::{ComponentProviderOptional, DynWorldProvider, WorldFallback};
+    use std::any::TypeId;
+
+    struct Health;
+
+    let mut world = World::new();
+    let entity = world.spawn((Health,));
+
+    let mut provider = DynWorldProvider::new(&world);
+    provider.register::<Health>();
+    let fallback = WorldFallback::new(&world, entity, ());
+    let _: Option<&Health> = fallback.get_optional();
+    // reports the location of the fallback's borrow as well
+    let _ = provider.get_mut(entity, TypeId::of::<Health>());
+}
+
+#[test]
+fn golden_test() {
+    use hecs_component_provider::{Golden, GoldenError};
+
+    #[derive(Debug)]
+    struct Health(i32);
+    #[derive(Debug)]
+    struct Armor;
+
+    let mut world = World::new();
+    let hero = world.spawn((Health(10), Armor));
+    let goblin = world.spawn((Health(3),));
+
+    let mut golden = Golden::new();
+    golden.register::<Health>().register_as::<Armor>("armor");
+    let path = std::env::temp_dir()
+        .join(format!(
+            "hecs_component_provider_golden_{}",
+            std::process::id()
+        ))
+        .join("combat.txt");
+
+    let entities = [("hero", hero), ("goblin", goblin)];
+    let error = golden.check(&world, entities, &path).unwrap_err();
+    assert!(matches!(error, GoldenError::Missing(_)));
+
+    golden.bless(&world, entities, &path).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "hero\n  Health: Health(10)\n  armor: Armor\ngoblin\n  Health: Health(3)\n"
+    );
+    golden.assert_matches(&world, entities, &path);
+
+    world.get::<&mut Health>(hero).unwrap().0 = 7;
+    world.despawn(goblin).unwrap();
+    let error = golden.check(&world, entities, &path).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("differs at line 2"), "{}", message);
+    assert!(
+        message.contains("expected:   Health: Health(10)"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("actual:   Health: Health(7)"),
+        "{}",
+        message
+    );
+    assert!(golden
+        .render(&world, entities)
+        .ends_with("goblin\n  <despawned>\n"));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+#[cfg(feature = "proptest")]
+fn world_strategy_test() {
+    use hecs_component_provider::{ComponentProvider, WorldStrategy};
+    use proptest::prelude::*;
+    use proptest::test_runner::TestRunner;
+    use std::collections::HashSet;
+
+    #[derive(Clone, Debug)]
+    struct Health(i32);
+    #[derive(Clone, Debug)]
+    struct Armor;
+    #[derive(Clone, Debug)]
+    struct Poisoned;
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct PoisonedQuery<'a> {
+        health: &'a mut Health,
+        poisoned: &'a Poisoned,
+    }
+
+    let strategy = WorldStrategy::new()
+        .component((0..100).prop_map(Health))
+        .component(Just(Armor))
+        .component(Just(Poisoned))
+        .matching::<PoisonedQuery>(2..4)
+        .noise(0..3)
+        .strategy();
+
+    let mut archetypes = HashSet::new();
+    let mut runner = TestRunner::deterministic();
+    for _ in 0..50 {
+        let generated = strategy.new_tree(&mut runner).unwrap().current();
+        let mut world = generated.build();
+        assert_eq!(world.len() as usize, generated.len());
+        let matching = world.query_mut::<PoisonedQuery>().into_iter().count();
+        assert!((2..4 + 3).contains(&matching));
+        assert!(generated.len() < 4 + 3);
+        assert!(world
+            .query_mut::<&Health>()
+            .into_iter()
+            .all(|health| (0..100).contains(&health.0)));
+        for entity in world.iter() {
+            archetypes.insert((
+                entity.has::<Health>(),
+                entity.has::<Armor>(),
+                entity.has::<Poisoned>(),
+            ));
+        }
+    }
+    // matching entities with and without armor, as well as noise
+    assert!(archetypes.contains(&(true, true, true)));
+    assert!(archetypes.contains(&(true, false, true)));
+    assert!(archetypes.len() > 2);
+
+    let missing = std::panic::catch_unwind(|| {
+        WorldStrategy::new()
+            .component(Just(Armor))
+            .matching::<PoisonedQuery>(1)
+            .strategy()
+    });
+    let message = missing.err().unwrap().downcast::<String>().unwrap();
+    assert!(
+        message.contains("Health` is required by a matching query"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn params_reload_test() {
+    use hecs_component_provider::{
+        default_trait_impl, gen_composed_component_providers, ComponentProvider,
+        ComponentProviderMut, Params, ParamsError,
+    };
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    struct MoveParams {
+        speed: i32,
+    }
+
+    #[default_trait_impl]
+    trait Move: ComponentProviderMut<Position> + ComponentProvider<MoveParams> {
+        fn step(&mut self) {
+            let speed = ComponentProvider::<MoveParams>::get(self).speed;
+            let position: &mut Position = self.get_mut();
+            position.0 += speed;
+        }
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct PositionQuery<'a> {
+        position: &'a mut Position,
+    }
+
+    gen_composed_component_providers!(
+        WithParams<'a>(PositionQuery<'a>, &'a Params<MoveParams>) {
+            0 => [mut Position],
+            1 => [MoveParams],
+        }
+    );
+
+    let path = std::env::temp_dir().join(format!("params_reload_test_{}", std::process::id()));
+    let write = |contents: &str, age_secs: u64| {
+        fs::write(&path, contents).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age_secs);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    };
+
+    write("1", 20);
+    let parse = |s: &str| s.trim().parse().map(|speed| MoveParams { speed });
+    let mut params = Params::load(&path, parse).unwrap();
+
+    let mut world = World::new();
+    let entity = world.spawn((Position(0),));
+    let mut run = |params: &Params<MoveParams>| {
+        for item in world.query_mut::<PositionQuery>() {
+            WithParams(item, params).step();
+        }
+    };
+
+    run(&params);
+    assert!(!params.reload_if_changed().unwrap());
+
+    write("5", 10);
+    assert!(params.reload_if_changed().unwrap());
+    assert_eq!(params.version(), 1);
+    run(&params);
+
+    // invalid contents keep the previous parameters
+    write("fast", 0);
+    assert!(matches!(
+        params.reload_if_changed(),
+        Err(ParamsError::Parse(_))
+    ));
+    assert_eq!(params.speed, 5);
+    assert!(!params.reload_if_changed().unwrap());
+
+    fs::remove_file(&path).unwrap();
+    assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(6));
+}
+
+#[test]
+fn entity_rng_test() {
+    use hecs_component_provider::RngSeed;
+
+    let mut world = World::new();
+    let a = world.spawn(());
+    let b = world.spawn(());
+
+    let mut seed = RngSeed::new(7);
+    let first = seed.rng_for(a).next_u64();
+    assert_eq!(seed.rng_for(a).next_u64(), first);
+    assert_ne!(seed.rng_for(b).next_u64(), first);
+    assert_ne!(RngSeed::new(8).rng_for(a).next_u64(), first);
+
+    seed.advance();
+    assert_eq!(seed.tick(), 1);
+    assert_ne!(seed.rng_for(a).next_u64(), first);
+
+    let mut rng = seed.rng_for(a);
+    for _ in 0..1000 {
+        assert!((10..20).contains(&rng.range(10..20)));
+        let f = rng.next_f32();
+        assert!((0.0..1.0).contains(&f));
+    }
+    assert!(!rng.chance(0.0) && rng.chance(1.0));
+}
+
+#[test]
+fn snapshot_test() {
+    use hecs_component_provider::{ComponentProvider, ComponentProviderMut, Snapshot};
+
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    struct Position(i32);
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    struct Velocity(i32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(owned(MovableBundle))]
+    struct MovableQuery<'a> {
+        position: &'a mut Position,
+        velocity: &'a Velocity,
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((Position(1), Velocity(10)));
+    let b = world.spawn((Position(2), Velocity(20)));
+    world.spawn((Position(3),));
+
+    let mut snapshot = Snapshot::<MovableQuery>::take(&world);
+    assert_eq!(snapshot.len(), 2);
+    assert!(!snapshot.is_empty());
+    assert_eq!(snapshot.get(b).unwrap().velocity, Velocity(20));
+
+    for (_, item) in snapshot.iter_mut() {
+        let position: &mut Position = item.get_mut();
+        position.0 += 100;
+    }
+    let positions: Vec<_> = snapshot
+        .iter()
+        .map(|(e, item)| (e, item.position.0))
+        .collect();
+    assert!(positions.contains(&(a, 101)) && positions.contains(&(b, 102)));
+    assert_eq!(snapshot.entities().len(), snapshot.items().len());
+
+    // the world is unchanged
+    assert_eq!(*world.get::<&Position>(a).unwrap(), Position(1));
+    assert!(Snapshot::<MovableQuery>::take(&World::new()).is_empty());
+
+    // per-observer snapshots only contain what each observer can see
+    let observers = [(a, 0), (b, 15)];
+    for (observer, min_velocity) in observers {
+        let visible = Snapshot::<MovableQuery>::take_filtered(&world, |entity, item| {
+            let velocity: &Velocity = item.get();
+            entity == observer || velocity.0 >= min_velocity
+        });
+        assert!(visible.get(observer).is_some());
+        assert_eq!(visible.len(), if min_velocity == 0 { 2 } else { 1 });
+    }
+    let mut none = Snapshot::<MovableQuery>::take_filtered(&world, |_, _| false).into_iter();
+    assert!(none.next().is_none());
+}
+
+#[test]
+fn double_buffered_test() {
+    use hecs_component_provider::{
+        default_trait_impl, flip, BufferedProvider, ComponentProvider, DoubleBuffered,
+    };
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Alive(bool);
+    #[derive(Clone, Debug, PartialEq)]
+    struct Age(u32);
+
+    #[default_trait_impl]
+    trait Blink: BufferedProvider<Alive> + BufferedProvider<Age> {
+        fn blink(&mut self) {
+            let &Alive(alive) = self.read();
+            let &Age(age) = self.read();
+            let next_alive: &mut Alive = self.write();
+            next_alive.0 = !alive;
+            let next_age: &mut Age = self.write();
+            next_age.0 = age + 1;
+        }
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct CellQuery<'a> {
+        alive: &'a mut DoubleBuffered<Alive>,
+        age: &'a mut DoubleBuffered<Age>,
+    }
+
+    let mut world = World::new();
+    let cell = world.spawn((
+        DoubleBuffered::new(Alive(false)),
+        DoubleBuffered::new(Age(0)),
+    ));
+
+    for frame in 1..=3 {
+        for mut item in world.query_mut::<CellQuery>() {
+            // every call sees the same previous state, so calling twice doesn't toggle back
+            item.blink();
+            item.blink();
+        }
+        flip::<Alive>(&mut world);
+        flip::<Age>(&mut world);
+
+        let item = world.query_one_mut::<CellQuery>(cell).unwrap();
+        assert_eq!(*item.alive.read(), Alive(frame % 2 == 1));
+        assert_eq!(*item.age.read(), Age(frame));
+    }
+
+    let mut buffered = DoubleBuffered::new(Age(1));
+    buffered.write().0 = 2;
+    assert_eq!(*buffered.read(), Age(1));
+    buffered.flip();
+    assert_eq!(*buffered.read(), Age(2));
+    // the next value starts from the current one
+    assert_eq!(*buffered.write(), Age(2));
+}
+
+#[test]
+fn save_game_test() {
+    use hecs::Entity;
+    use hecs_component_provider::{SaveError, SaveGame};
+    use std::convert::TryInto;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(i32);
+    struct Mana(i32);
+    #[derive(Debug, PartialEq)]
+    struct Target(Entity);
+    struct Transient;
+
+    fn encode_i32(value: i32) -> Result<Vec<u8>, String> {
+        Ok(value.to_le_bytes().to_vec())
+    }
+    fn decode_i32(bytes: &[u8]) -> Result<i32, String> {
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| "expected 4 bytes".to_string())?;
+        Ok(i32::from_le_bytes(bytes))
+    }
+
+    // version 1 called health "hp" and had mana
+    let mut v1 = SaveGame::new(1);
+    v1.register::<Health, _, _>("hp", |h| encode_i32(h.0), |b| decode_i32(b).map(Health))
+        .register::<Mana, _, _>("mana", |m| encode_i32(m.0), |b| decode_i32(b).map(Mana));
+
+    let mut v2 = SaveGame::new(2);
+    v2.register::<Health, _, _>("health", |h| encode_i32(h.0), |b| decode_i32(b).map(Health))
+        .register::<Target, _, _>(
+            "target",
+            |t| Ok::<_, String>(t.0.to_bits().get().to_le_bytes().to_vec()),
+            |b| {
+                let bits = u64::from_le_bytes(b.try_into().map_err(|_| "expected 8 bytes")?);
+                Entity::from_bits(bits).map(Target).ok_or("invalid entity")
+            },
+        )
+        .add_migration(1, |components| {
+            for component in components.iter_mut() {
+                if component.name == "hp" {
+                    component.name = "health".to_string();
+                }
+            }
+        });
+    assert_eq!(v2.version(), 2);
+
+    let mut world = World::new();
+    world.spawn((Transient,));
+    let mage = world.spawn((Health(5), Mana(20)));
+    let mut v1_save = Vec::new();
+    v1.save(&world, &mut v1_save).unwrap();
+
+    let loaded = v2.load(&v1_save[..]).unwrap();
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(*loaded.get::<&Health>(mage).unwrap(), Health(5));
+    // mana is no longer registered, so it was skipped
+    assert!(loaded.get::<&Mana>(mage).is_err());
+
+    let mut world = World::new();
+    let goblin = world.spawn((Health(3),));
+    let archer = world.spawn((Health(7), Target(goblin)));
+    world.despawn(goblin).unwrap();
+    let goblin = world.spawn((Health(4),));
+    world.insert_one(archer, Target(goblin)).unwrap();
+    let mut v2_save = Vec::new();
+    v2.save(&world, &mut v2_save).unwrap();
+    let loaded = v2.load(&v2_save[..]).unwrap();
+    // entity handles, including generations, are preserved
+    assert_eq!(*loaded.get::<&Target>(archer).unwrap(), Target(goblin));
+    assert_eq!(*loaded.get::<&Health>(goblin).unwrap(), Health(4));
+
+    assert!(matches!(
+        v1.load(&v2_save[..]),
+        Err(SaveError::UnsupportedVersion {
+            found: 2,
+            supported: 1
+        })
+    ));
+    assert!(matches!(
+        v2.load(&b"not a save"[..]),
+        Err(SaveError::InvalidFormat)
+    ));
+    assert!(matches!(
+        v2.load(&v2_save[..v2_save.len() - 1]),
+        Err(SaveError::Io(_))
+    ));
+
+    let mut strict = SaveGame::new(2);
+    strict.register::<Health, _, _>(
+        "health",
+        |h| encode_i32(h.0),
+        |_| Err::<Health, _>("corrupt"),
+    );
+    match strict.load(&v2_save[..]) {
+        Err(error @ SaveError::Component { .. }) => {
+            assert_eq!(
+                error.to_string(),
+                "failed to encode or decode component `health`: corrupt"
+            )
+        }
+        _ => panic!("expected a component error"),
+    }
+}
+
+#[test]
+fn hash_world_test() {
+    use hecs_component_provider::{hash_world, SaveError, SaveGame};
+
+    struct Health(u8);
+    struct Armor(u8);
+
+    fn registry(armor_first: bool) -> SaveGame {
+        let mut registry = SaveGame::new(1);
+        let register_health = |registry: &mut SaveGame| {
+            registry.register::<Health, _, _>(
+                "health",
+                |health| Ok::<_, SaveError>(vec![health.0]),
+                |bytes| Ok::<_, SaveError>(Health(bytes[0])),
+            );
+        };
+        if !armor_first {
+            register_health(&mut registry);
+        }
+        registry.register::<Armor, _, _>(
+            "armor",
+            |armor| Ok::<_, SaveError>(vec![armor.0]),
+            |bytes| Ok::<_, SaveError>(Armor(bytes[0])),
+        );
+        if armor_first {
+            register_health(&mut registry);
+        }
+        registry
+    }
+
+    let mut a = World::new();
+    let hero = a.spawn((Health(10), Armor(2)));
+    a.spawn((Health(3),));
+    a.spawn(("unregistered",));
+
+    // the same entities, stored in different archetype order, hash the same
+    let mut b = World::new();
+    b.spawn_at(hero, (Armor(2), Health(10), "unregistered"));
+    let goblin = b.spawn((Health(3),));
+    assert_eq!(
+        hash_world(&a, &registry(false)).unwrap(),
+        hash_world(&b, &registry(true)).unwrap()
+    );
+
+    b.get::<&mut Health>(goblin).unwrap().0 = 4;
+    assert_ne!(
+        hash_world(&a, &registry(false)).unwrap(),
+        hash_world(&b, &registry(false)).unwrap()
+    );
+}
+
+#[cfg(feature = "resources")]
+#[test]
+fn resources_test() {
+    use hecs_component_provider::{
+        default_trait_impl, gen_composed_component_providers, ComponentProvider,
+        ComponentProviderMut,
+    };
+    use resources::{Ref, Resources};
+
+    struct Multiplier(u32);
+    struct Score(u32);
+    struct Coin(u32);
+
+    #[default_trait_impl]
+    trait Value: ComponentProvider<Coin> + ComponentProvider<Multiplier> {
+        fn value(&self) -> u32 {
+            let &Coin(value) = self.get();
+            let &Multiplier(multiplier) = self.get();
+            value * multiplier
+        }
+    }
+
+    #[default_trait_impl]
+    trait AddScore: ComponentProviderMut<Score> {
+        fn add_score(&mut self, value: u32) {
+            let score: &mut Score = self.get_mut();
+            score.0 += value;
+        }
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct CoinQuery<'a> {
+        coin: &'a Coin,
+    }
+
+    gen_composed_component_providers!(
+        CoinWithMultiplier<'a>(CoinQuery<'a>, &'a Ref<'a, Multiplier>) {
+            0 => [Coin],
+            1 => [Multiplier],
+        }
+    );
+
+    let mut resources = Resources::default();
+    resources.insert(Multiplier(2));
+    resources.insert(Score(0));
+
+    let mut world = World::new();
+    world.spawn((Coin(5),));
+    world.spawn((Coin(10),));
+
+    let multiplier = resources.get::<Multiplier>().unwrap();
+    let mut score = resources.get_mut::<Score>().unwrap();
+    for item in world.query_mut::<CoinQuery>() {
+        let value = CoinWithMultiplier(item, &multiplier).value();
+        score.add_score(value);
+    }
+    let read_score: &Score = score.get();
+    assert_eq!(read_score.0, 30);
+}
+
+#[test]
+fn messages_test() {
+    use hecs_component_provider::{ComponentProvider, Inbox, MessageProvider, Outbox};
+
+    #[derive(Debug, PartialEq)]
+    struct Greeting(&'static str);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct InboxQuery<'a> {
+        inbox: &'a mut Inbox<Greeting>,
+    }
+
+    let mut world = World::new();
+    let alice = world.spawn((Inbox::<Greeting>::default(),));
+    let bob = world.spawn((Inbox::<Greeting>::default(),));
+    let mute = world.spawn(());
+    let gone = world.spawn((Inbox::<Greeting>::default(),));
+    world.despawn(gone).unwrap();
+
+    let outbox = Outbox::new();
+    outbox.send(alice, Greeting("hello"));
+    outbox.send(alice, Greeting("again"));
+    outbox.send(mute, Greeting("ignored"));
+    outbox.send(gone, Greeting("lost"));
+    assert_eq!(outbox.clone().pending(), 4);
+
+    assert_eq!(outbox.deliver(&mut world), 2);
+    assert_eq!(outbox.pending(), 0);
+    assert_eq!(world.get::<&Inbox<Greeting>>(bob).unwrap().len(), 0);
+
+    let mut item = world.query_one_mut::<InboxQuery>(alice).unwrap();
+    assert_eq!(item.messages(), [Greeting("hello"), Greeting("again")]);
+    let drained: Vec<_> = item.drain_messages().collect();
+    assert_eq!(drained, [Greeting("hello"), Greeting("again")]);
+    assert!(item.messages().is_empty());
+    assert!(item.inbox.is_empty());
+}
+
+#[test]
+fn random_access_cache_test() {
+    use hecs_component_provider::RandomAccessCache;
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Position(i32);
+    struct Target(hecs::Entity);
+    struct Aim(i32);
+
+    let mut world = World::new();
+    let enemy = world.spawn((Position(5),));
+    let turret = world.spawn((Target(enemy), Aim(0)));
+    let missing = world.spawn((Target(turret), Aim(0)));
+
+    let mut positions = RandomAccessCache::<&Position>::new();
+    let update_aims = |world: &World, positions: &mut RandomAccessCache<&Position>| {
+        let mut borrow = positions.borrow(world);
+        let view = borrow.view();
+        for (target, aim) in world.query::<(&Target, &mut Aim)>().iter() {
+            aim.0 = view.get(target.0).map_or(-1, |position| position.0);
+        }
+    };
+    update_aims(&world, &mut positions);
+    assert_eq!(world.get::<&Aim>(turret).unwrap().0, 5);
+    assert_eq!(world.get::<&Aim>(missing).unwrap().0, -1);
+
+    // the cache stays valid as entities move between archetypes and new ones are created
+    world.insert_one(turret, Position(1)).unwrap();
+    world.insert_one(enemy, true).unwrap();
+    *world.get::<&mut Position>(enemy).unwrap() = Position(7);
+    update_aims(&world, &mut positions);
+    assert_eq!(world.get::<&Aim>(turret).unwrap().0, 7);
+    assert_eq!(world.get::<&Aim>(missing).unwrap().0, 1);
+
+    world.despawn(enemy).unwrap();
+    let mut view = positions.view_mut(&mut world);
+    assert!(view.get_mut(enemy).is_none());
+    assert_eq!(view.get_mut(turret).map(|p| p.0), Some(1));
+}
+
+#[test]
+fn interior_mutability_test() {
+    use hecs_component_provider::{
+        Atomic, ComponentProvider, InteriorMutable, Locked, QueryAccess,
+    };
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct Hits(Vec<i32>);
+
+    struct Counter(AtomicU32);
+
+    impl InteriorMutable for Counter {}
+
+    type Accumulator = Atomic<u8>;
+
+    mod plain {
+        pub struct Atomic(pub u32);
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct TargetQuery<'a> {
+        damage: &'a Atomic<i16>,
+        hits: &'a Locked<Hits>,
+        heat: &'a mut Atomic<f32>,
+        armor: Option<&'a Atomic<bool>>,
+    }
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct CustomQuery<'a> {
+        counter: &'a Counter,
+        accumulator: &'a Accumulator,
+        plain: &'a plain::Atomic,
+    }
+
+    let interior: Vec<_> = CustomQuery::access()
+        .iter()
+        .map(|a| a.interior_mutable)
+        .collect();
+    assert_eq!(interior, [true, true, false]);
+
+    let mut world = World::new();
+    let counted = world.spawn((
+        Counter(AtomicU32::new(1)),
+        Atomic::new(2u8),
+        plain::Atomic(3),
+    ));
+    for item in world.query::<CustomQuery>().iter() {
+        item.counter.0.fetch_add(item.plain.0, Ordering::Relaxed);
+        item.accumulator.update(|a| a * 2);
+    }
+    let counter = world
+        .get::<&Counter>(counted)
+        .unwrap()
+        .0
+        .load(Ordering::Relaxed);
+    assert_eq!(counter, 4);
+    assert_eq!(world.get::<&Accumulator>(counted).unwrap().load(), 4);
+
+    let access = TargetQuery::access();
+    let flags: Vec<_> = access
+        .iter()
+        .map(|a| (a.mutable, a.interior_mutable, a.required))
+        .collect();
+    assert_eq!(
+        flags,
+        [
+            (false, true, true),
+            (false, true, true),
+            (true, true, true),
+            (false, true, false)
+        ]
+    );
+
+    let mut world = World::new();
+    let target = world.spawn((
+        Atomic::new(-3i16),
+        Locked::new(Hits(Vec::new())),
+        Atomic::new(0.5f32),
+    ));
+    for item in world.query::<TargetQuery>().iter() {
+        let damage: &Atomic<i16> = item.get();
+        assert_eq!(damage.update(|d| d - 2), -3);
+        assert_eq!(damage.swap(7), -5);
+        item.hits.with(|hits| hits.0.push(1));
+        item.hits.lock().0.push(2);
+        assert_eq!(item.heat.take(), 0.5);
+        assert!(item.armor.is_none());
+    }
+    assert_eq!(world.get::<&Atomic<i16>>(target).unwrap().load(), 7);
+    assert_eq!(world.get::<&Atomic<f32>>(target).unwrap().load(), 0.0);
+    let hits = world.remove_one::<Locked<Hits>>(target).unwrap();
+    assert_eq!(hits.into_inner().0, [1, 2]);
+}
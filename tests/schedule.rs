@@ -0,0 +1,1207 @@
+use hecs::World;
+use hecs_component_provider::{ComponentProvider, Schedule};
+
+#[derive(Debug, Eq, PartialEq)]
+struct Position(i32);
+#[derive(Debug, Eq, PartialEq)]
+struct Velocity(i32);
+
+#[derive(hecs::Query, ComponentProvider)]
+struct MovableQuery<'a> {
+    position: &'a mut Position,
+    velocity: &'a Velocity,
+}
+
+#[derive(hecs::Query, ComponentProvider)]
+struct PositionQuery<'a> {
+    position: &'a Position,
+}
+
+#[test]
+fn stats_test() {
+    let mut world = World::new();
+    world.spawn((Position(0), Velocity(1)));
+    world.spawn((Position(0), Velocity(2)));
+    world.spawn((Position(0),));
+
+    let mut schedule = Schedule::new();
+    schedule
+        .add_behavior::<MovableQuery, _>("move", |item| item.position.0 += item.velocity.0)
+        .add_behavior::<PositionQuery, _>("read", |_| {})
+        .add_system("spawn", |world| {
+            world.spawn((Velocity(0),));
+        });
+    schedule.run(&mut world);
+    schedule.run(&mut world);
+
+    let stats = schedule.stats();
+    let names: Vec<_> = stats.behaviors().iter().map(|b| b.name).collect();
+    assert_eq!(names, ["move", "read", "spawn"]);
+
+    let moved = stats.get("move").unwrap();
+    assert_eq!((moved.frames, moved.entities, moved.mutations), (2, 2, 2));
+    assert!(moved.total_duration >= moved.duration);
+    let read = stats.get("read").unwrap();
+    assert_eq!((read.entities, read.mutations), (3, 0));
+    assert_eq!(world.len(), 5);
+
+    let mut csv = Vec::new();
+    stats.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("name,frames,duration_us,total_duration_us,entities,mutations")
+    );
+    assert!(lines.next().unwrap().starts_with("\"move\",2,"));
+    assert_eq!(lines.count(), 2);
+}
+
+#[test]
+fn run_with_budget_test() {
+    use std::time::Duration;
+
+    let mut world = World::new();
+    for _ in 0..3 {
+        world.spawn((Position(0), Velocity(1)));
+    }
+    world.spawn((Position(0), Velocity(1), true));
+
+    let mut schedule = Schedule::new();
+    schedule
+        .add_behavior::<MovableQuery, _>("move", |item| item.position.0 += item.velocity.0)
+        .add_behavior::<PositionQuery, _>("read", |_| {});
+
+    // a zero budget still processes one entity per call, and each behavior matches four entities
+    let mut calls = 0;
+    while !schedule.run_with_budget(&mut world, Duration::from_secs(0)) {
+        calls += 1;
+        let resume = schedule.resume_point().unwrap();
+        assert!(resume.behavior <= 1);
+    }
+    assert_eq!(calls, 7);
+    assert_eq!(schedule.resume_point(), None);
+
+    let positions: Vec<_> = world
+        .query_mut::<&Position>()
+        .into_iter()
+        .map(|p| p.0)
+        .collect();
+    assert_eq!(positions, [1, 1, 1, 1]);
+
+    assert!(schedule.run_with_budget(&mut world, Duration::from_secs(60)));
+    assert_eq!(schedule.stats().get("move").unwrap().entities, 4);
+}
+
+#[test]
+fn incremental_runner_test() {
+    use hecs_component_provider::IncrementalRunner;
+
+    let mut world = World::new();
+    let entities: Vec<_> = (0..4)
+        .map(|_| world.spawn((Position(0), Velocity(1))))
+        .collect();
+
+    let mut runner = IncrementalRunner::<MovableQuery>::new(2);
+    let step = |item: MovableQuery| item.position.0 += item.velocity.0;
+    assert_eq!(runner.run(&mut world, step), 2);
+    assert!(!runner.is_pass_complete());
+
+    // despawned entities are skipped, and spawned entities wait for the next pass
+    world.despawn(entities[0]).unwrap();
+    world.despawn(entities[2]).unwrap();
+    let spawned = world.spawn((Position(0), Velocity(1)));
+    assert_eq!(runner.run(&mut world, step), 1);
+    assert!(runner.is_pass_complete());
+
+    let position = |world: &World, e| world.get::<&Position>(e).unwrap().0;
+    assert_eq!(position(&world, entities[1]), 1);
+    assert_eq!(position(&world, entities[3]), 1);
+    assert_eq!(position(&world, spawned), 0);
+
+    assert_eq!(runner.run(&mut world, step), 2);
+    assert_eq!(runner.run(&mut world, step), 1);
+    assert_eq!(position(&world, spawned), 1);
+
+    assert_eq!(runner.run(&mut world, step), 2);
+    runner.reset();
+    assert_eq!(runner.run(&mut world, step), 2);
+}
+
+#[test]
+fn run_behavior_sorted_test() {
+    use hecs_component_provider::run_behavior_sorted;
+
+    let mut world = World::new();
+    let a = world.spawn((Position(2), Velocity(0)));
+    let b = world.spawn((Position(1), Velocity(0)));
+    let c = world.spawn((Position(2), Velocity(0)));
+    world.spawn((Position(0),));
+
+    let mut order = Vec::new();
+    let count = run_behavior_sorted::<(hecs::Entity, MovableQuery), _>(
+        &mut world,
+        |(_, item)| item.position.0,
+        |(entity, item)| {
+            item.position.0 += 10;
+            order.push(entity);
+        },
+    );
+    assert_eq!(count, 3);
+    // ties are broken by entity id
+    assert_eq!(order, [b, a, c]);
+    assert_eq!(*world.get::<&Position>(b).unwrap(), Position(11));
+}
+
+#[test]
+fn stages_test() {
+    use hecs_component_provider::Stage;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let logger = |name: &'static str| {
+        let log = log.clone();
+        move |world: &mut World| log.borrow_mut().push((name, world.len()))
+    };
+
+    let mut world = World::new();
+    let mut schedule = Schedule::new();
+    let commands = schedule.commands();
+    schedule
+        .add_stage_after(Stage::Update, Stage::Custom("physics"))
+        .add_system_to_stage(Stage::PostUpdate, "post", logger("post"))
+        .add_system_to_stage(Stage::Custom("physics"), "physics", logger("physics"))
+        .add_system("update", logger("update"))
+        .add_system_to_stage(Stage::PreUpdate, "pre", move |_| {
+            commands.spawn((Position(0),));
+        });
+    assert_eq!(
+        schedule.stages(),
+        [
+            Stage::PreUpdate,
+            Stage::Update,
+            Stage::Custom("physics"),
+            Stage::PostUpdate
+        ]
+    );
+
+    schedule.run(&mut world);
+    // commands recorded in PreUpdate are applied before Update runs
+    assert_eq!(*log.borrow(), [("update", 1), ("physics", 1), ("post", 1)]);
+    let names: Vec<_> = schedule
+        .stats()
+        .behaviors()
+        .iter()
+        .map(|b| b.name)
+        .collect();
+    assert_eq!(names, ["pre", "update", "physics", "post"]);
+}
+
+#[test]
+#[should_panic(expected = "has not been added")]
+fn unknown_stage_test() {
+    let mut schedule = Schedule::new();
+    schedule.add_system_to_stage(
+        hecs_component_provider::Stage::Custom("missing"),
+        "x",
+        |_| {},
+    );
+}
+
+#[test]
+fn run_if_test() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let enabled = Rc::new(Cell::new(false));
+    let mut world = World::new();
+    let entity = world.spawn((Position(0), Velocity(1)));
+
+    let mut schedule = Schedule::new();
+    schedule
+        .add_behavior::<MovableQuery, _>("move", |item| item.position.0 += item.velocity.0)
+        .run_if("move", {
+            let enabled = enabled.clone();
+            move |world| enabled.get() && !world.is_empty()
+        });
+
+    schedule.run(&mut world);
+    assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(0));
+    enabled.set(true);
+    schedule.run(&mut world);
+    assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(1));
+    assert_eq!(schedule.stats().get("move").unwrap().frames, 1);
+}
+
+#[test]
+fn catch_panics_test() {
+    let mut world = World::new();
+    let entities: Vec<_> = (0..3)
+        .map(|i| world.spawn((Position(i), Velocity(1))))
+        .collect();
+
+    let mut schedule = Schedule::new();
+    schedule
+        .set_catch_panics(true)
+        .add_behavior::<MovableQuery, _>("move", |item| {
+            if item.position.0 == 1 {
+                panic!("corrupt position {}", item.position.0);
+            }
+            item.position.0 += item.velocity.0;
+        });
+
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    schedule.run(&mut world);
+    std::panic::set_hook(hook);
+
+    assert_eq!(schedule.panics().len(), 1);
+    let panic = &schedule.panics()[0];
+    assert_eq!(
+        (panic.behavior, panic.entity, panic.message.as_str()),
+        ("move", entities[1], "corrupt position 1")
+    );
+    assert_eq!(schedule.stats().get("move").unwrap().entities, 3);
+    assert_eq!(*world.get::<&Position>(entities[2]).unwrap(), Position(3));
+    assert!(schedule.take_panics().len() == 1 && schedule.panics().is_empty());
+}
+
+#[test]
+fn pause_test() {
+    let mut world = World::new();
+    let entity = world.spawn((Position(0), Velocity(1)));
+
+    let mut schedule = Schedule::new();
+    let clock = schedule.clock();
+    schedule
+        .add_behavior::<MovableQuery, _>("move", |item| item.position.0 += item.velocity.0)
+        .add_system("ui", |_| {})
+        .run_when_paused("ui")
+        .set_paused(true);
+
+    schedule.run(&mut world);
+    assert!(clock.get().paused);
+    assert_eq!(clock.get().scaled_delta(), 0.0);
+    assert_eq!(schedule.stats().get("move").unwrap().frames, 0);
+    assert_eq!(schedule.stats().get("ui").unwrap().frames, 1);
+
+    schedule
+        .set_paused(false)
+        .set_delta(0.5)
+        .set_time_scale(2.0);
+    schedule.run(&mut world);
+    assert_eq!(clock.get().scaled_delta(), 1.0);
+    assert_eq!(*world.get::<&Position>(entity).unwrap(), Position(1));
+}
+
+#[test]
+fn trace_test() {
+    use hecs_component_provider::{Stage, TraceEvent};
+    use std::time::Duration;
+
+    let mut world = World::new();
+    world.spawn((Position(0), Velocity(1)));
+
+    let mut schedule = Schedule::new();
+    schedule.add_stage_after(Stage::Update, Stage::Custom("physics \"fixed\""));
+    schedule
+        .add_behavior::<MovableQuery, _>("move", |item| item.position.0 += item.velocity.0)
+        .add_system_to_stage(Stage::Custom("physics \"fixed\""), "physics", |_| {})
+        .add_system_to_stage(Stage::PostUpdate, "never", |_| {})
+        .run_if("never", |_| false);
+
+    schedule.run(&mut world);
+    assert!(schedule.trace().is_none());
+
+    schedule.set_trace(true);
+    schedule.run(&mut world);
+    schedule.run_with_budget(&mut world, Duration::ZERO);
+
+    let trace = schedule.take_trace().unwrap();
+    assert!(schedule.trace().unwrap().runs().is_empty());
+    assert_eq!(trace.runs().len(), 2);
+    assert!(trace.runs()[0].complete);
+    assert!(!trace.runs()[1].complete);
+
+    let events: Vec<_> = trace.runs()[0]
+        .events
+        .iter()
+        .map(|event| match event {
+            TraceEvent::Behavior {
+                name,
+                entities,
+                skipped,
+                ..
+            } => format!("{}:{}:{}", name, entities, skipped),
+            TraceEvent::Flush { stage, .. } => format!("flush:{:?}", stage),
+            TraceEvent::ParallelBehavior { .. } => unreachable!(),
+        })
+        .collect();
+    assert_eq!(
+        events,
+        [
+            "move:1:false",
+            "flush:Update",
+            "physics:0:false",
+            "flush:Custom(\"physics \\\"fixed\\\"\")",
+            "never:0:true",
+            "flush:PostUpdate",
+        ]
+    );
+    assert_eq!(trace.runs()[1].events.len(), 2);
+
+    let mut json = Vec::new();
+    trace.write_json(&mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.contains(r#"{"type":"flush","stage":"physics \"fixed\"","offset_us":"#));
+    assert!(json.contains(r#""name":"never","stage":"PostUpdate","offset_us":"#));
+    assert!(json.ends_with("]}]\n"));
+
+    schedule.set_trace(false);
+    assert!(schedule.take_trace().is_none());
+}
+
+#[test]
+fn unmatched_stats_test() {
+    let mut world = World::new();
+    let mut schedule = Schedule::new();
+    schedule
+        .add_behavior::<MovableQuery, _>("move", |item| item.position.0 += item.velocity.0)
+        .add_behavior::<PositionQuery, _>("read", |_| {})
+        .add_system("system", |_| {});
+
+    for _ in 0..3 {
+        schedule.run(&mut world);
+    }
+    let unmatched: Vec<_> = schedule.stats().unmatched(3).map(|b| b.name).collect();
+    assert_eq!(unmatched, ["move", "read"]);
+    assert_eq!(schedule.stats().get("system").unwrap().empty_frames, 0);
+
+    world.spawn((Position(0),));
+    schedule.run(&mut world);
+    let stats = schedule.stats();
+    assert_eq!(stats.get("move").unwrap().empty_frames, 4);
+    assert_eq!(stats.get("read").unwrap().empty_frames, 0);
+    let unmatched: Vec<_> = stats.unmatched(0).map(|b| b.name).collect();
+    assert_eq!(unmatched, ["move"]);
+    assert_eq!(stats.unmatched(5).count(), 0);
+}
+
+#[test]
+fn run_behavior_joined_test() {
+    use hecs_component_provider::{run_behavior_joined, EntityMap};
+
+    let mut sim = World::new();
+    let mut render = World::new();
+    let mut map = EntityMap::new();
+
+    let a = sim.spawn((Position(1),));
+    let b = sim.spawn((Position(2),));
+    let unmapped = sim.spawn((Position(3),));
+    let ra = render.spawn((Position(0), Velocity(0)));
+    let rb = render.spawn((Velocity(0),));
+    let stale = render.spawn((Position(0), Velocity(0)));
+    map.insert(a, stale);
+    map.insert(a, ra);
+    map.insert(b, rb);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get_a(stale), None);
+    assert_eq!(map.get(unmapped), None);
+
+    let mut visited = Vec::new();
+    let count = run_behavior_joined::<PositionQuery, MovableQuery>(
+        &mut sim,
+        &mut render,
+        &map,
+        |sim, render| {
+            render.position.0 = sim.position.0 * 10;
+            visited.push(sim.position.0);
+        },
+    );
+    assert_eq!(count, 1);
+    assert_eq!(visited, [1]);
+    assert_eq!(*render.get::<&Position>(ra).unwrap(), Position(10));
+
+    assert_eq!(map.remove_b(ra), Some(a));
+    assert_eq!(map.remove(b), Some(rb));
+    assert!(map.is_empty());
+}
+
+#[test]
+fn run_behavior_in_set_test() {
+    use hecs_component_provider::{run_behavior_in_set, EntitySet};
+
+    let mut world = World::new();
+    let entities: Vec<_> = (0..100)
+        .map(|i| world.spawn((Position(i), Velocity(1))))
+        .collect();
+    let unmovable = world.spawn((Position(0),));
+
+    let mut set: EntitySet = entities.iter().copied().step_by(10).collect();
+    set.insert(unmovable);
+    assert!(!set.insert(entities[0]));
+    assert_eq!(set.len(), 11);
+    assert!(set.contains(entities[10]));
+    assert!(!set.contains(entities[11]));
+
+    world.despawn(entities[90]).unwrap();
+    let reused = world.spawn((Position(0), Velocity(1)));
+    assert!(!set.contains(reused));
+
+    let count = run_behavior_in_set::<MovableQuery>(&mut world, &set, |item| {
+        item.position.0 += item.velocity.0;
+    });
+    assert_eq!(count, 9);
+    assert_eq!(*world.get::<&Position>(entities[10]).unwrap(), Position(11));
+    assert_eq!(*world.get::<&Position>(entities[11]).unwrap(), Position(11));
+    assert_eq!(*world.get::<&Position>(reused).unwrap(), Position(0));
+
+    assert!(set.remove(entities[10]));
+    assert!(!set.remove(entities[10]));
+    let ids: Vec<_> = set.iter().map(|e| e.id()).collect();
+    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    set.clear();
+    assert!(set.is_empty());
+}
+
+#[test]
+fn run_with_test() {
+    use hecs_component_provider::{run_with, run_with_entity, ComponentProviderMut};
+
+    let mut world = World::new();
+    let a = world.spawn((Position(0), Velocity(1)));
+    let b = world.spawn((Position(0), Velocity(2)));
+    world.spawn((Position(0),));
+
+    let count = run_with::<MovableQuery>(&mut world, |item| {
+        let velocity = item.velocity.0;
+        let position: &mut Position = item.get_mut();
+        position.0 += velocity;
+    });
+    assert_eq!(count, 2);
+
+    let mut moved = Vec::new();
+    let count = run_with_entity::<MovableQuery>(&mut world, |entity, item| {
+        item.position.0 += item.velocity.0;
+        moved.push((entity, item.position.0));
+    });
+    assert_eq!(count, 2);
+    moved.sort_by_key(|(e, _)| e.id());
+    assert_eq!(moved, [(a, 2), (b, 4)]);
+}
+
+#[test]
+fn bind_test() {
+    use hecs_component_provider::{bind, BindBehavior};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn scale(item: MovableQuery, factor: &i32) {
+        item.position.0 *= factor;
+    }
+
+    let mut world = World::new();
+    world.spawn((Position(0), Velocity(1)));
+    world.spawn((Position(0), Velocity(2)));
+
+    let factor = Rc::new(Cell::new(2));
+    let mut system = bind::<MovableQuery, _, _>(factor.clone(), |item, factor| {
+        item.position.0 += item.velocity.0 * factor.get();
+    });
+    system(&mut world);
+    factor.set(10);
+    system(&mut world);
+
+    let mut double = scale.bind::<MovableQuery>(2);
+    double(&mut world);
+
+    let mut positions: Vec<_> = world
+        .query_mut::<&Position>()
+        .into_iter()
+        .map(|p| p.0)
+        .collect();
+    positions.sort();
+    assert_eq!(positions, [24, 48]);
+}
+
+#[test]
+fn group_test() {
+    use hecs_component_provider::Group;
+
+    const UI: Group = Group::layer(1);
+    const DEBUG: Group = Group::layer(2);
+
+    let mut world = World::new();
+    let ball = world.spawn((Position(0), Velocity(1)));
+    let button = world.spawn((Position(0), Velocity(1), UI));
+    let gizmo = world.spawn((Position(0), Velocity(1), UI | DEBUG));
+    // in the same archetype as the UI entities, so its group is read from the column
+    let crate_ = world.spawn((Position(0), Velocity(1), Group::DEFAULT));
+
+    let mut schedule = Schedule::new();
+    schedule
+        .add_behavior::<MovableQuery, _>("physics", |item| item.position.0 += item.velocity.0)
+        .add_behavior::<MovableQuery, _>("layout", |item| item.position.0 += 10)
+        .add_behavior::<MovableQuery, _>("everything", |item| item.position.0 += 100)
+        .in_groups("physics", Group::DEFAULT)
+        .in_groups("layout", UI)
+        .in_groups("everything", Group::ALL);
+    schedule.run(&mut world);
+
+    assert_eq!(*world.get::<&Position>(ball).unwrap(), Position(101));
+    assert_eq!(*world.get::<&Position>(button).unwrap(), Position(110));
+    assert_eq!(*world.get::<&Position>(gizmo).unwrap(), Position(110));
+    assert_eq!(*world.get::<&Position>(crate_).unwrap(), Position(101));
+    assert_eq!(schedule.stats().get("physics").unwrap().entities, 2);
+    assert_eq!(schedule.stats().get("layout").unwrap().entities, 2);
+    assert!((UI | DEBUG).contains(DEBUG));
+    assert!(!UI.contains(UI | DEBUG));
+}
+
+#[test]
+fn mutable_group_behavior_test() {
+    use hecs_component_provider::Group;
+
+    const UI: Group = Group::layer(1);
+    const DEBUG: Group = Group::layer(2);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct GroupQuery<'a> {
+        group: &'a mut Group,
+    }
+
+    let mut world = World::new();
+    let button = world.spawn((UI,));
+
+    let mut schedule = Schedule::new();
+    schedule.add_behavior::<GroupQuery, _>("show debug", |item| *item.group |= DEBUG);
+    schedule.run(&mut world);
+
+    assert_eq!(*world.get::<&Group>(button).unwrap(), UI | DEBUG);
+}
+
+#[test]
+#[should_panic(expected = "borrows `Group` mutably")]
+fn mutable_group_in_groups_test() {
+    use hecs_component_provider::Group;
+
+    let mut schedule = Schedule::new();
+    schedule
+        .add_behavior::<&mut Group, _>("regroup", |group| *group = Group::ALL)
+        .in_groups("regroup", Group::DEFAULT);
+}
+
+#[test]
+fn fork_test() {
+    use hecs_component_provider::fork;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Fuel(i32);
+    struct Name(&'static str);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(owned(ShipBundle))]
+    struct ShipQuery<'a> {
+        fuel: &'a mut Fuel,
+    }
+
+    let mut world = World::new();
+    let ship = world.spawn((Fuel(10), Name("ship")));
+    let wreck = world.spawn((Fuel(5),));
+    world.spawn((Name("station"),));
+
+    let burn = |world: &mut World| {
+        for item in world.query_mut::<ShipQuery>() {
+            item.fuel.0 -= 3;
+        }
+    };
+
+    let mut plan = fork::<ShipQuery>(&world);
+    assert_eq!(plan.world().len(), 2);
+    // only the owned bundle is copied
+    assert!(plan.world().get::<&Name>(plan.get(ship).unwrap()).is_err());
+    burn(plan.world_mut());
+    plan.discard();
+    assert_eq!(*world.get::<&Fuel>(ship).unwrap(), Fuel(10));
+
+    let mut plan = fork::<ShipQuery>(&world);
+    burn(plan.world_mut());
+    burn(plan.world_mut());
+    let copy = plan.get(wreck).unwrap();
+    assert_eq!(plan.original(copy), Some(wreck));
+    plan.world_mut().despawn(copy).unwrap();
+    plan.world_mut().spawn((Fuel(100),));
+
+    assert_eq!(plan.merge(&mut world), 1);
+    assert_eq!(*world.get::<&Fuel>(ship).unwrap(), Fuel(4));
+    assert_eq!(world.get::<&Name>(ship).unwrap().0, "ship");
+    assert_eq!(*world.get::<&Fuel>(wreck).unwrap(), Fuel(5));
+    assert_eq!(world.len(), 3);
+}
+
+#[test]
+fn rollback_test() {
+    use hecs_component_provider::RollbackBuffer;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Health(i32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(owned(HealthBundle))]
+    struct HealthQuery<'a> {
+        health: &'a mut Health,
+    }
+
+    let mut world = World::new();
+    let hero = world.spawn((Health(10),));
+    let goblin = world.spawn((Health(3),));
+    let mut rollback = RollbackBuffer::<HealthQuery>::new(2);
+    assert!(rollback.is_empty());
+    assert!(!rollback.rollback(&mut world, 0));
+    rollback.save(&world);
+
+    world.get::<&mut Health>(hero).unwrap().0 -= 1;
+    world.despawn(goblin).unwrap();
+    rollback.save(&world);
+
+    world.get::<&mut Health>(hero).unwrap().0 -= 1;
+    let ghost = world.spawn((Health(1),));
+    rollback.save(&world);
+    assert_eq!(rollback.len(), 2);
+    assert_eq!(rollback.next_tick(), 3);
+    // the first tick was dropped
+    assert!(!rollback.rollback(&mut world, 2));
+
+    assert!(rollback.rollback(&mut world, 1));
+    assert_eq!(rollback.next_tick(), 2);
+    assert_eq!(*world.get::<&Health>(hero).unwrap(), Health(9));
+    assert!(!world.contains(ghost));
+    assert!(!world.contains(goblin));
+
+    let mut rollback = RollbackBuffer::<HealthQuery>::new(4);
+    rollback.save(&world);
+    world.despawn(hero).unwrap();
+    rollback.save(&world);
+    assert!(rollback.rollback(&mut world, 1));
+    assert_eq!(*world.get::<&Health>(hero).unwrap(), Health(9));
+
+    // rolling back keeps the restored tick, so it can be restored again
+    world.get::<&mut Health>(hero).unwrap().0 = 0;
+    assert!(rollback.rollback(&mut world, 0));
+    assert_eq!(*world.get::<&Health>(hero).unwrap(), Health(9));
+
+    let mut schedule = Schedule::new();
+    schedule.add_behavior::<HealthQuery, _>("regenerate", |item| item.health.0 += 1);
+    let mut ticks = Vec::new();
+    rollback.save(&world);
+    rollback.save(&world);
+    assert!(rollback.resimulate(&mut world, &mut schedule, 2, |tick| ticks.push(tick)));
+    assert_eq!(ticks, [1, 2]);
+    assert_eq!(*world.get::<&Health>(hero).unwrap(), Health(11));
+    assert_eq!(rollback.next_tick(), 3);
+}
+
+#[test]
+fn prediction_test() {
+    use hecs_component_provider::Prediction;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Speed(i32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    #[provider(owned(SpeedBundle))]
+    struct SpeedQuery<'a> {
+        speed: &'a mut Speed,
+    }
+
+    let mut world = World::new();
+    let car = world.spawn((Speed(0),));
+    let mut schedule = Schedule::new();
+    schedule.add_behavior::<SpeedQuery, _>("accelerate", |item| item.speed.0 += 1);
+    let mut apply = |world: &mut World, boost: &i32| {
+        schedule.run(world);
+        world.get::<&mut Speed>(car).unwrap().0 += *boost;
+    };
+
+    let mut prediction = Prediction::<SpeedQuery, i32>::new();
+    for (tick, boost) in [(10, 0), (11, 10), (12, 0)] {
+        apply(&mut world, &boost);
+        prediction.record(tick, boost, &world);
+    }
+    assert_eq!(*world.get::<&Speed>(car).unwrap(), Speed(13));
+
+    // a correct prediction leaves the world alone
+    let bundle = |speed| SpeedBundle {
+        speed: Speed(speed),
+    };
+    let corrections = prediction.reconcile(&mut world, 10, vec![(car, bundle(1))], &mut apply);
+    assert!(corrections.is_empty());
+    assert_eq!(prediction.pending(), 2);
+    assert_eq!(*world.get::<&Speed>(car).unwrap(), Speed(13));
+
+    // the boost at tick 11 was rejected by the server
+    let corrections = prediction.reconcile(&mut world, 11, vec![(car, bundle(2))], &mut apply);
+    assert_eq!(corrections.len(), 1);
+    assert_eq!(corrections[0].entity, car);
+    assert_eq!(*world.get::<&Speed>(car).unwrap(), Speed(3));
+    assert_eq!(prediction.pending(), 1);
+
+    // ticks that weren't predicted are always corrected
+    let corrections = prediction.reconcile(&mut world, 20, vec![(car, bundle(9))], &mut apply);
+    assert_eq!(corrections[0].diff, None);
+    assert_eq!(prediction.pending(), 0);
+    assert_eq!(*world.get::<&Speed>(car).unwrap(), Speed(9));
+}
+
+#[cfg(feature = "puffin")]
+#[test]
+fn puffin_scopes_test() {
+    use std::sync::{Arc, Mutex};
+
+    struct Speed(i32);
+
+    let mut world = World::new();
+    world.spawn((Speed(1),));
+
+    let mut schedule = Schedule::new();
+    schedule.add_behavior::<&mut Speed, _>("accelerate", |speed| speed.0 += 1);
+    schedule.add_system("count", |_| {});
+
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let sink_names = names.clone();
+    puffin::set_scopes_on(true);
+    let sink = puffin::GlobalProfiler::lock().add_sink(Box::new(move |frame| {
+        let mut names = sink_names.lock().unwrap();
+        names.extend(
+            frame
+                .scope_delta
+                .iter()
+                .filter_map(|s| s.scope_name.clone()),
+        );
+    }));
+
+    schedule.run(&mut world);
+    puffin::GlobalProfiler::lock().new_frame();
+    puffin::GlobalProfiler::lock().remove_sink(sink);
+
+    let names = names.lock().unwrap();
+    assert!(names.iter().any(|name| name == "accelerate"));
+    assert!(names.iter().any(|name| name == "count"));
+}
+
+#[test]
+fn parallel_runner_test() {
+    use hecs_component_provider::{ParallelRunner, TraceEvent};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    struct Position(i32);
+    struct Velocity(i32);
+    struct Health(i32);
+
+    let mut world = World::new();
+    for i in 0..100 {
+        world.spawn((Position(0), Velocity(i), Health(0)));
+    }
+
+    let threads = Arc::new(Mutex::new(Vec::new()));
+    let record = |threads: &Arc<Mutex<Vec<thread::ThreadId>>>| {
+        let threads = threads.clone();
+        move || threads.lock().unwrap().push(thread::current().id())
+    };
+    let (move_thread, heal_thread) = (record(&threads), record(&threads));
+
+    let mut runner = ParallelRunner::new(4);
+    runner
+        .add_behavior::<(&mut Position, &Velocity), _>("move", move |(p, v)| {
+            p.0 += v.0;
+            move_thread();
+        })
+        .add_behavior::<&mut Health, _>("heal", move |h| {
+            h.0 += 1;
+            heal_thread();
+        })
+        .add_behavior::<(&mut Velocity, &Position), _>("bounce", |(v, p)| {
+            if p.0 > 50 {
+                v.0 = -v.0;
+            }
+        });
+    assert_eq!(runner.workers(), 4);
+    assert_eq!(runner.batches(), vec![vec!["move", "heal"], vec!["bounce"]]);
+
+    runner.set_trace(true);
+    assert_eq!(runner.run(&mut world), 300);
+    for (position, velocity, health) in world.query_mut::<(&Position, &Velocity, &Health)>() {
+        assert_eq!(position.0 > 50, velocity.0 < 0);
+        assert_eq!(health.0, 1);
+    }
+
+    // behaviors in the same batch ran on different threads
+    let mut threads = threads.lock().unwrap().clone();
+    threads.dedup();
+    assert!(threads.len() >= 2);
+
+    let trace = runner.take_trace().unwrap();
+    let mut events: Vec<_> = trace.runs()[0]
+        .events
+        .iter()
+        .map(|event| match event {
+            TraceEvent::ParallelBehavior {
+                name,
+                batch,
+                lane,
+                entities,
+                ..
+            } => (*batch, *lane, *name, *entities),
+            _ => unreachable!(),
+        })
+        .collect();
+    // the lanes of a batch are numbered by the order its behaviors started
+    events[..2].sort_by_key(|&(_, _, name, _)| name);
+    let lanes: Vec<_> = events.iter().map(|&(_, lane, _, _)| lane).collect();
+    assert!(lanes[..2].contains(&0) && lanes[..2].contains(&1));
+    let events: Vec<_> = events
+        .iter()
+        .map(|&(batch, _, name, entities)| (batch, name, entities))
+        .collect();
+    assert_eq!(
+        events,
+        [(0, "heal", 100), (0, "move", 100), (1, "bounce", 100)]
+    );
+    assert_eq!(lanes[2], 0);
+
+    let mut json = Vec::new();
+    trace.write_json(&mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.contains(r#"{"type":"parallel_behavior","name":"bounce","batch":1,"lane":0,"#));
+}
+
+#[test]
+fn async_runner_test() {
+    use hecs_component_provider::{AsyncRunner, BoxedTask};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    struct Request(u32);
+    struct Response(u32);
+
+    // resolves once the "server" has replied
+    struct Reply(Arc<Mutex<Option<u32>>>);
+
+    impl Future for Reply {
+        type Output = u32;
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<u32> {
+            match self.0.lock().unwrap().take() {
+                Some(reply) => Poll::Ready(reply),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    let tasks = Arc::new(Mutex::new(Vec::<BoxedTask>::new()));
+    let queue = tasks.clone();
+    let mut runner = AsyncRunner::new(move |task| queue.lock().unwrap().push(task));
+
+    let mut world = World::new();
+    let requester = world.spawn((Request(7),));
+    world.spawn((Request(9),));
+
+    let replies: Vec<_> = (0..2).map(|_| Arc::new(Mutex::new(None))).collect();
+    let mut unanswered = replies.clone();
+    let started = runner.spawn_behavior::<(hecs::Entity, &Request), _, _>(
+        &mut world,
+        |(entity, request), commands| {
+            let (id, reply) = (request.0, Reply(unanswered.remove(0)));
+            async move {
+                let value = reply.await;
+                commands.insert_one(entity, Response(id + value));
+                commands.remove_one::<Request>(entity);
+            }
+        },
+    );
+    assert_eq!(started, 2);
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut context = Context::from_waker(&waker);
+    let mut poll_all = || {
+        tasks
+            .lock()
+            .unwrap()
+            .retain_mut(|task| task.as_mut().poll(&mut context).is_pending())
+    };
+
+    // nothing changes while the tasks are waiting
+    poll_all();
+    runner.apply(&mut world);
+    assert_eq!(runner.pending(), 2);
+    assert!(world.get::<&Response>(requester).is_err());
+
+    // each frame applies the changes of the tasks that completed since the last one
+    *replies[0].lock().unwrap() = Some(100);
+    *replies[1].lock().unwrap() = Some(200);
+    poll_all();
+    assert_eq!(runner.pending(), 0);
+    runner.apply(&mut world);
+    let mut responses: Vec<u32> = world
+        .query_mut::<&Response>()
+        .into_iter()
+        .map(|r| r.0)
+        .collect();
+    responses.sort_unstable();
+    assert_eq!(responses.len(), 2);
+    assert!(responses == [107, 209] || responses == [109, 207]);
+    assert_eq!(world.query_mut::<&Request>().into_iter().count(), 0);
+
+    // cancelled tasks aren't pending
+    runner.spawn(std::future::pending());
+    assert_eq!(runner.pending(), 1);
+    tasks.lock().unwrap().clear();
+    assert_eq!(runner.pending(), 0);
+}
+
+#[test]
+fn throttle_test() {
+    struct Plan(u32);
+
+    let mut world = World::new();
+    let agent = world.spawn((Plan(0),));
+
+    let mut schedule = Schedule::new();
+    schedule
+        .add_behavior::<&mut Plan, _>("replan", |plan| plan.0 += 1)
+        .run_at_rate("replan", 4.0)
+        .add_system("tick", |_| {})
+        .run_every("tick", 2);
+
+    // frame times vary, but the rate doesn't depend on them, except that a long frame can't
+    // make up for missed runs
+    for delta in [0.125, 0.0625, 0.125, 0.25, 0.0625, 0.375, 0.25] {
+        schedule.set_delta(delta).run(&mut world);
+    }
+    assert_eq!(world.get::<&Plan>(agent).unwrap().0, 5);
+    assert_eq!(schedule.stats().get("tick").unwrap().frames, 4);
+
+    // paused runs don't count towards either throttle
+    schedule.set_paused(true);
+    for _ in 0..3 {
+        schedule.run(&mut world);
+    }
+    schedule.set_paused(false).set_delta(0.0);
+    schedule.run(&mut world);
+    assert_eq!(world.get::<&Plan>(agent).unwrap().0, 5);
+    assert_eq!(schedule.stats().get("tick").unwrap().frames, 4);
+    schedule.run(&mut world);
+    assert_eq!(schedule.stats().get("tick").unwrap().frames, 5);
+}
+
+#[test]
+fn entity_commands_test() {
+    use hecs_component_provider::{EntityCommands, EntityCommandsProvider};
+
+    #[derive(Debug, PartialEq)]
+    struct Health(i32);
+    struct Poisoned;
+    struct Corpse;
+
+    fn settle(health: &Health, item: &impl EntityCommandsProvider) {
+        if health.0 <= 0 {
+            item.commands().despawn();
+        } else if health.0 < 5 {
+            item.commands().insert_bundle((Poisoned, Corpse));
+            item.commands().remove::<Corpse>();
+        }
+    }
+
+    let mut world = World::new();
+    let dead = world.spawn((Health(0),));
+    let weak = world.spawn((Health(3),));
+    let healthy = world.spawn((Health(10),));
+
+    let mut schedule = Schedule::new();
+    let commands = schedule.commands();
+    schedule.add_behavior::<(hecs::Entity, &Health), _>("settle", move |(entity, health)| {
+        let entity_commands: EntityCommands = commands.entity(entity);
+        assert_eq!(entity_commands.entity(), entity);
+        settle(health, &entity_commands);
+    });
+    schedule.run(&mut world);
+
+    assert!(!world.contains(dead));
+    assert!(world.get::<&Poisoned>(weak).is_ok());
+    // commands are applied in the order they were recorded
+    assert!(world.get::<&Corpse>(weak).is_err());
+    assert_eq!(world.entity(healthy).unwrap().len(), 1);
+}
+
+#[test]
+fn signals_test() {
+    use hecs_component_provider::Signals;
+
+    #[derive(Debug, PartialEq)]
+    struct Alarm(u32);
+    struct Heard(Vec<u32>);
+
+    let mut world = World::new();
+    let bell = world.spawn(());
+    let guard = world.spawn((Heard(Vec::new()),));
+    let sleeper = world.spawn((Heard(Vec::new()),));
+    let statue = world.spawn(());
+
+    let signals = Signals::<Alarm>::new();
+    let guard_connection = signals.connect(bell, guard);
+    let sleeper_connection = signals.connect(bell, sleeper);
+    signals.connect(bell, statue);
+    // the guard relays alarms to the sleeper
+    signals.connect(guard, sleeper);
+    assert_eq!(signals.subscribers(bell), [guard, sleeper, statue]);
+    assert!(!signals.is_empty());
+
+    signals.emit(bell, Alarm(1));
+    signals.emit(bell, Alarm(2));
+    let relay = signals.clone();
+    let calls = signals.dispatch::<(hecs::Entity, &mut Heard), _>(
+        &mut world,
+        |(entity, heard), source, alarm| {
+            heard.0.push(alarm.0);
+            if entity == guard && source == bell {
+                relay.emit(guard, Alarm(alarm.0 * 10));
+            }
+        },
+    );
+    // the statue doesn't match the query
+    assert_eq!(calls, 4);
+    assert_eq!(world.get::<&Heard>(guard).unwrap().0, [1, 2]);
+    assert_eq!(world.get::<&Heard>(sleeper).unwrap().0, [1, 2]);
+
+    // relayed signals are delivered by the next dispatch
+    assert!(signals.disconnect(sleeper_connection));
+    assert!(!signals.disconnect(sleeper_connection));
+    signals.dispatch::<&mut Heard, _>(&mut world, |heard, _, alarm| heard.0.push(alarm.0));
+    assert_eq!(world.get::<&Heard>(sleeper).unwrap().0, [1, 2, 10, 20]);
+
+    // despawning the guard removes its connections in both directions
+    world.despawn(guard).unwrap();
+    signals.emit(bell, Alarm(3));
+    assert_eq!(signals.len(), 3);
+    signals.dispatch::<&mut Heard, _>(&mut world, |heard, _, alarm| heard.0.push(alarm.0));
+    assert!(!signals.is_connected(guard_connection));
+    assert_eq!(signals.subscribers(bell), [statue]);
+    assert_eq!(world.get::<&Heard>(sleeper).unwrap().0, [1, 2, 10, 20]);
+}
+
+#[test]
+#[cfg(feature = "mutation-log")]
+fn mutation_log_test() {
+    use hecs_component_provider::{ComponentProviderMut, MutationLog};
+
+    // a component only used here, since the global log is shared with tests running concurrently
+    struct Charge(u32);
+
+    #[derive(hecs::Query, ComponentProvider)]
+    struct BatteryQuery<'a> {
+        charge: &'a mut Charge,
+    }
+
+    let mut world = World::new();
+    let first = world.spawn((Charge(3),));
+    let second = world.spawn((Charge(5),));
+
+    let mut schedule = Schedule::new();
+    schedule.add_behavior::<BatteryQuery, _>("drain", |mut battery| {
+        let charge: &mut Charge = battery.get_mut();
+        charge.0 -= 1;
+    });
+    schedule.run(&mut world);
+    schedule.run(&mut world);
+    assert_eq!(schedule.tick(), 2);
+
+    let charges = |entity| -> Vec<_> {
+        schedule
+            .mutation_log()
+            .entries_for(entity)
+            .into_iter()
+            .map(|m| (m.behavior, m.tick))
+            .collect()
+    };
+    assert_eq!(
+        charges(first),
+        [(Some("drain"), Some(0)), (Some("drain"), Some(1))]
+    );
+    assert_eq!(
+        charges(second),
+        [(Some("drain"), Some(0)), (Some("drain"), Some(1))]
+    );
+    schedule.mutation_log().set_capacity(1);
+    assert_eq!(schedule.mutation_log().entries().len(), 1);
+
+    // mutations outside a schedule are recorded in the global log without context
+    MutationLog::global().set_capacity(1 << 16);
+    let mut query = world.query_one::<BatteryQuery>(first);
+    let _: &mut Charge = query.get().unwrap().get_mut();
+    let last = MutationLog::global()
+        .entries()
+        .into_iter()
+        .rev()
+        .find(|m| m.component.ends_with("::Charge"))
+        .unwrap();
+    assert_eq!((last.entity, last.behavior, last.tick), (None, None, None));
+    assert_eq!(schedule.mutation_log().entries().len(), 1);
+}
+
+#[test]
+fn teardown_test() {
+    use hecs_component_provider::Teardown;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let torn_down = Rc::new(RefCell::new(Vec::new()));
+    let mut teardown = Teardown::new();
+    teardown
+        .add::<&Position, _>({
+            let torn_down = torn_down.clone();
+            move |position| torn_down.borrow_mut().push(("position", position.0))
+        })
+        .add::<&Velocity, _>({
+            let torn_down = torn_down.clone();
+            move |velocity| torn_down.borrow_mut().push(("velocity", velocity.0))
+        });
+
+    let mut world = World::new();
+    let moving = world.spawn((Position(1), Velocity(2)));
+    let still = world.spawn((Position(3),));
+    teardown.despawn(&mut world, moving).unwrap();
+    teardown.despawn(&mut world, still).unwrap();
+    assert!(world.is_empty());
+    assert_eq!(
+        *torn_down.borrow(),
+        [("position", 1), ("velocity", 2), ("position", 3)]
+    );
+    assert!(teardown.despawn(&mut world, still).is_err());
+
+    // despawns recorded by teardown behaviors are torn down in the same stage
+    let mut schedule = Schedule::new();
+    let commands = schedule.commands();
+    let parent = world.spawn((Position(4), Velocity(5)));
+    let child = world.spawn((Position(6),));
+    schedule.add_teardown::<&Velocity, _>(move |_| commands.despawn(child));
+    let commands = schedule.commands();
+    schedule.add_system("despawn_parent", move |_| commands.despawn(parent));
+    torn_down.borrow_mut().clear();
+    schedule.add_teardown::<&Position, _>({
+        let torn_down = torn_down.clone();
+        move |position| torn_down.borrow_mut().push(("position", position.0))
+    });
+    schedule.run(&mut world);
+    assert!(world.is_empty());
+    assert_eq!(*torn_down.borrow(), [("position", 4), ("position", 6)]);
+}